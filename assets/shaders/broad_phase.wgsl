@@ -0,0 +1,111 @@
+// Uniform grid broad phase, see `src/gpu_broad_phase.rs`. Balls are binned by
+// their center, each ball is then checked against the balls in its own and
+// the neighbouring cells, so cells are at least as large as the largest reach
+// between two balls.
+
+struct Params {
+    origin: vec2<f32>;
+    cell_size: f32;
+    margin: f32;
+    columns: u32;
+    rows: u32;
+    ball_count: u32;
+    cell_capacity: u32;
+    max_pairs: u32;
+};
+
+struct Balls {
+    // position and radius of each ball
+    data: array<vec4<f32>>;
+};
+
+struct Counts {
+    data: array<atomic<u32>>;
+};
+
+struct Slots {
+    data: array<u32>;
+};
+
+struct Pairs {
+    count: atomic<u32>;
+    // balls which did not fit in their cell
+    dropped: atomic<u32>;
+    data: array<vec2<u32>>;
+};
+
+[[group(0), binding(0)]]
+var<uniform> params: Params;
+[[group(0), binding(1)]]
+var<storage, read> balls: Balls;
+[[group(0), binding(2)]]
+var<storage, read_write> counts: Counts;
+[[group(0), binding(3)]]
+var<storage, read_write> slots: Slots;
+[[group(0), binding(4)]]
+var<storage, read_write> pairs: Pairs;
+
+fn cell_of(position: vec2<f32>) -> vec2<i32> {
+    let cell = floor((position - params.origin) / params.cell_size);
+    return vec2<i32>(
+        clamp(i32(cell.x), 0, i32(params.columns) - 1),
+        clamp(i32(cell.y), 0, i32(params.rows) - 1),
+    );
+}
+
+[[stage(compute), workgroup_size(64)]]
+fn clear([[builtin(global_invocation_id)]] id: vec3<u32>) {
+    if (id.x == 0u) {
+        atomicStore(&pairs.count, 0u);
+        atomicStore(&pairs.dropped, 0u);
+    }
+    if (id.x < params.columns * params.rows) {
+        atomicStore(&counts.data[id.x], 0u);
+    }
+}
+
+[[stage(compute), workgroup_size(64)]]
+fn bin([[builtin(global_invocation_id)]] id: vec3<u32>) {
+    if (id.x >= params.ball_count) {
+        return;
+    }
+    let cell = cell_of(balls.data[id.x].xy);
+    let index = u32(cell.y) * params.columns + u32(cell.x);
+    let slot = atomicAdd(&counts.data[index], 1u);
+    if (slot < params.cell_capacity) {
+        slots.data[index * params.cell_capacity + slot] = id.x;
+    } else {
+        atomicAdd(&pairs.dropped, 1u);
+    }
+}
+
+[[stage(compute), workgroup_size(64)]]
+fn find_pairs([[builtin(global_invocation_id)]] id: vec3<u32>) {
+    let a = id.x;
+    if (a >= params.ball_count) {
+        return;
+    }
+    let ball = balls.data[a];
+    let cell = cell_of(ball.xy);
+    let last = vec2<i32>(i32(params.columns) - 1, i32(params.rows) - 1);
+
+    for (var y: i32 = max(cell.y - 1, 0); y <= min(cell.y + 1, last.y); y = y + 1) {
+        for (var x: i32 = max(cell.x - 1, 0); x <= min(cell.x + 1, last.x); x = x + 1) {
+            let index = u32(y) * params.columns + u32(x);
+            let count = min(atomicLoad(&counts.data[index]), params.cell_capacity);
+            for (var i: u32 = 0u; i < count; i = i + 1u) {
+                // each pair is added once, by the ball with the lowest index
+                let b = slots.data[index * params.cell_capacity + i];
+                let other = balls.data[b];
+                let delta = other.xy - ball.xy;
+                let reach = ball.z + other.z + params.margin;
+                if (b > a && dot(delta, delta) <= reach * reach) {
+                    let pair = atomicAdd(&pairs.count, 1u);
+                    if (pair < params.max_pairs) {
+                        pairs.data[pair] = vec2<u32>(a, b);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,36 @@
+// Draws all balls as instances of a unit circle, see `src/instancing.rs`.
+
+#import bevy_sprite::mesh2d_view_bind_group
+
+[[group(0), binding(0)]]
+var<uniform> view: View;
+
+struct Vertex {
+    [[location(0)]] position: vec2<f32>;
+    // center, depth and radius of the ball
+    [[location(1)]] ball: vec4<f32>;
+    [[location(2)]] color: vec4<f32>;
+};
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] color: vec4<f32>;
+};
+
+[[stage(vertex)]]
+fn vertex(vertex: Vertex) -> VertexOutput {
+    let position = vertex.ball.xy + vertex.position * vertex.ball.w;
+    var out: VertexOutput;
+    out.clip_position = view.view_proj * vec4<f32>(position, vertex.ball.z, 1.0);
+    out.color = vertex.color;
+    return out;
+}
+
+struct FragmentInput {
+    [[location(0)]] color: vec4<f32>;
+};
+
+[[stage(fragment)]]
+fn fragment(in: FragmentInput) -> [[location(0)]] vec4<f32> {
+    return in.color;
+}
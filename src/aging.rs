@@ -0,0 +1,136 @@
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::*;
+
+use crate::*;
+
+/// Gives balls a limited lifespan, over which they gradually shrink and fade
+/// out. Their mass shrinks along with their radius, so collisions stay
+/// consistent. Once their lifespan is over, balls are returned to the
+/// `BallPool`, so spawning balls at a steady rate results in a steady amount
+/// of balls.
+pub struct AgingPlugin {
+    lifespan: f32,
+}
+
+impl AgingPlugin {
+    pub fn with_lifespan(lifespan: f32) -> Self {
+        Self { lifespan }
+    }
+}
+
+impl Default for AgingPlugin {
+    fn default() -> Self { Self::with_lifespan(10.) }
+}
+
+impl Plugin for AgingPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Lifespan(self.lifespan.max(TIMESTEP)))
+            .add_system(age_spawned_balls)
+            .add_system_to_stage(CoreStage::PostUpdate, fade_aged_balls)
+            .add_system_to_stage(PhysicsStage, age_balls.before(PhysicsSystem::Integrate));
+    }
+}
+
+/// Simulated time after which balls are removed, in seconds.
+pub struct Lifespan(pub f32);
+
+// Fraction of their initial size balls shrink to at the end of their lifespan.
+// Balls never shrink to nothing, which would leave them without mass.
+const MIN_SCALE: f32 = 0.1;
+
+/// Time a ball has been alive.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Age {
+    pub elapsed: f32,
+    pub lifespan: f32,
+    // radius of the ball when it was spawned, which its mesh is built for
+    radius: f32,
+}
+
+impl Age {
+    pub fn new(radius: f32, lifespan: f32) -> Self {
+        Self {
+            elapsed: 0.,
+            lifespan,
+            radius,
+        }
+    }
+
+    /// Fraction of the lifespan which has passed.
+    #[inline]
+    pub fn fraction(&self) -> f32 { (self.elapsed / self.lifespan).min(1.) }
+
+    /// Scale of the ball relative to its initial size.
+    #[inline]
+    pub fn scale(&self) -> f32 { 1. - self.fraction() * (1. - MIN_SCALE) }
+
+    #[inline]
+    pub fn alpha(&self) -> f32 { 1. - self.fraction() }
+
+    #[inline]
+    pub fn radius(&self) -> f32 { self.radius * self.scale() }
+
+    /// Advance the age by `dt` seconds, returns `false` once the lifespan is
+    /// over.
+    #[inline]
+    pub fn advance(&mut self, dt: f32) -> bool {
+        self.elapsed += dt;
+        self.elapsed < self.lifespan
+    }
+}
+
+fn age_spawned_balls(
+    mut cmd: Commands,
+    lifespan: Res<Lifespan>,
+    mut spawned: EventReader<BallSpawned>,
+    query: Query<&Ball>,
+) {
+    // pooled balls are spawned again at their initial radius
+    for BallSpawned(entity) in spawned.iter() {
+        if let Ok(ball) = query.get(*entity) {
+            cmd.entity(*entity).insert(Age::new(ball.radius, lifespan.0));
+        }
+    }
+}
+
+fn age_balls(
+    mut cmd: Commands,
+    mut pool: ResMut<BallPool>,
+    mut query: Query<(Entity, &mut Age, &mut Ball, &mut Transform)>,
+) {
+    for (entity, mut age, mut ball, mut transform) in query.iter_mut() {
+        if !age.advance(TIMESTEP) {
+            // pool the ball at the size of its mesh
+            pool.release(&mut cmd, entity, &Ball::new(age.radius, MASS_MODEL));
+            continue;
+        }
+
+        *ball = Ball::new(age.radius(), MASS_MODEL);
+        transform.scale = Vec3::new(age.scale(), age.scale(), 1.);
+    }
+}
+
+fn fade_aged_balls(mut query: Query<(&mut DrawMode, &Age), With<Ball>>) {
+    for (mut draw_mode, age) in query.iter_mut() {
+        set_alpha(&mut draw_mode, age.alpha());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn balls_shrink_and_fade_over_their_lifespan() {
+        let mut age = Age::new(10., 2.);
+        assert_eq!((age.radius(), age.alpha()), (10., 1.));
+
+        assert!(age.advance(1.));
+        assert!((age.radius() - 10. * (1. + MIN_SCALE) / 2.).abs() < 1e-5, "{}", age.radius());
+        assert_eq!(age.alpha(), 0.5);
+
+        assert!(!age.advance(1.));
+        assert!((age.radius() - 10. * MIN_SCALE).abs() < 1e-5, "{}", age.radius());
+        assert_eq!(age.alpha(), 0.);
+    }
+}
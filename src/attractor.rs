@@ -0,0 +1,104 @@
+use bevy::prelude::*;
+
+use crate::*;
+
+/// Adds an attractor which pulls all balls towards it when enabled. It is
+/// moved and toggled using the `ActionAxes::attractor_movement` axis and the
+/// `Action::ToggleAttractor` action.
+pub struct AttractorPlugin {
+    strength: f32,
+}
+
+impl AttractorPlugin {
+    pub fn with_strength(strength: f32) -> Self {
+        Self { strength }
+    }
+}
+
+impl Default for AttractorPlugin {
+    fn default() -> Self { Self::with_strength(2_000_000.) }
+}
+
+impl Plugin for AttractorPlugin {
+    fn build(&self, app: &mut App) {
+        let strength = self.strength;
+        app.add_startup_system(move |mut cmd: Commands| {
+            cmd.spawn()
+                .insert(Attractor { strength, enabled: false })
+                .insert(Transform::default());
+        })
+            .bind_action(Action::ToggleAttractor, [Binding::Key(KeyCode::F)])
+            .bind_axis(AxisControl::AttractorMovement, [KeyCode::A, KeyCode::D, KeyCode::S, KeyCode::W])
+            .add_system(control_attractor)
+            .add_system(draw_attractor)
+            .add_system_to_stage(PhysicsStage, apply_attraction.before(PhysicsSystem::Integrate));
+    }
+}
+
+#[derive(Component)]
+pub struct Attractor {
+    /// Acceleration of a ball at a distance of a single unit. The acceleration
+    /// decreases with the square of the distance.
+    pub strength: f32,
+    pub enabled: bool,
+}
+
+// Speed of the attractor when moved at full tilt.
+const ATTRACTOR_SPEED: f32 = 400.;
+
+// Distance below which the attraction no longer increases, this prevents
+// extreme accelerations of balls which are very close to the attractor.
+const ATTRACTOR_MIN_DISTANCE: f32 = 20.;
+
+fn control_attractor(
+    actions: Res<Input<Action>>,
+    axes: Res<ActionAxes>,
+    edge: Res<EdgeCollider>,
+    time: Res<Time>,
+    mut query: Query<(&mut Attractor, &mut Transform)>,
+) {
+    for (mut attractor, mut transform) in query.iter_mut() {
+        if actions.just_pressed(Action::ToggleAttractor) {
+            attractor.enabled = !attractor.enabled;
+        }
+
+        let position = transform.translation.truncate()
+            + axes.attractor_movement * ATTRACTOR_SPEED * time.delta_seconds();
+        transform.translation = Vec3::from((edge.bounds.clamp_point(position), 0.));
+    }
+}
+
+fn draw_attractor(mut debug_lines: ResMut<DebugLines>, query: Query<(&Attractor, &Transform)>) {
+    for (attractor, transform) in query.iter() {
+        if attractor.enabled {
+            Bounds::new(transform.translation.truncate(), 8., 8.)
+                .debug_draw_lines(&mut debug_lines, Some(Color::YELLOW));
+        }
+    }
+}
+
+pub fn apply_attraction(
+    attractors: Query<(&Attractor, &Transform), Without<Ball>>,
+    mut balls: Query<(&Transform, &mut Force, &Ball)>,
+) {
+    for (attractor, attractor_transform) in attractors.iter() {
+        if !attractor.enabled {
+            continue;
+        }
+
+        let center = attractor_transform.translation.truncate();
+        for (transform, mut force, ball) in balls.iter_mut() {
+            force.0 += attraction(center, attractor.strength, transform.translation.truncate(), ball.mass);
+        }
+    }
+}
+
+/// Force with which an attractor at `center` pulls a ball of `mass` at
+/// `position`.
+#[inline]
+pub fn attraction(center: Vec2, strength: f32, position: Vec2, mass: f32) -> Vec2 {
+    let delta = center - position;
+    let distance = delta.length().max(ATTRACTOR_MIN_DISTANCE);
+
+    delta / distance * (strength / (distance * distance)) * mass
+}
@@ -0,0 +1,132 @@
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::*;
+
+use crate::*;
+
+/// Lets balls glow in their own color, brighter the faster they move, so
+/// high-energy collisions stand out. The renderer has no HDR bloom pass, so
+/// the glow is drawn as a few translucent halos behind each ball, which fade
+/// out towards the edge.
+pub struct BloomPlugin {
+    intensity: f32,
+    speed: f32,
+}
+
+impl BloomPlugin {
+    pub fn with_intensity(intensity: f32) -> Self {
+        Self { intensity, speed: 300. }
+    }
+}
+
+impl Default for BloomPlugin {
+    fn default() -> Self { Self::with_intensity(0.6) }
+}
+
+impl Plugin for BloomPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Bloom { intensity: self.intensity, speed: self.speed })
+            .add_system(add_glow)
+            .add_system(update_glow.after(add_glow));
+    }
+}
+
+pub struct Bloom {
+    /// Opacity of the glow of balls at full speed.
+    pub intensity: f32,
+    /// Speed at which balls glow at full intensity.
+    pub speed: f32,
+}
+
+impl Bloom {
+    /// Opacity of the glow of a ball moving at `speed`. The glow grows with
+    /// the square of the speed, like the energy of the ball.
+    #[inline]
+    pub fn alpha(&self, speed: f32) -> f32 {
+        let t = (speed / self.speed.max(f32::EPSILON)).min(1.);
+        self.intensity * t * t
+    }
+}
+
+// Radius of each halo relative to the ball, and its share of the glow.
+const GLOW_LAYERS: [(f32, f32); 3] = [(1.25, 0.5), (1.6, 0.3), (2.1, 0.2)];
+
+// Behind the ball the halos belong to.
+const GLOW_Z: f32 = -0.1;
+
+#[derive(Component)]
+struct GlowLayer {
+    scale: f32,
+    share: f32,
+}
+
+fn add_glow(
+    mut cmd: Commands,
+    balls: Query<(Entity, &Ball, Option<&Children>), Added<Ball>>,
+    layers: Query<(), With<GlowLayer>>,
+) {
+    for (entity, ball, children) in balls.iter() {
+        // pooled balls which are reused still have their halos
+        if children.map_or(false, |children| children.iter().any(|child| layers.get(*child).is_ok())) {
+            continue;
+        }
+        cmd.entity(entity).with_children(|parent| {
+            for (scale, share) in GLOW_LAYERS {
+                parent
+                    .spawn_bundle(GeometryBuilder::build_as(
+                        &shapes::Circle { radius: 1., center: Vec2::ZERO },
+                        DrawMode::Fill(FillMode::color(Color::NONE)),
+                        Transform::from_scale(Vec2::splat(ball.radius * scale).extend(1.))
+                            .with_translation(Vec3::new(0., 0., GLOW_Z)),
+                    ))
+                    .insert(GlowLayer { scale, share });
+            }
+        });
+    }
+}
+
+// Balls with their halos as children, pooled balls have no `Ball` and
+// `Velocity`.
+type GlowingBall<'a> = (Option<&'a Ball>, Option<&'a Velocity>, &'a DrawMode, &'a Visibility, &'a Children);
+
+fn update_glow(
+    bloom: Res<Bloom>,
+    balls: Query<GlowingBall, Without<GlowLayer>>,
+    mut layers: Query<(&mut Transform, &mut DrawMode, &mut Visibility, &GlowLayer)>,
+) {
+    for (ball, velocity, draw_mode, visibility, children) in balls.iter() {
+        let mut color = fill_color(draw_mode);
+        let alpha = match (ball, velocity) {
+            (Some(_), Some(velocity)) => bloom.alpha(velocity.speed()) * color.a(),
+            _ => 0.,
+        };
+        for child in children.iter() {
+            let (mut transform, mut layer_mode, mut layer_visibility, layer) = match layers.get_mut(*child) {
+                Ok(layer) => layer,
+                Err(_) => continue,
+            };
+            // the radius of balls can change, for example when they're reused
+            if let Some(ball) = ball {
+                transform.scale = Vec2::splat(ball.radius * layer.scale).extend(1.);
+            }
+
+            color.set_a(alpha * layer.share);
+            set_fill_color(&mut layer_mode, color);
+            // hidden balls don't hide their children
+            layer_visibility.is_visible = visibility.is_visible && alpha > 0.;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn faster_balls_glow_brighter() {
+        let bloom = Bloom { intensity: 0.8, speed: 100. };
+        assert_eq!(bloom.alpha(0.), 0.);
+        assert_eq!(bloom.alpha(50.), 0.2);
+        assert_eq!(bloom.alpha(100.), 0.8);
+        assert_eq!(bloom.alpha(400.), 0.8);
+    }
+}
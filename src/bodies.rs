@@ -0,0 +1,363 @@
+use bevy::ecs::system::EntityCommands;
+use bevy::prelude::*;
+use bevy_prototype_lyon::entity::Path;
+use bevy_prototype_lyon::prelude::*;
+#[cfg(feature = "scene")]
+use serde::{Deserialize, Serialize};
+
+use crate::*;
+
+// Amount of segments of each rounded end of a drawn capsule.
+const CAP_SEGMENTS: usize = 8;
+
+/// Shape of the balls of a kind. The radius of a ball is the radius of the
+/// circle around its shape, which is still used to find candidate pairs and
+/// to bounce off the walls.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "scene", derive(Serialize, Deserialize))]
+#[cfg_attr(not(feature = "scene"), allow(dead_code))]
+pub enum BallShape {
+    #[default]
+    Circle,
+    /// Rectangle where the height is `aspect` times the width.
+    Box { aspect: f32 },
+    /// Rounded rod, with caps of `thickness` times the radius.
+    Capsule { thickness: f32 },
+}
+
+impl BallShape {
+    /// Box of a body with `radius`, when it is one.
+    #[inline]
+    pub fn box_body(&self, radius: f32) -> Option<BoxBody> {
+        match *self {
+            BallShape::Box { aspect } => Some(BoxBody {
+                half_extents: Vec2::new(1., aspect).normalize_or_zero() * radius,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Capsule of a body with `radius`, when it is one.
+    #[inline]
+    pub fn capsule_body(&self, radius: f32) -> Option<CapsuleBody> {
+        match *self {
+            BallShape::Capsule { thickness } => {
+                let cap = radius * thickness.clamp(0., 1.);
+                Some(CapsuleBody { half_length: radius - cap, radius: cap })
+            }
+            _ => None,
+        }
+    }
+
+    /// Drawn shape of a body with `radius`.
+    pub fn path(&self, radius: f32) -> Path {
+        if let Some(body) = self.box_body(radius) {
+            return ShapePath::build_as(&shapes::Rectangle {
+                extents: body.half_extents * 2.,
+                origin: shapes::RectangleOrigin::Center,
+            });
+        }
+        if let Some(body) = self.capsule_body(radius) {
+            // half circles around both ends of the rod, counter clockwise
+            let right: Vec<Vec2> = (0..=CAP_SEGMENTS)
+                .map(|i| (i as f32 / CAP_SEGMENTS as f32 - 0.5) * std::f32::consts::PI)
+                .map(|angle| Vec2::new(body.half_length, 0.) + Vec2::new(angle.cos(), angle.sin()) * body.radius)
+                .collect();
+            let left = right.iter().map(|point| -*point);
+            return ShapePath::build_as(&shapes::Polygon {
+                points: right.iter().copied().chain(left).collect(),
+                closed: true,
+            });
+        }
+        ShapePath::build_as(&shapes::Circle {
+            radius,
+            ..default()
+        })
+    }
+
+    /// Give the ball of `entity`, which has `radius`, this shape. Replaces the
+    /// body and drawn shape it had before, bodies which aren't circles start
+    /// without spin.
+    pub fn apply(&self, entity: &mut EntityCommands, radius: f32) {
+        entity.remove::<BoxBody>()
+            .remove::<CapsuleBody>()
+            .remove::<CompoundCollider>()
+            .insert(self.path(radius));
+        if let Some(body) = self.box_body(radius) {
+            entity.insert(body).insert(AngularVelocity::default());
+        }
+        if let Some(body) = self.capsule_body(radius) {
+            entity.insert(body).insert(AngularVelocity::default());
+        }
+    }
+}
+
+/// Makes a ball a rectangle, centered on the ball and turned with its
+/// rotation.
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Reflect)]
+#[reflect(Component)]
+pub struct BoxBody {
+    pub half_extents: Vec2,
+}
+
+/// Makes a ball a capsule, a rod along the x axis of the ball with rounded
+/// ends of `radius`.
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Reflect)]
+#[reflect(Component)]
+pub struct CapsuleBody {
+    pub half_length: f32,
+    pub radius: f32,
+}
+
+/// Shape of a ball in the world.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BodyShape {
+    Circle { center: Vec2, radius: f32 },
+    /// Rectangle with its local x and y axes in the world.
+    Box { center: Vec2, axes: [Vec2; 2], half_extents: Vec2 },
+    /// Segment between `a` and `b`, rounded by `radius`.
+    Capsule { a: Vec2, b: Vec2, radius: f32 },
+}
+
+impl BodyShape {
+    pub fn of(transform: &Transform, ball: &Ball, box_body: Option<&BoxBody>, capsule: Option<&CapsuleBody>) -> Self {
+        let center = transform.translation.truncate();
+        let x_axis = (transform.rotation * Vec3::X).truncate().normalize_or_zero();
+        if let Some(body) = box_body {
+            return BodyShape::Box { center, axes: [x_axis, x_axis.perp()], half_extents: body.half_extents };
+        }
+        if let Some(body) = capsule {
+            let offset = x_axis * body.half_length;
+            return BodyShape::Capsule { a: center - offset, b: center + offset, radius: body.radius };
+        }
+        BodyShape::Circle { center, radius: ball.radius }
+    }
+}
+
+/// Components which give a ball another shape than its circle, as returned by
+/// a query of their options.
+pub type BodyParts<'a> = (Option<&'a BoxBody>, Option<&'a CapsuleBody>, Option<&'a CompoundCollider>);
+
+/// Shapes of a ball in the world, a single one unless it is a compound.
+pub fn body_shapes(transform: &Transform, ball: &Ball, (box_body, capsule, compound): BodyParts) -> Vec<BodyShape> {
+    match compound {
+        Some(compound) => compound.shapes(transform).collect(),
+        None => vec![BodyShape::of(transform, ball, box_body, capsule)],
+    }
+}
+
+/// Moment of inertia of a ball around its center, which depends on its shape.
+pub fn moment_of_inertia(ball: &Ball, (box_body, capsule, compound): BodyParts) -> f32 {
+    if let Some(compound) = compound {
+        return compound.moment_of_inertia();
+    }
+    if let Some(body) = box_body {
+        return ball.mass * body.half_extents.length_squared() / 3.;
+    }
+    if let Some(body) = capsule {
+        // the rod as a rectangle, and its caps as a disk at either end
+        let rod = 4. * body.half_length * body.radius;
+        let caps = std::f32::consts::PI * body.radius * body.radius;
+        let rod_mass = ball.mass * rod / (rod + caps);
+        let caps_mass = ball.mass - rod_mass;
+        return rod_mass * (body.half_length * body.half_length + body.radius * body.radius) / 3.
+            + caps_mass * (body.radius * body.radius / 2. + body.half_length * body.half_length);
+    }
+    ball.mass * ball.radius * ball.radius / 2.
+}
+
+/// Contact between two shapes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Contact {
+    /// Normal pointing from the first shape towards the second.
+    pub normal: Vec2,
+    /// Distance by which the shapes overlap along the normal.
+    pub depth: f32,
+    /// Point halfway between the surfaces of the shapes where they touch.
+    pub point: Vec2,
+}
+
+/// Deepest contact between any of the shapes of body `a` and any of `b`.
+pub fn body_contact(a: &[BodyShape], b: &[BodyShape]) -> Option<Contact> {
+    a.iter()
+        .flat_map(|a| b.iter().filter_map(move |b| shape_contact(a, b)))
+        .max_by(|x, y| x.depth.total_cmp(&y.depth))
+}
+
+/// Contact of shape `a` with shape `b`, or `None` when they don't touch.
+pub fn shape_contact(a: &BodyShape, b: &BodyShape) -> Option<Contact> {
+    use BodyShape::*;
+    match (*a, *b) {
+        (Circle { center: ca, radius: ra }, Circle { center: cb, radius: rb }) => circle_contact(ca, ra, cb, rb),
+        (Box { center, axes, half_extents }, Circle { center: point, radius }) => {
+            circle_box_contact(center, axes, half_extents, point, radius)
+        }
+        (Box { center: ca, axes: axes_a, half_extents: ha }, Box { center: cb, axes: axes_b, half_extents: hb }) => {
+            box_box_contact(ca, axes_a, ha, cb, axes_b, hb)
+        }
+        (Capsule { a: a1, b: b1, radius: ra }, Circle { center, radius: rb }) => {
+            circle_contact(closest_on_segment(a1, b1, center), ra, center, rb)
+        }
+        (Capsule { a: a1, b: b1, radius: ra }, Capsule { a: a2, b: b2, radius: rb }) => {
+            let (p, q) = closest_between_segments(a1, b1, a2, b2);
+            circle_contact(p, ra, q, rb)
+        }
+        (Box { center, axes, half_extents }, Capsule { a: a1, b: b1, radius }) => {
+            // the deepest of the ends and the point closest to the center of
+            // the box, which is exact unless the rod crosses a corner
+            [a1, b1, closest_on_segment(a1, b1, center)].into_iter()
+                .filter_map(|point| circle_box_contact(center, axes, half_extents, point, radius))
+                .max_by(|x, y| x.depth.total_cmp(&y.depth))
+        }
+        _ => shape_contact(b, a).map(|contact| Contact { normal: -contact.normal, ..contact }),
+    }
+}
+
+#[inline]
+fn circle_contact(a: Vec2, ra: f32, b: Vec2, rb: f32) -> Option<Contact> {
+    let delta = b - a;
+    let r = ra + rb;
+    if delta.length_squared() > r * r {
+        return None;
+    }
+    let distance = delta.length();
+    let normal = if distance > 0. { delta / distance } else { Vec2::X };
+    let depth = r - distance;
+    Some(Contact { normal, depth, point: a + normal * (ra - depth / 2.) })
+}
+
+// Contact from a box towards a circle, found within the frame of the box.
+#[inline]
+fn circle_box_contact(center: Vec2, axes: [Vec2; 2], half_extents: Vec2, point: Vec2, radius: f32) -> Option<Contact> {
+    let offset = point - center;
+    let local = Vec2::new(offset.dot(axes[0]), offset.dot(axes[1]));
+    let (normal, depth) = box_contact(Bounds::new(Vec2::ZERO, half_extents.x * 2., half_extents.y * 2.), local, radius)?;
+    let normal = axes[0] * normal.x + axes[1] * normal.y;
+    Some(Contact { normal, depth, point: point - normal * (radius - depth / 2.) })
+}
+
+// Separating axis test of two boxes, the axis with the least overlap is the
+// normal of the contact. The box which owns that axis is the reference box,
+// the other one touches it with its corner or side nearest to it.
+fn box_box_contact(
+    center_a: Vec2,
+    axes_a: [Vec2; 2],
+    half_a: Vec2,
+    center_b: Vec2,
+    axes_b: [Vec2; 2],
+    half_b: Vec2,
+) -> Option<Contact> {
+    let project = |axes: [Vec2; 2], half: Vec2, axis: Vec2| {
+        half.x * axes[0].dot(axis).abs() + half.y * axes[1].dot(axis).abs()
+    };
+    let delta = center_b - center_a;
+
+    let mut least: Option<(usize, Vec2, f32)> = None;
+    for (i, axis) in axes_a.into_iter().chain(axes_b).enumerate() {
+        let distance = delta.dot(axis);
+        let overlap = project(axes_a, half_a, axis) + project(axes_b, half_b, axis) - distance.abs();
+        if overlap < 0. {
+            return None;
+        }
+        if least.map_or(true, |(_, _, depth)| overlap < depth) {
+            least = Some((i, if distance < 0. { -axis } else { axis }, overlap));
+        }
+    }
+
+    let (i, normal, depth) = least?;
+    let point = if i < 2 {
+        let incident = box_support(center_b, axes_b, half_b, -normal);
+        clamp_to_face(incident, center_a, axes_a[1 - i], half_a[1 - i]) + normal * (depth / 2.)
+    } else {
+        let incident = box_support(center_a, axes_a, half_a, normal);
+        clamp_to_face(incident, center_b, axes_b[3 - i], half_b[3 - i]) - normal * (depth / 2.)
+    };
+    Some(Contact { normal, depth, point })
+}
+
+// Point of a box which is furthest in `direction`, the middle of a side when
+// the side faces that way.
+#[inline]
+fn box_support(center: Vec2, axes: [Vec2; 2], half_extents: Vec2, direction: Vec2) -> Vec2 {
+    let sign = |dot: f32| if dot.abs() < 1e-4 { 0. } else { dot.signum() };
+    center
+        + axes[0] * half_extents.x * sign(axes[0].dot(direction))
+        + axes[1] * half_extents.y * sign(axes[1].dot(direction))
+}
+
+// Keep `point` within the extent of a side of the reference box, along the
+// side with `tangent` and `half_length`.
+#[inline]
+fn clamp_to_face(point: Vec2, center: Vec2, tangent: Vec2, half_length: f32) -> Vec2 {
+    let along = (point - center).dot(tangent);
+    point + tangent * (along.clamp(-half_length, half_length) - along)
+}
+
+#[inline]
+fn closest_on_segment(a: Vec2, b: Vec2, point: Vec2) -> Vec2 {
+    let ab = b - a;
+    let length_sq = ab.length_squared();
+    if length_sq == 0. {
+        return a;
+    }
+    a + ab * ((point - a).dot(ab) / length_sq).clamp(0., 1.)
+}
+
+// Closest points of two segments, which don't intersect unless the capsules
+// around them are very thin.
+fn closest_between_segments(a1: Vec2, b1: Vec2, a2: Vec2, b2: Vec2) -> (Vec2, Vec2) {
+    let candidates = [
+        (a1, closest_on_segment(a2, b2, a1)),
+        (b1, closest_on_segment(a2, b2, b1)),
+        (closest_on_segment(a1, b1, a2), a2),
+        (closest_on_segment(a1, b1, b2), b2),
+    ];
+    candidates.into_iter()
+        .min_by(|(p1, q1), (p2, q2)| p1.distance_squared(*q1).total_cmp(&p2.distance_squared(*q2)))
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shapes_collide_along_the_least_overlap() {
+        let square = |center: Vec2, angle: f32| BodyShape::Box {
+            center,
+            axes: [Vec2::new(angle.cos(), angle.sin()), Vec2::new(-angle.sin(), angle.cos())],
+            half_extents: Vec2::splat(5.),
+        };
+        let circle = |center: Vec2| BodyShape::Circle { center, radius: 2. };
+        let normal_depth = |contact: Option<Contact>| contact.map(|contact| (contact.normal, contact.depth));
+
+        // circle against the top of the box, and the other way around
+        let contact = shape_contact(&square(Vec2::ZERO, 0.), &circle(Vec2::new(1., 6.)));
+        assert_eq!(contact, Some(Contact { normal: Vec2::Y, depth: 1., point: Vec2::new(1., 4.5) }));
+        let contact = shape_contact(&circle(Vec2::new(1., 6.)), &square(Vec2::ZERO, 0.));
+        assert_eq!(contact, Some(Contact { normal: -Vec2::Y, depth: 1., point: Vec2::new(1., 4.5) }));
+        assert_eq!(shape_contact(&square(Vec2::ZERO, 0.), &circle(Vec2::new(8., 8.))), None);
+
+        // boxes side by side overlap along x, and touch halfway their sides
+        let contact = shape_contact(&square(Vec2::ZERO, 0.), &square(Vec2::new(-9., 2.), 0.));
+        assert_eq!(contact, Some(Contact { normal: -Vec2::X, depth: 1., point: Vec2::new(-4.5, 2.) }));
+        // a box turned by 45 degrees reaches further, with its corner
+        let turned = square(Vec2::new(12., 0.), std::f32::consts::FRAC_PI_4);
+        let contact = shape_contact(&square(Vec2::ZERO, 0.), &turned).unwrap();
+        assert_eq!(contact.normal, Vec2::X);
+        assert!((contact.depth - (5. * 2f32.sqrt() - 7.)).abs() < 1e-5, "{}", contact.depth);
+        assert!(contact.point.abs_diff_eq(Vec2::new(5. - contact.depth / 2., 0.), 1e-5), "{}", contact.point);
+
+        // capsules touch at their closest points
+        let capsule = |a: Vec2, b: Vec2| BodyShape::Capsule { a, b, radius: 1. };
+        let contact = shape_contact(
+            &capsule(Vec2::new(-5., 0.), Vec2::new(5., 0.)),
+            &capsule(Vec2::new(3., 1.5), Vec2::new(3., 10.)),
+        );
+        assert_eq!(contact, Some(Contact { normal: Vec2::Y, depth: 0.5, point: Vec2::new(3., 0.75) }));
+        assert_eq!(
+            normal_depth(shape_contact(&square(Vec2::ZERO, 0.), &capsule(Vec2::new(-20., 5.5), Vec2::new(20., 5.5)))),
+            Some((Vec2::Y, 0.5)),
+        );
+    }
+}
@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+
+use crate::*;
+
+/// Adds flocking behavior to all balls with a `Boid` component. When
+/// `all_balls` is set, every spawned ball becomes a boid.
+pub struct BoidsPlugin {
+    pub all_balls: bool,
+    pub settings: BoidSettings,
+}
+
+impl Default for BoidsPlugin {
+    fn default() -> Self {
+        Self {
+            all_balls: true,
+            settings: BoidSettings::default(),
+        }
+    }
+}
+
+impl Plugin for BoidsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(self.settings)
+            .add_system_to_stage(PhysicsStage, apply_flocking.before(PhysicsSystem::Integrate));
+
+        if self.all_balls {
+            app.add_system(make_boids);
+        }
+    }
+}
+
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct Boid;
+
+#[derive(Clone, Copy, Debug)]
+pub struct BoidSettings {
+    /// Distance within which other boids are considered neighbors.
+    pub perception: f32,
+    /// Steer away from neighbors which are too close.
+    pub separation: f32,
+    /// Steer towards the average velocity of neighbors.
+    pub alignment: f32,
+    /// Steer towards the average position of neighbors.
+    pub cohesion: f32,
+    /// Boids don't accelerate beyond this speed by flocking.
+    pub max_speed: f32,
+}
+
+impl Default for BoidSettings {
+    fn default() -> Self {
+        Self {
+            perception: 50.,
+            separation: 4000.,
+            alignment: 1.,
+            cohesion: 0.5,
+            max_speed: 120.,
+        }
+    }
+}
+
+fn make_boids(mut cmd: Commands, mut spawned: EventReader<BallSpawned>) {
+    for BallSpawned(entity) in spawned.iter() {
+        cmd.entity(*entity).insert(Boid);
+    }
+}
+
+fn apply_flocking(
+    settings: Res<BoidSettings>,
+    edge: Res<EdgeCollider>,
+    mut query: Query<(Entity, &Transform, &Velocity, &mut Impulse, &Ball), With<Boid>>,
+) {
+    let mut tree = QuadTree::new(edge.bounds, Options::default());
+    let mut boids = HashMap::new();
+    for (entity, transform, velocity, _, _) in query.iter() {
+        let position = transform.translation.truncate();
+        let _ = tree.insert(Location::Point(position), entity);
+        boids.insert(entity, (position, velocity.0));
+    }
+
+    for (entity, _, velocity, mut impulse, ball) in query.iter_mut() {
+        let (position, _) = boids[&entity];
+        let mut separation = Vec2::ZERO;
+        let mut alignment = Vec2::ZERO;
+        let mut cohesion = Vec2::ZERO;
+        let mut neighbors = 0;
+
+        for (_, other) in tree.query_circle(position, settings.perception) {
+            if other == entity {
+                continue;
+            }
+
+            let (other_position, other_velocity) = boids[&other];
+            let delta = position - other_position;
+            let distance_squared = delta.length_squared().max(1.);
+
+            separation += delta / distance_squared;
+            alignment += other_velocity;
+            cohesion += other_position;
+            neighbors += 1;
+        }
+        if neighbors == 0 {
+            continue;
+        }
+
+        let neighbors = neighbors as f32;
+        let acceleration = separation * settings.separation
+            + (alignment / neighbors - velocity.0) * settings.alignment
+            + (cohesion / neighbors - position) * settings.cohesion;
+
+        // flocking may steer, but not speed up beyond the maximum
+        let mut steered = Velocity(velocity.0 + acceleration * TIMESTEP);
+        steered.clamp_speed(settings.max_speed.max(velocity.speed()));
+        impulse.0 += (steered.0 - velocity.0) * ball.mass;
+    }
+}
@@ -0,0 +1,65 @@
+use bevy::prelude::*;
+#[cfg(not(feature = "strict-math"))]
+use rand_distr::{Distribution, Normal};
+
+use crate::*;
+
+/// Adds small random impulses to every ball each physics tick, simulating
+/// thermal jitter.
+pub struct BrownianMotionPlugin {
+    temperature: f32,
+}
+
+impl BrownianMotionPlugin {
+    pub fn with_temperature(temperature: f32) -> Self {
+        Self { temperature }
+    }
+}
+
+impl Default for BrownianMotionPlugin {
+    fn default() -> Self { Self::with_temperature(500.) }
+}
+
+impl Plugin for BrownianMotionPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(BrownianMotion { temperature: self.temperature })
+            .add_system_to_stage(PhysicsStage, apply_brownian_motion.before(PhysicsSystem::Integrate));
+    }
+}
+
+pub struct BrownianMotion {
+    /// Strength of the random impulses. Lighter balls are pushed around more
+    /// than heavy ones at the same temperature.
+    pub temperature: f32,
+}
+
+fn apply_brownian_motion(
+    motion: Res<BrownianMotion>,
+    mut rng: ResMut<SimRng>,
+    mut query: Query<(&mut Impulse, &Ball)>,
+) {
+    if motion.temperature <= 0. {
+        return;
+    }
+
+    let rng = &mut *rng;
+
+    for (mut impulse, ball) in query.iter_mut() {
+        // the standard deviation of the velocity change grows with the square
+        // root of the elapsed time, as with a random walk
+        let sigma = f32::sqrt(motion.temperature * TIMESTEP / ball.mass);
+        // balls without a sensible mass, like massless or infinitely heavy
+        // ones, aren't jittered
+        if !sigma.is_finite() || sigma <= 0. {
+            continue;
+        }
+        #[cfg(not(feature = "strict-math"))]
+        let dv = {
+            let normal = Normal::new(0., sigma).unwrap();
+            Vec2::new(normal.sample(&mut **rng), normal.sample(&mut **rng))
+        };
+        #[cfg(feature = "strict-math")]
+        let dv = strict::gaussian(&mut **rng) * sigma;
+        impulse.0 += dv * ball.mass;
+    }
+}
@@ -0,0 +1,157 @@
+use std::f32::consts::{PI, TAU};
+
+use bevy::prelude::*;
+
+use crate::*;
+
+/// Spawns a ring of balls flying away from the cursor with
+/// `Action::SpawnBurst`, and blows the balls around the cursor away with
+/// `Action::Explode`. Both are recorded as edits, so they can be undone.
+pub struct BurstPlugin {
+    size: usize,
+}
+
+impl BurstPlugin {
+    pub fn with_size(size: usize) -> Self {
+        Self { size }
+    }
+}
+
+impl Default for BurstPlugin {
+    fn default() -> Self { Self::with_size(24) }
+}
+
+impl Plugin for BurstPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Burst { size: self.size, speed: 300., radius: 150., strength: 600. })
+            .bind_action(Action::SpawnBurst, [Binding::Key(KeyCode::B)])
+            .bind_action(Action::Explode, [Binding::Key(KeyCode::X)])
+            .add_system(spawn_burst)
+            .add_system(explode);
+    }
+}
+
+pub struct Burst {
+    /// Amount of balls spawned by a burst.
+    pub size: usize,
+    /// Speed of the balls spawned by a burst.
+    pub speed: f32,
+    /// Distance from the cursor within which balls are blown away.
+    pub radius: f32,
+    /// Change in speed of balls at the center of an explosion, which
+    /// decreases linearly towards its edge.
+    pub strength: f32,
+}
+
+impl Burst {
+    /// Directions of the balls of a burst, evenly spread over a circle.
+    pub fn directions(&self) -> impl Iterator<Item = Vec2> {
+        let size = self.size;
+        (0..size).map(move |i| {
+            let angle = i as f32 / size as f32 * TAU;
+            Vec2::new(angle.cos(), angle.sin())
+        })
+    }
+
+    /// Change in velocity of a ball at `position` by an explosion at
+    /// `center`, or `None` when the ball is out of reach.
+    pub fn blast(&self, center: Vec2, position: Vec2) -> Option<Vec2> {
+        let delta = position - center;
+        let distance = delta.length();
+        if distance >= self.radius {
+            return None;
+        }
+        // balls at the center are blown upwards
+        let direction = if distance > f32::EPSILON { delta / distance } else { Vec2::Y };
+        Some(direction * self.strength * (1. - distance / self.radius))
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn spawn_burst(
+    mut cmd: Commands,
+    mut rng: ResMut<SimRng>,
+    mut counts: ResMut<KindCounts>,
+    mut edits: EventWriter<Edit>,
+    mut colors: ResMut<SpawnColors>,
+    burst: Res<Burst>,
+    palette: Res<Palette>,
+    kinds: Res<BallKinds>,
+    actions: Res<Input<Action>>,
+    edge: Res<EdgeCollider>,
+    picker: Picker,
+) {
+    if !actions.just_pressed(Action::SpawnBurst) {
+        return;
+    }
+    let cursor = match picker.cursor() {
+        Some(cursor) => cursor,
+        None => return,
+    };
+
+    // balls start on a ring around the cursor, on which they just fit
+    let ring = burst.size as f32 * *BALL_RADIUS.end() / PI;
+    let rng = &mut **rng;
+    let mut spawned = Vec::with_capacity(burst.size);
+    for direction in burst.directions() {
+        let kind = match kinds.choose(rng, &counts) {
+            Some(kind) => kind,
+            None => break,
+        };
+        counts.add(kind);
+        let position = edge.bounds.clamp_point(cursor + direction * ring);
+        let ball = new_ball(rng, &kinds, kind, &edge, &mut colors, &palette, direction * burst.speed, position);
+        let mut entity = ball.spawn(&mut cmd);
+        kinds.get(kind).body.apply(&mut entity, ball.radius());
+        spawned.push(entity.id());
+    }
+    if !spawned.is_empty() {
+        edits.send(Edit::Spawned(spawned));
+    }
+}
+
+fn explode(
+    mut edits: EventWriter<Edit>,
+    burst: Res<Burst>,
+    actions: Res<Input<Action>>,
+    picker: Picker,
+    mut query: Query<(Entity, &Ball, &Transform, &mut Impulse)>,
+) {
+    if !actions.just_pressed(Action::Explode) {
+        return;
+    }
+    let cursor = match picker.cursor() {
+        Some(cursor) => cursor,
+        None => return,
+    };
+
+    let mut changes = Vec::new();
+    for (entity, ball, transform, mut impulse) in query.iter_mut() {
+        if let Some(change) = burst.blast(cursor, transform.translation.truncate()) {
+            impulse.0 += change * ball.mass;
+            changes.push((entity, change));
+        }
+    }
+    if !changes.is_empty() {
+        edits.send(Edit::Pushed(changes));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bursts_spread_and_explosions_fade_with_distance() {
+        let burst = Burst { size: 4, speed: 100., radius: 10., strength: 50. };
+        let directions: Vec<Vec2> = burst.directions().collect();
+        assert_eq!(directions.len(), 4);
+        assert!(directions[1].abs_diff_eq(Vec2::Y, 1e-6));
+        assert!(directions[2].abs_diff_eq(-Vec2::X, 1e-6));
+
+        let center = Vec2::new(5., 5.);
+        assert_eq!(burst.blast(center, Vec2::new(5., 5.)), Some(Vec2::new(0., 50.)));
+        assert_eq!(burst.blast(center, Vec2::new(10., 5.)), Some(Vec2::new(25., 0.)));
+        assert_eq!(burst.blast(center, Vec2::new(5., -5.)), None);
+    }
+}
@@ -0,0 +1,162 @@
+use std::time::Duration;
+
+use crate::*;
+
+/// Determines the leaf capacity of the quadtree which is used to find
+/// colliding balls.
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug)]
+pub enum CapacityModel {
+    /// All leaves have the same capacity.
+    Fixed(usize),
+    /// Leaves have a base capacity, which grows with each level of depth.
+    DepthScaled { capacity: usize, growth: usize },
+    /// Capacity is tuned each second, balancing the time spent on building
+    /// the tree against the time spent on checking pairs of balls.
+    Auto,
+}
+
+// Range within which the capacity is auto-tuned.
+const AUTO_CAPACITY: RangeInclusive<usize> = 1..=64;
+
+// Simulated time between auto-tuning the capacity, in seconds.
+const AUTO_CAPACITY_INTERVAL: f32 = 1.;
+
+/// Leaf capacity of the collision quadtree, including measurements to
+/// auto-tune it.
+pub struct TreeCapacity {
+    pub model: CapacityModel,
+    capacity: usize,
+    build_time: Duration,
+    pair_time: Duration,
+    elapsed: f32,
+}
+
+impl TreeCapacity {
+    pub fn new(model: CapacityModel) -> Self {
+        let capacity = match model {
+            CapacityModel::Fixed(capacity) => capacity,
+            CapacityModel::DepthScaled { capacity, .. } => capacity,
+            CapacityModel::Auto => 4,
+        };
+
+        Self {
+            model,
+            capacity,
+            build_time: Duration::ZERO,
+            pair_time: Duration::ZERO,
+            elapsed: 0.,
+        }
+    }
+
+    /// Current base capacity of a leaf.
+    #[allow(dead_code)]
+    #[inline]
+    pub fn capacity(&self) -> usize { self.capacity }
+
+    /// Options of the quadtree, with its capacity set.
+    #[inline]
+    pub fn options(&self, options: Options) -> Options {
+        let capacity_growth = match self.model {
+            CapacityModel::DepthScaled { growth, .. } => growth,
+            _ => 0,
+        };
+
+        Options {
+            capacity: self.capacity,
+            capacity_growth,
+            ..options
+        }
+    }
+
+    /// Record the time spent on building the tree during a physics tick.
+    #[inline]
+    pub fn record_build(&mut self, build_time: Duration) {
+        if matches!(self.model, CapacityModel::Auto) {
+            self.build_time += build_time;
+        }
+    }
+
+    /// End a physics tick, once all of its pairs are checked, and tune the
+    /// capacity when needed. Called once per tick.
+    pub fn end_tick(&mut self) {
+        if !matches!(self.model, CapacityModel::Auto) {
+            return;
+        }
+
+        self.elapsed += TIMESTEP;
+        if self.elapsed < AUTO_CAPACITY_INTERVAL {
+            return;
+        }
+
+        self.capacity = tuned_capacity(self.capacity, self.build_time, self.pair_time);
+        self.build_time = Duration::ZERO;
+        self.pair_time = Duration::ZERO;
+        self.elapsed = 0.;
+    }
+
+    /// Record time spent on finding and checking pairs of balls, which may be
+    /// spread over multiple systems during a tick.
+    #[inline]
+    pub fn record_pairs(&mut self, pair_time: Duration) {
+        if matches!(self.model, CapacityModel::Auto) {
+            self.pair_time += pair_time;
+        }
+    }
+}
+
+impl Default for TreeCapacity {
+    fn default() -> Self { Self::new(CapacityModel::Fixed(4)) }
+}
+
+/// Ends the tick of the `TreeCapacity`, after the collisions are resolved.
+pub(crate) fn tune_tree_capacity(mut capacity: ResMut<TreeCapacity>) {
+    capacity.end_tick();
+}
+
+// The time spent on pair checks grows with the capacity, while the time spent
+// on building the tree shrinks with it. Move the capacity towards the point
+// where both are equal, changing it by at most a factor of 2 at once.
+#[inline]
+fn tuned_capacity(capacity: usize, build_time: Duration, pair_time: Duration) -> usize {
+    let build = build_time.as_secs_f32();
+    let pair = pair_time.as_secs_f32();
+    if build <= 0. || pair <= 0. {
+        return capacity;
+    }
+
+    let factor = (build / pair).sqrt().clamp(0.5, 2.);
+    let capacity = (capacity as f32 * factor).round() as usize;
+    capacity.clamp(*AUTO_CAPACITY.start(), *AUTO_CAPACITY.end())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tuned_capacity_balances_times() {
+        let ms = Duration::from_millis;
+        assert_eq!(tuned_capacity(8, ms(4), ms(1)), 16);
+        assert_eq!(tuned_capacity(8, ms(1), ms(4)), 4);
+        assert_eq!(tuned_capacity(8, ms(2), ms(2)), 8);
+        assert_eq!(tuned_capacity(1, ms(1), ms(100)), 1);
+        assert_eq!(tuned_capacity(8, Duration::ZERO, ms(1)), 8);
+    }
+
+    #[test]
+    fn capacity_is_tuned_at_the_end_of_a_tick() {
+        let mut capacity = TreeCapacity::new(CapacityModel::Auto);
+        let mut elapsed = 0.;
+        while capacity.capacity() == 4 {
+            capacity.record_build(Duration::from_millis(1));
+            // the pairs of the tick are yet to be recorded
+            assert_eq!(capacity.capacity(), 4);
+            capacity.record_pairs(Duration::from_millis(4));
+            capacity.end_tick();
+            elapsed += TIMESTEP;
+            assert!(elapsed < AUTO_CAPACITY_INTERVAL + 2. * TIMESTEP);
+        }
+        assert_eq!(capacity.capacity(), 2);
+    }
+}
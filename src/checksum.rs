@@ -0,0 +1,51 @@
+use bevy::prelude::*;
+
+use crate::*;
+
+// Parameters of the 64 bit FNV-1a hash.
+const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+/// Hash of the positions and velocities of all balls, in the order of their
+/// entities. Worlds in which the physics played out identically have the same
+/// checksum. Unlike the hasher of the standard library, the hash is the same
+/// on every platform and release.
+pub fn state_checksum(world: &mut World) -> u64 {
+    let mut balls: Vec<(Entity, Vec2, Vec2)> = world
+        .query_filtered::<(Entity, &Transform, &Velocity), With<Ball>>()
+        .iter(world)
+        .map(|(entity, transform, velocity)| (entity, transform.translation.truncate(), velocity.0))
+        .collect();
+    balls.sort_unstable_by_key(|(entity, ..)| *entity);
+
+    let mut hash = FNV_OFFSET;
+    for (_, position, velocity) in balls {
+        for value in [position.x, position.y, velocity.x, velocity.y] {
+            for byte in value.to_bits().to_le_bytes() {
+                hash ^= byte as u64;
+                hash = hash.wrapping_mul(FNV_PRIME);
+            }
+        }
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checksum_follows_the_state_of_every_ball() {
+        let (mut world, entities) = headless_world(1689, 10, Bounds::new(Vec2::ZERO, 400., 300.));
+        let checksum = state_checksum(&mut world);
+        assert_eq!(state_checksum(&mut world), checksum);
+        // the same seed gives the same state
+        assert_eq!(state_checksum(&mut headless_world(1689, 10, Bounds::new(Vec2::ZERO, 400., 300.)).0), checksum);
+
+        world.get_mut::<Velocity>(entities[3]).unwrap().0.x += 1e-3;
+        assert_ne!(state_checksum(&mut world), checksum);
+        world.get_mut::<Velocity>(entities[3]).unwrap().0.x -= 1e-3;
+        world.get_mut::<Transform>(entities[7]).unwrap().translation.y += 1e-3;
+        assert_ne!(state_checksum(&mut world), checksum);
+    }
+}
@@ -0,0 +1,210 @@
+use std::collections::{HashMap, HashSet};
+
+use bevy::prelude::*;
+use bevy::render::camera::Camera2d;
+
+use crate::*;
+
+/// Lets the camera decide which chunks of a `BroadPhase::Chunked` world are
+/// active. Balls in chunks outside the view, grown by `margin`, are made
+/// `Dormant` until the camera comes near them again.
+pub struct ChunkedWorldPlugin {
+    margin: f32,
+}
+
+impl ChunkedWorldPlugin {
+    pub fn with_margin(margin: f32) -> Self {
+        Self { margin }
+    }
+}
+
+impl Default for ChunkedWorldPlugin {
+    fn default() -> Self { Self::with_margin(100.) }
+}
+
+impl Plugin for ChunkedWorldPlugin {
+    fn build(&self, app: &mut App) {
+        let margin = self.margin;
+        app.add_system(move |
+            mut chunks: ResMut<ChunkedWorld>,
+            windows: Res<Windows>,
+            cameras: Query<&Transform, With<Camera2d>>,
+        | {
+            if let (Some(window), Ok(camera)) = (windows.get_primary(), cameras.get_single()) {
+                chunks.view = Some(view_bounds(window, camera).expanded(margin));
+            }
+        })
+            .register_type::<Dormant>()
+            .add_system(mark_dormant_balls);
+    }
+}
+
+/// Excludes a ball in an inactive chunk from integration. It keeps its
+/// velocity for when its chunk is active again.
+#[derive(Component, Clone, Copy, Debug, Default, Reflect)]
+#[reflect(Component)]
+pub struct Dormant;
+
+/// World split into square chunks, each with a `QuadTree` of its own, so the
+/// arena can be far larger than a single tree handles well. A chunk is only
+/// kept while a ball which is awake overlaps it, and a chunk is active while
+/// it is in view. Balls in inactive chunks are dormant, they are only checked
+/// against balls which are awake. Balls which overlap the border of a chunk
+/// are stored in each chunk they overlap, which hands them over to the next
+/// chunk and finds the collisions across the border.
+#[derive(Default)]
+pub struct ChunkedWorld {
+    /// Part of the world which is in view, chunks outside of it are inactive.
+    /// Without a view, all chunks are active.
+    pub view: Option<Bounds>,
+    size: f32,
+    chunks: HashMap<IVec2, QuadTree>,
+    dormant: HashSet<Entity>,
+    pairs: Vec<[Entity; 2]>,
+}
+
+impl ChunkedWorld {
+    /// Candidate pairs of colliding balls, of which at least one is awake.
+    #[inline]
+    pub fn pairs(&self) -> &[[Entity; 2]] { &self.pairs }
+
+    /// Amount of chunks which are kept.
+    #[allow(dead_code)]
+    #[inline]
+    pub fn chunk_count(&self) -> usize { self.chunks.len() }
+
+    #[inline]
+    pub fn is_dormant(&self, entity: Entity) -> bool { self.dormant.contains(&entity) }
+
+    /// Chunk which contains `position`.
+    #[inline]
+    pub fn chunk_at(&self, position: Vec2) -> IVec2 {
+        (position / self.size).floor().as_ivec2()
+    }
+
+    #[inline]
+    pub fn chunk_bounds(&self, chunk: IVec2) -> Bounds {
+        Bounds::from_corners(chunk.as_vec2() * self.size, (chunk + IVec2::ONE).as_vec2() * self.size)
+    }
+
+    #[inline]
+    pub fn is_active(&self, chunk: IVec2) -> bool {
+        self.view.map_or(true, |view| view.intersects(self.chunk_bounds(chunk)))
+    }
+
+    // Chunks overlapped by a ball at `position` with `radius`.
+    fn overlapped(&self, position: Vec2, radius: f32) -> impl Iterator<Item = IVec2> {
+        let (min, max) = (self.chunk_at(position - radius), self.chunk_at(position + radius));
+        (min.x..=max.x).flat_map(move |x| (min.y..=max.y).map(move |y| IVec2::new(x, y)))
+    }
+
+    /// Split `balls` into chunks of `size` and find the pairs of balls which
+    /// share a leaf of a chunk. Returns the balls which could not be stored.
+    pub fn rebuild(&mut self, size: f32, options: Options, balls: &[(Entity, Vec2, f32)]) -> Vec<QuadTreeError> {
+        self.size = size.max(f32::EPSILON);
+        self.chunks.clear();
+        self.dormant.clear();
+        self.pairs.clear();
+
+        let mut kept = HashSet::new();
+        let mut reach: f32 = 0.;
+        for (entity, position, radius) in balls {
+            if self.is_active(self.chunk_at(*position)) {
+                kept.extend(self.overlapped(*position, *radius));
+            } else {
+                self.dormant.insert(*entity);
+            }
+            reach = reach.max(radius * 2.);
+        }
+        // the trees reach past their chunk, so the balls which overlap it fit
+        for chunk in kept {
+            self.chunks.insert(chunk, QuadTree::new(self.chunk_bounds(chunk).expanded(reach), options));
+        }
+
+        let mut errors = Vec::new();
+        for (entity, position, radius) in balls {
+            let location = Location::new(*position, radius * 2., radius * 2.);
+            for chunk in self.overlapped(*position, *radius) {
+                if let Some(tree) = self.chunks.get_mut(&chunk) {
+                    if let Err(err) = tree.insert(location, *entity) {
+                        errors.push(err);
+                    }
+                }
+            }
+        }
+
+        let (dormant, pairs) = (&self.dormant, &mut self.pairs);
+        for tree in self.chunks.values() {
+            tree.for_each_leaf(&mut |leaf| {
+                let elems = leaf.leaf_elements().unwrap();
+                for (i, (_, a)) in elems.iter().enumerate() {
+                    for (_, b) in elems[i + 1..].iter() {
+                        if !dormant.contains(a) || !dormant.contains(b) {
+                            pairs.push(if a < b { [*a, *b] } else { [*b, *a] });
+                        }
+                    }
+                }
+            });
+        }
+        // balls which overlap multiple chunks are paired in each of them
+        self.pairs.sort_unstable();
+        self.pairs.dedup();
+        errors
+    }
+}
+
+fn mark_dormant_balls(
+    mut cmd: Commands,
+    chunks: Res<ChunkedWorld>,
+    query: Query<(Entity, Option<&Dormant>), With<Ball>>,
+) {
+    for (entity, dormant) in query.iter() {
+        match (chunks.is_dormant(entity), dormant.is_some()) {
+            (true, false) => {
+                cmd.entity(entity).insert(Dormant);
+            }
+            (false, true) => {
+                cmd.entity(entity).remove::<Dormant>();
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn balls_are_paired_across_chunk_borders() {
+        let ball = |id: u32, x: f32, y: f32| (Entity::from_raw(id), Vec2::new(x, y), 4.);
+        let balls = [
+            // touching across the border of the first two chunks
+            ball(0, 98., 50.),
+            ball(1, 105., 50.),
+            // far away, touching each other
+            ball(2, 950., 950.),
+            ball(3, 955., 950.),
+        ];
+        let mut chunks = ChunkedWorld::default();
+        let errors = chunks.rebuild(100., Options::default(), &balls);
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert_eq!(chunks.chunk_count(), 3);
+        assert_eq!(chunks.pairs(), &[[balls[0].0, balls[1].0], [balls[2].0, balls[3].0]]);
+
+        // out of view, the far balls are dormant and their chunk is dropped
+        chunks.view = Some(Bounds::new(Vec2::new(60., 50.), 100., 100.));
+        chunks.rebuild(100., Options::default(), &balls);
+        assert_eq!(chunks.chunk_count(), 2);
+        assert_eq!(chunks.pairs(), &[[balls[0].0, balls[1].0]]);
+        assert!(!chunks.is_dormant(balls[1].0));
+        assert!(chunks.is_dormant(balls[2].0));
+
+        // an awake ball still collides with a dormant one across the border
+        chunks.view = Some(Bounds::new(Vec2::new(50., 50.), 50., 50.));
+        assert!(!chunks.is_active(chunks.chunk_at(balls[1].1)));
+        chunks.rebuild(100., Options::default(), &balls);
+        assert!(chunks.is_dormant(balls[1].0));
+        assert_eq!(chunks.pairs(), &[[balls[0].0, balls[1].0]]);
+    }
+}
@@ -0,0 +1,296 @@
+use std::fmt::{self, Formatter};
+
+use crate::{ExitCriteria, HeadlessPhysics, Scenario};
+
+pub const USAGE: &str = "\
+usage: bevy-collision-balls [command] [options]
+
+commands:
+    run                 interactive simulation, the default
+    bench               headless benchmark of the physics
+    bench compare       headless benchmark of each broad phase, side by side
+    soak                headless run which checks the physics for violations
+    headless            headless run until one of its exit criteria is met
+    replay <file>       headless playback of a recording, which checks that
+                        each tick plays out the same
+    validate <scene>    check a scene file without opening a window
+
+options:
+    --palette <name>    palette balls are colored with (run)
+    --colors <strategy> how balls are colored when they spawn, one of
+                        round-robin, random, radius, speed or quadrant (run)
+    --lang <code>       language of the help overlay, en or nl (run)
+    --kinds <file>      kinds of balls to spawn, a RON list (run)
+    --input <file>      keys of actions, a RON list of controls and their
+                        bindings (run)
+    --config <scene>    scene to start from, or a Bevy scene of balls when it
+                        ends in .scn.ron (run)
+    --prewarm <n>       amount of physics ticks to run before the window is
+                        opened (run)
+    --lockstep <addr>   UDP address to play in lockstep on, with the other
+                        player at --peer (run)
+    --peer <addr>       UDP address of the other player in lockstep (run)
+    --record <file>     record each physics tick, to play back with replay
+                        (run)
+    --ticks <n>         amount of physics ticks (bench, bench compare)
+    --duration <s>      amount of seconds to run for (soak)
+    --until-tick <n>    stop after this tick (headless)
+    --until-settled     stop once the kinetic energy of the balls stayed low
+                        for a while (headless)
+    --gravity <n>       downward acceleration of the balls (headless)
+    --drag <n>          fraction of their velocity balls lose per second
+                        (headless)
+    --restitution <n>   fraction of the speed which is preserved when balls
+                        bounce, from 0 to 1 (headless)
+    --balls <n>         amount of balls (bench, bench compare, soak, headless)
+    --seed <n>          seed of the random number generator (bench,
+                        bench compare, soak, headless)
+    --scenario <name>   distribution of the balls, one of uniform, point, line,
+                        diagonal or exponential (bench, bench compare, soak,
+                        headless)";
+
+/// What the binary does, selected by the first command line argument.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+    Run { config: Option<String>, prewarm: usize },
+    Bench { ticks: usize, balls: usize, seed: u64, scenario: Scenario },
+    /// Like `Bench`, once for each broad phase.
+    BenchCompare { ticks: usize, balls: usize, seed: u64, scenario: Scenario },
+    Soak { duration: u64, balls: usize, seed: u64, scenario: Scenario },
+    Headless { exit: ExitCriteria, physics: HeadlessPhysics, balls: usize, seed: u64, scenario: Scenario },
+    Replay(String),
+    Validate(String),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum CliError {
+    UnknownCommand(String),
+    /// A command is missing its positional argument, which is named.
+    MissingArgument(&'static str, &'static str),
+    InvalidValue(&'static str, String),
+    /// A command is missing all of the options it needs one of.
+    MissingOption(&'static str, &'static str),
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        use CliError::*;
+        match self {
+            UnknownCommand(command) => write!(f, "unknown command `{}`", command),
+            MissingArgument(command, name) => write!(f, "`{}` requires a <{}> argument", command, name),
+            InvalidValue(name, value) => write!(f, "invalid value `{}` for --{}", value, name),
+            MissingOption(command, options) => write!(f, "`{}` requires {}", command, options),
+        }
+    }
+}
+
+impl Command {
+    /// Parse the command from the arguments of the process.
+    #[inline]
+    pub fn from_args() -> Result<Self, CliError> {
+        Self::parse(&std::env::args().skip(1).collect::<Vec<_>>())
+    }
+
+    /// Parse the command from `args`, without the name of the binary. Without
+    /// a command, the simulation is run.
+    pub fn parse(args: &[String]) -> Result<Self, CliError> {
+        let positional = positional(args);
+        let command = match positional.first() {
+            Some(command) => command.as_str(),
+            None => "run",
+        };
+
+        match command {
+            "run" => Ok(Command::Run {
+                config: option_in(args, "config"),
+                prewarm: parse_option(args, "prewarm")?.unwrap_or(0),
+            }),
+            "bench" if positional.get(1).map_or(false, |sub| *sub == "compare") => Ok(Command::BenchCompare {
+                ticks: parse_option(args, "ticks")?.unwrap_or(1200),
+                balls: parse_option(args, "balls")?.unwrap_or(crate::BALLS as usize),
+                seed: parse_option(args, "seed")?.unwrap_or(0),
+                scenario: parse_option(args, "scenario")?.unwrap_or_default(),
+            }),
+            "bench" => Ok(Command::Bench {
+                ticks: parse_option(args, "ticks")?.unwrap_or(1200),
+                balls: parse_option(args, "balls")?.unwrap_or(crate::BALLS as usize),
+                seed: parse_option(args, "seed")?.unwrap_or(0),
+                scenario: parse_option(args, "scenario")?.unwrap_or_default(),
+            }),
+            "soak" => Ok(Command::Soak {
+                duration: parse_option(args, "duration")?.unwrap_or(3600),
+                balls: parse_option(args, "balls")?.unwrap_or(crate::BALLS as usize),
+                seed: parse_option(args, "seed")?.unwrap_or(0),
+                scenario: parse_option(args, "scenario")?.unwrap_or_default(),
+            }),
+            "headless" => {
+                let exit = ExitCriteria {
+                    until_tick: parse_option(args, "until-tick")?,
+                    until_settled: flag_in(args, "until-settled"),
+                };
+                if exit.is_empty() {
+                    return Err(CliError::MissingOption("headless", "--until-tick <n> or --until-settled"));
+                }
+                let defaults = HeadlessPhysics::default();
+                let physics = HeadlessPhysics {
+                    gravity: parse_option(args, "gravity")?.unwrap_or(defaults.gravity),
+                    drag: parse_option(args, "drag")?.unwrap_or(defaults.drag),
+                    restitution: parse_option(args, "restitution")?.unwrap_or(defaults.restitution),
+                };
+                if !physics.gravity.is_finite() {
+                    return Err(CliError::InvalidValue("gravity", physics.gravity.to_string()));
+                }
+                if !(physics.drag >= 0. && physics.drag.is_finite()) {
+                    return Err(CliError::InvalidValue("drag", physics.drag.to_string()));
+                }
+                if !(0. ..=1.).contains(&physics.restitution) {
+                    return Err(CliError::InvalidValue("restitution", physics.restitution.to_string()));
+                }
+                Ok(Command::Headless {
+                    exit,
+                    physics,
+                    balls: parse_option(args, "balls")?.unwrap_or(crate::BALLS as usize),
+                    seed: parse_option(args, "seed")?.unwrap_or(0),
+                    scenario: parse_option(args, "scenario")?.unwrap_or_default(),
+                })
+            }
+            "replay" => match positional.get(1) {
+                Some(path) => Ok(Command::Replay(path.to_string())),
+                None => Err(CliError::MissingArgument("replay", "file")),
+            },
+            "validate" => match positional.get(1) {
+                Some(path) => Ok(Command::Validate(path.to_string())),
+                None => Err(CliError::MissingArgument("validate", "scene")),
+            },
+            _ => Err(CliError::UnknownCommand(command.to_string())),
+        }
+    }
+}
+
+// Options which don't take a value.
+const FLAGS: [&str; 1] = ["--until-settled"];
+
+// Arguments which are neither an option nor the value of one.
+fn positional(args: &[String]) -> Vec<&String> {
+    let mut positional = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg.starts_with("--") {
+            if !arg.contains('=') && !FLAGS.contains(&arg.as_str()) {
+                args.next();
+            }
+            continue;
+        }
+        positional.push(arg);
+    }
+    positional
+}
+
+// Value of the option `--<name> <value>` or `--<name>=<value>` in `args`.
+fn option_in(args: &[String], name: &str) -> Option<String> {
+    let flag = format!("--{}", name);
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if *arg == flag {
+            return args.next().cloned();
+        }
+        if let Some(value) = arg.strip_prefix(&flag).and_then(|rest| rest.strip_prefix('=')) {
+            return Some(value.to_string());
+        }
+    }
+    None
+}
+
+// Whether the flag `--<name>` is in `args`.
+fn flag_in(args: &[String], name: &str) -> bool {
+    args.contains(&format!("--{}", name))
+}
+
+fn parse_option<T: std::str::FromStr>(args: &[String], name: &'static str) -> Result<Option<T>, CliError> {
+    match option_in(args, name) {
+        Some(value) => value.parse()
+            .map(Some)
+            .map_err(|_| CliError::InvalidValue(name, value)),
+        None => Ok(None),
+    }
+}
+
+/// Value of the command line option `--<name> <value>` or `--<name>=<value>`.
+#[inline]
+pub fn cli_option(name: &str) -> Option<String> {
+    option_in(&std::env::args().skip(1).collect::<Vec<_>>(), name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn commands_are_parsed_around_options() {
+        assert_eq!(Command::parse(&args("")), Ok(Command::Run { config: None, prewarm: 0 }));
+        assert_eq!(
+            Command::parse(&args("--palette warm run --config=a.ron --prewarm 600")),
+            Ok(Command::Run { config: Some("a.ron".to_string()), prewarm: 600 }),
+        );
+        assert_eq!(
+            Command::parse(&args("bench --ticks 10 --seed=3 --scenario line")),
+            Ok(Command::Bench { ticks: 10, balls: crate::BALLS as usize, seed: 3, scenario: Scenario::DenseLine }),
+        );
+        assert_eq!(
+            Command::parse(&args("bench --balls 50 compare")),
+            Ok(Command::BenchCompare { ticks: 1200, balls: 50, seed: 0, scenario: Scenario::Uniform }),
+        );
+        assert_eq!(
+            Command::parse(&args("soak --duration 60 --balls 10")),
+            Ok(Command::Soak { duration: 60, balls: 10, seed: 0, scenario: Scenario::Uniform }),
+        );
+        assert_eq!(Command::parse(&args("validate --palette warm a.ron")), Ok(Command::Validate("a.ron".to_string())));
+        assert_eq!(Command::parse(&args("replay --seed 3 run.replay")), Ok(Command::Replay("run.replay".to_string())));
+        assert_eq!(
+            Command::parse(&args("--until-settled headless --until-tick 500")),
+            Ok(Command::Headless {
+                exit: ExitCriteria { until_tick: Some(500), until_settled: true },
+                physics: HeadlessPhysics::default(),
+                balls: crate::BALLS as usize,
+                seed: 0,
+                scenario: Scenario::Uniform,
+            }),
+        );
+        assert_eq!(
+            Command::parse(&args("headless --until-settled --gravity 400 --drag=0.5 --restitution 0.8")),
+            Ok(Command::Headless {
+                exit: ExitCriteria { until_tick: None, until_settled: true },
+                physics: HeadlessPhysics { gravity: 400., drag: 0.5, restitution: 0.8 },
+                balls: crate::BALLS as usize,
+                seed: 0,
+                scenario: Scenario::Uniform,
+            }),
+        );
+        assert_eq!(
+            Command::parse(&args("headless --seed 2")),
+            Err(CliError::MissingOption("headless", "--until-tick <n> or --until-settled")),
+        );
+
+        assert_eq!(Command::parse(&args("validate")), Err(CliError::MissingArgument("validate", "scene")));
+        assert_eq!(Command::parse(&args("replay")), Err(CliError::MissingArgument("replay", "file")));
+        assert_eq!(Command::parse(&args("bench --ticks x")), Err(CliError::InvalidValue("ticks", "x".to_string())));
+        assert_eq!(Command::parse(&args("--prewarm -1")), Err(CliError::InvalidValue("prewarm", "-1".to_string())));
+        assert_eq!(
+            Command::parse(&args("headless --until-tick 5 --restitution 1.5")),
+            Err(CliError::InvalidValue("restitution", "1.5".to_string())),
+        );
+        assert_eq!(
+            Command::parse(&args("headless --until-tick 5 --drag -1")),
+            Err(CliError::InvalidValue("drag", "-1".to_string())),
+        );
+        assert_eq!(
+            Command::parse(&args("bench --scenario spiral")),
+            Err(CliError::InvalidValue("scenario", "spiral".to_string())),
+        );
+        assert_eq!(Command::parse(&args("walk")), Err(CliError::UnknownCommand("walk".to_string())));
+    }
+}
@@ -1,3 +1,4 @@
+use std::fmt::{self, Formatter};
 use std::vec::IntoIter;
 
 use bevy::prelude::{Entity, Transform};
@@ -7,71 +8,195 @@ use crate::*;
 #[derive(Debug)]
 pub struct EdgeCollider {
     pub(crate) bounds: Bounds,
+    /// Fraction of the speed perpendicular to a wall which is preserved when a
+    /// ball bounces off of it.
+    pub restitution: f32,
+    /// Coefficient of friction between the walls and the balls, which slows
+    /// down balls along a wall when they bounce off of it.
+    pub friction: f32,
+}
+
+/// Wall of an `EdgeCollider`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WallSide {
+    Left,
+    Right,
+    Top,
+    Bottom,
+}
+
+impl WallSide {
+    /// Normal of the wall, pointing into the arena.
+    #[inline]
+    pub fn normal(self) -> Vec2 {
+        match self {
+            WallSide::Left => Vec2::X,
+            WallSide::Right => -Vec2::X,
+            WallSide::Top => -Vec2::Y,
+            WallSide::Bottom => Vec2::Y,
+        }
+    }
+}
+
+/// Reason a ball which is asked for from outside of the simulation is not
+/// spawned, see `EdgeCollider::check_spawn`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SpawnError {
+    /// The radius must be finite, positive and at most the largest radius of
+    /// `BALL_RADIUS`.
+    RadiusOutOfRange(f32),
+    NonFinitePosition(Vec2),
+    NonFiniteVelocity(Vec2),
+}
+
+impl fmt::Display for SpawnError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        use SpawnError::*;
+        match self {
+            RadiusOutOfRange(radius) => write!(f, "radius {} is not within (0, {}]", radius, BALL_RADIUS.end()),
+            NonFinitePosition(position) => write!(f, "position {} is not finite", position),
+            NonFiniteVelocity(velocity) => write!(f, "velocity {} is not finite", velocity),
+        }
+    }
+}
+
+/// A ball bounced off of a wall, `impact_speed` is its speed towards the wall
+/// before the bounce.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WallHit {
+    pub side: WallSide,
+    pub impact_speed: f32,
 }
 
 impl EdgeCollider {
+    #[allow(dead_code)]
     #[inline]
     pub fn new(bounds: Bounds) -> Self {
-        Self { bounds }
+        Self::with_restitution(bounds, 1.)
+    }
+
+    #[inline]
+    pub fn with_restitution(bounds: Bounds, restitution: f32) -> Self {
+        Self { bounds, restitution, friction: 0. }
+    }
+
+    #[inline]
+    pub fn with_friction(self, friction: f32) -> Self {
+        Self { friction, ..self }
     }
 
+    /// Area within which a ball with a radius up to `margin` can be spawned
+    /// without touching any of the walls.
     #[inline]
-    pub fn range_x(&self, padding: f32) -> RangeInclusive<f32> {
-        (self.bounds.left() + padding)..=(self.bounds.right() - padding - padding)
+    pub fn spawn_area(&self, margin: f32) -> Bounds {
+        self.bounds.shrunk(margin)
+    }
+
+    /// Check a ball which is asked for from outside of the simulation, like by
+    /// a client over the network. Returns its position, moved within the spawn
+    /// area of its radius.
+    #[allow(dead_code)]
+    pub fn check_spawn(&self, position: Vec2, velocity: Vec2, radius: f32) -> Result<Vec2, SpawnError> {
+        if !radius.is_finite() || radius <= 0. || radius > *BALL_RADIUS.end() {
+            return Err(SpawnError::RadiusOutOfRange(radius));
+        }
+        if !position.is_finite() {
+            return Err(SpawnError::NonFinitePosition(position));
+        }
+        if !velocity.is_finite() {
+            return Err(SpawnError::NonFiniteVelocity(velocity));
+        }
+        Ok(self.spawn_area(radius).clamp_point(position))
     }
 
+    /// Indicates if `ball` touches or crosses any of the walls, in which case
+    /// it has to be checked against them.
     #[inline]
-    pub fn range_y(&self, padding: f32) -> RangeInclusive<f32> {
-        (self.bounds.bottom() + padding)..=(self.bounds.top() - padding - padding)
+    pub fn touches_wall(&self, ball: &Ball, transform: &Transform) -> bool {
+        let (x, y) = (transform.translation.x, transform.translation.y);
+        x <= self.bounds.left() + ball.radius
+            || x >= self.bounds.right() - ball.radius
+            || y >= self.bounds.top() - ball.radius
+            || y <= self.bounds.bottom() + ball.radius
     }
 
     #[inline]
-    pub fn check_left(&self, ball: &Ball, transform: &mut Transform, velocity: &mut Velocity) -> bool {
+    pub fn check_left(&self, ball: &Ball, transform: &mut Transform, velocity: &mut Velocity) -> Option<WallHit> {
         let min_x = self.bounds.left() + ball.radius;
         if transform.translation.x > min_x {
-            return false;
+            return None;
         }
 
-        transform.translation.x = min_x + (min_x - transform.translation.x);
-        velocity.0.x *= -1.0;
-        return true;
+        let restitution = self.restitution * ball.restitution;
+        transform.translation.x = min_x + (min_x - transform.translation.x) * restitution;
+        return Some(self.bounce(WallSide::Left, restitution, &mut velocity.0));
     }
 
     #[inline]
-    pub fn check_right(&self, ball: &Ball, transform: &mut Transform, velocity: &mut Velocity) -> bool {
+    pub fn check_right(&self, ball: &Ball, transform: &mut Transform, velocity: &mut Velocity) -> Option<WallHit> {
         let max_x = self.bounds.right() - ball.radius;
         if transform.translation.x < max_x {
-            return false;
+            return None;
         }
 
-        transform.translation.x = max_x - (transform.translation.x - max_x);
-        velocity.0.x *= -1.0;
-        return true;
+        let restitution = self.restitution * ball.restitution;
+        transform.translation.x = max_x - (transform.translation.x - max_x) * restitution;
+        return Some(self.bounce(WallSide::Right, restitution, &mut velocity.0));
     }
 
     #[inline]
-    pub fn check_top(&self, ball: &Ball, transform: &mut Transform, velocity: &mut Velocity) -> bool {
+    pub fn check_top(&self, ball: &Ball, transform: &mut Transform, velocity: &mut Velocity) -> Option<WallHit> {
         let max_y = self.bounds.top() - ball.radius;
         if transform.translation.y < max_y {
-            return false;
+            return None;
         }
 
-        transform.translation.y = max_y - (transform.translation.y - max_y);
-        velocity.0.y *= -1.0;
-        return true;
+        let restitution = self.restitution * ball.restitution;
+        transform.translation.y = max_y - (transform.translation.y - max_y) * restitution;
+        return Some(self.bounce(WallSide::Top, restitution, &mut velocity.0));
     }
 
     #[inline]
-    pub fn check_bottom(&self, ball: &Ball, transform: &mut Transform, velocity: &mut Velocity) -> bool {
+    pub fn check_bottom(&self, ball: &Ball, transform: &mut Transform, velocity: &mut Velocity) -> Option<WallHit> {
         let min_y = self.bounds.bottom() + ball.radius;
         if transform.translation.y > min_y {
-            return false;
+            return None;
         }
 
-        transform.translation.y = min_y + (min_y - transform.translation.y);
-        velocity.0.y *= -1.0;
-        return true;
+        let restitution = self.restitution * ball.restitution;
+        transform.translation.y = min_y + (min_y - transform.translation.y) * restitution;
+        return Some(self.bounce(WallSide::Bottom, restitution, &mut velocity.0));
     }
+
+    // Reverse the velocity perpendicular to the wall, and slow down along it.
+    #[inline]
+    fn bounce(&self, side: WallSide, restitution: f32, velocity: &mut Vec2) -> WallHit {
+        let impact_speed = bounce_off_surface(velocity, side.normal(), restitution, self.friction);
+        WallHit { side, impact_speed }
+    }
+}
+
+/// Bounce `velocity` off of an immovable surface with `normal`, which points
+/// away from the surface. The speed along the normal is reversed and scaled by
+/// `restitution`. The speed along the surface is reduced by the friction
+/// impulse, which grows with the impact, but never reverses. Returns the speed
+/// towards the surface before the bounce.
+#[inline]
+pub fn bounce_off_surface(velocity: &mut Vec2, normal: Vec2, restitution: f32, friction: f32) -> f32 {
+    let normal_speed = velocity.dot(normal);
+    let impact_speed = normal_speed.abs();
+    let tangent = *velocity - normal * normal_speed;
+
+    let tangent_speed = tangent.length();
+    let slowdown = friction * (1. + restitution) * impact_speed;
+    let tangent = if tangent_speed > slowdown {
+        tangent * (1. - slowdown / tangent_speed)
+    } else {
+        Vec2::ZERO
+    };
+
+    *velocity = tangent - normal * normal_speed * restitution;
+    impact_speed
 }
 
 #[derive(Debug)]
@@ -91,28 +216,125 @@ impl BallCollisions {
         }
     }
 
+    #[inline]
+    pub fn clear(&mut self) { self.store.clear(); }
+
+    /// Pairs of balls which collided.
+    #[inline]
+    pub fn as_slice(&self) -> &[[Entity; 2]] { &self.store }
+
+    #[inline]
+    pub fn push(&mut self, a: Entity, b: Entity) { self.store.push([a, b]); }
+
+    #[allow(dead_code)]
     #[inline]
     pub fn check(&mut self, balls: [(Entity, &mut Transform, &Ball); 2]) {
         let [(a, transform_a, ball_a), (b, transform_b, ball_b)] = balls;
+        if separate_balls([(transform_a, ball_a), (transform_b, ball_b)]) {
+            self.store.push([a, b]);
+        }
+    }
+}
 
-        let x = transform_a.translation.x - transform_b.translation.x;
-        let y = transform_a.translation.y - transform_b.translation.y;
-        let r = ball_a.radius + ball_b.radius;
+/// New positions which move two touching or overlapping balls apart, or
+/// `None` when the balls don't collide.
+#[inline]
+pub fn separated_positions(balls: [(&Transform, &Ball); 2]) -> Option<[Vec2; 2]> {
+    separated_positions_by(balls, 1.)
+}
 
-        let mut distance = (x * x) + (y * y);
-        if distance > (r * r) {
-            return;
-        }
+/// New positions which resolve `fraction` of the overlap of two touching or
+/// overlapping balls, or `None` when the balls don't collide.
+#[inline]
+pub fn separated_positions_by(balls: [(&Transform, &Ball); 2], fraction: f32) -> Option<[Vec2; 2]> {
+    let [(transform_a, ball_a), (transform_b, ball_b)] = balls;
 
-        distance = f32::sqrt(distance);
-        let overlap = (distance - r) * 0.5;
+    let x = transform_a.translation.x - transform_b.translation.x;
+    let y = transform_a.translation.y - transform_b.translation.y;
+    let r = ball_a.radius + ball_b.radius;
 
-        transform_a.translation.x -= overlap * x / distance;
-        transform_a.translation.y -= overlap * y / distance;
-        transform_b.translation.x += overlap * x / distance;
-        transform_b.translation.y += overlap * y / distance;
-        self.store.push([a, b]);
+    let mut distance = (x * x) + (y * y);
+    if distance > (r * r) {
+        return None;
     }
+
+    distance = f32::sqrt(distance);
+    let overlap = (distance - r) * 0.5 * fraction;
+    let offset = Vec2::new(overlap * x / distance, overlap * y / distance);
+
+    return Some([
+        transform_a.translation.truncate() - offset,
+        transform_b.translation.truncate() + offset,
+    ]);
+}
+
+// Move overlapping balls apart, returns `true` when the balls collided.
+#[inline]
+pub fn separate_balls(balls: [(&mut Transform, &Ball); 2]) -> bool {
+    let [(transform_a, ball_a), (transform_b, ball_b)] = balls;
+    let [a, b] = match separated_positions([(transform_a, ball_a), (transform_b, ball_b)]) {
+        Some(positions) => positions,
+        None => return false,
+    };
+
+    transform_a.translation.x = a.x;
+    transform_a.translation.y = a.y;
+    transform_b.translation.x = b.x;
+    transform_b.translation.y = b.y;
+    return true;
+}
+
+/// Normal pointing from `area` towards a ball at `position` with `radius`,
+/// and the depth by which the ball overlaps the area. Returns `None` when they
+/// don't touch.
+#[inline]
+pub fn box_contact(area: Bounds, position: Vec2, radius: f32) -> Option<(Vec2, f32)> {
+    let closest = area.clamp_point(position);
+    let delta = position - closest;
+    if delta.length_squared() > radius * radius {
+        return None;
+    }
+    if delta != Vec2::ZERO {
+        let distance = delta.length();
+        return Some((delta / distance, radius - distance));
+    }
+
+    // the center is inside the area, push it out along the shortest way
+    let exits = [
+        (Vec2::new(-1., 0.), position.x - area.left()),
+        (Vec2::new(1., 0.), area.right() - position.x),
+        (Vec2::new(0., -1.), position.y - area.bottom()),
+        (Vec2::new(0., 1.), area.top() - position.y),
+    ];
+    let (normal, distance) = exits.into_iter()
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .unwrap();
+    Some((normal, radius + distance))
+}
+
+/// Bounce a ball off of a solid box at `area`, which moves with
+/// `area_velocity`. The ball is moved out of the box and keeps `restitution`
+/// of its speed towards it, relative to the box. Returns that relative speed
+/// when the ball bounced.
+#[inline]
+pub fn bounce_off_box(
+    area: Bounds,
+    area_velocity: Vec2,
+    restitution: f32,
+    ball: &Ball,
+    transform: &mut Transform,
+    velocity: &mut Velocity,
+) -> Option<f32> {
+    let (normal, depth) = box_contact(area, transform.translation.truncate(), ball.radius)?;
+    transform.translation.x += normal.x * depth;
+    transform.translation.y += normal.y * depth;
+
+    let approach = (velocity.0 - area_velocity).dot(normal);
+    if approach >= 0. {
+        return None;
+    }
+    velocity.0 -= normal * approach * (1. + restitution);
+    Some(-approach)
 }
 
 impl IntoIterator for BallCollisions {
@@ -124,9 +346,157 @@ impl IntoIterator for BallCollisions {
     }
 }
 
+/// Determines how candidate pairs of colliding balls are found.
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BroadPhase {
+    /// Balls are stored by their area in a `QuadTree`, balls which share a
+    /// leaf are checked against each other.
+    QuadTree,
+    /// Balls are stored by their center in a `LinearQuadTree`, with the value
+    /// being its depth. Each ball is checked against the balls within reach of
+    /// the largest possible radius.
+    Linear(u8),
+    /// Balls are stored by their center in a `QuadTree` which tracks the
+    /// largest radius within each region. Each ball is checked against the
+    /// balls within reach of its own radius and the largest radius nearby.
+    Reach,
+    /// Candidate pairs are cached and reused while balls move less than half
+    /// the value, which is the margin added to the bounding circle of balls.
+    Cached(f32),
+    /// Like `QuadTree`, but the tree is kept across ticks and balls are moved
+    /// within it. At most the value of leaves are split each tick, further
+    /// splits are deferred to later ticks. This smooths out spikes in the tick
+    /// time when a dense cluster forms suddenly. The value must be at least 1.
+    Deferred(usize),
+    /// Balls are stored in square chunks of the value in size, each with a
+    /// `QuadTree` of its own, see `ChunkedWorld`.
+    Chunked(f32),
+    /// Balls are binned into a uniform grid on the GPU, see
+    /// `GpuBroadPhasePlugin`. The pairs are a frame old, so they're found with
+    /// the bounding circles of the balls grown by the value.
+    #[cfg(feature = "gpu")]
+    Gpu(f32),
+    /// Each ball is checked against every other ball. This is slow, but it
+    /// can't miss a pair, so the other broad phases are compared with it.
+    BruteForce,
+}
+
+impl Default for BroadPhase {
+    fn default() -> Self { Self::QuadTree }
+}
+
+impl BroadPhase {
+    pub fn name(&self) -> &'static str {
+        match self {
+            BroadPhase::QuadTree => "quadtree",
+            BroadPhase::Linear(_) => "linear",
+            BroadPhase::Reach => "reach",
+            BroadPhase::Cached(_) => "cached",
+            BroadPhase::Deferred(_) => "deferred",
+            BroadPhase::Chunked(_) => "chunked",
+            #[cfg(feature = "gpu")]
+            BroadPhase::Gpu(_) => "gpu",
+            BroadPhase::BruteForce => "brute force",
+        }
+    }
+}
+
+/// Builds the `QuadTree` of the broad phase on the threads of the
+/// `ComputeTaskPool` when inserted as a resource, once it holds at least
+/// `min_balls` balls. Below that, spawning the tasks costs more than it saves.
+#[derive(Clone, Copy, Debug)]
+pub struct ParallelTreeBuild {
+    pub min_balls: usize,
+}
+
+/// Buffers which are reused by the collision checks of each tick, so they
+/// don't allocate once they have grown large enough.
+pub struct PairBuffer {
+    pub(crate) pairs: Vec<[Entity; 2]>,
+    pub(crate) collisions: BallCollisions,
+    /// Colliding pairs of which either ball has a `BoxBody`, `CapsuleBody` or
+    /// `CompoundCollider`, with their contact.
+    pub(crate) shaped: Vec<([Entity; 2], Contact)>,
+    pub(crate) balls: Vec<(Entity, Vec2, f32)>,
+    /// Balls found by a query of the broad phase.
+    pub(crate) found: Vec<(Location, Entity)>,
+}
+
+impl PairBuffer {
+    #[inline]
+    pub fn clear(&mut self) {
+        self.pairs.clear();
+        self.collisions.clear();
+        self.shaped.clear();
+        self.balls.clear();
+    }
+
+    /// Add a candidate pair of colliding balls.
+    #[inline]
+    pub fn push(&mut self, a: Entity, b: Entity) {
+        self.pairs.push(if a < b { [a, b] } else { [b, a] });
+    }
+
+    #[inline]
+    pub fn extend(&mut self, pairs: &[[Entity; 2]]) {
+        self.pairs.extend_from_slice(pairs);
+    }
+
+    /// Remove pairs which were added more than once, for example because the
+    /// balls share multiple leaves of a quadtree.
+    #[inline]
+    pub fn dedup(&mut self) {
+        self.pairs.sort_unstable();
+        self.pairs.dedup();
+    }
+
+    #[inline]
+    pub fn pairs(&self) -> &[[Entity; 2]] { &self.pairs }
+}
+
+impl Default for PairBuffer {
+    fn default() -> Self {
+        Self {
+            pairs: Vec::new(),
+            collisions: BallCollisions::new(None),
+            shaped: Vec::new(),
+            balls: Vec::new(),
+            found: Vec::new(),
+        }
+    }
+}
+
+/// Determines how much energy is preserved when two balls collide.
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CollisionModel {
+    /// Balls bounce off of each other without losing any kinetic energy.
+    Elastic,
+    /// Balls stick together along the collision normal, their velocities
+    /// along the normal are equalized.
+    PerfectlyInelastic,
+}
+
+impl CollisionModel {
+    /// Coefficient of restitution belonging to the model.
+    #[inline]
+    pub fn restitution(&self) -> f32 {
+        use CollisionModel::*;
+        match *self {
+            Elastic => 1.0,
+            PerfectlyInelastic => 0.0,
+        }
+    }
+}
+
+impl Default for CollisionModel {
+    fn default() -> Self { Self::Elastic }
+}
+
 // Update velocity according to mass, after the balls bounce off of each other.
 #[inline]
-pub fn balls_bounce_after_collision(balls: [(&Transform, &mut Velocity, &Ball); 2]) {
+pub fn balls_bounce_after_collision(model: CollisionModel, balls: [(&Transform, &mut Velocity, &Ball); 2]) {
     let [(transform_a, velocity_a, ball_a), (transform_b, velocity_b, ball_b)] = balls;
 
     let x = transform_a.translation.x - transform_b.translation.x;
@@ -139,10 +509,212 @@ pub fn balls_bounce_after_collision(balls: [(&Transform, &mut Velocity, &Ball);
     let kx = velocity_a.0.x - velocity_b.0.x;
     let ky = velocity_a.0.y - velocity_b.0.y;
 
-    let p = 2.0 * ((nx * kx) + (ny * ky)) / (ball_a.mass + ball_b.mass);
+    let restitution = model.restitution() * ball_a.restitution.min(ball_b.restitution);
+    let p = (1.0 + restitution) * ((nx * kx) + (ny * ky)) / (ball_a.mass + ball_b.mass);
 
     velocity_a.0.x -= p * ball_b.mass * nx;
     velocity_a.0.y -= p * ball_b.mass * ny;
     velocity_b.0.x += p * ball_a.mass * nx;
     velocity_b.0.y += p * ball_a.mass * ny;
 }
+
+/// Motion and mass of a body which takes part in a contact.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ContactBody {
+    pub center: Vec2,
+    pub velocity: Vec2,
+    /// Angular velocity, or `None` for bodies which don't turn.
+    pub spin: Option<f32>,
+    pub mass: f32,
+    /// Moment of inertia around the center.
+    pub inertia: f32,
+    pub restitution: f32,
+    /// Point of the world a pinned body turns around, it doesn't move
+    /// otherwise.
+    pub pivot: Option<Vec2>,
+}
+
+impl ContactBody {
+    // Velocity of the point of the body at `point`.
+    #[inline]
+    fn velocity_at(&self, point: Vec2) -> Vec2 {
+        let spin = self.spin.unwrap_or_default();
+        match self.pivot {
+            Some(pivot) => (point - pivot).perp() * spin,
+            None => self.velocity + (point - self.center).perp() * spin,
+        }
+    }
+
+    // Moment of inertia around the pivot of a pinned body.
+    #[inline]
+    fn pivot_inertia(&self, pivot: Vec2) -> f32 {
+        self.inertia + self.mass * (self.center - pivot).length_squared()
+    }
+
+    // Inverse of the mass resisting an impulse along `normal` at `point`,
+    // including the part which turns the body.
+    #[inline]
+    fn inverse_mass_at(&self, point: Vec2, normal: Vec2) -> f32 {
+        match (self.pivot, self.spin) {
+            (Some(_), None) => 0.,
+            (Some(pivot), Some(_)) => {
+                let arm = (point - pivot).perp_dot(normal);
+                arm * arm / self.pivot_inertia(pivot)
+            }
+            (None, Some(_)) if self.inertia > 0. => {
+                let arm = (point - self.center).perp_dot(normal);
+                1. / self.mass + arm * arm / self.inertia
+            }
+            (None, _) => 1. / self.mass,
+        }
+    }
+
+    #[inline]
+    fn apply_impulse(&mut self, point: Vec2, impulse: Vec2) {
+        if let Some(pivot) = self.pivot {
+            let inertia = self.pivot_inertia(pivot);
+            if let Some(spin) = &mut self.spin {
+                *spin += (point - pivot).perp_dot(impulse) / inertia;
+                self.velocity = (self.center - pivot).perp() * *spin;
+            }
+            return;
+        }
+
+        self.velocity += impulse / self.mass;
+        if let Some(spin) = &mut self.spin {
+            if self.inertia > 0. {
+                *spin += (point - self.center).perp_dot(impulse) / self.inertia;
+            }
+        }
+    }
+}
+
+/// Update the velocity and spin of two bodies which bounce off of each other
+/// at `contact`. This is the general form of `balls_bounce_after_collision`:
+/// a push which doesn't go through the center of a body also turns it, in
+/// proportion to its moment of inertia, and pinned bodies only turn around
+/// their pivot. Bodies which already move apart at the contact are left
+/// alone.
+pub fn bounce_at_contact(model: CollisionModel, contact: &Contact, bodies: &mut [ContactBody; 2]) {
+    let [body_a, body_b] = bodies;
+    let point = contact.point;
+
+    let approach = (body_a.velocity_at(point) - body_b.velocity_at(point)).dot(contact.normal);
+    if approach <= 0. {
+        return;
+    }
+    let inverse_mass = body_a.inverse_mass_at(point, contact.normal) + body_b.inverse_mass_at(point, contact.normal);
+    if inverse_mass <= 0. {
+        return;
+    }
+
+    let restitution = model.restitution() * body_a.restitution.min(body_b.restitution);
+    let impulse = contact.normal * (1.0 + restitution) * approach / inverse_mass;
+    body_a.apply_impulse(point, -impulse);
+    body_b.apply_impulse(point, impulse);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wall_bounce_applies_restitution() {
+        let edge = EdgeCollider::with_restitution(Bounds::new(Vec2::ZERO, 100., 100.), 0.5);
+        let ball = Ball::new(5., MassModel::Constant(1.));
+        let mut transform = Transform::from_xyz(-47., 0., 0.);
+        let mut velocity = Velocity(Vec2::new(-20., 10.));
+
+        let hit = edge.check_left(&ball, &mut transform, &mut velocity);
+        assert_eq!(hit, Some(WallHit { side: WallSide::Left, impact_speed: 20. }));
+        assert_eq!(velocity.0, Vec2::new(10., 10.));
+        assert_eq!(transform.translation.x, -44.);
+
+        assert_eq!(edge.check_right(&ball, &mut transform, &mut velocity), None);
+    }
+
+    #[test]
+    fn spawns_are_checked_and_kept_within_the_walls() {
+        let edge = EdgeCollider::new(Bounds::new(Vec2::ZERO, 100., 100.));
+        assert_eq!(edge.check_spawn(Vec2::new(10., -20.), Vec2::X, 5.), Ok(Vec2::new(10., -20.)));
+        assert_eq!(edge.check_spawn(Vec2::new(80., -50.), Vec2::X, 5.), Ok(Vec2::new(45., -45.)));
+
+        assert_eq!(edge.check_spawn(Vec2::ZERO, Vec2::X, 0.), Err(SpawnError::RadiusOutOfRange(0.)));
+        assert_eq!(edge.check_spawn(Vec2::ZERO, Vec2::X, -1.), Err(SpawnError::RadiusOutOfRange(-1.)));
+        let radius = BALL_RADIUS.end() + 1.;
+        assert_eq!(edge.check_spawn(Vec2::ZERO, Vec2::X, radius), Err(SpawnError::RadiusOutOfRange(radius)));
+        assert!(matches!(edge.check_spawn(Vec2::ZERO, Vec2::X, f32::NAN), Err(SpawnError::RadiusOutOfRange(_))));
+        let position = Vec2::new(f32::INFINITY, 0.);
+        assert_eq!(edge.check_spawn(position, Vec2::X, 5.), Err(SpawnError::NonFinitePosition(position)));
+        assert!(matches!(edge.check_spawn(Vec2::ZERO, Vec2::NAN, 5.), Err(SpawnError::NonFiniteVelocity(_))));
+    }
+
+    #[test]
+    fn wall_friction_slows_balls_along_the_wall() {
+        let edge = EdgeCollider::with_restitution(Bounds::new(Vec2::ZERO, 100., 100.), 0.5).with_friction(0.2);
+        let ball = Ball::new(5., MassModel::Constant(1.));
+        let mut transform = Transform::from_xyz(0., -45., 0.);
+        let mut velocity = Velocity(Vec2::new(30., -20.));
+
+        edge.check_bottom(&ball, &mut transform, &mut velocity);
+        assert_eq!(velocity.0, Vec2::new(24., 10.));
+
+        // friction stops the ball along the wall, without reversing it
+        let mut velocity = Velocity(Vec2::new(-3., -20.));
+        edge.check_bottom(&ball, &mut transform, &mut velocity);
+        assert_eq!(velocity.0, Vec2::new(0., 10.));
+    }
+
+    #[test]
+    fn ball_bounces_off_moving_box() {
+        let area = Bounds::new(Vec2::ZERO, 40., 10.);
+        let ball = Ball::new(5., MassModel::Constant(1.));
+        let mut transform = Transform::from_xyz(0., 8., 0.);
+        let mut velocity = Velocity(Vec2::new(10., -20.));
+
+        let hit = bounce_off_box(area, Vec2::new(0., 10.), 1., &ball, &mut transform, &mut velocity);
+        assert_eq!(hit, Some(30.));
+        assert_eq!(velocity.0, Vec2::new(10., 40.));
+        assert_eq!(transform.translation.y, 10.);
+
+        // a ball inside the box is pushed out of the nearest side
+        assert_eq!(box_contact(area, Vec2::new(18., 1.), 5.), Some((Vec2::X, 7.)));
+        assert_eq!(box_contact(area, Vec2::new(0., 20.), 5.), None);
+    }
+
+    #[test]
+    fn off_center_impacts_turn_bodies() {
+        let contact = Contact { normal: Vec2::X, depth: 0., point: Vec2::new(5., 3.) };
+        let body = |center: Vec2, velocity: Vec2, spin: Option<f32>| ContactBody {
+            center,
+            velocity,
+            spin,
+            mass: 2.,
+            inertia: 20.,
+            restitution: 1.,
+            pivot: None,
+        };
+        let energy = |bodies: &[ContactBody; 2]| bodies.iter()
+            .map(|body| body.mass * body.velocity.length_squared() + body.inertia * body.spin.unwrap_or_default().powi(2))
+            .sum::<f32>();
+
+        // a ball hits the right side of a box above its center, which starts
+        // turning counter clockwise
+        let mut bodies = [body(Vec2::ZERO, Vec2::ZERO, Some(0.)), body(Vec2::new(7., 3.), Vec2::new(-10., 0.), None)];
+        let before = energy(&bodies);
+        bounce_at_contact(CollisionModel::Elastic, &contact, &mut bodies);
+        assert!(bodies[0].spin.unwrap() > 0.);
+        assert_eq!(bodies[0].velocity * 2. + bodies[1].velocity * 2., Vec2::new(-20., 0.));
+        assert!((energy(&bodies) - before).abs() < 1e-3, "{} {}", before, energy(&bodies));
+
+        // they move apart now, so they don't bounce again
+        let after = bodies;
+        bounce_at_contact(CollisionModel::Elastic, &contact, &mut bodies);
+        assert_eq!(bodies, after);
+
+        // without spin, bodies bounce like balls
+        let mut bodies = [body(Vec2::ZERO, Vec2::ZERO, None), body(Vec2::new(7., 0.), Vec2::new(-10., 0.), None)];
+        bounce_at_contact(CollisionModel::Elastic, &Contact { point: Vec2::new(5., 0.), ..contact }, &mut bodies);
+        assert_eq!((bodies[0].velocity, bodies[1].velocity), (Vec2::new(-10., 0.), Vec2::ZERO));
+    }
+}
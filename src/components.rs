@@ -1,43 +1,434 @@
+use bevy::ecs::system::EntityCommands;
 use bevy::math::Vec2;
 use bevy::prelude::*;
 use bevy_prototype_lyon::entity::ShapeBundle;
 
 use crate::*;
 
-#[derive(Component)]
-pub struct Velocity(pub(crate) Vec2);
+/// Velocity of a ball, in pixels per second.
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Reflect)]
+#[reflect(Component)]
+pub struct Velocity(pub Vec2);
 
-#[derive(Component)]
+impl Velocity {
+    #[inline]
+    pub fn speed(&self) -> f32 { self.0.length() }
+
+    /// Slow down to `max` when moving faster, keeping the direction.
+    #[inline]
+    pub fn clamp_speed(&mut self, max: f32) {
+        self.0 = self.0.clamp_length_max(max);
+    }
+
+    /// Mirror the velocity off a surface with `normal`, without losing speed.
+    /// The normal is expected to be normalized.
+    #[allow(dead_code)]
+    #[inline]
+    pub fn reflect(&mut self, normal: Vec2) {
+        self.0 -= 2. * self.0.dot(normal) * normal;
+    }
+}
+
+/// Spin of a ball, in radians per second. Positive values spin counter
+/// clockwise. Balls without it don't turn.
+#[derive(Component, Clone, Copy, Debug, Default, Reflect)]
+#[reflect(Component)]
+pub struct AngularVelocity(pub f32);
+
+/// Force which is applied to a ball during the next physics tick. Systems add
+/// to it, and it is cleared after integration.
+#[derive(Component, Clone, Copy, Debug, Default, Reflect)]
+#[reflect(Component)]
+pub struct Force(pub Vec2);
+
+/// Instant change in momentum which is applied to a ball at the start of the
+/// next physics tick. Systems add to it, and it is cleared after integration.
+#[derive(Component, Clone, Copy, Debug, Default, Reflect)]
+#[reflect(Component)]
+pub struct Impulse(pub Vec2);
+
+#[derive(Component, Clone, Copy, Debug, Reflect)]
+#[reflect(Component)]
 pub struct Ball {
     pub radius: f32,
     pub mass: f32,
+    /// Fraction of the speed which is preserved when the ball bounces, on top
+    /// of the restitution of the collision model or the walls.
+    pub restitution: f32,
+}
+
+impl Default for Ball {
+    fn default() -> Self { Self::new(0., MASS_MODEL) }
+}
+
+impl Ball {
+    #[inline]
+    pub fn new(radius: f32, mass_model: MassModel) -> Self {
+        Self {
+            radius,
+            mass: mass_model.mass(radius),
+            restitution: 1.,
+        }
+    }
+}
+
+/// Determines the mass of a ball based on its radius.
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug)]
+pub enum MassModel {
+    /// Mass grows with the area of the ball, like a hollow sphere. The value is
+    /// the density.
+    Area(f32),
+    /// Mass grows with the volume of the ball, like a solid sphere. The value
+    /// is the density.
+    Volume(f32),
+    /// All balls have the same mass, regardless of their size.
+    Constant(f32),
+    /// Mass is calculated by the provided function, which receives the radius.
+    Explicit(fn(f32) -> f32),
+}
+
+impl MassModel {
+    #[inline]
+    pub fn mass(&self, radius: f32) -> f32 {
+        use MassModel::*;
+        match *self {
+            Area(density) => density * radius * radius,
+            Volume(density) => density * radius * radius * radius,
+            Constant(mass) => mass,
+            Explicit(f) => f(radius),
+        }
+    }
+
+    /// The same model with another density. Models without a density are
+    /// returned as is.
+    #[inline]
+    #[allow(dead_code)]
+    pub fn with_density(self, density: f32) -> Self {
+        use MassModel::*;
+        match self {
+            Area(_) => Area(density),
+            Volume(_) => Volume(density),
+            model => model,
+        }
+    }
+}
+
+/// Outline which is drawn around a ball.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Outline {
+    pub color: Color,
+    pub width: f32,
+}
+
+/// How a ball is drawn.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BallStyle {
+    pub fill: Color,
+    pub outline: Option<Outline>,
+}
+
+impl BallStyle {
+    #[allow(dead_code)]
+    #[inline]
+    pub fn fill(color: Color) -> Self {
+        Self {
+            fill: color,
+            outline: None,
+        }
+    }
+
+    #[inline]
+    pub fn draw_mode(&self) -> DrawMode {
+        match self.outline {
+            Some(outline) => DrawMode::Outlined {
+                fill_mode: FillMode::color(self.fill),
+                outline_mode: StrokeMode::new(outline.color, outline.width),
+            },
+            None => DrawMode::Fill(FillMode::color(self.fill)),
+        }
+    }
+}
+
+/// Change the fill color of a ball, while keeping its outline.
+#[inline]
+pub fn set_fill_color(draw_mode: &mut DrawMode, color: Color) {
+    match draw_mode {
+        DrawMode::Fill(fill_mode) | DrawMode::Outlined { fill_mode, .. } => fill_mode.color = color,
+        DrawMode::Stroke(stroke_mode) => stroke_mode.color = color,
+    }
+}
+
+/// Fill color of a ball, or its outline color when it isn't filled.
+#[inline]
+pub fn fill_color(draw_mode: &DrawMode) -> Color {
+    match draw_mode {
+        DrawMode::Fill(fill_mode) | DrawMode::Outlined { fill_mode, .. } => fill_mode.color,
+        DrawMode::Stroke(stroke_mode) => stroke_mode.color,
+    }
+}
+
+/// Change the opacity of all colors of a ball.
+#[inline]
+pub fn set_alpha(draw_mode: &mut DrawMode, alpha: f32) {
+    match draw_mode {
+        DrawMode::Fill(fill_mode) => { fill_mode.color.set_a(alpha); }
+        DrawMode::Stroke(stroke_mode) => { stroke_mode.color.set_a(alpha); }
+        DrawMode::Outlined { fill_mode, outline_mode } => {
+            fill_mode.color.set_a(alpha);
+            outline_mode.color.set_a(alpha);
+        }
+    }
+}
+
+/// How a ball is drawn. Whichever way it is drawn, the colors of the ball are
+/// kept in its `DrawMode`, so systems which color balls work with either.
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Reflect)]
+#[reflect_value(Component, PartialEq)]
+pub enum BallVisual {
+    /// The shape of the ball, filled and outlined with its `DrawMode`.
+    #[default]
+    Shape,
+    /// Sprite of the `BallAtlas` at this index, scaled to the radius of the
+    /// ball. Requires the `BallSpritesPlugin`.
+    Sprite(usize),
 }
 
 #[derive(Bundle)]
 pub struct BallBundle {
     pub ball: Ball,
     pub velocity: Velocity,
+    pub force: Force,
+    pub impulse: Impulse,
+    pub visual: BallVisual,
 
     #[bundle]
     pub shape_bundle: ShapeBundle,
 }
 
 impl BallBundle {
-    pub fn new(color: Color, radius: f32, velocity: Vec2, position: Vec2) -> Self {
+    /// Builder of a ball with `radius`, at rest at the origin.
+    #[inline]
+    pub fn builder(radius: f32) -> BallBuilder {
+        BallBuilder::new(radius)
+    }
+}
+
+/// Builds a `BallBundle`, along with the optional components of a ball. Only
+/// the components which are asked for are inserted when the ball is spawned.
+#[derive(Clone, Copy)]
+pub struct BallBuilder {
+    radius: f32,
+    mass_model: MassModel,
+    mass: Option<f32>,
+    restitution: f32,
+    velocity: Vec2,
+    position: Vec2,
+    draw_mode: DrawMode,
+    visual: BallVisual,
+    kind: Option<Kind>,
+    angular_velocity: Option<f32>,
+}
+
+impl BallBuilder {
+    pub fn new(radius: f32) -> Self {
         Self {
+            radius,
+            mass_model: MASS_MODEL,
+            mass: None,
+            restitution: 1.,
+            velocity: Vec2::ZERO,
+            position: Vec2::ZERO,
+            draw_mode: BallStyle::fill(Color::WHITE).draw_mode(),
+            visual: BallVisual::Shape,
+            kind: None,
+            angular_velocity: None,
+        }
+    }
+
+    pub fn with_velocity(self, velocity: Vec2) -> Self {
+        Self { velocity, ..self }
+    }
+
+    pub fn with_position(self, position: Vec2) -> Self {
+        Self { position, ..self }
+    }
+
+    pub fn with_style(self, style: BallStyle) -> Self {
+        self.with_draw_mode(style.draw_mode())
+    }
+
+    /// Draw the ball with the colors of another ball.
+    pub fn with_draw_mode(self, draw_mode: DrawMode) -> Self {
+        Self { draw_mode, ..self }
+    }
+
+    /// Draw the ball with `visual` instead of its shape.
+    #[allow(dead_code)]
+    pub fn with_visual(self, visual: BallVisual) -> Self {
+        Self { visual, ..self }
+    }
+
+    /// Derive the mass from the radius with `mass_model`, instead of
+    /// `MASS_MODEL`.
+    #[allow(dead_code)]
+    pub fn with_mass_model(self, mass_model: MassModel) -> Self {
+        Self { mass_model, ..self }
+    }
+
+    /// Material of the ball, as the density of its mass model.
+    #[allow(dead_code)]
+    pub fn with_density(self, density: f32) -> Self {
+        Self { mass_model: self.mass_model.with_density(density), ..self }
+    }
+
+    /// Give the ball `mass`, regardless of its mass model.
+    pub fn with_mass(self, mass: f32) -> Self {
+        Self { mass: Some(mass), ..self }
+    }
+
+    #[allow(dead_code)]
+    pub fn with_restitution(self, restitution: f32) -> Self {
+        Self { restitution, ..self }
+    }
+
+    /// Make the ball of `kind`, which takes the mass model and restitution of
+    /// the kind. Overrides of those go after it, as it replaces them.
+    pub fn with_kind(self, kinds: &BallKinds, kind: Kind) -> Self {
+        let ball_kind = kinds.get(kind);
+        Self {
+            kind: Some(kind),
+            mass_model: ball_kind.mass_model(),
+            restitution: ball_kind.restitution,
+            ..self
+        }
+    }
+
+    /// Let the ball spin, starting with `angular_velocity`.
+    #[allow(dead_code)]
+    pub fn with_angular_velocity(self, angular_velocity: f32) -> Self {
+        Self { angular_velocity: Some(angular_velocity), ..self }
+    }
+
+    #[inline]
+    pub fn radius(&self) -> f32 { self.radius }
+
+    /// The components every ball has, without the optional ones.
+    pub fn build(self) -> BallBundle {
+        let mut shape_bundle = GeometryBuilder::build_as(
+            &shapes::Circle {
+                radius: self.radius,
+                ..default()
+            },
+            self.draw_mode,
+            Transform::from_translation(Vec3::from((self.position, 0.))),
+        );
+        if let BallVisual::Sprite(_) = self.visual {
+            // the shape is kept empty, its colors are still used for the sprite
+            shape_bundle.path = ShapePath::new().build();
+        }
+
+        BallBundle {
             ball: Ball {
-                radius,
-                mass: radius * radius,
+                radius: self.radius,
+                mass: self.mass.unwrap_or_else(|| self.mass_model.mass(self.radius)),
+                restitution: self.restitution,
             },
-            velocity: Velocity(velocity),
-            shape_bundle: GeometryBuilder::build_as(
-                &shapes::Circle {
-                    radius,
-                    ..default()
-                },
-                DrawMode::Fill(FillMode::color(color)),
-                Transform::from_translation(Vec3::from((position, 0.))),
-            ),
-        }
-    }
-}
\ No newline at end of file
+            velocity: Velocity(self.velocity),
+            force: Force::default(),
+            impulse: Impulse::default(),
+            visual: self.visual,
+            shape_bundle,
+        }
+    }
+
+    /// Spawn the ball with the components which were asked for.
+    pub fn spawn<'w, 's, 'a>(self, cmd: &'a mut Commands<'w, 's>) -> EntityCommands<'w, 's, 'a> {
+        let mut entity = cmd.spawn_bundle(self.build());
+        if let Some(kind) = self.kind {
+            entity.insert(kind);
+        }
+        if let Some(angular_velocity) = self.angular_velocity {
+            entity.insert(AngularVelocity(angular_velocity));
+        }
+        entity
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::any::TypeId;
+
+    use bevy::reflect::{ReflectMut, TypeRegistryInternal};
+
+    use super::*;
+
+    #[test]
+    fn velocity_helpers_keep_or_limit_the_speed() {
+        let mut velocity = Velocity(Vec2::new(30., -40.));
+        assert_eq!(velocity.speed(), 50.);
+
+        velocity.reflect(Vec2::Y);
+        assert_eq!(velocity, Velocity(Vec2::new(30., 40.)));
+        velocity.clamp_speed(100.);
+        assert_eq!(velocity.speed(), 50.);
+        velocity.clamp_speed(10.);
+        assert!(velocity.0.abs_diff_eq(Vec2::new(6., 8.), 1e-5), "{:?}", velocity);
+    }
+
+    #[test]
+    fn ball_components_can_be_changed_through_reflection() {
+        let mut registry = TypeRegistryInternal::default();
+        registry.register::<Ball>();
+        registry.register::<BallVisual>();
+
+        let mut world = World::new();
+        let entity = world.spawn()
+            .insert(Ball::new(4., MassModel::Constant(2.)))
+            .insert(BallVisual::Shape)
+            .id();
+        let reflect_ball = registry.get_type_data::<ReflectComponent>(TypeId::of::<Ball>()).unwrap();
+        let reflected = reflect_ball.reflect_component(&world, entity).unwrap();
+        let mut ball = reflected.clone_value();
+        if let ReflectMut::Struct(ball) = ball.reflect_mut() {
+            assert_eq!(ball.field("mass").and_then(|mass| mass.downcast_ref::<f32>()), Some(&2.));
+            *ball.field_mut("radius").unwrap().downcast_mut::<f32>().unwrap() = 8.;
+        }
+        reflect_ball.apply_component(&mut world, entity, &*ball);
+        assert_eq!(world.get::<Ball>(entity).unwrap().radius, 8.);
+
+        let reflect_visual = registry.get_type_data::<ReflectComponent>(TypeId::of::<BallVisual>()).unwrap();
+        reflect_visual.apply_component(&mut world, entity, &BallVisual::Sprite(2));
+        assert_eq!(world.get::<BallVisual>(entity), Some(&BallVisual::Sprite(2)));
+    }
+
+    #[test]
+    fn balls_only_get_the_components_they_are_built_with() {
+        let kinds = BallKinds::new(vec![
+            BallKind::default(),
+            BallKind { name: "bouncy".to_string(), restitution: 0.5, ..default() },
+        ]).unwrap();
+        let plain = BallBundle::builder(4.).with_position(Vec2::new(1., 2.));
+        let heavy = BallBundle::builder(4.).with_kind(&kinds, Kind(1)).with_mass(80.).with_angular_velocity(2.);
+
+        let mut world = World::new();
+        let mut stage = SystemStage::single_threaded().with_system(move |mut cmd: Commands| {
+            plain.spawn(&mut cmd);
+            heavy.spawn(&mut cmd);
+        });
+        stage.run(&mut world);
+
+        let mut query = world.query::<(&Ball, &Transform, Option<&Kind>, Option<&AngularVelocity>)>();
+        let mut balls: Vec<_> = query.iter(&world).collect();
+        balls.sort_by_key(|(_, _, kind, _)| kind.is_some());
+        let (ball, transform, kind, spin) = balls[0];
+        assert_eq!((ball.mass, ball.restitution), (MASS_MODEL.mass(4.), 1.));
+        assert_eq!(transform.translation, Vec3::new(1., 2., 0.));
+        assert!(kind.is_none() && spin.is_none());
+
+        let (ball, _, kind, spin) = balls[1];
+        assert_eq!((ball.mass, ball.restitution), (80., 0.5));
+        assert_eq!(kind, Some(&Kind(1)));
+        assert_eq!(spin.map(|spin| spin.0), Some(2.));
+    }
+}
@@ -0,0 +1,206 @@
+use bevy::prelude::*;
+use bevy_prototype_lyon::entity::Path;
+use bevy_prototype_lyon::prelude::*;
+use rand::distributions::{Distribution, Uniform};
+
+use crate::*;
+
+/// Spawns balls which are made of several circles, shaped like peanuts and
+/// snowmen, next to the regular balls.
+pub struct CompoundPlugin {
+    count: usize,
+}
+
+impl CompoundPlugin {
+    pub fn with_count(count: usize) -> Self {
+        Self { count }
+    }
+}
+
+impl Default for CompoundPlugin {
+    fn default() -> Self { Self::with_count(10) }
+}
+
+impl Plugin for CompoundPlugin {
+    fn build(&self, app: &mut App) {
+        let count = self.count;
+        app.add_startup_system(move |mut cmd: Commands, mut rng: ResMut<SimRng>, palette: Res<Palette>| {
+            let rng = &mut **rng;
+            let area = Bounds::new(Vec2::ZERO, WIDTH, HEIGHT).shrunk(*BALL_RADIUS.end() * 2.);
+            let rand_radius = Uniform::from(BALL_RADIUS);
+            let rand_velocity = Uniform::from(-50.0..=50.0);
+
+            for i in 0..count {
+                let radius = rand_radius.sample(rng);
+                let collider = if i % 2 == 0 {
+                    CompoundCollider::peanut(radius, MASS_MODEL)
+                } else {
+                    CompoundCollider::snowman(radius, MASS_MODEL)
+                };
+                let velocity = Vec2::new(rand_velocity.sample(rng), rand_velocity.sample(rng));
+                let bundle = collider.bundle(BallStyle::fill(palette.pick(rng)), MASS_MODEL, velocity, random_point_in(rng, area));
+                cmd.spawn_bundle(bundle)
+                    .insert(collider)
+                    .insert(AngularVelocity::default());
+            }
+        });
+    }
+}
+
+/// Circle of a `CompoundCollider`, at a fixed offset from the center of the
+/// body it is part of.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CircleCollider {
+    pub offset: Vec2,
+    pub radius: f32,
+}
+
+impl CircleCollider {
+    #[inline]
+    pub fn new(offset: Vec2, radius: f32) -> Self {
+        Self { offset, radius }
+    }
+}
+
+/// Makes a ball a rigid body of several circles, which turn with its rotation
+/// and share its velocity. The `Ball` of the entity is the circle around all
+/// of them, with their combined mass, so the broad phase and the walls treat
+/// the body as a whole.
+#[derive(Component, Clone, Debug, PartialEq)]
+pub struct CompoundCollider {
+    pub circles: Vec<CircleCollider>,
+    inertia: f32,
+}
+
+impl CompoundCollider {
+    /// Body of `circles`, which are moved so the center of mass according to
+    /// `mass_model` lies at the center of the body.
+    pub fn new(mut circles: Vec<CircleCollider>, mass_model: MassModel) -> Self {
+        let mass: f32 = circles.iter().map(|circle| mass_model.mass(circle.radius)).sum();
+        if mass > 0. {
+            let center = circles.iter()
+                .fold(Vec2::ZERO, |sum, circle| sum + circle.offset * mass_model.mass(circle.radius)) / mass;
+            for circle in circles.iter_mut() {
+                circle.offset -= center;
+            }
+        }
+        // each circle is a solid disk, moved away from the center of mass
+        let inertia = circles.iter()
+            .map(|circle| {
+                mass_model.mass(circle.radius) * (circle.radius * circle.radius / 2. + circle.offset.length_squared())
+            })
+            .sum();
+        Self { circles, inertia }
+    }
+
+    /// Two equal circles which overlap a little, fitting within `radius`.
+    pub fn peanut(radius: f32, mass_model: MassModel) -> Self {
+        let lobe = radius * 0.55;
+        Self::new(vec![
+            CircleCollider::new(Vec2::new(lobe - radius, 0.), lobe),
+            CircleCollider::new(Vec2::new(radius - lobe, 0.), lobe),
+        ], mass_model)
+    }
+
+    /// Three stacked circles, each smaller than the one below it.
+    pub fn snowman(radius: f32, mass_model: MassModel) -> Self {
+        let unit = radius / 2.4;
+        Self::new(vec![
+            CircleCollider::new(Vec2::new(0., -1.4 * unit), unit),
+            CircleCollider::new(Vec2::new(0., 0.2 * unit), 0.75 * unit),
+            CircleCollider::new(Vec2::new(0., 1.35 * unit), 0.5 * unit),
+        ], mass_model)
+    }
+
+    /// Radius of the circle around all circles of the body.
+    #[inline]
+    pub fn radius(&self) -> f32 {
+        self.circles.iter()
+            .map(|circle| circle.offset.length() + circle.radius)
+            .fold(0., f32::max)
+    }
+
+    /// Moment of inertia of the body around its center of mass.
+    #[inline]
+    pub fn moment_of_inertia(&self) -> f32 {
+        self.inertia
+    }
+
+    /// Ball of the body as a whole.
+    #[inline]
+    pub fn ball(&self, mass_model: MassModel) -> Ball {
+        Ball {
+            radius: self.radius(),
+            mass: self.circles.iter().map(|circle| mass_model.mass(circle.radius)).sum(),
+            restitution: 1.,
+        }
+    }
+
+    /// Drawn shape of the body, its circles on top of each other.
+    pub fn path(&self) -> Path {
+        self.circles.iter()
+            .fold(ShapePath::new(), |path, circle| path.add(&shapes::Circle {
+                radius: circle.radius,
+                center: circle.offset,
+            }))
+            .build()
+    }
+
+    /// Ball at `position` which has the shape and mass of this body.
+    pub fn bundle(&self, style: BallStyle, mass_model: MassModel, velocity: Vec2, position: Vec2) -> BallBundle {
+        let mut bundle = BallBundle::builder(self.radius())
+            .with_style(style)
+            .with_mass(self.ball(mass_model).mass)
+            .with_velocity(velocity)
+            .with_position(position)
+            .build();
+        bundle.shape_bundle.path = self.path();
+        bundle
+    }
+
+    /// Circles of the body in the world, when its ball has `transform`.
+    pub fn shapes<'a>(&'a self, transform: &'a Transform) -> impl Iterator<Item = BodyShape> + 'a {
+        let center = transform.translation.truncate();
+        self.circles.iter().map(move |circle| BodyShape::Circle {
+            center: center + (transform.rotation * circle.offset.extend(0.)).truncate(),
+            radius: circle.radius,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compound_colliders_move_as_one_body() {
+        let model = MassModel::Area(1.);
+        let snowman = CompoundCollider::snowman(12., model);
+        let ball = snowman.ball(model);
+        assert!(ball.radius <= 12. + 1e-4, "{}", ball.radius);
+        assert_eq!(ball.mass, snowman.circles.iter().map(|circle| model.mass(circle.radius)).sum::<f32>());
+
+        // the heavier bottom circle pulls the center of mass down, so it is
+        // closer to the center than the top one
+        let center_of_mass = snowman.circles.iter()
+            .fold(Vec2::ZERO, |sum, circle| sum + circle.offset * model.mass(circle.radius));
+        assert!(center_of_mass.length() < 1e-4, "{}", center_of_mass);
+        assert!(-snowman.circles[0].offset.y < snowman.circles[2].offset.y);
+
+        // a ball in the waist of a peanut only touches it near its lobes
+        let peanut = CompoundCollider::peanut(10., model);
+        let transform = Transform::default();
+        let shapes: Vec<BodyShape> = peanut.shapes(&transform).collect();
+        let ball = |center: Vec2| [BodyShape::Circle { center, radius: 1. }];
+        assert_eq!(body_contact(&shapes, &ball(Vec2::new(0., 5.))), None);
+        let Contact { normal, depth, .. } = body_contact(&shapes, &ball(Vec2::new(5.5, 5.))).unwrap();
+        assert!(normal.x > 0. && normal.y > 0., "{}", normal);
+        assert!(depth > 0.);
+
+        // the circles turn along with the body
+        let turned = Transform::from_rotation(Quat::from_rotation_z(std::f32::consts::FRAC_PI_2));
+        let shapes: Vec<BodyShape> = peanut.shapes(&turned).collect();
+        assert!(body_contact(&shapes, &ball(Vec2::new(0., 9.))).is_some());
+        assert_eq!(body_contact(&shapes, &ball(Vec2::new(9., 0.))), None);
+    }
+}
@@ -0,0 +1,160 @@
+use std::collections::HashMap;
+use std::mem;
+
+use crate::*;
+
+// Fraction of the impulse of the previous tick which is applied up front to a
+// persistent contact. Slightly less than all of it, so stale contacts don't
+// push balls apart.
+const WARM_START: f32 = 0.8;
+
+/// Contacts between balls which are kept across ticks, keyed by their pair of
+/// entities. Colliding balls are resolved by iterating over all contacts
+/// multiple times, accumulating the impulse of each contact. The impulse of a
+/// contact which persists is applied again at the start of the next tick, so
+/// resting and stacked balls settle instead of jittering.
+///
+/// Only used when collisions are not resolved in parallel islands.
+pub struct ContactCache {
+    iterations: usize,
+    impulses: HashMap<[Entity; 2], f32>,
+    previous: HashMap<[Entity; 2], f32>,
+    contacts: Vec<Contact>,
+}
+
+struct Contact {
+    pair: [Entity; 2],
+    // points from the first towards the second ball
+    normal: Vec2,
+    mass: f32,
+    target: f32,
+    impulse: f32,
+}
+
+impl ContactCache {
+    pub fn with_iterations(iterations: usize) -> Self {
+        Self {
+            iterations: iterations.max(1),
+            impulses: HashMap::new(),
+            previous: HashMap::new(),
+            contacts: Vec::new(),
+        }
+    }
+
+    /// Accumulated impulse of the contact between `pair` during the last tick.
+    #[allow(dead_code)]
+    #[inline]
+    pub fn impulse(&self, pair: [Entity; 2]) -> Option<f32> {
+        self.impulses.get(&pair).copied()
+    }
+
+    /// Bounce off the colliding `pairs` of balls, warm starting the contacts
+    /// which persist from the previous tick.
+    pub fn solve(
+        &mut self,
+        model: CollisionModel,
+        pairs: &[[Entity; 2]],
+        query: &mut Query<(Entity, &mut Transform, &mut Velocity, &Ball)>,
+    ) {
+        mem::swap(&mut self.impulses, &mut self.previous);
+        self.impulses.clear();
+        self.contacts.clear();
+
+        for pair in pairs {
+            let [
+            (_, transform_a, mut velocity_a, ball_a),
+            (_, transform_b, mut velocity_b, ball_b)
+            ] = query.many_mut(*pair);
+
+            let offset = (transform_b.translation - transform_a.translation).truncate();
+            let normal = offset.normalize_or_zero();
+            if normal == Vec2::ZERO {
+                continue;
+            }
+
+            let approach = (velocity_b.0 - velocity_a.0).dot(normal);
+            let impulse = self.previous.get(pair).map_or(0., |impulse| impulse * WARM_START);
+            velocity_a.0 -= normal * impulse / ball_a.mass;
+            velocity_b.0 += normal * impulse / ball_b.mass;
+
+            self.contacts.push(Contact {
+                pair: *pair,
+                normal,
+                mass: ball_a.mass * ball_b.mass / (ball_a.mass + ball_b.mass),
+                target: -model.restitution() * ball_a.restitution.min(ball_b.restitution) * approach.min(0.),
+                impulse,
+            });
+        }
+
+        for _ in 0..self.iterations {
+            for contact in self.contacts.iter_mut() {
+                let [
+                (_, _, mut velocity_a, ball_a),
+                (_, _, mut velocity_b, ball_b)
+                ] = query.many_mut(contact.pair);
+
+                let relative = (velocity_b.0 - velocity_a.0).dot(contact.normal);
+                let accumulated = (contact.impulse + (contact.target - relative) * contact.mass).max(0.);
+                let delta = accumulated - contact.impulse;
+                contact.impulse = accumulated;
+
+                velocity_a.0 -= contact.normal * delta / ball_a.mass;
+                velocity_b.0 += contact.normal * delta / ball_b.mass;
+            }
+        }
+
+        for contact in self.contacts.iter() {
+            self.impulses.insert(contact.pair, contact.impulse);
+        }
+    }
+}
+
+impl Default for ContactCache {
+    fn default() -> Self { Self::with_iterations(4) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solve_system(
+        mut cache: ResMut<ContactCache>,
+        pairs: Res<Vec<[Entity; 2]>>,
+        mut query: Query<(Entity, &mut Transform, &mut Velocity, &Ball)>,
+    ) {
+        cache.solve(CollisionModel::PerfectlyInelastic, &pairs, &mut query);
+    }
+
+    #[test]
+    fn resting_contact_is_warm_started() {
+        let mut world = World::new();
+        let mut spawn = |y: f32, mass: f32| {
+            world.spawn()
+                .insert(Transform::from_xyz(0., y, 0.))
+                .insert(Velocity(Vec2::ZERO))
+                .insert(Ball::new(5., MassModel::Constant(mass)))
+                .id()
+        };
+        // a light ball resting on top of a heavy one
+        let pair = [spawn(0., 1e6), spawn(10., 1.)];
+        world.insert_resource(ContactCache::with_iterations(4));
+        world.insert_resource(vec![pair]);
+
+        let mut stage = SystemStage::single_threaded().with_system(solve_system);
+        for _ in 0..3 {
+            // the top ball is pulled down onto the bottom one each tick
+            world.get_mut::<Velocity>(pair[1]).unwrap().0.y -= 10.;
+            stage.run(&mut world);
+
+            let impulse = world.resource::<ContactCache>().impulse(pair).unwrap();
+            assert!((impulse - 10.).abs() < 1e-3, "{}", impulse);
+            assert!(world.get::<Velocity>(pair[1]).unwrap().0.y.abs() < 1e-3);
+        }
+
+        // without the pull the warm started impulse is taken back, instead of
+        // pushing the balls apart
+        stage.run(&mut world);
+        assert_eq!(world.resource::<ContactCache>().impulse(pair), Some(0.));
+        assert!(world.get::<Velocity>(pair[1]).unwrap().0.y.abs() < 1e-3);
+    }
+}
@@ -0,0 +1,140 @@
+use bevy::prelude::*;
+
+use crate::*;
+
+/// Adds conveyor regions, which accelerate all balls inside of them in a
+/// single direction. Regions are drawn as outlines with arrows that flow in
+/// the direction of their force.
+pub struct ConveyorPlugin {
+    regions: Vec<ConveyorRegion>,
+}
+
+impl ConveyorPlugin {
+    pub fn with_regions(regions: Vec<ConveyorRegion>) -> Self {
+        Self { regions }
+    }
+}
+
+impl Default for ConveyorPlugin {
+    fn default() -> Self {
+        Self::with_regions(vec![
+            ConveyorRegion {
+                bounds: Bounds::new(Vec2::new(0., -HEIGHT / 4.), WIDTH / 2., 80.),
+                force: Vec2::new(200., 0.),
+            },
+            ConveyorRegion {
+                bounds: Bounds::new(Vec2::new(0., HEIGHT / 4.), WIDTH / 2., 80.),
+                force: Vec2::new(-200., 0.),
+            },
+        ])
+    }
+}
+
+impl Plugin for ConveyorPlugin {
+    fn build(&self, app: &mut App) {
+        let regions = self.regions.clone();
+        app.add_startup_system(move |mut cmd: Commands| {
+            for region in &regions {
+                cmd.spawn().insert(*region);
+            }
+        })
+            .add_system(draw_conveyors)
+            .add_system_to_stage(PhysicsStage, apply_conveyors.before(PhysicsSystem::Integrate));
+    }
+}
+
+#[derive(Component, Clone, Copy, Debug)]
+pub struct ConveyorRegion {
+    pub bounds: Bounds,
+    /// Acceleration of balls whose center is within the region.
+    pub force: Vec2,
+}
+
+// Distance between the arrows drawn within a region.
+const ARROW_SPACING: f32 = 40.;
+const ARROW_LENGTH: f32 = 12.;
+// Speed at which arrows flow through a region.
+const ARROW_SPEED: f32 = 30.;
+
+fn apply_conveyors(
+    tree: Res<BallTree>,
+    regions: Query<&ConveyorRegion>,
+    mut balls: Query<(Entity, &Transform, &mut Force, &Ball)>,
+) {
+    for region in regions.iter() {
+        // the tree is a tick old, so candidates are checked against their
+        // current position; without a tree all balls are checked
+        if tree.0.is_empty() {
+            for (_, transform, mut force, ball) in balls.iter_mut() {
+                if region.bounds.contains(transform.translation.truncate()) {
+                    force.0 += region.force * ball.mass;
+                }
+            }
+            continue;
+        }
+
+        for (_, entity) in tree.0.query_area(region.bounds) {
+            if let Ok((_, transform, mut force, ball)) = balls.get_mut(entity) {
+                if region.bounds.contains(transform.translation.truncate()) {
+                    force.0 += region.force * ball.mass;
+                }
+            }
+        }
+    }
+}
+
+/// Positions of the arrows within `bounds` after `distance` of flowing in
+/// `direction`. Arrows leaving the bounds wrap around to the other side.
+pub fn arrow_positions(bounds: Bounds, direction: Vec2, distance: f32) -> Vec<Vec2> {
+    let size = Vec2::new(bounds.width(), bounds.height());
+    let count = (size / ARROW_SPACING).ceil().max(Vec2::ONE);
+    let spacing = size / count;
+    let offset = direction * distance.rem_euclid(ARROW_SPACING);
+
+    let mut positions = Vec::with_capacity((count.x * count.y) as usize);
+    for x in 0..count.x as usize {
+        for y in 0..count.y as usize {
+            let cell = (Vec2::new(x as f32, y as f32) + 0.5) * spacing + offset;
+            let wrapped = Vec2::new(cell.x.rem_euclid(size.x), cell.y.rem_euclid(size.y));
+            positions.push(bounds.min() + wrapped);
+        }
+    }
+    positions
+}
+
+fn draw_conveyors(mut debug_lines: ResMut<DebugLines>, time: Res<Time>, query: Query<&ConveyorRegion>) {
+    let color = Color::rgba(0.4, 0.8, 1., 0.6);
+    for region in query.iter() {
+        region.bounds.debug_draw_lines(&mut debug_lines, Some(color));
+
+        let direction = region.force.normalize_or_zero();
+        if direction == Vec2::ZERO {
+            continue;
+        }
+
+        let distance = time.seconds_since_startup() as f32 * ARROW_SPEED;
+        let head = Vec2::new(-direction.y, direction.x) * ARROW_LENGTH / 3.;
+        for position in arrow_positions(region.bounds, direction, distance) {
+            let tip = position + direction * ARROW_LENGTH / 2.;
+            let tail = position - direction * ARROW_LENGTH / 2.;
+            let back = tip - direction * ARROW_LENGTH / 3.;
+
+            debug_lines.line_colored(tail.extend(0.), tip.extend(0.), 0., color);
+            debug_lines.line_colored(tip.extend(0.), (back + head).extend(0.), 0., color);
+            debug_lines.line_colored(tip.extend(0.), (back - head).extend(0.), 0., color);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arrows_wrap_around_the_region() {
+        let bounds = Bounds::from_corners(Vec2::ZERO, Vec2::new(80., 40.));
+        assert_eq!(arrow_positions(bounds, Vec2::X, 0.), vec![Vec2::new(20., 20.), Vec2::new(60., 20.)]);
+        assert_eq!(arrow_positions(bounds, Vec2::X, 30.), vec![Vec2::new(50., 20.), Vec2::new(10., 20.)]);
+        assert_eq!(arrow_positions(bounds, Vec2::X, 40.), arrow_positions(bounds, Vec2::X, 0.));
+    }
+}
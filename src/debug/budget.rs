@@ -0,0 +1,138 @@
+use bevy::diagnostic::{Diagnostics, FrameTimeDiagnosticsPlugin};
+use bevy::prelude::*;
+
+/// Keeps interactive sessions responsive by degrading the physics debug
+/// drawing when the average frame time exceeds the budget. The pair lines are
+/// dropped first, then the region outlines. They return, in reverse order,
+/// once the frame time is comfortably below the budget again.
+pub struct DebugBudgetPlugin {
+    budget: f32,
+}
+
+impl DebugBudgetPlugin {
+    /// Budget of a single frame, in milliseconds.
+    pub fn with_budget(budget: f32) -> Self {
+        Self { budget }
+    }
+}
+
+impl Default for DebugBudgetPlugin {
+    fn default() -> Self { Self::with_budget(1000. / 60.) }
+}
+
+impl Plugin for DebugBudgetPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(DebugDetail::with_budget(self.budget))
+            .add_system(govern_debug_detail);
+    }
+}
+
+/// How much of the physics debug drawing is done, from most to least detail.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DetailLevel {
+    Full,
+    NoPairLines,
+    NoRegionOutlines,
+}
+
+pub struct DebugDetail {
+    pub level: DetailLevel,
+    budget: f32,
+    settle: u32,
+}
+
+// Fraction of the budget the frame time must drop below before detail is
+// restored, so the governor does not flip back and forth around the budget.
+const HEADROOM: f32 = 0.7;
+
+// Frames to wait after a change, so the averaged frame time reflects it before
+// the next decision is made.
+const SETTLE_FRAMES: u32 = 30;
+
+impl DebugDetail {
+    pub fn with_budget(budget: f32) -> Self {
+        Self { level: DetailLevel::Full, budget, settle: 0 }
+    }
+
+    #[inline]
+    pub fn pair_lines(&self) -> bool {
+        self.level < DetailLevel::NoPairLines
+    }
+
+    #[inline]
+    pub fn region_outlines(&self) -> bool {
+        self.level < DetailLevel::NoRegionOutlines
+    }
+
+    /// Adjust the level of detail to the average frame time, in milliseconds.
+    pub fn update(&mut self, frame_time: f32) {
+        if self.settle > 0 {
+            self.settle -= 1;
+            return;
+        }
+
+        let level = if frame_time > self.budget {
+            match self.level {
+                DetailLevel::Full => DetailLevel::NoPairLines,
+                _ => DetailLevel::NoRegionOutlines,
+            }
+        } else if frame_time < self.budget * HEADROOM {
+            match self.level {
+                DetailLevel::NoRegionOutlines => DetailLevel::NoPairLines,
+                _ => DetailLevel::Full,
+            }
+        } else {
+            self.level
+        };
+
+        if level != self.level {
+            self.level = level;
+            self.settle = SETTLE_FRAMES;
+        }
+    }
+}
+
+fn govern_debug_detail(mut detail: ResMut<DebugDetail>, diagnostics: Res<Diagnostics>) {
+    let frame_time = diagnostics.get(FrameTimeDiagnosticsPlugin::FRAME_TIME)
+        .and_then(|frame_time| frame_time.average());
+    if let Some(frame_time) = frame_time {
+        detail.update(frame_time as f32 * 1000.);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settle(detail: &mut DebugDetail) {
+        while detail.settle > 0 {
+            detail.update(0.);
+        }
+    }
+
+    #[test]
+    fn detail_is_degraded_and_restored_in_order() {
+        let mut detail = DebugDetail::with_budget(10.);
+        detail.update(12.);
+        assert!(!detail.pair_lines() && detail.region_outlines());
+
+        // Changes wait for the average to settle.
+        detail.update(12.);
+        assert_eq!(detail.level, DetailLevel::NoPairLines);
+        settle(&mut detail);
+        detail.update(12.);
+        assert_eq!(detail.level, DetailLevel::NoRegionOutlines);
+        settle(&mut detail);
+        detail.update(12.);
+        assert_eq!(detail.level, DetailLevel::NoRegionOutlines);
+
+        // Within the headroom nothing changes.
+        detail.update(8.);
+        assert_eq!(detail.level, DetailLevel::NoRegionOutlines);
+        detail.update(5.);
+        assert!(!detail.pair_lines() && detail.region_outlines());
+        settle(&mut detail);
+        detail.update(5.);
+        assert_eq!(detail.level, DetailLevel::Full);
+    }
+}
@@ -0,0 +1,159 @@
+use std::time::Duration;
+
+use bevy::diagnostic::{Diagnostic, DiagnosticId, Diagnostics};
+use bevy::prelude::*;
+
+use crate::*;
+
+/// Registers diagnostics of the collision pipeline, which are measured per
+/// physics tick and averaged over the ticks of each frame. They are logged by
+/// `LogDiagnosticsPlugin` and shown in the window title, when those are used.
+pub struct PhysicsDiagnosticsPlugin;
+
+impl PhysicsDiagnosticsPlugin {
+    pub const CANDIDATE_PAIRS: DiagnosticId =
+        DiagnosticId::from_u128(0x5fd1_3c7a_2e4b_4f0e_9b6a_1d2c_7e8f_0a01);
+    pub const COLLISIONS: DiagnosticId =
+        DiagnosticId::from_u128(0x5fd1_3c7a_2e4b_4f0e_9b6a_1d2c_7e8f_0a02);
+    pub const TREE_BUILD_TIME: DiagnosticId =
+        DiagnosticId::from_u128(0x5fd1_3c7a_2e4b_4f0e_9b6a_1d2c_7e8f_0a03);
+    pub const RESOLVE_TIME: DiagnosticId =
+        DiagnosticId::from_u128(0x5fd1_3c7a_2e4b_4f0e_9b6a_1d2c_7e8f_0a04);
+    pub const INTEGRATE_TIME: DiagnosticId =
+        DiagnosticId::from_u128(0x5fd1_3c7a_2e4b_4f0e_9b6a_1d2c_7e8f_0a05);
+    pub const BROAD_PHASE_TIME: DiagnosticId =
+        DiagnosticId::from_u128(0x5fd1_3c7a_2e4b_4f0e_9b6a_1d2c_7e8f_0a06);
+    pub const NARROW_PHASE_TIME: DiagnosticId =
+        DiagnosticId::from_u128(0x5fd1_3c7a_2e4b_4f0e_9b6a_1d2c_7e8f_0a07);
+    pub const DEBUG_DRAW_TIME: DiagnosticId =
+        DiagnosticId::from_u128(0x5fd1_3c7a_2e4b_4f0e_9b6a_1d2c_7e8f_0a08);
+}
+
+impl Plugin for PhysicsDiagnosticsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PhysicsTimings>()
+            .add_startup_system(setup_physics_diagnostics)
+            .add_system_to_stage(CoreStage::PostUpdate, record_physics_diagnostics);
+    }
+}
+
+// Amount of frames the diagnostics keep a history of.
+const HISTORY_LENGTH: usize = 20;
+
+/// Measurements of the physics ticks since the diagnostics were last recorded.
+#[derive(Default)]
+pub struct PhysicsTimings {
+    ticks: u32,
+    pairs: usize,
+    build: Duration,
+    resolve: Duration,
+    stages: [Duration; 4],
+}
+
+impl PhysicsTimings {
+    /// Record the broad phase of a tick. Called once per tick.
+    #[inline]
+    pub fn record_broad_phase(&mut self, build: Duration, pairs: usize) {
+        self.ticks += 1;
+        self.build += build;
+        self.pairs += pairs;
+    }
+
+    #[inline]
+    pub fn record_resolve(&mut self, resolve: Duration) {
+        self.resolve += resolve;
+    }
+
+    /// Record the time spent in a step of a tick. Resolving is recorded with
+    /// `record_resolve` instead, and the other steps aren't timed.
+    #[inline]
+    pub fn record_stage(&mut self, stage: PhysicsSystem, time: Duration) {
+        let index = match stage {
+            PhysicsSystem::Integrate => 0,
+            PhysicsSystem::BroadPhase => 1,
+            PhysicsSystem::NarrowPhase => 2,
+            PhysicsSystem::DebugDraw => 3,
+            PhysicsSystem::Resolve => return self.record_resolve(time),
+            PhysicsSystem::Constraints => return,
+        };
+        self.stages[index] += time;
+    }
+}
+
+fn setup_physics_diagnostics(mut diagnostics: ResMut<Diagnostics>) {
+    use PhysicsDiagnosticsPlugin as P;
+    diagnostics.add(Diagnostic::new(P::CANDIDATE_PAIRS, "candidate_pairs", HISTORY_LENGTH));
+    diagnostics.add(Diagnostic::new(P::COLLISIONS, "collisions", HISTORY_LENGTH));
+    diagnostics.add(Diagnostic::new(P::TREE_BUILD_TIME, "tree_build_time", HISTORY_LENGTH).with_suffix("ms"));
+    diagnostics.add(Diagnostic::new(P::RESOLVE_TIME, "resolve_time", HISTORY_LENGTH).with_suffix("ms"));
+    diagnostics.add(Diagnostic::new(P::INTEGRATE_TIME, "integrate_time", HISTORY_LENGTH).with_suffix("ms"));
+    diagnostics.add(Diagnostic::new(P::BROAD_PHASE_TIME, "broad_phase_time", HISTORY_LENGTH).with_suffix("ms"));
+    diagnostics.add(Diagnostic::new(P::NARROW_PHASE_TIME, "narrow_phase_time", HISTORY_LENGTH).with_suffix("ms"));
+    diagnostics.add(Diagnostic::new(P::DEBUG_DRAW_TIME, "debug_draw_time", HISTORY_LENGTH).with_suffix("ms"));
+}
+
+// Frames without a physics tick are skipped, instead of reporting zeros.
+fn record_physics_diagnostics(
+    mut diagnostics: ResMut<Diagnostics>,
+    mut timings: ResMut<PhysicsTimings>,
+    mut collided: EventReader<BallCollided>,
+) {
+    let collisions = collided.iter().count();
+    if timings.ticks == 0 {
+        return;
+    }
+
+    use PhysicsDiagnosticsPlugin as P;
+    let ticks = timings.ticks as f64;
+    diagnostics.add_measurement(P::CANDIDATE_PAIRS, timings.pairs as f64 / ticks);
+    diagnostics.add_measurement(P::COLLISIONS, collisions as f64 / ticks);
+    diagnostics.add_measurement(P::TREE_BUILD_TIME, timings.build.as_secs_f64() * 1000. / ticks);
+    diagnostics.add_measurement(P::RESOLVE_TIME, timings.resolve.as_secs_f64() * 1000. / ticks);
+    let stages = [P::INTEGRATE_TIME, P::BROAD_PHASE_TIME, P::NARROW_PHASE_TIME, P::DEBUG_DRAW_TIME];
+    for (id, time) in stages.into_iter().zip(timings.stages) {
+        diagnostics.add_measurement(id, time.as_secs_f64() * 1000. / ticks);
+    }
+    *timings = PhysicsTimings::default();
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::event::Events;
+
+    use super::*;
+
+    #[test]
+    fn measurements_are_averaged_over_ticks() {
+        let mut world = World::new();
+        world.insert_resource(Diagnostics::default());
+        world.insert_resource(PhysicsTimings::default());
+        world.insert_resource(Events::<BallCollided>::default());
+
+        SystemStage::single_threaded()
+            .with_system(setup_physics_diagnostics)
+            .run(&mut world);
+        let mut stage = SystemStage::single_threaded().with_system(record_physics_diagnostics);
+        stage.run(&mut world);
+
+        let mut timings = world.resource_mut::<PhysicsTimings>();
+        timings.record_broad_phase(Duration::from_millis(2), 10);
+        timings.record_broad_phase(Duration::from_millis(4), 30);
+        timings.record_resolve(Duration::from_millis(1));
+        timings.record_stage(PhysicsSystem::Integrate, Duration::from_millis(3));
+        timings.record_stage(PhysicsSystem::Resolve, Duration::from_millis(1));
+        let entity = world.spawn().id();
+        world.resource_mut::<Events<BallCollided>>().send(BallCollided(entity, entity));
+        stage.run(&mut world);
+
+        let diagnostics = world.resource::<Diagnostics>();
+        let value = |id| diagnostics.get_measurement(id).unwrap().value;
+        assert_eq!(value(PhysicsDiagnosticsPlugin::CANDIDATE_PAIRS), 20.);
+        assert_eq!(value(PhysicsDiagnosticsPlugin::COLLISIONS), 0.5);
+        assert!((value(PhysicsDiagnosticsPlugin::TREE_BUILD_TIME) - 3.).abs() < 1e-9);
+        assert!((value(PhysicsDiagnosticsPlugin::RESOLVE_TIME) - 1.).abs() < 1e-9);
+        assert!((value(PhysicsDiagnosticsPlugin::INTEGRATE_TIME) - 1.5).abs() < 1e-9);
+        assert_eq!(value(PhysicsDiagnosticsPlugin::DEBUG_DRAW_TIME), 0.);
+        // The first run had no ticks, so nothing was measured.
+        assert_eq!(diagnostics.get(PhysicsDiagnosticsPlugin::CANDIDATE_PAIRS).unwrap().measurements().count(), 1);
+    }
+}
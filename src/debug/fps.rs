@@ -2,6 +2,9 @@ use bevy::core::FixedTimestep;
 use bevy::diagnostic::{Diagnostics, FrameTimeDiagnosticsPlugin};
 use bevy::prelude::*;
 
+use super::PhysicsDiagnosticsPlugin;
+use crate::kinds::{BallKinds, KindCounts};
+
 pub struct WindowTitleFpsPlugin {
     rate: f64,
 }
@@ -30,9 +33,23 @@ fn update(
     mut windows: ResMut<Windows>,
     window_descriptor: Res<WindowDescriptor>,
     diagnostics: Res<Diagnostics>,
+    kinds: Option<Res<BallKinds>>,
+    counts: Option<Res<KindCounts>>,
 ) {
     if let Some(fps) = diagnostics.get_measurement(FrameTimeDiagnosticsPlugin::FPS) {
+        let mut title = format!("{}: {}", window_descriptor.title, fps.value.to_string());
+        let pairs = diagnostics.get(PhysicsDiagnosticsPlugin::CANDIDATE_PAIRS).and_then(|pairs| pairs.average());
+        let collisions = diagnostics.get(PhysicsDiagnosticsPlugin::COLLISIONS).and_then(|collisions| collisions.average());
+        if let (Some(pairs), Some(collisions)) = (pairs, collisions) {
+            title += &format!(" - {:.0} pairs, {:.0} collisions", pairs, collisions);
+        }
+        if let (Some(kinds), Some(counts)) = (kinds, counts) {
+            if kinds.as_slice().len() > 1 {
+                title += &format!(" - {}", counts.summary(&kinds));
+            }
+        }
+
         let window = windows.primary_mut();
-        window.set_title(format!("{}: {}", window_descriptor.title, fps.value.to_string()));
+        window.set_title(title);
     }
 }
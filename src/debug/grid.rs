@@ -0,0 +1,140 @@
+use bevy::prelude::*;
+use bevy::render::camera::Camera2d;
+
+use crate::*;
+
+/// Draws a grid of world space lines every `spacing` units, with every fifth
+/// line and the axes highlighted, toggled with `Action::ToggleGrid`. The grid
+/// covers the visible area of the view, so positions and scales stay readable
+/// wherever the camera is. When zoomed out far, lines are skipped to keep the
+/// amount of lines in check.
+pub struct BackgroundGridPlugin {
+    spacing: f32,
+}
+
+impl BackgroundGridPlugin {
+    pub fn with_spacing(spacing: f32) -> Self {
+        Self { spacing }
+    }
+}
+
+impl Default for BackgroundGridPlugin {
+    fn default() -> Self { Self::with_spacing(50.) }
+}
+
+impl Plugin for BackgroundGridPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(BackgroundGrid::with_spacing(self.spacing))
+            .bind_action(Action::ToggleGrid, [Binding::Key(KeyCode::G)])
+            .add_system(toggle_background_grid)
+            .add_system(draw_background_grid.after(toggle_background_grid));
+    }
+}
+
+pub struct BackgroundGrid {
+    pub enabled: bool,
+    /// Distance between lines, in world units.
+    pub spacing: f32,
+}
+
+/// Kind of a line of the grid, from least to most prominent.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GridLine {
+    Minor,
+    Major,
+    Axis,
+}
+
+// Every this many lines is a major line.
+const MAJOR_EVERY: i64 = 5;
+
+// Lines in either direction above which the spacing is doubled.
+const MAX_LINES: f32 = 200.;
+
+impl BackgroundGrid {
+    pub fn with_spacing(spacing: f32) -> Self {
+        Self { enabled: false, spacing: spacing.max(f32::EPSILON) }
+    }
+
+    /// Lines of the grid which cross `view`. The spacing is doubled until
+    /// there are no more than `MAX_LINES` lines in either direction.
+    pub fn lines(&self, view: Bounds) -> Vec<(Vec2, Vec2, GridLine)> {
+        let mut spacing = self.spacing;
+        while view.width().max(view.height()) / spacing > MAX_LINES {
+            spacing *= 2.;
+        }
+        let kind = |index: i64, doubled: i64| {
+            if index == 0 {
+                GridLine::Axis
+            } else if (index * doubled) % MAJOR_EVERY == 0 {
+                GridLine::Major
+            } else {
+                GridLine::Minor
+            }
+        };
+        let doubled = (spacing / self.spacing).round() as i64;
+
+        let mut lines = Vec::new();
+        for index in (view.left() / spacing).ceil() as i64..=(view.right() / spacing).floor() as i64 {
+            let x = index as f32 * spacing;
+            lines.push((Vec2::new(x, view.bottom()), Vec2::new(x, view.top()), kind(index, doubled)));
+        }
+        for index in (view.bottom() / spacing).ceil() as i64..=(view.top() / spacing).floor() as i64 {
+            let y = index as f32 * spacing;
+            lines.push((Vec2::new(view.left(), y), Vec2::new(view.right(), y), kind(index, doubled)));
+        }
+        lines
+    }
+}
+
+fn toggle_background_grid(mut grid: ResMut<BackgroundGrid>, actions: Res<Input<Action>>) {
+    if actions.just_pressed(Action::ToggleGrid) {
+        grid.enabled = !grid.enabled;
+    }
+}
+
+fn draw_background_grid(
+    grid: Res<BackgroundGrid>,
+    windows: Res<Windows>,
+    cameras: Query<&Transform, With<Camera2d>>,
+    mut debug_lines: ResMut<DebugLines>,
+) {
+    if !grid.enabled {
+        return;
+    }
+    let (window, camera) = match (windows.get_primary(), cameras.iter().next()) {
+        (Some(window), Some(camera)) => (window, camera),
+        _ => return,
+    };
+
+    for (a, b, kind) in grid.lines(view_bounds(window, camera)) {
+        let color = match kind {
+            GridLine::Minor => Color::rgba(1., 1., 1., 0.08),
+            GridLine::Major => Color::rgba(1., 1., 1., 0.2),
+            GridLine::Axis => Color::rgba(0.4, 0.8, 1., 0.6),
+        };
+        debug_lines.line_colored(a.extend(0.), b.extend(0.), 0., color);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grid_lines_cover_the_view() {
+        let grid = BackgroundGrid::with_spacing(10.);
+        let lines = grid.lines(Bounds::from_corners(Vec2::new(-15., -5.), Vec2::new(25., 5.)));
+        let vertical: Vec<f32> = lines.iter().filter(|(a, b, _)| a.x == b.x).map(|(a, ..)| a.x).collect();
+        assert_eq!(vertical, vec![-10., 0., 10., 20.]);
+        assert_eq!(lines.len(), 4 + 1);
+        assert!(lines.contains(&(Vec2::new(0., -5.), Vec2::new(0., 5.), GridLine::Axis)));
+        assert!(lines.contains(&(Vec2::new(-15., 0.), Vec2::new(25., 0.), GridLine::Axis)));
+
+        // zoomed out, the spacing is doubled while major lines stay major
+        let lines = grid.lines(Bounds::new(Vec2::ZERO, 10_000., 100.));
+        assert!(lines.len() <= 2 * MAX_LINES as usize + 2);
+        let major = lines.iter().find(|(a, b, kind)| a.x == b.x && *kind == GridLine::Major).unwrap();
+        assert_eq!(major.0.x.abs() % 50., 0.);
+    }
+}
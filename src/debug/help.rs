@@ -0,0 +1,224 @@
+use std::str::FromStr;
+
+use bevy::prelude::*;
+use bevy::render::camera::Camera2d;
+
+use crate::*;
+
+/// Lists the keys of all actions and axes in the corner of the view, toggled
+/// with `Action::ToggleHelp`. The list is generated from the `InputMap`,
+/// so it only shows the keys of the features which are active, and describes
+/// them in the selected language.
+pub struct HelpOverlayPlugin {
+    language: Language,
+}
+
+impl HelpOverlayPlugin {
+    pub fn with_language(language: Language) -> Self {
+        Self { language }
+    }
+}
+
+impl Default for HelpOverlayPlugin {
+    fn default() -> Self { Self::with_language(Language::English) }
+}
+
+impl Plugin for HelpOverlayPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(HelpOverlay { enabled: false, language: self.language })
+            .bind_action(Action::ToggleHelp, [Binding::Key(KeyCode::H)])
+            .add_system(toggle_help_overlay)
+            .add_system(draw_help_overlay.after(toggle_help_overlay));
+    }
+}
+
+pub struct HelpOverlay {
+    pub enabled: bool,
+    pub language: Language,
+}
+
+/// Languages the help overlay is available in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Language {
+    English,
+    Dutch,
+}
+
+impl FromStr for Language {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "en" => Ok(Self::English),
+            "nl" => Ok(Self::Dutch),
+            _ => Err(()),
+        }
+    }
+}
+
+impl Language {
+    /// What `control` does, in this language.
+    pub fn describe(&self, control: Control) -> &'static str {
+        use Action::*;
+        use AxisControl::*;
+        use Language::*;
+        match (control, self) {
+            (Control::Action(Pause), English) => "pause",
+            (Control::Action(Pause), Dutch) => "pauzeren",
+            (Control::Action(Reset), English) => "reset balls",
+            (Control::Action(Reset), Dutch) => "ballen herstellen",
+            (Control::Action(Rewind), English) => "play backwards",
+            (Control::Action(Rewind), Dutch) => "terugspoelen",
+            (Control::Action(SpawnBalls), English) => "spawn balls",
+            (Control::Action(SpawnBalls), Dutch) => "ballen toevoegen",
+            (Control::Action(DespawnBalls), English) => "remove balls",
+            (Control::Action(DespawnBalls), Dutch) => "ballen verwijderen",
+            (Control::Action(SpawnBurst), English) => "burst of balls",
+            (Control::Action(SpawnBurst), Dutch) => "salvo ballen",
+            (Control::Action(Explode), English) => "explosion",
+            (Control::Action(Explode), Dutch) => "explosie",
+            (Control::Action(ToggleDebug), English) => "toggle debug lines",
+            (Control::Action(ToggleDebug), Dutch) => "debuglijnen aan/uit",
+            (Control::Action(ToggleAttractor), English) => "toggle attractor",
+            (Control::Action(ToggleAttractor), Dutch) => "aantrekker aan/uit",
+            (Control::Action(ToggleLabels), English) => "toggle labels",
+            (Control::Action(ToggleLabels), Dutch) => "labels aan/uit",
+            (Control::Action(ToggleGrid), English) => "toggle grid",
+            (Control::Action(ToggleGrid), Dutch) => "raster aan/uit",
+            (Control::Action(ToggleHelp), English) => "toggle help",
+            (Control::Action(ToggleHelp), Dutch) => "help aan/uit",
+            (Control::Action(ToggleStageTimings), English) => "toggle stage timings",
+            (Control::Action(ToggleStageTimings), Dutch) => "staptijden aan/uit",
+            (Control::Action(DeleteSelection), English) => "delete selection",
+            (Control::Action(DeleteSelection), Dutch) => "selectie verwijderen",
+            (Control::Action(FreezeSelection), English) => "freeze selection",
+            (Control::Action(FreezeSelection), Dutch) => "selectie bevriezen",
+            (Control::Action(RecolorSelection), English) => "recolor selection",
+            (Control::Action(RecolorSelection), Dutch) => "selectie herkleuren",
+            (Control::Action(PushSelection), English) => "push selection",
+            (Control::Action(PushSelection), Dutch) => "selectie duwen",
+            (Control::Action(SelectArea), English) => "select area",
+            (Control::Action(SelectArea), Dutch) => "gebied selecteren",
+            (Control::Action(SlowMotionRegion), English) => "slow motion region",
+            (Control::Action(SlowMotionRegion), Dutch) => "slow motion gebied",
+            (Control::Action(LabelRegion), English) => "label region",
+            (Control::Action(LabelRegion), Dutch) => "labelgebied",
+            (Control::Action(Undo), English) => "undo",
+            (Control::Action(Undo), Dutch) => "ongedaan maken",
+            (Control::Action(Redo), English) => "redo",
+            (Control::Action(Redo), Dutch) => "opnieuw",
+            (Control::Action(SaveScene), English) => "save scene",
+            (Control::Action(SaveScene), Dutch) => "scene opslaan",
+            (Control::Action(ExportBevyScene), English) => "export bevy scene",
+            (Control::Action(ExportBevyScene), Dutch) => "bevy scene exporteren",
+            (Control::Action(ToggleInspector), English) => "toggle quadtree inspector",
+            (Control::Action(ToggleInspector), Dutch) => "quadtree-inspector aan/uit",
+            (Control::Axis(GravityTilt), English) => "tilt gravity",
+            (Control::Axis(GravityTilt), Dutch) => "zwaartekracht kantelen",
+            (Control::Axis(AttractorMovement), English) => "move attractor",
+            (Control::Axis(AttractorMovement), Dutch) => "aantrekker bewegen",
+            (Control::Axis(PaddleMovement), English) => "move paddle",
+            (Control::Axis(PaddleMovement), Dutch) => "peddel bewegen",
+        }
+    }
+}
+
+/// Name of `binding`, as it is shown in the help overlay.
+pub fn binding_name(binding: Binding) -> String {
+    let key_name = |key: KeyCode| match key {
+        KeyCode::Equals => "=".to_string(),
+        KeyCode::Minus => "-".to_string(),
+        KeyCode::NumpadAdd => "num+".to_string(),
+        KeyCode::NumpadSubtract => "num-".to_string(),
+        KeyCode::Back => "backspace".to_string(),
+        key => format!("{:?}", key).to_lowercase(),
+    };
+    let button_name = |button: MouseButton| match button {
+        MouseButton::Other(button) => format!("mouse{}", button),
+        button => format!("mouse-{:?}", button).to_lowercase(),
+    };
+    match binding {
+        Binding::Key(key) => key_name(key),
+        Binding::Ctrl(key) => format!("ctrl+{}", key_name(key)),
+        Binding::Mouse(button) => button_name(button),
+        Binding::ShiftMouse(button) => format!("shift+{}", button_name(button)),
+    }
+}
+
+/// Lines of the help overlay: the keys of each control followed by what it
+/// does, in the order the controls were bound.
+pub fn help_lines(bindings: &InputMap, language: Language) -> Vec<String> {
+    let lines: Vec<(String, &str)> = bindings.iter()
+        .filter(|(_, keys)| !keys.is_empty())
+        .map(|(control, keys)| {
+            let keys: Vec<String> = keys.iter().map(|binding| binding_name(*binding)).collect();
+            (keys.join(" "), language.describe(*control))
+        })
+        .collect();
+
+    let width = lines.iter().map(|(keys, _)| keys.chars().count()).max().unwrap_or_default();
+    lines.into_iter()
+        .map(|(keys, description)| format!("{:width$}  {}", keys, description, width = width))
+        .collect()
+}
+
+// Height of the characters on screen, in pixels.
+const CHAR_HEIGHT: f32 = 12.;
+
+// Distance from the top left corner of the view, in pixels.
+const MARGIN: f32 = 16.;
+
+fn toggle_help_overlay(mut overlay: ResMut<HelpOverlay>, actions: Res<Input<Action>>) {
+    if actions.just_pressed(Action::ToggleHelp) {
+        overlay.enabled = !overlay.enabled;
+    }
+}
+
+fn draw_help_overlay(
+    overlay: Res<HelpOverlay>,
+    bindings: Res<InputMap>,
+    windows: Res<Windows>,
+    cameras: Query<&Transform, With<Camera2d>>,
+    mut debug_lines: ResMut<DebugLines>,
+) {
+    if !overlay.enabled {
+        return;
+    }
+    let (window, camera) = match (windows.get_primary(), cameras.iter().next()) {
+        (Some(window), Some(camera)) => (window, camera),
+        _ => return,
+    };
+
+    // the overlay keeps its size on screen when the view is zoomed
+    let scale = camera.scale.x;
+    let height = CHAR_HEIGHT * scale;
+    let top_left = view_bounds(window, camera).top_left() + Vec2::new(MARGIN, -MARGIN) * scale;
+    for (i, line) in help_lines(&bindings, overlay.language).iter().enumerate() {
+        let origin = top_left - Vec2::new(0., (i + 1) as f32 * height * 1.75);
+        for (a, b) in text_lines(line, origin, height) {
+            debug_lines.line_colored(a.extend(0.), b.extend(0.), 0., Color::WHITE);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn help_lists_the_bound_keys() {
+        let mut bindings = InputMap::default();
+        bindings.bind(Control::Action(Action::Undo), [Binding::Ctrl(KeyCode::Z)]);
+        bindings.bind(Control::Axis(AxisControl::PaddleMovement), [Binding::Key(KeyCode::Q), Binding::Key(KeyCode::E)]);
+        bindings.bind(Control::Action(Action::Undo), [Binding::Key(KeyCode::Back)]);
+        bindings.bind(Control::Action(Action::SelectArea), [Binding::ShiftMouse(MouseButton::Left)]);
+
+        assert_eq!(help_lines(&bindings, Language::English), vec![
+            "ctrl+z backspace  undo",
+            "q e               move paddle",
+            "shift+mouse-left  select area",
+        ]);
+        assert_eq!(help_lines(&bindings, Language::Dutch)[1], "q e               peddel bewegen");
+        assert_eq!("nl".parse(), Ok(Language::Dutch));
+    }
+}
@@ -0,0 +1,242 @@
+use bevy::input::InputSystem;
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContext, EguiPlugin};
+
+use crate::*;
+
+/// Shows the `BallTree` in an egui window, toggled with
+/// `Action::ToggleInspector`. Each node can be expanded to show its bounds,
+/// depth and amount of elements, and leaves list the balls they hold.
+/// Clicking the bounds of a node outlines its region in the world, clicking a
+/// ball selects it. While a single ball is selected, a second window shows
+/// its mass, which can be edited directly or recomputed from a `MassModel`.
+/// Clicks on the windows don't reach the world.
+pub struct QuadTreeInspectorPlugin;
+
+impl Plugin for QuadTreeInspectorPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugin(EguiPlugin)
+            .init_resource::<QuadTreeInspector>()
+            .bind_action(Action::ToggleInspector, [Binding::Key(KeyCode::F4)])
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                block_world_clicks.after(InputSystem).before(update_actions),
+            )
+            .add_system(toggle_inspector)
+            .add_system(inspector_window.after(toggle_inspector))
+            .add_system(draw_inspected_node.after(inspector_window));
+    }
+}
+
+pub struct QuadTreeInspector {
+    pub open: bool,
+    /// Node whose region is outlined in the world.
+    pub node: Option<NodeId>,
+    /// Model the mass of the selected ball is recomputed with.
+    pub mass_model: MassModel,
+}
+
+impl Default for QuadTreeInspector {
+    fn default() -> Self {
+        Self { open: false, node: None, mass_model: MASS_MODEL }
+    }
+}
+
+// Builds a mass model from its density or mass.
+type MassModelFn = fn(f32) -> MassModel;
+
+// Models which can be picked in the ball window, by name.
+const MASS_MODELS: [(&str, MassModelFn); 3] = [
+    ("area", MassModel::Area),
+    ("volume", MassModel::Volume),
+    ("constant", MassModel::Constant),
+];
+
+// Lightest mass which can be entered, balls without mass can't be pushed.
+const MIN_MASS: f32 = 0.01;
+
+/// Name of `model` and its density or mass, or `None` for models which are
+/// computed by a function.
+pub fn mass_model_parts(model: MassModel) -> Option<(&'static str, f32)> {
+    match model {
+        MassModel::Area(density) => Some(("area", density)),
+        MassModel::Volume(density) => Some(("volume", density)),
+        MassModel::Constant(mass) => Some(("constant", mass)),
+        MassModel::Explicit(_) => None,
+    }
+}
+
+/// Label of the header of node `id` of a tree.
+pub fn node_label<A: Aggregate>(id: NodeId, node: &QuadTree<A>) -> String {
+    let kind = match (node.is_leaf(), node.is_empty()) {
+        (_, true) => "empty",
+        (true, false) => "leaf",
+        (false, false) => "node",
+    };
+    format!("{} (depth {}, {} elements)", kind, id.depth(), node.count())
+}
+
+fn toggle_inspector(actions: Res<Input<Action>>, mut inspector: ResMut<QuadTreeInspector>) {
+    if actions.just_pressed(Action::ToggleInspector) {
+        inspector.open = !inspector.open;
+    }
+}
+
+// Keep clicks and drags on the window from picking or selecting balls.
+fn block_world_clicks(mut egui: ResMut<EguiContext>, mut mouse: ResMut<Input<MouseButton>>) {
+    if egui.ctx_mut().wants_pointer_input() {
+        for button in [MouseButton::Left, MouseButton::Right, MouseButton::Middle] {
+            mouse.reset(button);
+        }
+    }
+}
+
+fn inspector_window(
+    mut egui: ResMut<EguiContext>,
+    mut inspector: ResMut<QuadTreeInspector>,
+    mut selection: ResMut<Selection>,
+    ball_tree: Res<BallTree>,
+    mut balls: Query<&mut Ball>,
+) {
+    if !inspector.open {
+        return;
+    }
+
+    let mut open = true;
+    let mut node = inspector.node;
+    let mut ball = None;
+    egui::Window::new("quadtree").open(&mut open).show(egui.ctx_mut(), |ui| {
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            node_ui(ui, &ball_tree.0, NodeId::ROOT, &mut node, &mut ball, &selection);
+        });
+    });
+
+    inspector.open = open;
+    inspector.node = node;
+    if let Some(ball) = ball {
+        selection.entities.clear();
+        selection.entities.insert(ball);
+    }
+
+    let selected = match selection.entities.iter().collect::<Vec<_>>()[..] {
+        [entity] => entity,
+        _ => return,
+    };
+    let mut ball = match balls.get_mut(*selected) {
+        Ok(ball) => ball,
+        Err(_) => return,
+    };
+    let (radius, mut mass) = (ball.radius, ball.mass);
+    let mut model = inspector.mass_model;
+    egui::Window::new("ball").show(egui.ctx_mut(), |ui| {
+        ui.label(format!("ball {:?}, radius {:.1}", selected, radius));
+        ui.horizontal(|ui| {
+            ui.label("mass");
+            ui.add(egui::DragValue::new(&mut mass).speed(0.1).clamp_range(MIN_MASS..=f32::MAX));
+        });
+        ui.horizontal(|ui| {
+            mass_model_ui(ui, &mut model);
+            if ui.button("apply").clicked() {
+                mass = model.mass(radius).max(MIN_MASS);
+            }
+        });
+    });
+
+    inspector.mass_model = model;
+    // only touch the ball when it changed, to keep its change detection quiet
+    if mass != ball.mass {
+        ball.mass = mass;
+    }
+}
+
+// Pick the kind of `model`, which keeps its density or mass, and edit that
+// value. Models computed by a function can only be replaced.
+fn mass_model_ui(ui: &mut egui::Ui, model: &mut MassModel) {
+    let (name, mut value) = mass_model_parts(*model).unwrap_or(("explicit", 1.));
+    let mut kind = MASS_MODELS.iter().position(|(option, _)| *option == name);
+    egui::ComboBox::from_id_source("mass model")
+        .selected_text(name)
+        .show_ui(ui, |ui| {
+            for (index, (option, _)) in MASS_MODELS.iter().enumerate() {
+                ui.selectable_value(&mut kind, Some(index), *option);
+            }
+        });
+    if let Some(index) = kind {
+        ui.add(egui::DragValue::new(&mut value).speed(0.01).clamp_range(0.0..=f32::MAX));
+        *model = (MASS_MODELS[index].1)(value);
+    }
+}
+
+fn node_ui<A: Aggregate>(
+    ui: &mut egui::Ui,
+    tree: &QuadTree<A>,
+    id: NodeId,
+    node: &mut Option<NodeId>,
+    ball: &mut Option<Entity>,
+    selection: &Selection,
+) {
+    egui::CollapsingHeader::new(node_label(id, tree))
+        .id_source(id)
+        .default_open(id == NodeId::ROOT)
+        .show(ui, |ui| {
+            let bounds = tree.bounds();
+            let (min, max) = (bounds.min(), bounds.max());
+            let text = format!("bounds ({:.1}, {:.1}) - ({:.1}, {:.1})", min.x, min.y, max.x, max.y);
+            if ui.selectable_label(*node == Some(id), text).clicked() {
+                *node = if *node == Some(id) { None } else { Some(id) };
+            }
+
+            for (location, entity) in tree.leaf_elements().unwrap_or_default() {
+                let center = location.center();
+                let text = format!("ball {:?} at ({:.1}, {:.1})", entity, center.x, center.y);
+                if ui.selectable_label(selection.entities.contains(entity), text).clicked() {
+                    *ball = Some(*entity);
+                }
+            }
+            for (index, child) in tree.children().into_iter().flatten().enumerate() {
+                node_ui(ui, child, id.child(index), node, ball, selection);
+            }
+        });
+}
+
+fn draw_inspected_node(
+    inspector: Res<QuadTreeInspector>,
+    ball_tree: Res<BallTree>,
+    mut debug_lines: ResMut<DebugLines>,
+) {
+    if !inspector.open {
+        return;
+    }
+    if let Some(node) = inspector.node.and_then(|id| ball_tree.0.node(id)) {
+        node.bounds().debug_draw_lines(&mut debug_lines, Some(Color::CYAN));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn node_labels_show_kind_depth_and_elements() {
+        let options = Options::builder().capacity(1).max_depth(2).build().unwrap();
+        let mut tree = QuadTree::new(Bounds::new(Vec2::ZERO, 100., 100.), options);
+        tree.insert(Location::Point(Vec2::new(-10., -10.)), Entity::from_raw(1)).unwrap();
+        tree.insert(Location::Point(Vec2::new(10., 10.)), Entity::from_raw(2)).unwrap();
+
+        assert_eq!(node_label(NodeId::ROOT, &tree), "node (depth 0, 2 elements)");
+        let leaf = tree.leaf_at(Vec2::new(10., 10.)).unwrap();
+        assert_eq!(node_label(leaf, tree.node(leaf).unwrap()), "leaf (depth 1, 1 elements)");
+        let children = tree.children().unwrap();
+        assert_eq!(children.iter().filter(|child| child.is_empty()).count(), 2);
+    }
+
+    #[test]
+    fn mass_models_are_rebuilt_from_their_parts() {
+        assert!(mass_model_parts(MassModel::Explicit(|radius| radius)).is_none());
+        for model in [MassModel::Area(2.), MassModel::Volume(0.5), MassModel::Constant(3.)] {
+            let (name, value) = mass_model_parts(model).unwrap();
+            let (_, build) = MASS_MODELS.iter().find(|(option, _)| *option == name).unwrap();
+            assert_eq!(build(value).mass(4.), model.mass(4.));
+        }
+    }
+}
@@ -0,0 +1,159 @@
+use bevy::math::{const_vec2, Vec2, Vec3};
+use bevy::prelude::*;
+
+use crate::*;
+
+/// Labels balls with their entity id, toggled with `Action::ToggleLabels`. To
+/// avoid clutter only balls near the cursor are labeled, or the balls within a
+/// region which is selected by dragging with `Action::LabelRegion`, the middle
+/// mouse button by default. A click without dragging removes the region.
+pub struct BallLabelsPlugin;
+
+impl Plugin for BallLabelsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<BallLabels>()
+            .bind_action(Action::ToggleLabels, [Binding::Key(KeyCode::L)])
+            .bind_action(Action::LabelRegion, [Binding::Mouse(MouseButton::Middle)])
+            .add_system(toggle_ball_labels)
+            .add_system(select_label_region)
+            .add_system(draw_ball_labels.after(toggle_ball_labels).after(select_label_region));
+    }
+}
+
+#[derive(Default)]
+pub struct BallLabels {
+    pub enabled: bool,
+    pub region: Option<Bounds>,
+    cursor: Option<Vec2>,
+}
+
+// Balls within this distance of the cursor are labeled, when there is no
+// region selected.
+const CURSOR_RADIUS: f32 = 100.;
+
+// Regions smaller than this, in either direction, are discarded.
+const MIN_REGION_SIZE: f32 = 4.;
+
+// Height of the digits of a label.
+const DIGIT_HEIGHT: f32 = 8.;
+
+fn toggle_ball_labels(mut labels: ResMut<BallLabels>, actions: Res<Input<Action>>) {
+    if actions.just_pressed(Action::ToggleLabels) {
+        labels.enabled = !labels.enabled;
+    }
+}
+
+fn select_label_region(
+    mut labels: ResMut<BallLabels>,
+    mut drag_start: Local<Option<Vec2>>,
+    actions: Res<Input<Action>>,
+    picker: Picker,
+) {
+    labels.cursor = picker.cursor();
+    let cursor = match labels.cursor {
+        Some(cursor) if labels.enabled => cursor,
+        _ => return,
+    };
+
+    if actions.just_pressed(Action::LabelRegion) {
+        *drag_start = Some(cursor);
+    }
+    if let Some(start) = *drag_start {
+        let region = Bounds::from_corners(start, cursor);
+        labels.region = if region.width() < MIN_REGION_SIZE || region.height() < MIN_REGION_SIZE {
+            None
+        } else {
+            Some(region)
+        };
+    }
+    if actions.just_released(Action::LabelRegion) {
+        *drag_start = None;
+    }
+}
+
+fn draw_ball_labels(
+    labels: Res<BallLabels>,
+    mut debug_lines: ResMut<DebugLines>,
+    query: Query<(Entity, &Transform, &Ball)>,
+) {
+    if !labels.enabled {
+        return;
+    }
+
+    let visible: Box<dyn Fn(Vec2) -> bool> = match (labels.region, labels.cursor) {
+        (Some(region), _) => {
+            region.debug_draw_lines(&mut debug_lines, Some(Color::YELLOW));
+            Box::new(move |position| region.contains(position))
+        }
+        (None, Some(cursor)) => Box::new(move |position| position.distance(cursor) <= CURSOR_RADIUS),
+        (None, None) => return,
+    };
+
+    for (entity, transform, ball) in query.iter() {
+        let position = transform.translation.truncate();
+        if !visible(position) {
+            continue;
+        }
+
+        // place the label at the top right of the ball
+        let origin = position + Vec2::splat(ball.radius * 0.7);
+        for (a, b) in number_lines(entity.id(), origin, DIGIT_HEIGHT) {
+            debug_lines.line_colored(Vec3::from((a, 0.)), Vec3::from((b, 0.)), 0., Color::YELLOW);
+        }
+    }
+}
+
+// Segments of a seven segment display, as the corners of a digit which are
+// connected, in units of the digit's width.
+const SEGMENTS: [(Vec2, Vec2); 7] = [
+    (const_vec2!([0., 2.]), const_vec2!([1., 2.])), // top
+    (const_vec2!([1., 2.]), const_vec2!([1., 1.])), // top right
+    (const_vec2!([1., 1.]), const_vec2!([1., 0.])), // bottom right
+    (const_vec2!([0., 0.]), const_vec2!([1., 0.])), // bottom
+    (const_vec2!([0., 0.]), const_vec2!([0., 1.])), // bottom left
+    (const_vec2!([0., 1.]), const_vec2!([0., 2.])), // top left
+    (const_vec2!([0., 1.]), const_vec2!([1., 1.])), // middle
+];
+
+// Segments which are lit for each digit, as a bit per segment.
+const DIGITS: [u8; 10] = [
+    0b0111111, 0b0000110, 0b1011011, 0b1001111, 0b1100110,
+    0b1101101, 0b1111101, 0b0000111, 0b1111111, 0b1101111,
+];
+
+/// Lines which draw `number` in seven segment digits of `height`, with the
+/// bottom left corner of the first digit at `origin`.
+pub fn number_lines(number: u32, origin: Vec2, height: f32) -> Vec<(Vec2, Vec2)> {
+    let width = height / 2.;
+    let advance = width * 1.5;
+
+    number.to_string()
+        .bytes()
+        .enumerate()
+        .flat_map(|(i, digit)| {
+            let offset = origin + Vec2::new(i as f32 * advance, 0.);
+            let lit = DIGITS[(digit - b'0') as usize];
+            SEGMENTS.iter()
+                .enumerate()
+                .filter(move |(segment, _)| lit & (1 << segment) != 0)
+                .map(move |(_, (a, b))| (offset + *a * width, offset + *b * width))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numbers_are_drawn_as_seven_segment_digits() {
+        let lines = number_lines(18, Vec2::new(10., 0.), 4.);
+        assert_eq!(lines.len(), 2 + 7);
+
+        // the 1 is only the right side of the first digit
+        assert_eq!(lines[0], (Vec2::new(12., 4.), Vec2::new(12., 2.)));
+        assert_eq!(lines[1], (Vec2::new(12., 2.), Vec2::new(12., 0.)));
+        // the 8 starts one and a half digit width further
+        assert_eq!(lines[2], (Vec2::new(13., 4.), Vec2::new(15., 4.)));
+    }
+}
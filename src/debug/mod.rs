@@ -1,7 +1,25 @@
 pub use bevy_prototype_debug_lines::{DebugLines, DebugLinesPlugin};
 
+pub use budget::*;
+pub use diagnostics::*;
 pub use draw_lines::*;
 pub use fps::*;
+pub use grid::*;
+pub use help::*;
+#[cfg(feature = "inspector")]
+pub use inspector::*;
+pub use labels::*;
+pub use stages::*;
+pub use text::*;
 
+mod budget;
+mod diagnostics;
 mod draw_lines;
 mod fps;
+mod grid;
+mod help;
+#[cfg(feature = "inspector")]
+mod inspector;
+mod labels;
+mod stages;
+mod text;
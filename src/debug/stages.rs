@@ -0,0 +1,136 @@
+use bevy::diagnostic::{DiagnosticId, Diagnostics};
+use bevy::prelude::*;
+use bevy::render::camera::Camera2d;
+
+use crate::*;
+
+/// Shows how long each step of a physics tick takes in the corner of the view,
+/// toggled with `Action::ToggleStageTimings`. Each step is listed with its
+/// average time in milliseconds and a bar of its share of the tick, so a step
+/// which regressed stands out where the frame rate alone doesn't tell which.
+pub struct StageTimingsPlugin;
+
+impl Plugin for StageTimingsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<StageTimingsOverlay>()
+            .bind_action(Action::ToggleStageTimings, [Binding::Key(KeyCode::F5)])
+            .add_system(toggle_stage_timings)
+            .add_system(draw_stage_timings.after(toggle_stage_timings));
+    }
+}
+
+#[derive(Default)]
+pub struct StageTimingsOverlay {
+    pub enabled: bool,
+}
+
+/// Steps of a physics tick which are timed, with the diagnostic of their time.
+pub const TIMED_STAGES: [(&str, DiagnosticId); 5] = [
+    ("integrate", PhysicsDiagnosticsPlugin::INTEGRATE_TIME),
+    ("broad", PhysicsDiagnosticsPlugin::BROAD_PHASE_TIME),
+    ("narrow", PhysicsDiagnosticsPlugin::NARROW_PHASE_TIME),
+    ("resolve", PhysicsDiagnosticsPlugin::RESOLVE_TIME),
+    ("debug draw", PhysicsDiagnosticsPlugin::DEBUG_DRAW_TIME),
+];
+
+/// Average time of each of the `TIMED_STAGES` in milliseconds, or 0 when it
+/// wasn't measured yet.
+pub fn stage_times(diagnostics: &Diagnostics) -> [(&'static str, f64); 5] {
+    TIMED_STAGES.map(|(name, id)| (name, diagnostics.get(id).and_then(|time| time.average()).unwrap_or(0.)))
+}
+
+/// Lines of text of the overlay, each with the share of the tick of its step.
+/// The first line is the total time of a tick.
+pub fn stage_lines(times: &[(&str, f64)]) -> Vec<(String, f32)> {
+    let total: f64 = times.iter().map(|(_, time)| time).sum();
+    let mut lines = vec![(format!("{:<10} {:>6.2} ms", "tick", total), 1.)];
+    for (name, time) in times {
+        let share = if total > 0. { time / total } else { 0. };
+        lines.push((format!("{:<10} {:>6.2} ms", name, time), share as f32));
+    }
+    lines
+}
+
+// Height of the characters on screen, in pixels.
+const CHAR_HEIGHT: f32 = 12.;
+
+// Distance to the edges of the view, in pixels.
+const MARGIN: f32 = 16.;
+
+// Width of a bar of a step which takes the whole tick, in pixels.
+const BAR_WIDTH: f32 = 120.;
+
+fn toggle_stage_timings(actions: Res<Input<Action>>, mut overlay: ResMut<StageTimingsOverlay>) {
+    if actions.just_pressed(Action::ToggleStageTimings) {
+        overlay.enabled = !overlay.enabled;
+    }
+}
+
+fn draw_stage_timings(
+    overlay: Res<StageTimingsOverlay>,
+    diagnostics: Res<Diagnostics>,
+    windows: Res<Windows>,
+    cameras: Query<&Transform, With<Camera2d>>,
+    mut debug_lines: ResMut<DebugLines>,
+) {
+    if !overlay.enabled {
+        return;
+    }
+    let (window, camera) = match (windows.get_primary(), cameras.iter().next()) {
+        (Some(window), Some(camera)) => (window, camera),
+        _ => return,
+    };
+
+    // the overlay keeps its size on screen when the view is zoomed, the text
+    // is placed left of the bars in the top right corner
+    let scale = camera.scale.x;
+    let height = CHAR_HEIGHT * scale;
+    let lines = stage_lines(&stage_times(&diagnostics));
+    let text_width = lines.iter().map(|(text, _)| text.len()).max().unwrap_or(0) as f32 * height * 0.75;
+    let top_right = view_bounds(window, camera).top_right() - Vec2::new(MARGIN, MARGIN) * scale;
+    let left = top_right.x - (BAR_WIDTH + MARGIN) * scale - text_width;
+    for (i, (text, share)) in lines.iter().enumerate() {
+        let origin = Vec2::new(left, top_right.y - (i + 1) as f32 * height * 1.75);
+        for (a, b) in text_lines(text, origin, height) {
+            debug_lines.line_colored(a.extend(0.), b.extend(0.), 0., Color::WHITE);
+        }
+        if i == 0 {
+            continue;
+        }
+        let color = if *share > 0.5 { Color::ORANGE_RED } else { Color::GREEN };
+        let start = Vec2::new(top_right.x - BAR_WIDTH * scale, origin.y + height / 2.);
+        let end = start + Vec2::new(BAR_WIDTH * scale * share, 0.);
+        for offset in [-height / 4., 0., height / 4.] {
+            let offset = Vec2::new(0., offset);
+            debug_lines.line_colored((start + offset).extend(0.), (end + offset).extend(0.), 0., color);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::diagnostic::Diagnostic;
+
+    use super::*;
+
+    #[test]
+    fn steps_are_listed_with_their_share_of_the_tick() {
+        let mut diagnostics = Diagnostics::default();
+        diagnostics.add(Diagnostic::new(PhysicsDiagnosticsPlugin::BROAD_PHASE_TIME, "broad_phase_time", 4));
+        diagnostics.add_measurement(PhysicsDiagnosticsPlugin::BROAD_PHASE_TIME, 1.);
+        diagnostics.add_measurement(PhysicsDiagnosticsPlugin::BROAD_PHASE_TIME, 2.);
+        diagnostics.add(Diagnostic::new(PhysicsDiagnosticsPlugin::RESOLVE_TIME, "resolve_time", 4));
+        diagnostics.add_measurement(PhysicsDiagnosticsPlugin::RESOLVE_TIME, 0.5);
+
+        let times = stage_times(&diagnostics);
+        assert_eq!(times[1], ("broad", 1.5));
+        assert_eq!(times[3], ("resolve", 0.5));
+        assert_eq!(times[0], ("integrate", 0.));
+
+        let lines = stage_lines(&times);
+        assert_eq!(lines.len(), 6);
+        assert_eq!(lines[0], ("tick         2.00 ms".to_string(), 1.));
+        assert_eq!(lines[2], ("broad        1.50 ms".to_string(), 0.75));
+        assert_eq!(lines[5].1, 0.);
+    }
+}
@@ -0,0 +1,110 @@
+use bevy::math::{const_vec2, Vec2};
+
+// Segments of a sixteen segment display, as the corners of a character which
+// are connected, in units of the character's width. Each is named by a letter,
+// the halves of the horizontal segments by its lower and upper case.
+const SEGMENTS: [(char, Vec2, Vec2); 16] = [
+    ('a', const_vec2!([0., 2.]), const_vec2!([0.5, 2.])),  // top left
+    ('A', const_vec2!([0.5, 2.]), const_vec2!([1., 2.])),  // top right
+    ('b', const_vec2!([1., 2.]), const_vec2!([1., 1.])),   // upper right
+    ('c', const_vec2!([1., 1.]), const_vec2!([1., 0.])),   // lower right
+    ('d', const_vec2!([1., 0.]), const_vec2!([0.5, 0.])),  // bottom right
+    ('D', const_vec2!([0.5, 0.]), const_vec2!([0., 0.])),  // bottom left
+    ('e', const_vec2!([0., 0.]), const_vec2!([0., 1.])),   // lower left
+    ('f', const_vec2!([0., 1.]), const_vec2!([0., 2.])),   // upper left
+    ('g', const_vec2!([0., 1.]), const_vec2!([0.5, 1.])),  // middle left
+    ('G', const_vec2!([0.5, 1.]), const_vec2!([1., 1.])),  // middle right
+    ('h', const_vec2!([0., 2.]), const_vec2!([0.5, 1.])),  // upper left diagonal
+    ('i', const_vec2!([0.5, 2.]), const_vec2!([0.5, 1.])), // upper center
+    ('j', const_vec2!([1., 2.]), const_vec2!([0.5, 1.])),  // upper right diagonal
+    ('k', const_vec2!([0.5, 1.]), const_vec2!([0., 0.])),  // lower left diagonal
+    ('l', const_vec2!([0.5, 1.]), const_vec2!([0.5, 0.])), // lower center
+    ('m', const_vec2!([0.5, 1.]), const_vec2!([1., 0.])),  // lower right diagonal
+];
+
+// Segments which are lit for a character, unknown characters are blank.
+// Lower case letters are drawn as upper case ones.
+fn lit_segments(c: char) -> &'static str {
+    match c.to_ascii_uppercase() {
+        'A' => "aAbcefgG",
+        'B' => "aAbcdDGil",
+        'C' => "aAdDef",
+        'D' => "aAbcdDil",
+        'E' => "aAdDefg",
+        'F' => "aAefg",
+        'G' => "aAcdDefG",
+        'H' => "bcefgG",
+        'I' => "aAdDil",
+        'J' => "bcdDe",
+        'K' => "efgjm",
+        'L' => "dDef",
+        'M' => "bcefhj",
+        'N' => "bcefhm",
+        'O' => "aAbcdDef",
+        'P' => "aAbefgG",
+        'Q' => "aAbcdDefm",
+        'R' => "aAbefgGm",
+        'S' => "aAcdDfgG",
+        'T' => "aAil",
+        'U' => "bcdDef",
+        'V' => "efjk",
+        'W' => "bcefkm",
+        'X' => "hjkm",
+        'Y' => "hjl",
+        'Z' => "aAdDjk",
+        '0' => "aAbcdDefjk",
+        '1' => "bcj",
+        '2' => "aAbdDegG",
+        '3' => "aAbcdDG",
+        '4' => "bcfgG",
+        '5' => "aAcdDfgG",
+        '6' => "aAcdDefgG",
+        '7' => "aAbc",
+        '8' => "aAbcdDefgG",
+        '9' => "aAbcdDfgG",
+        '-' => "gG",
+        '+' => "gGil",
+        '=' => "dDgG",
+        '/' => "jk",
+        '(' => "jm",
+        ')' => "hk",
+        '.' | ',' => "D",
+        '\'' => "i",
+        _ => "",
+    }
+}
+
+/// Lines which draw `text` in sixteen segment characters of `height`, with
+/// the bottom left corner of the first character at `origin`.
+pub fn text_lines(text: &str, origin: Vec2, height: f32) -> Vec<(Vec2, Vec2)> {
+    let width = height / 2.;
+    let advance = width * 1.5;
+
+    text.chars()
+        .enumerate()
+        .flat_map(|(i, c)| {
+            let offset = origin + Vec2::new(i as f32 * advance, 0.);
+            let lit = lit_segments(c);
+            SEGMENTS.iter()
+                .filter(move |(segment, ..)| lit.contains(*segment))
+                .map(move |(_, a, b)| (offset + *a * width, offset + *b * width))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_is_drawn_as_sixteen_segment_characters() {
+        let lines = text_lines("T x", Vec2::new(10., 0.), 4.);
+        assert_eq!(lines.len(), 4 + 4);
+
+        // the T is its top and its center
+        assert_eq!(lines[0], (Vec2::new(10., 4.), Vec2::new(11., 4.)));
+        assert_eq!(lines[3], (Vec2::new(11., 2.), Vec2::new(11., 0.)));
+        // the space is blank, the x starts three character widths further
+        assert_eq!(lines[4], (Vec2::new(16., 4.), Vec2::new(17., 2.)));
+    }
+}
@@ -0,0 +1,197 @@
+use bevy::core::FixedTimestep;
+use bevy::prelude::*;
+
+use crate::*;
+
+/// Shows the density of balls as a coarse grid of translucent cells behind
+/// them, colored from cold to hot by the amount of balls within each cell.
+/// The counts are taken from the quadtree of the last physics tick, or from
+/// the balls themselves before the first tick, and are updated a few times
+/// per second.
+pub struct DensityGridPlugin {
+    cell_size: f32,
+    rate: f64,
+}
+
+impl DensityGridPlugin {
+    pub fn with_cell_size(cell_size: f32) -> Self {
+        Self { cell_size, rate: 4. }
+    }
+}
+
+impl Default for DensityGridPlugin {
+    fn default() -> Self { Self::with_cell_size(32.) }
+}
+
+impl Plugin for DensityGridPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(DensityGrid::with_cell_size(self.cell_size))
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(FixedTimestep::steps_per_second(self.rate))
+                    .with_system(update_density_grid)
+                    .with_system(draw_density_grid.after(update_density_grid)),
+            );
+    }
+}
+
+pub struct DensityGrid {
+    pub cell_size: f32,
+    pub bounds: Bounds,
+    pub columns: usize,
+    pub rows: usize,
+    /// Amount of balls which overlap each cell, row by row from the bottom
+    /// left cell.
+    pub counts: Vec<u32>,
+}
+
+impl DensityGrid {
+    pub fn with_cell_size(cell_size: f32) -> Self {
+        Self {
+            cell_size: cell_size.max(1.),
+            bounds: Bounds::new(Vec2::ZERO, 0., 0.),
+            columns: 0,
+            rows: 0,
+            counts: Vec::new(),
+        }
+    }
+
+    /// Area covered by the cell at `column` and `row`.
+    #[inline]
+    pub fn cell(&self, column: usize, row: usize) -> Bounds {
+        let min = self.bounds.bottom_left() + Vec2::new(column as f32, row as f32) * self.cell_size;
+        Bounds::from_corners(min, min + Vec2::splat(self.cell_size))
+    }
+
+    /// Replace the counts with the amount of balls in `tree` which overlap
+    /// each cell. The grid is resized to cover the bounds of the tree.
+    pub fn fill(&mut self, tree: &QuadTree) {
+        self.resize(tree.bounds());
+        self.counts.clear();
+        for row in 0..self.rows {
+            for column in 0..self.columns {
+                let cell = self.cell(column, row);
+                self.counts.push(tree.query_area(cell).len() as u32);
+            }
+        }
+    }
+
+    /// Like `fill`, but counts `balls`, by their center and radius, without a
+    /// tree. The grid is resized to cover `bounds`.
+    pub fn fill_with_balls(&mut self, bounds: Bounds, balls: impl Iterator<Item = (Vec2, f32)>) {
+        self.resize(bounds);
+        self.counts.clear();
+        self.counts.resize(self.columns * self.rows, 0);
+        if self.counts.is_empty() {
+            return;
+        }
+        let cell_of = |offset: f32, cells: usize| ((offset / self.cell_size).floor().max(0.) as usize).min(cells - 1);
+        for (center, radius) in balls {
+            let min = center - Vec2::splat(radius) - self.bounds.bottom_left();
+            let max = center + Vec2::splat(radius) - self.bounds.bottom_left();
+            if max.x < 0. || max.y < 0. || min.x > self.bounds.width() || min.y > self.bounds.height() {
+                continue;
+            }
+            for row in cell_of(min.y, self.rows)..=cell_of(max.y, self.rows) {
+                for column in cell_of(min.x, self.columns)..=cell_of(max.x, self.columns) {
+                    self.counts[row * self.columns + column] += 1;
+                }
+            }
+        }
+    }
+
+    fn resize(&mut self, bounds: Bounds) {
+        self.bounds = bounds;
+        self.columns = (self.bounds.width() / self.cell_size).ceil() as usize;
+        self.rows = (self.bounds.height() / self.cell_size).ceil() as usize;
+    }
+}
+
+#[derive(Component)]
+struct DensityCell(usize);
+
+const GRID_Z: f32 = -1.;
+const GRID_ALPHA: f32 = 0.35;
+
+fn update_density_grid(
+    mut grid: ResMut<DensityGrid>,
+    ball_tree: Res<BallTree>,
+    edge: Res<EdgeCollider>,
+    balls: Query<(&Transform, &Ball)>,
+) {
+    // before the first physics tick there is no tree, all balls are counted
+    if ball_tree.0.is_empty() {
+        let balls = balls.iter().map(|(transform, ball)| (transform.translation.truncate(), ball.radius));
+        grid.fill_with_balls(edge.bounds, balls);
+        return;
+    }
+    grid.fill(&ball_tree.0);
+}
+
+fn draw_density_grid(
+    mut cmd: Commands,
+    grid: Res<DensityGrid>,
+    mut cells: Query<(Entity, &mut Transform, &mut DrawMode, &DensityCell)>,
+) {
+    // cells are unit squares, which are (re)spawned when the grid is resized
+    if cells.iter().count() != grid.counts.len() {
+        for (entity, ..) in cells.iter() {
+            cmd.entity(entity).despawn();
+        }
+        for i in 0..grid.counts.len() {
+            cmd.spawn_bundle(GeometryBuilder::build_as(
+                &shapes::Rectangle {
+                    extents: Vec2::ONE,
+                    origin: shapes::RectangleOrigin::BottomLeft,
+                },
+                DrawMode::Fill(FillMode::color(Color::NONE)),
+                Transform::default(),
+            ))
+                .insert(DensityCell(i));
+        }
+        return;
+    }
+
+    let densest = grid.counts.iter().copied().max().unwrap_or_default().max(1) as f32;
+    for (_, mut transform, mut draw_mode, cell) in cells.iter_mut() {
+        let bounds = grid.cell(cell.0 % grid.columns, cell.0 / grid.columns);
+        transform.translation = bounds.bottom_left().extend(GRID_Z);
+        transform.scale = Vec2::splat(grid.cell_size).extend(1.);
+
+        let count = grid.counts[cell.0];
+        let mut color = heat_color(count as f32 / densest);
+        color.set_a(if count == 0 { 0. } else { GRID_ALPHA });
+        set_fill_color(&mut draw_mode, color);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn balls_are_counted_in_the_cells_they_overlap() {
+        let mut tree = QuadTree::new(Bounds::new(Vec2::new(40., 20.), 80., 40.), Options::default());
+        let mut world = World::new();
+        let mut insert = |center: Vec2, size: f32| {
+            tree.insert(Location::new(center, size, size), world.spawn().id()).unwrap();
+        };
+        insert(Vec2::new(10., 10.), 4.);
+        insert(Vec2::new(12., 8.), 4.);
+        // overlaps the two top right cells
+        insert(Vec2::new(60., 30.), 4.);
+
+        let mut grid = DensityGrid::with_cell_size(20.);
+        grid.fill(&tree);
+        assert_eq!((grid.columns, grid.rows), (4, 2));
+        assert_eq!(grid.counts, vec![2, 0, 0, 0, 0, 0, 1, 1]);
+        assert!(grid.cell(3, 1) == Bounds::from_corners(Vec2::new(60., 20.), Vec2::new(80., 40.)));
+
+        // without a tree, the balls themselves give the same counts
+        let balls = [(Vec2::new(10., 10.), 2.), (Vec2::new(12., 8.), 2.), (Vec2::new(60., 30.), 2.)];
+        let mut without_tree = DensityGrid::with_cell_size(20.);
+        without_tree.fill_with_balls(tree.bounds(), balls.into_iter());
+        assert_eq!((without_tree.columns, without_tree.rows), (4, 2));
+        assert_eq!(without_tree.counts, grid.counts);
+    }
+}
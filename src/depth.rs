@@ -0,0 +1,96 @@
+use bevy::prelude::*;
+use bevy::transform::TransformSystem;
+
+use crate::*;
+
+/// Orders balls front to back by setting their z-translation, so for example
+/// small balls are drawn on top of big ones instead of in arbitrary order.
+pub struct DepthSortPlugin {
+    key: DepthSort,
+}
+
+impl DepthSortPlugin {
+    pub fn with_key(key: DepthSort) -> Self {
+        Self { key }
+    }
+}
+
+impl Default for DepthSortPlugin {
+    fn default() -> Self { Self::with_key(DepthSort::Radius) }
+}
+
+impl Plugin for DepthSortPlugin {
+    fn build(&self, app: &mut App) {
+        // depth must be up to date before it is propagated to the global
+        // transform which is rendered
+        let system = match self.key {
+            DepthSort::Radius => depth_by_radius.before(TransformSystem::TransformPropagate),
+            DepthSort::Speed => depth_by_speed.before(TransformSystem::TransformPropagate),
+        };
+        app.insert_resource(self.key)
+            .add_system_to_stage(CoreStage::PostUpdate, system);
+    }
+}
+
+/// Property of balls which determines which are drawn in front.
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DepthSort {
+    /// Smaller balls are drawn in front of bigger ones.
+    Radius,
+    /// Faster balls are drawn in front of slower ones.
+    Speed,
+}
+
+// Speed at which a ball is halfway to the front.
+const SPEED_SCALE: f32 = 100.;
+
+impl DepthSort {
+    /// Z-translation of a ball, within `0..1`. Higher values are in front.
+    #[inline]
+    pub fn depth(&self, ball: &Ball, velocity: Vec2) -> f32 {
+        match self {
+            DepthSort::Radius => 1. / (1. + ball.radius),
+            DepthSort::Speed => {
+                let speed = velocity.length();
+                speed / (speed + SPEED_SCALE)
+            }
+        }
+    }
+}
+
+// The radius only changes when a ball is (re)spawned or resized, so only
+// update those balls.
+fn depth_by_radius(mut query: Query<(&mut Transform, &Ball, &Velocity), Changed<Ball>>) {
+    for (mut transform, ball, velocity) in query.iter_mut() {
+        transform.translation.z = DepthSort::Radius.depth(ball, velocity.0);
+    }
+}
+
+fn depth_by_speed(mut query: Query<(&mut Transform, &Ball, &Velocity)>) {
+    for (mut transform, ball, velocity) in query.iter_mut() {
+        let depth = DepthSort::Speed.depth(ball, velocity.0);
+        if transform.translation.z != depth {
+            transform.translation.z = depth;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn smaller_and_faster_balls_are_in_front() {
+        let small = Ball::new(2., MassModel::Constant(1.));
+        let big = Ball::new(16., MassModel::Constant(1.));
+        let radius = DepthSort::Radius;
+        assert!(radius.depth(&small, Vec2::ZERO) > radius.depth(&big, Vec2::ZERO));
+
+        let speed = DepthSort::Speed;
+        let slow = speed.depth(&big, Vec2::new(10., 0.));
+        let fast = speed.depth(&big, Vec2::new(0., -500.));
+        assert!(fast > slow);
+        assert!((0. ..1.).contains(&slow) && (0. ..1.).contains(&fast));
+    }
+}
@@ -0,0 +1,233 @@
+//! Saves the balls as a Bevy `DynamicScene` with `Action::ExportBevyScene`,
+//! and spawns such a `.scn.ron` file on startup when it is passed with
+//! `--config <path>`. Unlike a `SceneConfig`, these hold every reflected
+//! component of the balls, and nothing but the balls.
+
+use std::fs;
+use std::path::Path;
+
+use bevy::ecs::entity::EntityMap;
+use bevy::ecs::reflect::ReflectMapEntities;
+use bevy::prelude::*;
+use bevy::reflect::TypeRegistry;
+use bevy::scene::serde::SceneDeserializer;
+use bevy::scene::{DynamicEntity, SceneSpawnError};
+use bevy_prototype_lyon::prelude::*;
+use serde::de::DeserializeSeed;
+
+use crate::*;
+
+/// Path of a Bevy scene which is spawned on startup.
+pub struct BevySceneImport(pub String);
+
+/// Colors of a ball in a Bevy scene, as its `DrawMode` can't be reflected.
+/// Balls only have it while they are saved or loaded.
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Reflect)]
+#[reflect(Component)]
+pub struct SceneStyle {
+    pub fill: Color,
+    pub outline: Color,
+    /// Width of the outline, or 0 when the ball has no outline.
+    pub outline_width: f32,
+}
+
+impl From<&DrawMode> for SceneStyle {
+    fn from(draw_mode: &DrawMode) -> Self {
+        match draw_mode {
+            DrawMode::Outlined { outline_mode, .. } => Self {
+                fill: fill_color(draw_mode),
+                outline: outline_mode.color,
+                outline_width: outline_mode.options.line_width,
+            },
+            _ => Self { fill: fill_color(draw_mode), ..default() },
+        }
+    }
+}
+
+impl From<SceneStyle> for BallStyle {
+    fn from(style: SceneStyle) -> Self {
+        Self {
+            fill: style.fill,
+            outline: Some(Outline { color: style.outline, width: style.outline_width })
+                .filter(|outline| outline.width > 0.),
+        }
+    }
+}
+
+/// All balls of `world` as a Bevy scene, with each of their components which
+/// is reflected. Components which refer to other entities are left out, as
+/// those entities aren't part of the scene.
+pub fn ball_scene(world: &mut World) -> DynamicScene {
+    let registry = world.resource::<TypeRegistry>().clone();
+    let registry = registry.read();
+    let balls: Vec<(Entity, SceneStyle)> = world.query_filtered::<(Entity, &DrawMode), With<Ball>>()
+        .iter(world)
+        .map(|(entity, draw_mode)| (entity, SceneStyle::from(draw_mode)))
+        .collect();
+
+    let entities = balls.into_iter()
+        .map(|(entity, style)| {
+            let mut components: Vec<Box<dyn Reflect>> = world.entity(entity).archetype().components()
+                .filter_map(|id| registry.get(world.components().get_info(id)?.type_id()?))
+                .filter(|registration| registration.data::<ReflectMapEntities>().is_none())
+                .filter_map(|registration| registration.data::<ReflectComponent>())
+                .filter_map(|reflect| reflect.reflect_component(world, entity))
+                .map(|component| component.clone_value())
+                .collect();
+            components.push(Box::new(style));
+            DynamicEntity { entity: entity.id(), components }
+        })
+        .collect();
+    DynamicScene { entities }
+}
+
+/// Parse a Bevy scene from RON, its components have to be in `registry`.
+pub fn parse_ball_scene(text: &str, registry: &TypeRegistry) -> Result<DynamicScene, SceneError> {
+    let mut deserializer = ron::de::Deserializer::from_str(text).map_err(SceneError::Ron)?;
+    SceneDeserializer { type_registry: &registry.read() }
+        .deserialize(&mut deserializer)
+        .map_err(SceneError::Ron)
+}
+
+/// Spawn the balls of `scene` into `world`. The components which aren't
+/// reflected, like the shape of a ball, are added as well. Returns the
+/// spawned balls.
+pub fn spawn_ball_scene(world: &mut World, scene: &DynamicScene) -> Result<Vec<Entity>, SceneSpawnError> {
+    let mut entities = EntityMap::default();
+    scene.write_to_world(world, &mut entities)?;
+    let spawned: Vec<Entity> = entities.values().collect();
+    for entity in spawned.iter() {
+        complete_ball(world, *entity);
+    }
+    Ok(spawned)
+}
+
+// Add the bundle of a ball to `entity`, which was spawned from a scene, while
+// keeping the components it has.
+fn complete_ball(world: &mut World, entity: Entity) {
+    let components = world.entity(entity);
+    let (ball, transform) = match (components.get::<Ball>(), components.get::<Transform>()) {
+        (Some(ball), Some(transform)) => (*ball, *transform),
+        _ => return,
+    };
+    let velocity = components.get::<Velocity>().copied().unwrap_or_default();
+    let visual = components.get::<BallVisual>().copied().unwrap_or_default();
+    let style = components.get::<SceneStyle>().copied().unwrap_or_default();
+    let body = components.get::<Kind>()
+        .zip(world.get_resource::<BallKinds>())
+        .map(|(kind, kinds)| kinds.get(*kind).body);
+
+    let mut bundle = BallBundle::builder(ball.radius)
+        .with_style(style.into())
+        .with_visual(visual)
+        .with_velocity(velocity.0)
+        .build();
+    bundle.ball = ball;
+    bundle.shape_bundle.transform = transform;
+    if let (Some(body), BallVisual::Shape) = (body, visual) {
+        bundle.shape_bundle.path = body.path(ball.radius);
+    }
+    let mut entity = world.entity_mut(entity);
+    entity.remove::<SceneStyle>();
+    entity.insert_bundle(bundle);
+}
+
+pub(crate) fn export_bevy_scene(world: &mut World) {
+    if !world.resource::<Input<Action>>().just_pressed(Action::ExportBevyScene) {
+        return;
+    }
+    let path = Path::new(&world.resource::<SceneExport>().0).with_extension("scn.ron");
+    let registry = world.resource::<TypeRegistry>().clone();
+    let scene = ball_scene(world);
+    let saved = scene.serialize_ron(&registry)
+        .map_err(SceneError::Ron)
+        .and_then(|text| fs::write(&path, text).map_err(SceneError::Io));
+    match saved {
+        Ok(()) => println!("scene: saved {} balls to {}", scene.entities.len(), path.display()),
+        Err(err) => println!("scene: unable to save {}: {}", path.display(), err),
+    }
+}
+
+// Replace the randomly spawned balls with the balls of the Bevy scene.
+pub(crate) fn spawn_bevy_scene(world: &mut World) {
+    let path = match world.remove_resource::<BevySceneImport>() {
+        Some(import) => import.0,
+        None => return,
+    };
+    let registry = world.resource::<TypeRegistry>().clone();
+    let scene = match fs::read_to_string(&path).map_err(SceneError::Io)
+        .and_then(|text| parse_ball_scene(&text, &registry))
+    {
+        Ok(scene) => scene,
+        Err(err) => {
+            println!("scene: unable to load {}: {}", path, err);
+            return;
+        }
+    };
+
+    let balls: Vec<Entity> = world.query_filtered::<Entity, With<Ball>>().iter(world).collect();
+    for entity in balls {
+        world.entity_mut(entity).despawn_recursive();
+    }
+    if let Err(err) = spawn_ball_scene(world, &scene) {
+        println!("scene: unable to spawn {}: {}", path, err);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn balls_survive_a_round_trip_through_a_bevy_scene() {
+        let registry = TypeRegistry::default();
+        {
+            let mut registry = registry.write();
+            registry.register::<f32>();
+            registry.register::<usize>();
+            registry.register::<Vec2>();
+            registry.register::<Vec3>();
+            registry.register::<Quat>();
+            registry.register::<Color>();
+            registry.register::<Transform>();
+            registry.register::<Ball>();
+            registry.register::<Velocity>();
+            registry.register::<Kind>();
+            registry.register::<SceneStyle>();
+        }
+        let kinds = BallKinds::new(vec![
+            BallKind::default(),
+            BallKind { name: "brick".to_string(), body: BallShape::Box { aspect: 0.5 }, ..default() },
+        ]).unwrap();
+
+        let mut world = World::new();
+        world.insert_resource(registry.clone());
+        let style = BallStyle { fill: Color::RED, outline: Some(Outline { color: Color::BLACK, width: 2. }) };
+        world.spawn()
+            .insert_bundle(BallBundle::builder(6.).with_style(style).with_velocity(Vec2::X).with_position(Vec2::Y).build())
+            .insert(Kind(1));
+        world.spawn().insert(Transform::default());
+
+        let scene = ball_scene(&mut world);
+        assert_eq!(scene.entities.len(), 1);
+        let text = scene.serialize_ron(&registry).unwrap();
+        let scene = parse_ball_scene(&text, &registry).unwrap();
+
+        let mut world = World::new();
+        world.insert_resource(registry);
+        world.insert_resource(kinds);
+        let spawned = spawn_ball_scene(&mut world, &scene).unwrap();
+        assert_eq!(spawned.len(), 1);
+        let entity = world.entity(spawned[0]);
+        assert_eq!(entity.get::<Ball>().unwrap().radius, 6.);
+        assert_eq!(entity.get::<Velocity>(), Some(&Velocity(Vec2::X)));
+        assert_eq!(entity.get::<Transform>().unwrap().translation, Vec3::Y);
+        assert_eq!(entity.get::<Kind>(), Some(&Kind(1)));
+        assert_eq!(SceneStyle::from(entity.get::<DrawMode>().unwrap()), SceneStyle {
+            fill: Color::RED,
+            outline: Color::BLACK,
+            outline_width: 2.,
+        });
+        assert!(!entity.contains::<SceneStyle>());
+    }
+}
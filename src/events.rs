@@ -0,0 +1,51 @@
+use bevy::prelude::*;
+
+use crate::*;
+
+/// Sends `BallSpawned` and `BallDespawned` events, so systems can react to
+/// balls being added or removed without having to track them themselves.
+/// Also registers the `BallCollided` and `BallHitWall` events.
+pub struct BallEventsPlugin;
+
+impl Plugin for BallEventsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<BallSpawned>()
+            .add_event::<BallDespawned>()
+            .add_event::<BallCollided>()
+            .add_event::<BallHitWall>()
+            .add_system_to_stage(CoreStage::PostUpdate, send_ball_spawned)
+            .add_system_to_stage(CoreStage::PostUpdate, send_ball_despawned);
+    }
+}
+
+/// A ball entity was spawned.
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug)]
+pub struct BallSpawned(pub Entity);
+
+/// A ball entity was despawned, or its `Ball` component was removed.
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug)]
+pub struct BallDespawned(pub Entity);
+
+/// Two balls collided and bounced off of each other.
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug)]
+pub struct BallCollided(pub Entity, pub Entity);
+
+/// A ball bounced off of a wall of the `EdgeCollider`.
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug)]
+pub struct BallHitWall(pub Entity, pub WallHit);
+
+fn send_ball_spawned(query: Query<Entity, Added<Ball>>, mut events: EventWriter<BallSpawned>) {
+    for entity in query.iter() {
+        events.send(BallSpawned(entity));
+    }
+}
+
+fn send_ball_despawned(removed: RemovedComponents<Ball>, mut events: EventWriter<BallDespawned>) {
+    for entity in removed.iter() {
+        events.send(BallDespawned(entity));
+    }
+}
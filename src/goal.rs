@@ -0,0 +1,184 @@
+use bevy::prelude::*;
+
+use crate::*;
+
+/// Adds goal zones which capture the balls that enter them. Each zone counts
+/// the balls it captured, its score is drawn above it. Sends a `GoalScored`
+/// event for every captured ball.
+pub struct GoalPlugin {
+    zones: Vec<GoalZone>,
+}
+
+impl GoalPlugin {
+    pub fn with_zones(zones: Vec<GoalZone>) -> Self {
+        Self { zones }
+    }
+}
+
+impl Default for GoalPlugin {
+    fn default() -> Self {
+        let size = Vec2::new(120., 60.);
+        let bottom = -HEIGHT / 2. + size.y / 2.;
+        let respawn = Vec2::new(0., HEIGHT / 3.);
+        Self::with_zones(vec![
+            GoalZone::new(Bounds::new(Vec2::new(-WIDTH / 4., bottom), size.x, size.y), GoalCapture::Despawn),
+            GoalZone::new(Bounds::new(Vec2::new(WIDTH / 4., bottom), size.x, size.y), GoalCapture::Respawn(respawn)),
+        ])
+    }
+}
+
+impl Plugin for GoalPlugin {
+    fn build(&self, app: &mut App) {
+        let zones = self.zones.clone();
+        app.add_event::<GoalScored>()
+            .add_startup_system(move |mut cmd: Commands| {
+                for zone in &zones {
+                    cmd.spawn().insert(*zone);
+                }
+            })
+            .add_system(draw_goal_zones)
+            .add_system_to_stage(PhysicsStage, hold_captured_balls.before(PhysicsSystem::Integrate))
+            .add_system_to_stage(PhysicsStage, capture_balls.after(PhysicsSystem::Resolve));
+    }
+}
+
+/// What happens to a ball once it enters a `GoalZone`.
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GoalCapture {
+    /// The ball is removed from the simulation.
+    Despawn,
+    /// The ball stops and stays within the zone.
+    Hold,
+    /// The ball is moved to this position, where it is dropped again.
+    Respawn(Vec2),
+}
+
+#[derive(Component, Clone, Copy, Debug)]
+pub struct GoalZone {
+    pub bounds: Bounds,
+    pub capture: GoalCapture,
+    /// Amount of balls captured by the zone.
+    pub score: u32,
+}
+
+impl GoalZone {
+    pub fn new(bounds: Bounds, capture: GoalCapture) -> Self {
+        Self {
+            bounds,
+            capture,
+            score: 0,
+        }
+    }
+}
+
+/// A ball was captured by a goal zone. Contains the zone and the ball.
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug)]
+pub struct GoalScored(pub Entity, pub Entity);
+
+/// Marks a ball which is held by a goal zone.
+#[derive(Component)]
+pub struct Captured;
+
+const SCORE_HEIGHT: f32 = 16.;
+const SCORE_MARGIN: f32 = 8.;
+
+fn capture_balls(
+    mut cmd: Commands,
+    mut pool: ResMut<BallPool>,
+    mut scored: EventWriter<GoalScored>,
+    tree: Res<BallTree>,
+    mut zones: Query<(Entity, &mut GoalZone)>,
+    mut balls: Query<(Entity, &mut Transform, &mut Velocity, &Ball), Without<Captured>>,
+) {
+    // balls which are pooled or held are only updated at the end of the stage
+    let mut captured = Vec::new();
+    for (zone_entity, mut zone) in zones.iter_mut() {
+        // the tree was built before balls were separated, so candidates are
+        // checked against their current position; without a tree all balls
+        // are checked
+        let candidates: Vec<Entity> = if tree.0.is_empty() {
+            balls.iter().map(|(entity, ..)| entity).collect()
+        } else {
+            tree.0.query_area(zone.bounds)
+                .into_iter()
+                .map(|(_, entity)| entity)
+                .collect()
+        };
+
+        for entity in candidates {
+            if captured.contains(&entity) {
+                continue;
+            }
+            let (_, mut transform, mut velocity, ball) = match balls.get_mut(entity) {
+                Ok(ball) => ball,
+                Err(_) => continue,
+            };
+            if !zone.bounds.contains(transform.translation.truncate()) {
+                continue;
+            }
+
+            match zone.capture {
+                GoalCapture::Despawn => pool.release(&mut cmd, entity, ball),
+                GoalCapture::Hold => {
+                    velocity.0 = Vec2::ZERO;
+                    cmd.entity(entity).insert(Captured);
+                }
+                GoalCapture::Respawn(position) => {
+                    transform.translation.x = position.x;
+                    transform.translation.y = position.y;
+                    velocity.0 = Vec2::ZERO;
+                }
+            }
+
+            zone.score += 1;
+            scored.send(GoalScored(zone_entity, entity));
+            captured.push(entity);
+        }
+    }
+}
+
+// Held balls don't move by themselves, but can still be pushed around by
+// other balls.
+fn hold_captured_balls(mut query: Query<&mut Velocity, (With<Captured>, With<Ball>)>) {
+    for mut velocity in query.iter_mut() {
+        if velocity.0 != Vec2::ZERO {
+            velocity.0 = Vec2::ZERO;
+        }
+    }
+}
+
+/// Bottom left corner of a score of `digits` with `height`, so it is centered
+/// above `bounds`.
+#[inline]
+pub fn score_origin(bounds: Bounds, digits: usize, height: f32) -> Vec2 {
+    // digits are half as wide as they are high, with half a digit in between
+    let width = digits as f32 * height * 0.75 - height * 0.25;
+    Vec2::new(bounds.center().x - width / 2., bounds.top() + SCORE_MARGIN)
+}
+
+fn draw_goal_zones(mut debug_lines: ResMut<DebugLines>, query: Query<&GoalZone>) {
+    for zone in query.iter() {
+        zone.bounds.debug_draw_lines(&mut debug_lines, Some(Color::GOLD));
+
+        let origin = score_origin(zone.bounds, zone.score.to_string().len(), SCORE_HEIGHT);
+        for (a, b) in number_lines(zone.score, origin, SCORE_HEIGHT) {
+            debug_lines.line_colored(a.extend(0.), b.extend(0.), 0., Color::GOLD);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn score_is_centered_above_the_zone() {
+        let bounds = Bounds::from_corners(Vec2::ZERO, Vec2::new(100., 40.));
+
+        // a single digit is 8 wide, two are 8 + 4 + 8 wide
+        assert_eq!(score_origin(bounds, 1, 16.), Vec2::new(46., 40. + SCORE_MARGIN));
+        assert_eq!(score_origin(bounds, 2, 16.), Vec2::new(40., 40. + SCORE_MARGIN));
+    }
+}
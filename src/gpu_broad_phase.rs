@@ -0,0 +1,393 @@
+//! Prototype of a broad phase on the GPU, to compare against the quadtree on
+//! the CPU with hundreds of thousands of balls. Balls are extracted to the
+//! render world, binned into a uniform grid by a compute shader, and the
+//! candidate pairs are read back once the frame is rendered.
+//! `BroadPhase::Gpu` then uses the latest pairs which were read back.
+//!
+//! The pairs are a frame old when they're used, so they're found with the
+//! bounding circles of the balls grown by a margin, which should cover the
+//! distance balls travel during a frame. Reading back blocks until the GPU is
+//! done with the frame.
+
+use std::borrow::Cow;
+use std::sync::{Arc, Mutex};
+
+use bevy::core_pipeline::node::MAIN_PASS_DEPENDENCIES;
+use bevy::prelude::*;
+use bevy::render::render_graph::{self, RenderGraph};
+use bevy::render::render_resource::*;
+use bevy::render::renderer::{RenderContext, RenderDevice, RenderQueue};
+use bevy::render::{RenderApp, RenderStage};
+
+use crate::*;
+
+pub struct GpuBroadPhasePlugin {
+    cell_capacity: u32,
+    max_pairs: u32,
+}
+
+impl GpuBroadPhasePlugin {
+    pub fn with_max_pairs(max_pairs: u32) -> Self {
+        Self { cell_capacity: 32, max_pairs }
+    }
+}
+
+impl Default for GpuBroadPhasePlugin {
+    fn default() -> Self { Self::with_max_pairs(1 << 18) }
+}
+
+impl Plugin for GpuBroadPhasePlugin {
+    fn build(&self, app: &mut App) {
+        let pairs = GpuPairs::default();
+        app.insert_resource(pairs.clone());
+
+        let render_app = match app.get_sub_app_mut(RenderApp) {
+            Ok(render_app) => render_app,
+            Err(_) => {
+                println!("gpu: unable to find candidate pairs without a renderer");
+                return;
+            }
+        };
+        render_app.insert_resource(pairs)
+            .insert_resource(GridLimits { cell_capacity: self.cell_capacity, max_pairs: self.max_pairs })
+            .init_resource::<GridPipeline>()
+            .init_resource::<GridBuffers>()
+            .add_system_to_stage(RenderStage::Extract, extract_balls)
+            .add_system_to_stage(RenderStage::Prepare, prepare_grid)
+            .add_system_to_stage(RenderStage::Cleanup, read_back_pairs);
+
+        let mut graph = render_app.world.resource_mut::<RenderGraph>();
+        graph.add_node(GRID_NODE, GridNode);
+        graph.add_node_edge(GRID_NODE, MAIN_PASS_DEPENDENCIES).unwrap();
+    }
+}
+
+const GRID_NODE: &str = "gpu_broad_phase";
+
+// Invocations of each workgroup of the compute shader.
+const WORKGROUP_SIZE: u32 = 64;
+
+// Size of the parameters of the compute shader, rounded up to the alignment
+// of uniforms.
+const PARAMS_SIZE: u64 = 48;
+
+/// Candidate pairs of colliding balls which were last read back from the GPU,
+/// shared between the main and the render world.
+#[derive(Clone, Default)]
+pub struct GpuPairs(Arc<Mutex<Vec<[Entity; 2]>>>);
+
+impl GpuPairs {
+    pub fn latest(&self) -> Vec<[Entity; 2]> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+#[derive(Clone, Copy)]
+struct GridLimits {
+    /// Balls each cell of the grid holds, further balls are dropped.
+    cell_capacity: u32,
+    /// Pairs which are read back, further pairs are dropped.
+    max_pairs: u32,
+}
+
+// Balls of the frame, in the order they're uploaded, which is how the pairs
+// refer to them.
+struct ExtractedBalls {
+    bounds: Bounds,
+    margin: f32,
+    max_radius: f32,
+    entities: Vec<Entity>,
+    balls: Vec<[f32; 4]>,
+}
+
+fn extract_balls(
+    mut cmd: Commands,
+    broad_phase: Res<BroadPhase>,
+    edge: Res<EdgeCollider>,
+    query: Query<(Entity, &Transform, &Ball)>,
+) {
+    let margin = match *broad_phase {
+        BroadPhase::Gpu(margin) => margin,
+        _ => {
+            cmd.remove_resource::<ExtractedBalls>();
+            return;
+        }
+    };
+
+    let mut extracted = ExtractedBalls {
+        bounds: edge.bounds,
+        margin,
+        max_radius: 0.,
+        entities: Vec::new(),
+        balls: Vec::new(),
+    };
+    for (entity, transform, ball) in query.iter() {
+        extracted.max_radius = extracted.max_radius.max(ball.radius);
+        extracted.entities.push(entity);
+        extracted.balls.push([transform.translation.x, transform.translation.y, ball.radius, 0.]);
+    }
+    cmd.insert_resource(extracted);
+}
+
+struct GridPipeline {
+    layout: BindGroupLayout,
+    clear: CachedComputePipelineId,
+    bin: CachedComputePipelineId,
+    find_pairs: CachedComputePipelineId,
+}
+
+impl GridPipeline {
+    // Pipelines of the passes, once they're compiled.
+    fn passes<'a>(&self, cache: &'a PipelineCache) -> Option<[&'a ComputePipeline; 3]> {
+        Some([
+            cache.get_compute_pipeline(self.clear)?,
+            cache.get_compute_pipeline(self.bin)?,
+            cache.get_compute_pipeline(self.find_pairs)?,
+        ])
+    }
+}
+
+impl FromWorld for GridPipeline {
+    fn from_world(world: &mut World) -> Self {
+        let storage = |binding: u32, read_only: bool| BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStages::COMPUTE,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let layout = world.resource::<RenderDevice>().create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("gpu_broad_phase_layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: BufferSize::new(PARAMS_SIZE),
+                    },
+                    count: None,
+                },
+                storage(1, true),
+                storage(2, false),
+                storage(3, false),
+                storage(4, false),
+            ],
+        });
+
+        let shader = world.resource::<AssetServer>().load("shaders/broad_phase.wgsl");
+        let mut cache = world.resource_mut::<PipelineCache>();
+        let mut queue = |entry_point: &'static str| cache.queue_compute_pipeline(ComputePipelineDescriptor {
+            label: Some(Cow::from(entry_point)),
+            layout: Some(vec![layout.clone()]),
+            shader: shader.clone(),
+            shader_defs: Vec::new(),
+            entry_point: Cow::from(entry_point),
+        });
+        Self {
+            clear: queue("clear"),
+            bin: queue("bin"),
+            find_pairs: queue("find_pairs"),
+            layout,
+        }
+    }
+}
+
+// Parameters of the compute shader, laid out like its `Params`, and the amount
+// of cells of the grid.
+fn grid_params(balls: &ExtractedBalls, limits: GridLimits) -> (Vec<u8>, u32) {
+    // balls are checked against the neighbouring cells only, so a cell spans
+    // the largest reach between two balls
+    let cell_size = (balls.max_radius * 2. + balls.margin).max(1.);
+    let columns = (balls.bounds.width() / cell_size).ceil().max(1.) as u32;
+    let rows = (balls.bounds.height() / cell_size).ceil().max(1.) as u32;
+
+    let mut params = Vec::with_capacity(PARAMS_SIZE as usize);
+    for value in [balls.bounds.min().x, balls.bounds.min().y, cell_size, balls.margin] {
+        params.extend(value.to_le_bytes());
+    }
+    for value in [columns, rows, balls.balls.len() as u32, limits.cell_capacity, limits.max_pairs] {
+        params.extend(value.to_le_bytes());
+    }
+    params.resize(PARAMS_SIZE as usize, 0);
+    (params, columns * rows)
+}
+
+// Buffers are kept with their size, and replaced once they're too small.
+#[derive(Default)]
+struct GridBuffers {
+    params: Option<(Buffer, u64)>,
+    balls: Option<(Buffer, u64)>,
+    counts: Option<(Buffer, u64)>,
+    slots: Option<(Buffer, u64)>,
+    pairs: Option<(Buffer, u64)>,
+    readback: Option<(Buffer, u64)>,
+    // the grid of the frame which is dispatched
+    frame: Option<GridFrame>,
+}
+
+struct GridFrame {
+    bind_group: BindGroup,
+    cells: u32,
+    balls: u32,
+    // bytes of the pairs which are copied to be read back
+    pairs_size: u64,
+}
+
+// Buffer in `slot` which holds at least `size` bytes, it is replaced by a
+// larger one when it doesn't.
+fn reserve(device: &RenderDevice, slot: &mut Option<(Buffer, u64)>, size: u64, usage: BufferUsages) -> Buffer {
+    if let Some((buffer, _)) = slot.as_ref().filter(|(_, reserved)| *reserved >= size) {
+        return buffer.clone();
+    }
+    let size = size.next_power_of_two();
+    let buffer = device.create_buffer(&BufferDescriptor {
+        label: Some("gpu_broad_phase"),
+        size,
+        usage,
+        mapped_at_creation: false,
+    });
+    *slot = Some((buffer.clone(), size));
+    buffer
+}
+
+fn prepare_grid(
+    device: Res<RenderDevice>,
+    queue: Res<RenderQueue>,
+    pipeline: Res<GridPipeline>,
+    cache: Res<PipelineCache>,
+    limits: Res<GridLimits>,
+    balls: Option<Res<ExtractedBalls>>,
+    mut buffers: ResMut<GridBuffers>,
+) {
+    buffers.frame = None;
+    let balls = match balls {
+        Some(balls) if !balls.balls.is_empty() => balls,
+        _ => return,
+    };
+    if pipeline.passes(&cache).is_none() {
+        return;
+    }
+
+    let (params, cells) = grid_params(&balls, *limits);
+    let ball_bytes: Vec<u8> = balls.balls.iter().flatten().flat_map(|value| value.to_le_bytes()).collect();
+
+    let buffers = &mut *buffers;
+    let storage = BufferUsages::STORAGE;
+    let pairs_size = 8 + limits.max_pairs as u64 * 8;
+    let params_buffer = reserve(&device, &mut buffers.params, PARAMS_SIZE, BufferUsages::UNIFORM | BufferUsages::COPY_DST);
+    let balls_buffer = reserve(&device, &mut buffers.balls, ball_bytes.len() as u64, storage | BufferUsages::COPY_DST);
+    let counts = reserve(&device, &mut buffers.counts, cells as u64 * 4, storage);
+    let slots = reserve(&device, &mut buffers.slots, (cells * limits.cell_capacity) as u64 * 4, storage);
+    let pairs = reserve(&device, &mut buffers.pairs, pairs_size, storage | BufferUsages::COPY_SRC);
+    reserve(&device, &mut buffers.readback, pairs_size, BufferUsages::MAP_READ | BufferUsages::COPY_DST);
+    queue.write_buffer(&params_buffer, 0, &params);
+    queue.write_buffer(&balls_buffer, 0, &ball_bytes);
+
+    let bind_group = device.create_bind_group(&BindGroupDescriptor {
+        label: Some("gpu_broad_phase"),
+        layout: &pipeline.layout,
+        entries: &[
+            BindGroupEntry { binding: 0, resource: params_buffer.as_entire_binding() },
+            BindGroupEntry { binding: 1, resource: balls_buffer.as_entire_binding() },
+            BindGroupEntry { binding: 2, resource: counts.as_entire_binding() },
+            BindGroupEntry { binding: 3, resource: slots.as_entire_binding() },
+            BindGroupEntry { binding: 4, resource: pairs.as_entire_binding() },
+        ],
+    });
+    buffers.frame = Some(GridFrame { bind_group, cells, balls: balls.balls.len() as u32, pairs_size });
+}
+
+// Clears the grid, bins the balls and finds the pairs, which are then copied
+// to be read back.
+struct GridNode;
+
+impl render_graph::Node for GridNode {
+    fn run(
+        &self,
+        _graph: &mut render_graph::RenderGraphContext,
+        render_context: &mut RenderContext,
+        world: &World,
+    ) -> Result<(), render_graph::NodeRunError> {
+        let buffers = world.resource::<GridBuffers>();
+        let passes = world.resource::<GridPipeline>().passes(world.resource::<PipelineCache>());
+        let (frame, passes, pairs, readback) = match (&buffers.frame, passes, &buffers.pairs, &buffers.readback) {
+            (Some(frame), Some(passes), Some((pairs, _)), Some((readback, _))) => (frame, passes, pairs, readback),
+            _ => return Ok(()),
+        };
+
+        {
+            let mut pass = render_context.command_encoder.begin_compute_pass(&ComputePassDescriptor {
+                label: Some(GRID_NODE),
+            });
+            pass.set_bind_group(0, &frame.bind_group, &[]);
+            for (pipeline, invocations) in passes.into_iter().zip([frame.cells, frame.balls, frame.balls]) {
+                pass.set_pipeline(pipeline);
+                pass.dispatch((invocations + WORKGROUP_SIZE - 1) / WORKGROUP_SIZE, 1, 1);
+            }
+        }
+        render_context.command_encoder.copy_buffer_to_buffer(pairs, 0, readback, 0, frame.pairs_size);
+        Ok(())
+    }
+}
+
+// Read back the pairs of the frame once it is rendered.
+fn read_back_pairs(
+    device: Res<RenderDevice>,
+    limits: Res<GridLimits>,
+    gpu_pairs: Res<GpuPairs>,
+    balls: Option<Res<ExtractedBalls>>,
+    mut buffers: ResMut<GridBuffers>,
+    mut warned: Local<bool>,
+) {
+    let (frame, balls, readback) = match (buffers.frame.take(), balls, &buffers.readback) {
+        (Some(frame), Some(balls), Some((readback, _))) => (frame, balls, readback),
+        _ => return,
+    };
+
+    let slice = readback.slice(..frame.pairs_size);
+    device.map_buffer(&slice, MapMode::Read);
+    let found = {
+        let bytes = slice.get_mapped_range();
+        let u32_at = |at: usize| u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap()) as usize;
+        let (count, dropped) = (u32_at(0), u32_at(4));
+        if (count > limits.max_pairs as usize || dropped > 0) && !*warned {
+            println!("gpu: the grid is full, {} balls and {} pairs were dropped", dropped, count.saturating_sub(limits.max_pairs as usize));
+            *warned = true;
+        }
+        (0..count.min(limits.max_pairs as usize))
+            .map(|i| [balls.entities[u32_at(8 + i * 8)], balls.entities[u32_at(12 + i * 8)]])
+            .collect()
+    };
+    readback.unmap();
+    *gpu_pairs.0.lock().unwrap() = found;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grid_cells_span_the_reach_of_the_largest_balls() {
+        let balls = ExtractedBalls {
+            bounds: Bounds::new(Vec2::ZERO, 200., 100.),
+            margin: 4.,
+            max_radius: 8.,
+            entities: vec![Entity::from_raw(0)],
+            balls: vec![[0., 0., 8., 0.]],
+        };
+        let limits = GridLimits { cell_capacity: 32, max_pairs: 64 };
+        let (params, cells) = grid_params(&balls, limits);
+        assert_eq!(cells, 10 * 5);
+
+        let f32_at = |at: usize| f32::from_le_bytes(params[at..at + 4].try_into().unwrap());
+        let u32_at = |at: usize| u32::from_le_bytes(params[at..at + 4].try_into().unwrap());
+        assert_eq!(params.len() as u64, PARAMS_SIZE);
+        assert_eq!([f32_at(0), f32_at(4), f32_at(8), f32_at(12)], [-100., -50., 20., 4.]);
+        assert_eq!([u32_at(16), u32_at(20), u32_at(24), u32_at(28), u32_at(32)], [10, 5, 1, 32, 64]);
+    }
+}
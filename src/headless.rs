@@ -0,0 +1,382 @@
+use std::time::{Duration, Instant};
+
+use bevy::ecs::event::Events;
+use bevy::prelude::*;
+
+use crate::*;
+
+/// Spawn a seeded scene of `balls` within `bounds` inside a new world, with
+/// all resources the physics stage needs, but without any rendering or
+/// windowing. Returns the world and the spawned balls.
+#[allow(dead_code)]
+#[inline]
+pub fn headless_world(seed: u64, balls: usize, bounds: Bounds) -> (World, Vec<Entity>) {
+    scenario_world(Scenario::Uniform, seed, balls, bounds)
+}
+
+/// Like `headless_world`, with the balls distributed by `scenario`.
+pub fn scenario_world(scenario: Scenario, seed: u64, balls: usize, bounds: Bounds) -> (World, Vec<Entity>) {
+    let mut world = World::new();
+    let mut rng = SimRng::new(Some(seed));
+
+    let edge = EdgeCollider::new(bounds);
+    let spawn_area = edge.spawn_area(*BALL_RADIUS.end());
+
+    let entities = (0..balls)
+        .map(|i| {
+            let (radius, velocity, position) = scenario.ball(&mut rng, i, balls, spawn_area);
+            world.spawn()
+                .insert_bundle(BallBundle::builder(radius).with_velocity(velocity).with_position(position).build())
+                .id()
+        })
+        .collect();
+
+    world.insert_resource(edge);
+    world.insert_resource(rng);
+    world.insert_resource(BroadPhase::default());
+    world.insert_resource(PairBuffer::default());
+    world.insert_resource(BallTree::default());
+    world.insert_resource(ChunkedWorld::default());
+    world.insert_resource(COLLISION_MODEL);
+    world.insert_resource(TreeCapacity::default());
+    world.insert_resource(Paused(false));
+    world.insert_resource(Gravity(Vec2::ZERO));
+    world.insert_resource(Integrator::default());
+    world.insert_resource(Drag::default());
+    world.insert_resource(SlowMotion::default());
+    world.insert_resource(DebugLines::default());
+    world.insert_resource(Events::<BallCollided>::default());
+    world.insert_resource(Events::<BallHitWall>::default());
+    (world, entities)
+}
+
+/// Run the physics of a seeded scene for `ticks` ticks as fast as possible,
+/// and print how long the ticks took. Exits with an error when the balls of
+/// the scenario did not survive the ticks.
+pub fn bench(ticks: usize, balls: usize, seed: u64, scenario: Scenario) {
+    let (mut world, entities) = scenario_world(scenario, seed, balls, Bounds::new(Vec2::ZERO, WIDTH, HEIGHT));
+    world.insert_resource(BROAD_PHASE);
+    world.insert_resource(TreeCapacity::new(QUADTREE_CAPACITY));
+    let (elapsed, pairs) = run_ticks(&mut world, ticks, CHECKSUM_INTERVAL);
+
+    let ticks = ticks.max(1);
+    println!("scenario:   {}", scenario.name());
+    println!("balls:      {}", balls);
+    println!("ticks:      {}", ticks);
+    println!("total:      {:.3} s", elapsed.as_secs_f64());
+    println!("per tick:   {:.3} ms", elapsed.as_secs_f64() * 1000. / ticks as f64);
+    println!("ticks/s:    {:.1}", ticks as f64 / elapsed.as_secs_f64());
+    println!("pairs/tick: {:.1}", pairs as f64 / ticks as f64);
+    println!("checksum:   {:016x}", state_checksum(&mut world));
+
+    if let Err(err) = Scenario::check(&world, &entities) {
+        println!("scenario `{}` failed: {}", scenario.name(), err);
+        std::process::exit(1);
+    }
+}
+
+// Run `ticks` physics ticks, returns how long they took and the amount of
+// candidate pairs of all ticks together. Prints the checksum of the state
+// every `checksum_interval` ticks.
+fn run_ticks(world: &mut World, ticks: usize, checksum_interval: Option<usize>) -> (Duration, usize) {
+    let mut stage = physics_stage();
+    let mut pairs = 0;
+    let start = Instant::now();
+    for tick in 1..=ticks {
+        stage.run(world);
+        pairs += world.resource::<PairBuffer>().pairs().len();
+        if checksum_interval.map_or(false, |interval| tick % interval.max(1) == 0) {
+            println!("tick {:>8}: {:016x}", tick, state_checksum(world));
+        }
+    }
+    (start.elapsed(), pairs)
+}
+
+/// Broad phases which are compared, the first is the baseline the others are
+/// compared to.
+pub const COMPARED: [BroadPhase; 7] = [
+    BroadPhase::BruteForce,
+    BroadPhase::QuadTree,
+    BroadPhase::Linear(6),
+    BroadPhase::Reach,
+    BroadPhase::Cached(4.),
+    BroadPhase::Deferred(4),
+    BroadPhase::Chunked(256.),
+];
+
+/// Outcome of a seeded scene with one of the broad phases.
+pub struct Comparison {
+    pub broad_phase: BroadPhase,
+    pub elapsed: Duration,
+    pub pairs: usize,
+    pub checksum: u64,
+    pub check: Result<(), ScenarioError>,
+}
+
+/// Run the same seeded scene for `ticks` ticks with each of the `COMPARED`
+/// broad phases.
+pub fn compare(ticks: usize, balls: usize, seed: u64, scenario: Scenario) -> Vec<Comparison> {
+    COMPARED.into_iter()
+        .map(|broad_phase| {
+            let (mut world, entities) = scenario_world(scenario, seed, balls, Bounds::new(Vec2::ZERO, WIDTH, HEIGHT));
+            world.insert_resource(broad_phase);
+            world.insert_resource(TreeCapacity::new(QUADTREE_CAPACITY));
+            let (elapsed, pairs) = run_ticks(&mut world, ticks, None);
+            Comparison {
+                broad_phase,
+                elapsed,
+                pairs,
+                checksum: state_checksum(&mut world),
+                check: Scenario::check(&world, &entities),
+            }
+        })
+        .collect()
+}
+
+/// Benchmark each broad phase on the same seeded scene and print them side by
+/// side, with the checksum of the final state of the balls. Separating a pair
+/// of balls can make them overlap another ball, so broad phases which find
+/// other candidate pairs may play out differently. Those are marked, and exits
+/// with an error when the balls did not survive the ticks.
+pub fn bench_compare(ticks: usize, balls: usize, seed: u64, scenario: Scenario) {
+    let comparisons = compare(ticks, balls, seed, scenario);
+    let baseline = &comparisons[0];
+    let ticks = ticks.max(1);
+
+    println!("scenario: {}, balls: {}, ticks: {}, seed: {}", scenario.name(), balls, ticks, seed);
+    println!();
+    println!("{:<12} {:>10} {:>8} {:>11}  checksum", "broad phase", "per tick", "speedup", "pairs/tick");
+    for comparison in comparisons.iter() {
+        let same = comparison.checksum == baseline.checksum;
+        println!(
+            "{:<12} {:>7.3} ms {:>7.2}x {:>11.1}  {:016x}{}",
+            comparison.broad_phase.name(),
+            comparison.elapsed.as_secs_f64() * 1000. / ticks as f64,
+            baseline.elapsed.as_secs_f64() / comparison.elapsed.as_secs_f64(),
+            comparison.pairs as f64 / ticks as f64,
+            comparison.checksum,
+            if same { "" } else { " differs" },
+        );
+    }
+
+    let mut failed = false;
+    for comparison in comparisons.iter() {
+        if let Err(err) = &comparison.check {
+            println!("scenario `{}` failed with {}: {}", scenario.name(), comparison.broad_phase.name(), err);
+            failed = true;
+        }
+    }
+    if failed {
+        std::process::exit(1);
+    }
+}
+
+/// When a headless run stops. It stops at whichever criteria is met first,
+/// or never without any.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ExitCriteria {
+    /// Stop after this tick.
+    pub until_tick: Option<usize>,
+    /// Stop once the balls settled, see `SETTLED_ENERGY`.
+    pub until_settled: bool,
+}
+
+/// Criteria which ended a headless run.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Exit {
+    Tick,
+    Settled,
+}
+
+impl ExitCriteria {
+    #[inline]
+    pub fn is_empty(&self) -> bool { self.until_tick.is_none() && !self.until_settled }
+}
+
+/// Keeps track of the exit criteria over the ticks of a run.
+pub struct ExitCheck {
+    criteria: ExitCriteria,
+    threshold: f32,
+    settled_ticks: usize,
+}
+
+impl ExitCheck {
+    /// Check `criteria` for a run of `balls` balls.
+    pub fn new(criteria: ExitCriteria, balls: usize) -> Self {
+        Self { criteria, threshold: SETTLED_ENERGY * balls as f32, settled_ticks: 0 }
+    }
+
+    /// Criteria which is met after `tick`, at which the balls have a total
+    /// kinetic energy of `energy`.
+    pub fn check(&mut self, tick: usize, energy: f32) -> Option<Exit> {
+        if energy < self.threshold {
+            self.settled_ticks += 1;
+        } else {
+            self.settled_ticks = 0;
+        }
+        if self.criteria.until_settled && self.settled_ticks >= SETTLED_TICKS {
+            return Some(Exit::Settled);
+        }
+        if self.criteria.until_tick.map_or(false, |until| tick >= until) {
+            return Some(Exit::Tick);
+        }
+        None
+    }
+}
+
+/// Gravity, drag and restitution of a headless run, which let its balls
+/// settle. The defaults keep all energy, like the other headless commands.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HeadlessPhysics {
+    /// Downward acceleration of the balls.
+    pub gravity: f32,
+    /// Fraction of their velocity balls lose per second.
+    pub drag: f32,
+    /// Fraction of the speed which is preserved when balls bounce off of each
+    /// other or the walls. Under gravity, balls only come to rest on the floor
+    /// without any restitution, otherwise they keep hopping. Balls which pile
+    /// up keep jostling under gravity, so a crowded scene may never settle.
+    pub restitution: f32,
+}
+
+impl Default for HeadlessPhysics {
+    fn default() -> Self {
+        Self { gravity: 0., drag: 0., restitution: 1. }
+    }
+}
+
+impl HeadlessPhysics {
+    /// Apply to the scene in `world`, of which `entities` are the balls.
+    pub fn apply(&self, world: &mut World, entities: &[Entity]) {
+        world.insert_resource(Gravity(Vec2::new(0., -self.gravity)));
+        world.insert_resource(Drag(self.drag));
+        for entity in entities {
+            if let Some(mut ball) = world.get_mut::<Ball>(*entity) {
+                ball.restitution = self.restitution;
+            }
+        }
+    }
+}
+
+/// Run the physics of the scene in `world` until one of the `criteria` is met.
+/// Returns the amount of ticks which were run, and the criteria which ended
+/// the run.
+pub fn run_until(world: &mut World, entities: &[Entity], criteria: ExitCriteria) -> (usize, Exit) {
+    let mut check = ExitCheck::new(criteria, entities.len());
+    let mut stage = physics_stage();
+    let mut tick = 0;
+    if criteria.until_tick == Some(0) {
+        return (tick, Exit::Tick);
+    }
+    loop {
+        stage.run(world);
+        tick += 1;
+        if let Some(exit) = check.check(tick, kinetic_energy(world, entities)) {
+            return (tick, exit);
+        }
+        if CHECKSUM_INTERVAL.map_or(false, |interval| tick % interval.max(1) == 0) {
+            println!("tick {:>8}: {:016x}", tick, state_checksum(world));
+        }
+    }
+}
+
+/// Run the physics of a seeded scene with `physics` as fast as possible until
+/// one of the exit `criteria` is met, and print the final tick and checksum.
+pub fn headless(criteria: ExitCriteria, physics: HeadlessPhysics, balls: usize, seed: u64, scenario: Scenario) {
+    let (mut world, entities) = scenario_world(scenario, seed, balls, Bounds::new(Vec2::ZERO, WIDTH, HEIGHT));
+    world.insert_resource(BROAD_PHASE);
+    world.insert_resource(TreeCapacity::new(QUADTREE_CAPACITY));
+    physics.apply(&mut world, &entities);
+    let start = Instant::now();
+    let (ticks, exit) = run_until(&mut world, &entities, criteria);
+
+    println!("scenario: {}", scenario.name());
+    println!("balls:    {}", balls);
+    match exit {
+        Exit::Tick => println!("exit:     reached tick {}", ticks),
+        Exit::Settled => println!("exit:     settled for {} ticks", SETTLED_TICKS),
+    }
+    println!("ticks:    {}", ticks);
+    println!("total:    {:.3} s", start.elapsed().as_secs_f64());
+    println!("energy:   {:.3}", kinetic_energy(&world, &entities));
+    println!("checksum: {:016x}", state_checksum(&mut world));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn broad_phases_are_compared_by_checksum() {
+        let comparisons = compare(60, 200, 1688, Scenario::Uniform);
+        assert_eq!(comparisons.len(), COMPARED.len());
+        // the same broad phase always plays out the same
+        for (comparison, again) in comparisons.iter().zip(compare(60, 200, 1688, Scenario::Uniform)) {
+            assert!(comparison.check.is_ok(), "{}", comparison.broad_phase.name());
+            assert_eq!(comparison.checksum, again.checksum, "{}", comparison.broad_phase.name());
+        }
+
+        // with few balls, separating a pair doesn't push either ball into
+        // another one, so each broad phase plays out like the baseline
+        let comparisons = compare(60, 50, 1688, Scenario::Uniform);
+        let baseline = &comparisons[0];
+        assert_eq!(baseline.broad_phase, BroadPhase::BruteForce);
+        for comparison in comparisons.iter() {
+            assert_eq!(comparison.checksum, baseline.checksum, "{}", comparison.broad_phase.name());
+        }
+    }
+
+    #[test]
+    fn broad_phases_find_all_pairs_of_the_baseline() {
+        let pairs = |broad_phase| {
+            let (mut world, _) = scenario_world(Scenario::Uniform, 1688, 400, Bounds::new(Vec2::ZERO, WIDTH, HEIGHT));
+            world.insert_resource(broad_phase);
+            world.insert_resource(TreeCapacity::new(QUADTREE_CAPACITY));
+            run_ticks(&mut world, 1, None);
+            world.resource::<PairBuffer>().pairs().to_vec()
+        };
+
+        let baseline = pairs(BroadPhase::BruteForce);
+        assert!(!baseline.is_empty());
+        for broad_phase in COMPARED.into_iter().skip(1) {
+            let found = pairs(broad_phase);
+            let missed = baseline.iter().filter(|pair| found.binary_search(pair).is_err()).count();
+            assert_eq!(missed, 0, "{}", broad_phase.name());
+        }
+    }
+
+    #[test]
+    fn every_broad_phase_fills_the_ball_tree() {
+        for broad_phase in COMPARED {
+            let (mut world, entities) = headless_world(1638, 50, Bounds::new(Vec2::ZERO, WIDTH, HEIGHT));
+            world.insert_resource(broad_phase);
+            run_ticks(&mut world, 1, None);
+            let tree = &world.resource::<BallTree>().0;
+            assert_eq!(tree.entities().count(), entities.len(), "{}", broad_phase.name());
+        }
+    }
+
+    #[test]
+    fn runs_stop_at_the_first_exit_criteria() {
+        let criteria = ExitCriteria { until_tick: Some(SETTLED_TICKS * 2), until_settled: true };
+        let (mut world, entities) = headless_world(1702, 20, Bounds::new(Vec2::ZERO, 400., 400.));
+        assert_eq!(run_until(&mut world, &entities, criteria), (SETTLED_TICKS * 2, Exit::Tick));
+
+        // balls which lose their energy to drag and inelastic bounces come to
+        // rest on the floor, and settle once they rested long enough
+        let (mut world, entities) = headless_world(1702, 20, Bounds::new(Vec2::ZERO, 400., 400.));
+        HeadlessPhysics { gravity: 400., drag: 2., restitution: 0. }.apply(&mut world, &entities);
+        let criteria = ExitCriteria { until_tick: Some(SETTLED_TICKS * 20), until_settled: true };
+        let (ticks, exit) = run_until(&mut world, &entities, criteria);
+        assert_eq!(exit, Exit::Settled);
+        assert!(ticks > SETTLED_TICKS, "{}", ticks);
+
+        let mut check = ExitCheck::new(ExitCriteria { until_tick: None, until_settled: true }, 1);
+        for tick in 1..SETTLED_TICKS {
+            assert_eq!(check.check(tick, 0.), None);
+        }
+        // the energy has to stay below the threshold in a row
+        assert_eq!(check.check(SETTLED_TICKS, SETTLED_ENERGY), None);
+        assert_eq!(check.check(SETTLED_TICKS + 1, 0.), None);
+    }
+}
@@ -0,0 +1,132 @@
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::*;
+
+use crate::*;
+
+/// Gives each ball a temperature, which partially equalizes when balls
+/// collide. Balls are colored according to their temperature. Balls which
+/// spawn on the left half of the arena start hot, the others start cold.
+pub struct HeatPlugin {
+    transfer_rate: f32,
+}
+
+impl HeatPlugin {
+    pub fn with_transfer_rate(transfer_rate: f32) -> Self {
+        Self { transfer_rate }
+    }
+}
+
+impl Default for HeatPlugin {
+    fn default() -> Self { Self::with_transfer_rate(0.5) }
+}
+
+impl Plugin for HeatPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(HeatTransfer { rate: self.transfer_rate })
+            .register_type::<Heat>()
+            .add_system(heat_spawned_balls)
+            .add_system(color_by_heat)
+            .add_system_to_stage(PhysicsStage, heat_transfer.after(PhysicsSystem::Resolve));
+    }
+}
+
+/// Temperature of a ball, where 0 is cold and 1 is hot.
+#[derive(Component, Clone, Copy, Debug, Default, Reflect)]
+#[reflect(Component)]
+pub struct Heat(pub f32);
+
+pub struct HeatTransfer {
+    /// Fraction of the temperature difference which is equalized on contact.
+    pub rate: f32,
+}
+
+// Color ramp from cold to hot.
+const HEAT_COLORS: [Color; 4] = [
+    Color::rgb(0.1, 0.2, 0.9),
+    Color::rgb(0.6, 0.2, 0.7),
+    Color::rgb(0.95, 0.3, 0.1),
+    Color::rgb(1.0, 0.9, 0.3),
+];
+
+#[inline]
+pub fn heat_color(heat: f32) -> Color {
+    let scaled = heat.clamp(0., 1.) * (HEAT_COLORS.len() - 1) as f32;
+    let index = (scaled as usize).min(HEAT_COLORS.len() - 2);
+    let t = scaled - index as f32;
+
+    let [r1, g1, b1, _] = HEAT_COLORS[index].as_rgba_f32();
+    let [r2, g2, b2, _] = HEAT_COLORS[index + 1].as_rgba_f32();
+    Color::rgb(r1 + (r2 - r1) * t, g1 + (g2 - g1) * t, b1 + (b2 - b1) * t)
+}
+
+fn heat_spawned_balls(
+    mut cmd: Commands,
+    mut spawned: EventReader<BallSpawned>,
+    query: Query<&Transform>,
+) {
+    for BallSpawned(entity) in spawned.iter() {
+        if let Ok(transform) = query.get(*entity) {
+            let heat = if transform.translation.x < 0. { 1. } else { 0. };
+            cmd.entity(*entity).insert(Heat(heat));
+        }
+    }
+}
+
+fn heat_transfer(
+    transfer: Res<HeatTransfer>,
+    mut collided: EventReader<BallCollided>,
+    mut query: Query<(&mut Heat, &Ball)>,
+) {
+    for BallCollided(a, b) in collided.iter() {
+        if let Ok([(mut heat_a, ball_a), (mut heat_b, ball_b)]) = query.get_many_mut([*a, *b]) {
+            // heavier balls hold more heat, so they change temperature slower
+            let equilibrium = (heat_a.0 * ball_a.mass + heat_b.0 * ball_b.mass) / (ball_a.mass + ball_b.mass);
+            heat_a.0 += (equilibrium - heat_a.0) * transfer.rate;
+            heat_b.0 += (equilibrium - heat_b.0) * transfer.rate;
+        }
+    }
+}
+
+fn color_by_heat(mut query: Query<(&Heat, &mut DrawMode), Changed<Heat>>) {
+    for (heat, mut draw_mode) in query.iter_mut() {
+        set_fill_color(&mut draw_mode, heat_color(heat.0));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn colliding_balls_share_their_heat() {
+        let (mut world, entities) = headless_world(1616, 0, Bounds::new(Vec2::ZERO, 400., 300.));
+        assert!(entities.is_empty());
+        world.insert_resource(HeatTransfer { rate: 0.5 });
+
+        // a light hot ball and a heavy cold one, which head into each other
+        let mut spawn = |position: Vec2, velocity: Vec2, mass: f32, heat: f32| world.spawn()
+            .insert_bundle(BallBundle::builder(10.).with_mass(mass).with_position(position).with_velocity(velocity).build())
+            .insert(Heat(heat))
+            .id();
+        let hot = spawn(Vec2::new(-15., 0.), Vec2::new(100., 0.), 1., 1.);
+        let cold = spawn(Vec2::new(15., 0.), Vec2::new(-100., 0.), 3., 0.);
+        let total_heat = |world: &mut World| world.query::<(&Heat, &Ball)>()
+            .iter(world)
+            .map(|(heat, ball)| heat.0 * ball.mass)
+            .sum::<f32>();
+        let before = total_heat(&mut world);
+
+        let mut stage = physics_stage().with_system(heat_transfer.after(PhysicsSystem::Resolve));
+        for _ in 0..10 {
+            stage.run(&mut world);
+        }
+
+        // both move halfway to the mass weighted equilibrium of 0.25, and no
+        // heat is lost
+        let heat = |entity| world.get::<Heat>(entity).unwrap().0;
+        assert!((heat(hot) - 0.625).abs() < 1e-5, "{}", heat(hot));
+        assert!((heat(cold) - 0.125).abs() < 1e-5, "{}", heat(cold));
+        let after = total_heat(&mut world);
+        assert!((after - before).abs() < 1e-5, "{} != {}", after, before);
+    }
+}
@@ -0,0 +1,230 @@
+use bevy::core::FixedTimestep;
+use bevy::math::const_vec2;
+use bevy::prelude::*;
+use bevy::render::camera::Camera2d;
+use bevy_prototype_lyon::entity::Path;
+
+use crate::*;
+
+/// Plots the distribution of ball speeds as a histogram in the bottom left
+/// corner of the view, together with the 2D Maxwell-Boltzmann distribution
+/// which has the same average kinetic energy.
+pub struct SpeedHistogramPlugin {
+    bins: usize,
+    rate: f64,
+}
+
+impl SpeedHistogramPlugin {
+    pub fn with_bins(bins: usize) -> Self {
+        Self { bins, rate: 4. }
+    }
+}
+
+impl Default for SpeedHistogramPlugin {
+    fn default() -> Self { Self::with_bins(32) }
+}
+
+impl Plugin for SpeedHistogramPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(SpeedHistogram::with_bins(self.bins))
+            .add_startup_system(spawn_speed_histogram)
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(FixedTimestep::steps_per_second(self.rate))
+                    .with_system(update_speed_histogram)
+                    .with_system(draw_speed_histogram.after(update_speed_histogram)),
+            );
+    }
+}
+
+pub struct SpeedHistogram {
+    /// Amount of balls per bin, the bins evenly divide `0..=max_speed`.
+    pub counts: Vec<u32>,
+    pub max_speed: f32,
+    /// Average of the squared speed of all balls.
+    pub mean_square_speed: f32,
+    pub total: usize,
+}
+
+impl SpeedHistogram {
+    pub fn with_bins(bins: usize) -> Self {
+        Self {
+            counts: vec![0; bins.max(1)],
+            max_speed: 0.,
+            mean_square_speed: 0.,
+            total: 0,
+        }
+    }
+
+    /// Replace the contents of the histogram with `speeds`.
+    pub fn fill(&mut self, speeds: &[f32]) {
+        self.counts.iter_mut().for_each(|count| *count = 0);
+        self.total = speeds.len();
+        self.max_speed = speeds.iter().copied().fold(0., f32::max);
+        self.mean_square_speed = match speeds.len() {
+            0 => 0.,
+            len => speeds.iter().map(|speed| speed * speed).sum::<f32>() / len as f32,
+        };
+        if self.max_speed <= 0. {
+            return;
+        }
+
+        let bins = self.counts.len();
+        for speed in speeds {
+            let bin = (speed / self.bin_width()) as usize;
+            self.counts[bin.min(bins - 1)] += 1;
+        }
+    }
+
+    #[inline]
+    pub fn bin_width(&self) -> f32 { self.max_speed / self.counts.len() as f32 }
+
+    /// Amount of balls a bin at `speed` should contain, when the speeds follow
+    /// the 2D Maxwell-Boltzmann distribution.
+    #[inline]
+    pub fn expected(&self, speed: f32) -> f32 {
+        // in 2D the distribution is a Rayleigh distribution, with a scale
+        // determined by the average kinetic energy
+        let sigma_sq = self.mean_square_speed / 2.;
+        if sigma_sq <= 0. {
+            return 0.;
+        }
+
+        let density = speed / sigma_sq * (-speed * speed / (2. * sigma_sq)).exp();
+        density * self.bin_width() * self.total as f32
+    }
+}
+
+#[derive(Component)]
+struct HistogramBar(usize);
+
+#[derive(Component)]
+struct HistogramCurve;
+
+// Positioned at the bottom left corner of the plot.
+#[derive(Component)]
+struct HistogramAnchor;
+
+const PLOT_SIZE: Vec2 = const_vec2!([240., 120.]);
+const PLOT_MARGIN: f32 = 16.;
+const PLOT_Z: f32 = 10.;
+const CURVE_SAMPLES: usize = 64;
+
+fn spawn_speed_histogram(mut cmd: Commands, histogram: Res<SpeedHistogram>) {
+    cmd.spawn_bundle(GeometryBuilder::build_as(
+        &shapes::Rectangle {
+            extents: PLOT_SIZE,
+            origin: shapes::RectangleOrigin::BottomLeft,
+        },
+        DrawMode::Stroke(StrokeMode::new(Color::DARK_GRAY, 1.)),
+        Transform::default(),
+    ))
+        .insert(HistogramAnchor);
+
+    // bars are unit squares, which are scaled to their size
+    for i in 0..histogram.counts.len() {
+        cmd.spawn_bundle(GeometryBuilder::build_as(
+            &shapes::Rectangle {
+                extents: Vec2::ONE,
+                origin: shapes::RectangleOrigin::BottomLeft,
+            },
+            DrawMode::Fill(FillMode::color(Color::rgba(1., 1., 1., 0.4))),
+            Transform::default(),
+        ))
+            .insert(HistogramBar(i));
+    }
+
+    cmd.spawn_bundle(GeometryBuilder::build_as(
+        &shapes::Polygon {
+            points: vec![Vec2::ZERO, Vec2::new(PLOT_SIZE.x, 0.)],
+            closed: false,
+        },
+        DrawMode::Stroke(StrokeMode::new(Color::ORANGE, 1.5)),
+        Transform::default(),
+    ))
+        .insert(HistogramCurve)
+        .insert(HistogramAnchor);
+}
+
+fn update_speed_histogram(
+    mut histogram: ResMut<SpeedHistogram>,
+    mut speeds: Local<Vec<f32>>,
+    query: Query<&Velocity, With<Ball>>,
+) {
+    speeds.clear();
+    speeds.extend(query.iter().map(Velocity::speed));
+    histogram.fill(&speeds);
+}
+
+fn draw_speed_histogram(
+    histogram: Res<SpeedHistogram>,
+    windows: Res<Windows>,
+    cameras: Query<&Transform, With<Camera2d>>,
+    mut anchors: Query<&mut Transform, (With<HistogramAnchor>, Without<Camera2d>)>,
+    mut bars: Query<(&mut Transform, &HistogramBar), (Without<HistogramAnchor>, Without<Camera2d>)>,
+    mut curves: Query<&mut Path, With<HistogramCurve>>,
+) {
+    // keep the plot in the corner of the view, at a constant size on screen
+    let (origin, scale) = match (windows.get_primary(), cameras.get_single()) {
+        (Some(window), Ok(camera)) => {
+            let scale = camera.scale.truncate();
+            (view_bounds(window, camera).bottom_left() + PLOT_MARGIN * scale, scale)
+        }
+        _ => return,
+    };
+
+    for mut transform in anchors.iter_mut() {
+        transform.translation = origin.extend(PLOT_Z);
+        transform.scale = scale.extend(1.);
+    }
+
+    let tallest = histogram.counts.iter().copied().max().unwrap_or_default().max(1) as f32;
+    let bar_width = PLOT_SIZE.x / histogram.counts.len() as f32;
+    for (mut transform, bar) in bars.iter_mut() {
+        let height = histogram.counts[bar.0] as f32 / tallest * PLOT_SIZE.y;
+        transform.translation = (origin + Vec2::new(bar.0 as f32 * bar_width, 0.) * scale).extend(PLOT_Z);
+        // leave a small gap between bars
+        transform.scale = (Vec2::new((bar_width - 1.).max(1.), height) * scale).extend(1.);
+    }
+
+    if histogram.max_speed <= 0. {
+        return;
+    }
+    let points: Vec<Vec2> = (0..=CURVE_SAMPLES)
+        .map(|i| {
+            let t = i as f32 / CURVE_SAMPLES as f32;
+            let height = histogram.expected(t * histogram.max_speed) / tallest * PLOT_SIZE.y;
+            Vec2::new(t * PLOT_SIZE.x, height.min(PLOT_SIZE.y))
+        })
+        .collect();
+    for mut path in curves.iter_mut() {
+        *path = ShapePath::build_as(&shapes::Polygon {
+            points: points.clone(),
+            closed: false,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn histogram_bins_speeds_and_matches_expected_total() {
+        let mut histogram = SpeedHistogram::with_bins(4);
+        histogram.fill(&[0., 1., 1.5, 3.9, 4.]);
+
+        assert_eq!(histogram.counts, vec![1, 2, 0, 2]);
+        assert_eq!(histogram.bin_width(), 1.);
+
+        // the expected counts of many narrow bins add up to all balls
+        let mut histogram = SpeedHistogram::with_bins(1000);
+        let speeds: Vec<f32> = (1..=100).map(|i| i as f32 / 10.).collect();
+        histogram.fill(&speeds);
+        histogram.max_speed *= 10.;
+        let expected: f32 = (0..1000)
+            .map(|i| histogram.expected((i as f32 + 0.5) * histogram.bin_width()))
+            .sum();
+        assert!((expected - 100.).abs() < 0.5, "{}", expected);
+    }
+}
@@ -0,0 +1,105 @@
+use bevy::prelude::*;
+
+use crate::*;
+
+type Hook = Box<dyn FnMut(&mut World) + Send + Sync>;
+
+/// Points within a physics tick at which hooks are run.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum HookPoint {
+    /// Before the balls are moved.
+    PrePhysics,
+    /// After all systems of the tick ran, before the balls they spawned or
+    /// despawned are added or removed.
+    PostResolve,
+    /// At the end of the tick, after spawned and despawned balls are added
+    /// and removed.
+    PostPhysics,
+}
+
+/// Callbacks with full access to the world, which are run at fixed points of
+/// each physics tick. This gives code which embeds the simulation a way to
+/// inspect or change it, without changing the systems of the `PhysicsStage`.
+#[derive(Default)]
+pub struct SimulationHooks {
+    hooks: Vec<(HookPoint, Hook)>,
+}
+
+impl SimulationHooks {
+    /// Register `hook` to run at `point` of each tick, after the hooks which
+    /// were registered before it.
+    pub fn add(&mut self, point: HookPoint, hook: impl FnMut(&mut World) + Send + Sync + 'static) -> &mut Self {
+        self.hooks.push((point, Box::new(hook)));
+        self
+    }
+
+    fn run(&mut self, point: HookPoint, world: &mut World) {
+        for (_, hook) in self.hooks.iter_mut().filter(|(at, _)| *at == point) {
+            hook(world);
+        }
+    }
+}
+
+fn run_hooks(world: &mut World, point: HookPoint) {
+    if world.contains_resource::<SimulationHooks>() {
+        world.resource_scope(|world, mut hooks: Mut<SimulationHooks>| hooks.run(point, world));
+    }
+}
+
+pub(crate) fn run_pre_physics_hooks(world: &mut World) {
+    run_hooks(world, HookPoint::PrePhysics);
+}
+
+pub(crate) fn run_post_resolve_hooks(world: &mut World) {
+    run_hooks(world, HookPoint::PostResolve);
+}
+
+pub(crate) fn run_post_physics_hooks(world: &mut World) {
+    run_hooks(world, HookPoint::PostPhysics);
+}
+
+// Physics stage of a world which is stepped manually, kept between steps so
+// the state of its systems is preserved.
+#[allow(dead_code)]
+struct SteppedStage(SystemStage);
+
+/// Advance the simulation in `world` by a single physics tick, including its
+/// hooks. Meant for headless worlds, like the ones of tests.
+#[allow(dead_code)]
+pub fn step(world: &mut World) {
+    if !world.contains_resource::<SteppedStage>() {
+        world.insert_resource(SteppedStage(physics_stage()));
+    }
+    world.resource_scope(|world, mut stage: Mut<SteppedStage>| stage.0.run(world));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    #[test]
+    fn hooks_run_in_order_each_step() {
+        let (mut world, entities) = headless_world(1667, 4, Bounds::new(Vec2::ZERO, 200., 200.));
+        let calls = Arc::new(Mutex::new(Vec::new()));
+
+        let mut hooks = SimulationHooks::default();
+        for point in [HookPoint::PostPhysics, HookPoint::PrePhysics, HookPoint::PostResolve] {
+            let calls = calls.clone();
+            hooks.add(point, move |_| calls.lock().unwrap().push(point));
+        }
+        // hooks have full access to the world
+        let ball = entities[0];
+        hooks.add(HookPoint::PostPhysics, move |world| {
+            world.get_mut::<Velocity>(ball).unwrap().0 = Vec2::ZERO;
+        });
+        world.insert_resource(hooks);
+
+        step(&mut world);
+        step(&mut world);
+        use HookPoint::*;
+        assert_eq!(*calls.lock().unwrap(), [PrePhysics, PostResolve, PostPhysics, PrePhysics, PostResolve, PostPhysics]);
+        assert_eq!(world.get::<Velocity>(ball).unwrap().0, Vec2::ZERO);
+    }
+}
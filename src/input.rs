@@ -0,0 +1,347 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+#[cfg(feature = "scene")]
+use serde::{Deserialize, Serialize};
+
+use crate::quadtree::Bounds;
+#[cfg(feature = "scene")]
+use crate::SceneError;
+
+/// Maps keyboard, mouse and gamepad input to simulation actions, so systems
+/// don't need to know which device triggered them. Actions are available as
+/// an `Input<Action>` resource, analog input as the `ActionAxes` resource.
+/// The keys and mouse buttons of each action are declared through `BindInput`
+/// by the plugins which use the action, and can be rebound from a file.
+pub struct ActionInputPlugin;
+
+impl Plugin for ActionInputPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Input<Action>>()
+            .init_resource::<ActionAxes>()
+            .init_resource::<InputMap>()
+            .bind_action(Action::SpawnBalls, [Binding::Key(KeyCode::Equals), Binding::Key(KeyCode::NumpadAdd)])
+            .bind_action(Action::DespawnBalls, [Binding::Key(KeyCode::Minus), Binding::Key(KeyCode::NumpadSubtract)])
+            .bind_action(Action::Pause, [Binding::Key(KeyCode::P)])
+            .bind_action(Action::Reset, [Binding::Key(KeyCode::R)])
+            .bind_action(Action::ToggleDebug, [Binding::Key(KeyCode::F3)])
+            .bind_axis(AxisControl::GravityTilt, [KeyCode::Left, KeyCode::Right, KeyCode::Down, KeyCode::Up])
+            .add_startup_system(report_conflicting_bindings)
+            .add_system_to_stage(CoreStage::PreUpdate, update_actions);
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "scene", derive(Serialize, Deserialize))]
+pub enum Action {
+    Pause,
+    Reset,
+    /// Held while playing the simulation backwards.
+    Rewind,
+    SpawnBalls,
+    DespawnBalls,
+    SpawnBurst,
+    Explode,
+    ToggleDebug,
+    ToggleAttractor,
+    ToggleLabels,
+    ToggleGrid,
+    ToggleHelp,
+    ToggleStageTimings,
+    DeleteSelection,
+    FreezeSelection,
+    RecolorSelection,
+    PushSelection,
+    /// Held while dragging the marquee of the selection.
+    SelectArea,
+    /// Held while dragging the region of slow motion.
+    SlowMotionRegion,
+    /// Held while dragging the region in which balls are labeled.
+    LabelRegion,
+    Undo,
+    Redo,
+    // only bound with the `scene` feature
+    #[allow(dead_code)]
+    SaveScene,
+    #[allow(dead_code)]
+    ExportBevyScene,
+    // only bound with the `inspector` feature
+    #[allow(dead_code)]
+    ToggleInspector,
+}
+
+#[derive(Default)]
+pub struct ActionAxes {
+    /// Tilt of gravity, each axis is within -1..=1.
+    pub gravity_tilt: Vec2,
+    /// Movement of the attractor, each axis is within -1..=1.
+    pub attractor_movement: Vec2,
+    /// Sideways movement of the paddle, within -1..=1.
+    pub paddle_movement: f32,
+}
+
+/// Analog input which keys control at full strength.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "scene", derive(Serialize, Deserialize))]
+pub enum AxisControl {
+    GravityTilt,
+    AttractorMovement,
+    PaddleMovement,
+}
+
+/// Action or axis which is bound to keys.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "scene", derive(Serialize, Deserialize))]
+pub enum Control {
+    Action(Action),
+    Axis(AxisControl),
+}
+
+/// Key or mouse button which triggers an action.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "scene", derive(Serialize, Deserialize))]
+pub enum Binding {
+    Key(KeyCode),
+    /// Key while either control key is held.
+    Ctrl(KeyCode),
+    Mouse(MouseButton),
+    /// Mouse button while either shift key is held.
+    ShiftMouse(MouseButton),
+}
+
+impl Binding {
+    #[inline]
+    fn pressed(&self, keys: &Input<KeyCode>, buttons: &Input<MouseButton>) -> bool {
+        match *self {
+            Binding::Key(key) => keys.pressed(key),
+            Binding::Ctrl(key) => keys.pressed(key) && keys.any_pressed([KeyCode::LControl, KeyCode::RControl]),
+            Binding::Mouse(button) => buttons.pressed(button),
+            Binding::ShiftMouse(button) => buttons.pressed(button) && keys.any_pressed([KeyCode::LShift, KeyCode::RShift]),
+        }
+    }
+}
+
+/// Keys and mouse buttons of all actions and axes, in the order they were
+/// declared. Plugins declare the keys of the actions they use through
+/// `BindInput`, so the keys of all active features are known in a single
+/// place, where they can be rebound and checked for conflicts.
+#[derive(Default)]
+pub struct InputMap(Vec<(Control, Vec<Binding>)>);
+
+impl InputMap {
+    /// Bind `control` to `keys`, next to the keys it was bound to before.
+    /// Axes take their keys in pairs of the negative and positive direction,
+    /// first along x and then along y.
+    pub fn bind(&mut self, control: Control, keys: impl IntoIterator<Item = Binding>) {
+        let index = match self.0.iter().position(|(bound, _)| *bound == control) {
+            Some(index) => index,
+            None => {
+                self.0.push((control, Vec::new()));
+                self.0.len() - 1
+            }
+        };
+        self.0[index].1.extend(keys);
+    }
+
+    /// Bind `control` to `keys` instead of the keys it was bound to before.
+    pub fn rebind(&mut self, control: Control, keys: impl IntoIterator<Item = Binding>) {
+        match self.0.iter_mut().find(|(bound, _)| *bound == control) {
+            Some((_, bound)) => *bound = keys.into_iter().collect(),
+            None => self.0.push((control, keys.into_iter().collect())),
+        }
+    }
+
+    /// Load bindings from a RON file, holding a list of controls with their
+    /// keys. Each replaces the keys of its control when applied with
+    /// `rebind`.
+    #[cfg(feature = "scene")]
+    pub fn load_bindings(path: impl AsRef<std::path::Path>) -> Result<Vec<(Control, Vec<Binding>)>, SceneError> {
+        let text = std::fs::read_to_string(path).map_err(SceneError::Io)?;
+        ron::from_str(&text).map_err(SceneError::Ron)
+    }
+
+    /// Keys `control` is bound to.
+    pub fn keys(&self, control: Control) -> &[Binding] {
+        self.0.iter()
+            .find(|(bound, _)| *bound == control)
+            .map_or(&[], |(_, keys)| keys.as_slice())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &(Control, Vec<Binding>)> {
+        self.0.iter()
+    }
+
+    /// Bindings which trigger more than one control, with those controls.
+    pub fn conflicts(&self) -> Vec<(Binding, Vec<Control>)> {
+        let mut conflicts: Vec<(Binding, Vec<Control>)> = Vec::new();
+        for (control, keys) in self.0.iter() {
+            for binding in keys.iter() {
+                let others: Vec<Control> = self.0.iter()
+                    .filter(|(other, other_keys)| other != control && other_keys.contains(binding))
+                    .map(|(other, _)| *other)
+                    .collect();
+                if !others.is_empty() && !conflicts.iter().any(|(conflict, _)| conflict == binding) {
+                    conflicts.push((*binding, std::iter::once(*control).chain(others).collect()));
+                }
+            }
+        }
+        conflicts
+    }
+
+    // Value of `axis` from the keys which are held, with each direction
+    // within -1..=1.
+    fn axis(&self, axis: AxisControl, keys: &Input<KeyCode>, buttons: &Input<MouseButton>) -> Vec2 {
+        let bound = self.keys(Control::Axis(axis));
+        let direction = |negative: usize| {
+            let pressed = |i: usize| bound.get(i).map_or(false, |binding| binding.pressed(keys, buttons));
+            (pressed(negative + 1) as i8 - pressed(negative) as i8) as f32
+        };
+        Vec2::new(direction(0), direction(2))
+    }
+}
+
+/// Declares the keys and mouse buttons of actions and axes in the `InputMap`
+/// resource.
+pub trait BindInput {
+    fn bind_action(&mut self, action: Action, keys: impl IntoIterator<Item = Binding>) -> &mut Self;
+
+    fn bind_axis(&mut self, axis: AxisControl, keys: impl IntoIterator<Item = KeyCode>) -> &mut Self;
+}
+
+impl BindInput for App {
+    fn bind_action(&mut self, action: Action, keys: impl IntoIterator<Item = Binding>) -> &mut Self {
+        self.world.get_resource_or_insert_with(InputMap::default).bind(Control::Action(action), keys);
+        self
+    }
+
+    fn bind_axis(&mut self, axis: AxisControl, keys: impl IntoIterator<Item = KeyCode>) -> &mut Self {
+        self.world.get_resource_or_insert_with(InputMap::default)
+            .bind(Control::Axis(axis), keys.into_iter().map(Binding::Key));
+        self
+    }
+}
+
+// Gamepad buttons of actions, next to their keys.
+const GAMEPAD_ACTIONS: [(GamepadButtonType, Action); 5] = [
+    (GamepadButtonType::Start, Action::Pause),
+    (GamepadButtonType::RightTrigger2, Action::SpawnBalls),
+    (GamepadButtonType::LeftTrigger2, Action::DespawnBalls),
+    (GamepadButtonType::North, Action::ToggleAttractor),
+    (GamepadButtonType::Select, Action::ToggleLabels),
+];
+
+// Analog input below this value is ignored.
+const DEAD_ZONE: f32 = 0.15;
+
+#[inline]
+fn stick(axes: &Axis<GamepadAxis>, gamepad: Gamepad, x: GamepadAxisType, y: GamepadAxisType) -> Vec2 {
+    let stick = Vec2::new(
+        axes.get(GamepadAxis(gamepad, x)).unwrap_or(0.),
+        axes.get(GamepadAxis(gamepad, y)).unwrap_or(0.),
+    );
+    if stick.length() < DEAD_ZONE { Vec2::ZERO } else { stick }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn update_actions(
+    mut actions: ResMut<Input<Action>>,
+    mut action_axes: ResMut<ActionAxes>,
+    bindings: Res<InputMap>,
+    keys: Res<Input<KeyCode>>,
+    mouse_buttons: Res<Input<MouseButton>>,
+    gamepads: Res<Gamepads>,
+    buttons: Res<Input<GamepadButton>>,
+    axes: Res<Axis<GamepadAxis>>,
+) {
+    let mut gravity_tilt = bindings.axis(AxisControl::GravityTilt, &keys, &mouse_buttons);
+    let mut attractor_movement = bindings.axis(AxisControl::AttractorMovement, &keys, &mouse_buttons);
+    let mut paddle_movement = bindings.axis(AxisControl::PaddleMovement, &keys, &mouse_buttons).x;
+
+    // actions which are no longer held are released below
+    let mut active: HashMap<Action, bool> = actions.get_pressed().map(|action| (*action, false)).collect();
+    for (control, bound) in bindings.iter() {
+        if let Control::Action(action) = control {
+            *active.entry(*action).or_default() |= bound.iter().any(|binding| binding.pressed(&keys, &mouse_buttons));
+        }
+    }
+
+    for gamepad in gamepads.iter() {
+        let gamepad = *gamepad;
+        gravity_tilt += stick(&axes, gamepad, GamepadAxisType::LeftStickX, GamepadAxisType::LeftStickY);
+        attractor_movement += stick(&axes, gamepad, GamepadAxisType::RightStickX, GamepadAxisType::RightStickY);
+        paddle_movement += (buttons.pressed(GamepadButton(gamepad, GamepadButtonType::DPadRight)) as i8
+            - buttons.pressed(GamepadButton(gamepad, GamepadButtonType::DPadLeft)) as i8) as f32;
+
+        for (button, action) in GAMEPAD_ACTIONS {
+            *active.entry(action).or_default() |= buttons.pressed(GamepadButton(gamepad, button));
+        }
+    }
+
+    action_axes.gravity_tilt = gravity_tilt.clamp(Vec2::splat(-1.), Vec2::ONE);
+    action_axes.attractor_movement = attractor_movement.clamp(Vec2::splat(-1.), Vec2::ONE);
+    action_axes.paddle_movement = paddle_movement.clamp(-1., 1.);
+
+    actions.clear();
+    for (action, active) in active {
+        update_action(&mut actions, action, active);
+    }
+}
+
+// Bindings are only final once all plugins are built and the bindings from the
+// command line are applied, so they are checked at startup.
+fn report_conflicting_bindings(bindings: Res<InputMap>) {
+    for (binding, controls) in bindings.conflicts() {
+        println!("input: {:?} is bound to each of {:?}", binding, controls);
+    }
+}
+
+#[inline]
+fn update_action(actions: &mut Input<Action>, action: Action, active: bool) {
+    if active && !actions.pressed(action) {
+        actions.press(action);
+    } else if !active && actions.pressed(action) {
+        actions.release(action);
+    }
+}
+
+/// Position of the cursor in world coordinates, when it is within `window`.
+/// Assumes `camera` is an orthographic 2D camera without rotation.
+#[inline]
+pub fn cursor_to_world(window: &Window, camera: &Transform) -> Option<Vec2> {
+    let cursor = window.cursor_position()?;
+    let size = Vec2::new(window.width(), window.height());
+    Some((cursor - size / 2.) * camera.scale.truncate() + camera.translation.truncate())
+}
+
+/// Area of the world which is visible through `camera` in `window`. Assumes
+/// `camera` is an orthographic 2D camera without rotation.
+#[inline]
+pub fn view_bounds(window: &Window, camera: &Transform) -> Bounds {
+    let size = Vec2::new(window.width(), window.height()) * camera.scale.truncate();
+    Bounds::new(camera.translation.truncate(), size.x, size.y)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bindings_are_rebound_and_checked_for_conflicts() {
+        let mut bindings = InputMap::default();
+        bindings.bind(Control::Action(Action::Pause), [Binding::Key(KeyCode::P)]);
+        bindings.bind(Control::Action(Action::Reset), [Binding::Key(KeyCode::R)]);
+        bindings.bind(Control::Action(Action::Explode), [Binding::Mouse(MouseButton::Left)]);
+        bindings.bind(Control::Action(Action::SelectArea), [Binding::ShiftMouse(MouseButton::Left)]);
+        assert!(bindings.conflicts().is_empty());
+
+        bindings.rebind(Control::Action(Action::Reset), [Binding::Key(KeyCode::P), Binding::Ctrl(KeyCode::R)]);
+        assert_eq!(bindings.keys(Control::Action(Action::Reset)), [Binding::Key(KeyCode::P), Binding::Ctrl(KeyCode::R)]);
+        assert_eq!(bindings.conflicts(), vec![
+            (Binding::Key(KeyCode::P), vec![Control::Action(Action::Pause), Control::Action(Action::Reset)]),
+        ]);
+
+        // controls which weren't bound before are added
+        bindings.rebind(Control::Action(Action::Undo), [Binding::Key(KeyCode::U)]);
+        assert_eq!(bindings.keys(Control::Action(Action::Undo)), [Binding::Key(KeyCode::U)]);
+    }
+}
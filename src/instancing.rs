@@ -0,0 +1,334 @@
+use std::f32::consts::TAU;
+
+use bevy::core::FloatOrd;
+use bevy::core_pipeline::Transparent2d;
+use bevy::ecs::system::lifetimeless::{Read, SQuery, SRes};
+use bevy::ecs::system::SystemParamItem;
+use bevy::prelude::*;
+use bevy::render::render_phase::{
+    AddRenderCommand, DrawFunctions, EntityRenderCommand, RenderCommandResult, RenderPhase, SetItemPipeline,
+    TrackedRenderPass,
+};
+use bevy::render::render_resource::*;
+use bevy::render::renderer::RenderDevice;
+use bevy::render::texture::BevyDefault;
+use bevy::render::view::Msaa;
+use bevy::render::{RenderApp, RenderStage};
+use bevy::sprite::{Mesh2dPipeline, Mesh2dPipelineKey, SetMesh2dViewBindGroup};
+use bevy_prototype_lyon::prelude::*;
+
+use crate::*;
+
+/// Draws all circular balls with a single instanced draw call, instead of a
+/// mesh per ball, so hundreds of thousands of balls can be drawn. Each ball is
+/// an instance of one circle mesh, with its position, radius and fill color.
+/// The shapes of those balls are hidden, so outlines aren't drawn. Balls
+/// which aren't circles, or are drawn as sprites, keep being drawn as before.
+pub struct InstancedBallsPlugin {
+    segments: usize,
+}
+
+impl InstancedBallsPlugin {
+    pub fn with_segments(segments: usize) -> Self {
+        Self { segments }
+    }
+}
+
+impl Default for InstancedBallsPlugin {
+    fn default() -> Self { Self::with_segments(32) }
+}
+
+impl Plugin for InstancedBallsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system(|mut cmd: Commands| {
+            cmd.spawn().insert(BallInstancer);
+        })
+            .add_system_to_stage(CoreStage::PostUpdate, hide_ball_shapes);
+
+        let render_app = match app.get_sub_app_mut(RenderApp) {
+            Ok(render_app) => render_app,
+            Err(_) => {
+                println!("instancing: unable to draw balls without a renderer");
+                return;
+            }
+        };
+        let pipeline = BallInstancePipeline::new(&mut render_app.world, self.segments);
+        render_app.insert_resource(pipeline)
+            .add_render_command::<Transparent2d, DrawBallInstances>()
+            .init_resource::<SpecializedRenderPipelines<BallInstancePipeline>>()
+            .add_system_to_stage(RenderStage::Extract, extract_ball_instances)
+            .add_system_to_stage(RenderStage::Prepare, prepare_instance_buffers)
+            .add_system_to_stage(RenderStage::Queue, queue_ball_instances);
+    }
+}
+
+// Circular balls, the instances are drawn as circles.
+type Circles = (Without<BoxBody>, Without<CapsuleBody>, Without<CompoundCollider>);
+
+type ChangedVisibility = Or<(Added<Ball>, Changed<BallVisual>, Changed<Visibility>)>;
+
+// Hide the shapes of balls which are drawn as instances. Pooled balls are shown
+// again when they are reused, so they're hidden again.
+fn hide_ball_shapes(mut query: Query<(&BallVisual, &mut Visibility), (With<Ball>, Circles, ChangedVisibility)>) {
+    for (visual, mut visibility) in query.iter_mut() {
+        let visible = *visual != BallVisual::Shape;
+        if visibility.is_visible != visible {
+            visibility.is_visible = visible;
+        }
+    }
+}
+
+/// Unit circle as a list of triangles, with `segments` segments.
+pub fn circle_triangles(segments: usize) -> Vec<Vec2> {
+    let segments = segments.max(3);
+    let point = |i: usize| {
+        let angle = i as f32 / segments as f32 * TAU;
+        Vec2::new(angle.cos(), angle.sin())
+    };
+    (0..segments)
+        .flat_map(|i| [Vec2::ZERO, point(i), point(i + 1)])
+        .collect()
+}
+
+// Entity the instances of all balls are drawn with, which is the same in the
+// main and the render world.
+#[derive(Component)]
+struct BallInstancer;
+
+// Size of the data of each instance: the center, depth and radius of a ball,
+// followed by its color.
+const INSTANCE_SIZE: u64 = 32;
+
+// Instances of all balls of the frame.
+#[derive(Component)]
+struct BallInstances {
+    data: Vec<u8>,
+    len: u32,
+    z: f32,
+}
+
+#[derive(Component)]
+struct InstanceBuffer {
+    buffer: Buffer,
+    len: u32,
+}
+
+fn extract_ball_instances(
+    mut cmd: Commands,
+    instancer: Query<Entity, With<BallInstancer>>,
+    query: Query<(&Ball, &BallVisual, &GlobalTransform, &DrawMode), Circles>,
+) {
+    let instancer = match instancer.get_single() {
+        Ok(instancer) => instancer,
+        Err(_) => return,
+    };
+    let mut instances = BallInstances { data: Vec::new(), len: 0, z: 0. };
+    for (ball, visual, transform, draw_mode) in query.iter() {
+        if *visual != BallVisual::Shape {
+            continue;
+        }
+        let position = transform.translation;
+        let color = fill_color(draw_mode).as_linear_rgba_f32();
+        for value in [position.x, position.y, position.z, ball.radius].into_iter().chain(color) {
+            instances.data.extend(value.to_le_bytes());
+        }
+        instances.len += 1;
+        instances.z = instances.z.max(position.z);
+    }
+    if instances.len > 0 {
+        cmd.get_or_spawn(instancer).insert(instances);
+    }
+}
+
+fn prepare_instance_buffers(
+    mut cmd: Commands,
+    device: Res<RenderDevice>,
+    query: Query<(Entity, &BallInstances)>,
+) {
+    for (entity, instances) in query.iter() {
+        let buffer = device.create_buffer_with_data(&BufferInitDescriptor {
+            label: Some("ball_instances"),
+            contents: &instances.data,
+            usage: BufferUsages::VERTEX,
+        });
+        cmd.entity(entity).insert(InstanceBuffer { buffer, len: instances.len });
+    }
+}
+
+pub struct BallInstancePipeline {
+    mesh2d_pipeline: Mesh2dPipeline,
+    shader: Handle<Shader>,
+    circle: Buffer,
+    vertices: u32,
+}
+
+impl BallInstancePipeline {
+    fn new(world: &mut World, segments: usize) -> Self {
+        let vertices = circle_triangles(segments);
+        let data: Vec<u8> = vertices.iter()
+            .flat_map(|vertex| vertex.to_array())
+            .flat_map(|value| value.to_le_bytes())
+            .collect();
+        let circle = world.resource::<RenderDevice>().create_buffer_with_data(&BufferInitDescriptor {
+            label: Some("ball_instance_circle"),
+            contents: &data,
+            usage: BufferUsages::VERTEX,
+        });
+
+        Self {
+            mesh2d_pipeline: Mesh2dPipeline::from_world(world),
+            shader: world.resource::<AssetServer>().load("shaders/instanced_balls.wgsl"),
+            circle,
+            vertices: vertices.len() as u32,
+        }
+    }
+}
+
+impl SpecializedRenderPipeline for BallInstancePipeline {
+    type Key = Mesh2dPipelineKey;
+
+    fn specialize(&self, key: Self::Key) -> RenderPipelineDescriptor {
+        let attribute = |format: VertexFormat, offset: u64, shader_location: u32| VertexAttribute {
+            format,
+            offset,
+            shader_location,
+        };
+        RenderPipelineDescriptor {
+            vertex: VertexState {
+                shader: self.shader.clone(),
+                shader_defs: Vec::new(),
+                entry_point: "vertex".into(),
+                buffers: vec![
+                    VertexBufferLayout {
+                        array_stride: 8,
+                        step_mode: VertexStepMode::Vertex,
+                        attributes: vec![attribute(VertexFormat::Float32x2, 0, 0)],
+                    },
+                    VertexBufferLayout {
+                        array_stride: INSTANCE_SIZE,
+                        step_mode: VertexStepMode::Instance,
+                        attributes: vec![
+                            attribute(VertexFormat::Float32x4, 0, 1),
+                            attribute(VertexFormat::Float32x4, 16, 2),
+                        ],
+                    },
+                ],
+            },
+            fragment: Some(FragmentState {
+                shader: self.shader.clone(),
+                shader_defs: Vec::new(),
+                entry_point: "fragment".into(),
+                targets: vec![ColorTargetState {
+                    format: TextureFormat::bevy_default(),
+                    blend: Some(BlendState::ALPHA_BLENDING),
+                    write_mask: ColorWrites::ALL,
+                }],
+            }),
+            layout: Some(vec![self.mesh2d_pipeline.view_layout.clone()]),
+            primitive: PrimitiveState {
+                front_face: FrontFace::Ccw,
+                cull_mode: None,
+                unclipped_depth: false,
+                polygon_mode: PolygonMode::Fill,
+                conservative: false,
+                topology: PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+            },
+            depth_stencil: None,
+            multisample: MultisampleState {
+                count: key.msaa_samples(),
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            label: Some("ball_instance_pipeline".into()),
+        }
+    }
+}
+
+fn queue_ball_instances(
+    draw_functions: Res<DrawFunctions<Transparent2d>>,
+    pipeline: Res<BallInstancePipeline>,
+    mut pipelines: ResMut<SpecializedRenderPipelines<BallInstancePipeline>>,
+    mut cache: ResMut<PipelineCache>,
+    msaa: Res<Msaa>,
+    instances: Query<(Entity, &BallInstances)>,
+    mut views: Query<&mut RenderPhase<Transparent2d>>,
+) {
+    let draw_function = match draw_functions.read().get_id::<DrawBallInstances>() {
+        Some(draw_function) => draw_function,
+        None => return,
+    };
+    let key = Mesh2dPipelineKey::from_msaa_samples(msaa.samples);
+    let pipeline = pipelines.specialize(&mut cache, &pipeline, key);
+
+    for mut phase in views.iter_mut() {
+        for (entity, instances) in instances.iter() {
+            phase.add(Transparent2d {
+                sort_key: FloatOrd(instances.z),
+                entity,
+                pipeline,
+                draw_function,
+                batch_range: None,
+            });
+        }
+    }
+}
+
+type DrawBallInstances = (SetItemPipeline, SetMesh2dViewBindGroup<0>, DrawInstancedCircles);
+
+struct DrawInstancedCircles;
+
+impl EntityRenderCommand for DrawInstancedCircles {
+    type Param = (SRes<BallInstancePipeline>, SQuery<Read<InstanceBuffer>>);
+
+    #[inline]
+    fn render<'w>(
+        _view: Entity,
+        item: Entity,
+        (pipeline, buffers): SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        let instances = match buffers.get_inner(item) {
+            Ok(instances) => instances,
+            Err(_) => return RenderCommandResult::Failure,
+        };
+        let pipeline = pipeline.into_inner();
+        pass.set_vertex_buffer(0, pipeline.circle.slice(..));
+        pass.set_vertex_buffer(1, instances.buffer.slice(..));
+        pass.draw(0..pipeline.vertices, 0..instances.len);
+        RenderCommandResult::Success
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shapes_of_instanced_balls_are_hidden() {
+        let triangles = circle_triangles(4);
+        assert_eq!(triangles.len(), 12);
+        assert!(triangles[1].abs_diff_eq(Vec2::X, 1e-6));
+        assert!(triangles[2].abs_diff_eq(Vec2::Y, 1e-6));
+        assert!(triangles[11].abs_diff_eq(Vec2::X, 1e-6));
+
+        let mut world = World::new();
+        let mut stage = SystemStage::single_threaded().with_system(hide_ball_shapes);
+        let style = BallStyle::fill(Color::RED);
+        let ball = |x: f32| BallBundle::builder(5.).with_style(style).with_position(Vec2::new(x, 0.));
+        let circle = world.spawn().insert_bundle(ball(0.).build()).id();
+        let sprite = world.spawn().insert_bundle(ball(20.).with_visual(BallVisual::Sprite(1)).build()).id();
+        let boxed = world.spawn().insert_bundle(ball(40.).build()).insert(BoxBody { half_extents: Vec2::ONE }).id();
+        stage.run(&mut world);
+
+        let visible = |world: &World, entity: Entity| world.get::<Visibility>(entity).unwrap().is_visible;
+        assert!(!visible(&world, circle));
+        assert!(visible(&world, sprite));
+        assert!(visible(&world, boxed));
+
+        // reused from the pool, the ball is shown again
+        world.get_mut::<Visibility>(circle).unwrap().is_visible = true;
+        stage.run(&mut world);
+        assert!(!visible(&world, circle));
+    }
+}
@@ -0,0 +1,217 @@
+use std::time::Instant;
+
+use bevy::prelude::*;
+
+use crate::*;
+
+/// Numerical method which advances the position and velocity of balls.
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Integrator {
+    /// Updates velocity first, then moves using the new velocity. Cheap and
+    /// stable, but only first order accurate.
+    SemiImplicitEuler,
+    /// Velocity Verlet, second order accurate and conserves energy well.
+    Verlet,
+    /// Classic fourth order Runge-Kutta, the most accurate and most expensive.
+    Rk4,
+}
+
+impl Default for Integrator {
+    fn default() -> Self { Self::SemiImplicitEuler }
+}
+
+/// Linear drag which slows down all balls, as a fraction of the velocity that
+/// is lost per second.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Drag(pub f32);
+
+/// Accelerations which are evaluated by the integrator itself, since they may
+/// depend on the state of a ball.
+#[derive(Clone, Copy, Debug)]
+pub struct ForceField {
+    /// Constant acceleration, like gravity and the accumulated `Force`.
+    pub acceleration: Vec2,
+    pub drag: f32,
+}
+
+impl ForceField {
+    #[inline]
+    pub fn acceleration(&self, _position: Vec2, velocity: Vec2) -> Vec2 {
+        self.acceleration - velocity * self.drag
+    }
+}
+
+/// Advance `position` and `velocity` by `dt` seconds, returns the new position
+/// and velocity.
+#[inline]
+pub fn integrate(integrator: Integrator, field: &ForceField, dt: f32, position: Vec2, velocity: Vec2) -> (Vec2, Vec2) {
+    match integrator {
+        Integrator::SemiImplicitEuler => {
+            let velocity = velocity + field.acceleration(position, velocity) * dt;
+            (position + velocity * dt, velocity)
+        }
+        Integrator::Verlet => {
+            let acceleration = field.acceleration(position, velocity);
+            let new_position = position + velocity * dt + acceleration * (0.5 * dt * dt);
+            // drag depends on velocity, so estimate it before the final update
+            let estimate = velocity + acceleration * dt;
+            let new_acceleration = field.acceleration(new_position, estimate);
+            (new_position, velocity + (acceleration + new_acceleration) * (0.5 * dt))
+        }
+        Integrator::Rk4 => {
+            let k1_x = velocity;
+            let k1_v = field.acceleration(position, velocity);
+            let k2_x = velocity + k1_v * (0.5 * dt);
+            let k2_v = field.acceleration(position + k1_x * (0.5 * dt), k2_x);
+            let k3_x = velocity + k2_v * (0.5 * dt);
+            let k3_v = field.acceleration(position + k2_x * (0.5 * dt), k3_x);
+            let k4_x = velocity + k3_v * dt;
+            let k4_v = field.acceleration(position + k3_x * dt, k4_x);
+
+            (
+                position + (k1_x + 2. * k2_x + 2. * k3_x + k4_x) * (dt / 6.),
+                velocity + (k1_v + 2. * k2_v + 2. * k3_v + k4_v) * (dt / 6.),
+            )
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn apply_velocity(
+    integrator: Res<Integrator>,
+    gravity: Res<Gravity>,
+    drag: Res<Drag>,
+    slow_motion: Res<SlowMotion>,
+    kinds: Option<Res<BallKinds>>,
+    timings: Option<ResMut<PhysicsTimings>>,
+    lod_settings: Option<Res<LodSettings>>,
+    edge: Res<EdgeCollider>,
+    mut query: Query<(
+        &mut Transform,
+        &mut Velocity,
+        &mut Force,
+        &mut Impulse,
+        &Ball,
+        Option<&mut Lod>,
+        Option<&Frozen>,
+        Option<&Dormant>,
+        Option<&Kind>,
+    )>,
+) {
+    let start = Instant::now();
+    let lod_due = lod_settings.map_or(true, |settings| settings.is_due());
+    for (mut transform, mut velocity, mut force, mut impulse, ball, lod, frozen, dormant, kind) in query.iter_mut() {
+        // frozen balls don't build up momentum for when they are unfrozen
+        if frozen.is_some() {
+            velocity.0 = Vec2::ZERO;
+            force.0 = Vec2::ZERO;
+            impulse.0 = Vec2::ZERO;
+            continue;
+        }
+        // time stands still in inactive chunks, forces on dormant balls are
+        // lost
+        if dormant.is_some() {
+            force.0 = Vec2::ZERO;
+            impulse.0 = Vec2::ZERO;
+            continue;
+        }
+
+        // balls within the slow motion region advance by a smaller step
+        let position = transform.translation.truncate();
+        let mut dt = TIMESTEP * slow_motion.time_scale_at(position);
+
+        if let Some(mut lod) = lod {
+            match lod.step(dt, lod_due) {
+                Some(step) => dt = step,
+                None => {
+                    // keep the effect of forces for when the ball is moved
+                    impulse.0 += force.0 * dt;
+                    force.0 = Vec2::ZERO;
+                    continue;
+                }
+            }
+        }
+
+        // accumulate all forces which are evaluated during integration
+        let gravity = if BallKinds::has_gravity(kinds.as_deref(), kind) { gravity.0 } else { Vec2::ZERO };
+        let field = ForceField {
+            acceleration: gravity + force.0 / ball.mass,
+            drag: drag.0,
+        };
+        velocity.0 += impulse.0 / ball.mass;
+
+        let (mut new_position, new_velocity) = integrate(*integrator, &field, dt, position, velocity.0);
+        // a ball which catches up on skipped ticks could otherwise end up far
+        // beyond a wall, it stops at the wall and bounces off of it instead
+        if dt > TIMESTEP {
+            new_position = edge.bounds.shrunk(ball.radius).clamp_point(new_position);
+        }
+        // resting balls are not marked as changed
+        if new_position != position {
+            transform.translation.x = new_position.x;
+            transform.translation.y = new_position.y;
+        }
+        velocity.0 = new_velocity;
+
+        force.0 = Vec2::ZERO;
+        impulse.0 = Vec2::ZERO;
+    }
+    if let Some(mut timings) = timings {
+        timings.record_stage(PhysicsSystem::Integrate, start.elapsed());
+    }
+}
+
+// Balls which are neither frozen nor dormant.
+type Moving = (Without<Frozen>, Without<Dormant>);
+
+/// Turn balls by their spin.
+pub fn apply_spin(
+    slow_motion: Res<SlowMotion>,
+    mut query: Query<(&mut Transform, &AngularVelocity), Moving>,
+) {
+    for (mut transform, spin) in query.iter_mut() {
+        if spin.0 == 0. {
+            continue;
+        }
+        let dt = TIMESTEP * slow_motion.time_scale_at(transform.translation.truncate());
+        transform.rotate(strict::rotation_z(spin.0 * dt));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn simulate(integrator: Integrator, field: ForceField, steps: usize) -> (Vec2, Vec2) {
+        let (mut position, mut velocity) = (Vec2::ZERO, Vec2::new(10., 20.));
+        for _ in 0..steps {
+            (position, velocity) = integrate(integrator, &field, TIMESTEP, position, velocity);
+        }
+        (position, velocity)
+    }
+
+    #[test]
+    fn second_order_integrators_are_exact_under_gravity() {
+        let field = ForceField { acceleration: Vec2::new(0., -400.), drag: 0. };
+        let t = 120. * TIMESTEP;
+        let expected = Vec2::new(10., 20.) * t + field.acceleration * (0.5 * t * t);
+
+        for integrator in [Integrator::Verlet, Integrator::Rk4] {
+            let (position, _) = simulate(integrator, field, 120);
+            assert!(position.abs_diff_eq(expected, 1e-2), "{:?}: {} != {}", integrator, position, expected);
+        }
+
+        // semi-implicit euler overshoots by half a step of gravity each tick
+        let (position, _) = simulate(Integrator::SemiImplicitEuler, field, 120);
+        assert!(!position.abs_diff_eq(expected, 1e-2));
+    }
+
+    #[test]
+    fn rk4_follows_exponential_drag() {
+        let field = ForceField { acceleration: Vec2::ZERO, drag: 2. };
+        let (_, velocity) = simulate(Integrator::Rk4, field, 120);
+        let expected = Vec2::new(10., 20.) * (-2f32).exp();
+        assert!(velocity.abs_diff_eq(expected, 1e-4), "{} != {}", velocity, expected);
+    }
+}
@@ -0,0 +1,223 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use bevy::tasks::TaskPool;
+
+use crate::*;
+
+/// Resolves collisions of separate islands in parallel when inserted as a
+/// resource. An island is a group of balls which are connected by candidate
+/// pairs, so islands never share a ball and can be resolved without locking.
+/// Islands give the exact same results as resolving all pairs in order.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CollisionIslands;
+
+/// Disjoint sets of indices, with path halving and union by rank.
+pub struct UnionFind {
+    parent: Vec<usize>,
+    rank: Vec<u8>,
+}
+
+impl UnionFind {
+    pub fn new(len: usize) -> Self {
+        Self {
+            parent: (0..len).collect(),
+            rank: vec![0; len],
+        }
+    }
+
+    /// Representative of the set which contains `x`.
+    #[inline]
+    pub fn find(&mut self, mut x: usize) -> usize {
+        while self.parent[x] != x {
+            self.parent[x] = self.parent[self.parent[x]];
+            x = self.parent[x];
+        }
+        x
+    }
+
+    /// Merge the sets which contain `a` and `b`.
+    #[inline]
+    pub fn union(&mut self, a: usize, b: usize) {
+        let a = self.find(a);
+        let b = self.find(b);
+        if a == b {
+            return;
+        }
+
+        match self.rank[a].cmp(&self.rank[b]) {
+            std::cmp::Ordering::Less => self.parent[a] = b,
+            std::cmp::Ordering::Greater => self.parent[b] = a,
+            std::cmp::Ordering::Equal => {
+                self.parent[b] = a;
+                self.rank[a] += 1;
+            }
+        }
+    }
+}
+
+// Copy of a ball's state, which is owned by the task resolving its island.
+struct IslandBall {
+    entity: Entity,
+    transform: Transform,
+    velocity: Velocity,
+    ball: Ball,
+}
+
+#[derive(Default)]
+struct Island {
+    balls: Vec<IslandBall>,
+    // pairs of indices into `balls`
+    pairs: Vec<[usize; 2]>,
+}
+
+impl Island {
+    // Check and resolve all pairs in order, returns the pairs which collided.
+    fn resolve(&mut self, model: CollisionModel) -> Vec<[usize; 2]> {
+        let mut collided = Vec::new();
+        for [a, b] in self.pairs.iter().copied() {
+            let [ball_a, ball_b] = pair_mut(&mut self.balls, a, b);
+            if separate_balls([
+                (&mut ball_a.transform, &ball_a.ball),
+                (&mut ball_b.transform, &ball_b.ball),
+            ]) {
+                collided.push([a, b]);
+            }
+        }
+
+        for [a, b] in collided.iter().copied() {
+            let [ball_a, ball_b] = pair_mut(&mut self.balls, a, b);
+            balls_bounce_after_collision(model, [
+                (&ball_a.transform, &mut ball_a.velocity, &ball_a.ball),
+                (&ball_b.transform, &mut ball_b.velocity, &ball_b.ball),
+            ]);
+        }
+        collided
+    }
+}
+
+#[inline]
+fn pair_mut<T>(slice: &mut [T], a: usize, b: usize) -> [&mut T; 2] {
+    debug_assert_ne!(a, b);
+    if a < b {
+        let (left, right) = slice.split_at_mut(b);
+        [&mut left[a], &mut right[0]]
+    } else {
+        let (left, right) = slice.split_at_mut(a);
+        [&mut right[0], &mut left[b]]
+    }
+}
+
+// Group the balls of all `pairs` in islands.
+fn build_islands(
+    pairs: &[[Entity; 2]],
+    query: &Query<(Entity, &mut Transform, &mut Velocity, &Ball)>,
+) -> Vec<Island> {
+    let mut index = HashMap::new();
+    let mut entities = Vec::new();
+    for entity in pairs.iter().flatten() {
+        index.entry(*entity).or_insert_with(|| {
+            entities.push(*entity);
+            entities.len() - 1
+        });
+    }
+
+    let mut sets = UnionFind::new(entities.len());
+    for [a, b] in pairs {
+        sets.union(index[a], index[b]);
+    }
+
+    let mut islands: Vec<Island> = Vec::new();
+    let mut island_of_set = HashMap::new();
+    let mut local = Vec::with_capacity(entities.len());
+    for (i, entity) in entities.iter().enumerate() {
+        let island = *island_of_set.entry(sets.find(i)).or_insert_with(|| {
+            islands.push(Island::default());
+            islands.len() - 1
+        });
+
+        let (_, transform, velocity, ball) = query.get(*entity).unwrap();
+        let balls = &mut islands[island].balls;
+        balls.push(IslandBall {
+            entity: *entity,
+            transform: *transform,
+            velocity: Velocity(velocity.0),
+            ball: Ball {
+                radius: ball.radius,
+                mass: ball.mass,
+                restitution: ball.restitution,
+            },
+        });
+        local.push((island, balls.len() - 1));
+    }
+
+    for [a, b] in pairs {
+        let (island, a) = local[index[a]];
+        let (_, b) = local[index[b]];
+        islands[island].pairs.push([a, b]);
+    }
+    islands
+}
+
+/// Check and resolve all `pairs`, spreading the islands over the tasks of
+/// `pool`.
+pub fn resolve_islands(
+    pool: &TaskPool,
+    pairs: &[[Entity; 2]],
+    model: CollisionModel,
+    collided: &mut EventWriter<BallCollided>,
+    query: &mut Query<(Entity, &mut Transform, &mut Velocity, &Ball)>,
+) {
+    let islands = build_islands(pairs, query);
+    if islands.is_empty() {
+        return;
+    }
+
+    let mut batches: Vec<Vec<Island>> = Vec::new();
+    batches.resize_with(pool.thread_num().clamp(1, islands.len()), Vec::new);
+    let len = batches.len();
+    for (i, island) in islands.into_iter().enumerate() {
+        batches[i % len].push(island);
+    }
+
+    let results = pool.scope(|scope| {
+        for batch in batches {
+            scope.spawn(async move {
+                batch.into_iter()
+                    .map(|mut island| {
+                        let collided = island.resolve(model);
+                        (island, collided)
+                    })
+                    .collect::<Vec<_>>()
+            });
+        }
+    });
+
+    for (island, island_collided) in results.into_iter().flatten() {
+        for [a, b] in island_collided {
+            collided.send(BallCollided(island.balls[a].entity, island.balls[b].entity));
+        }
+        for ball in island.balls {
+            let (_, mut transform, mut velocity, _) = query.get_mut(ball.entity).unwrap();
+            *transform = ball.transform;
+            velocity.0 = ball.velocity.0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn union_find_groups_sets() {
+        let mut sets = UnionFind::new(6);
+        sets.union(0, 1);
+        sets.union(2, 3);
+        sets.union(1, 3);
+
+        assert_eq!(sets.find(0), sets.find(2));
+        assert_ne!(sets.find(0), sets.find(4));
+        assert_ne!(sets.find(4), sets.find(5));
+    }
+}
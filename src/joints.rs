@@ -0,0 +1,159 @@
+use bevy::prelude::*;
+
+use crate::*;
+
+/// Adds bars which are pinned at their center, and are turned by the balls
+/// which hit them.
+pub struct PinwheelPlugin {
+    anchors: Vec<Vec2>,
+    length: f32,
+}
+
+impl PinwheelPlugin {
+    pub fn with_anchors(anchors: Vec<Vec2>) -> Self {
+        Self { anchors, length: 160. }
+    }
+}
+
+impl Default for PinwheelPlugin {
+    fn default() -> Self {
+        Self::with_anchors(vec![
+            Vec2::new(-WIDTH / 4., 0.),
+            Vec2::new(WIDTH / 4., 0.),
+        ])
+    }
+}
+
+impl Plugin for PinwheelPlugin {
+    fn build(&self, app: &mut App) {
+        let anchors = self.anchors.clone();
+        let length = self.length;
+        app.add_startup_system(move |mut cmd: Commands| {
+            let shape = BallShape::Box { aspect: PINWHEEL_ASPECT };
+            let radius = length / 2.;
+            for anchor in &anchors {
+                let mut entity = BallBundle::builder(radius)
+                    .with_style(BallStyle::fill(Color::GRAY))
+                    .with_mass(MASS_MODEL.mass(radius) * PINWHEEL_DENSITY)
+                    .with_position(*anchor)
+                    .spawn(&mut cmd);
+                shape.apply(&mut entity, radius);
+                entity.insert(PinJoint::new(*anchor));
+            }
+        });
+    }
+}
+
+// Height of the bar of a pinwheel, relative to its length.
+const PINWHEEL_ASPECT: f32 = 0.08;
+
+// Mass of a pinwheel relative to a ball of the same radius, so balls don't
+// spin it up too quickly.
+const PINWHEEL_DENSITY: f32 = 0.5;
+
+/// Holds a point of a body at a fixed point of the world, around which the
+/// body turns freely when it has an `AngularVelocity`. Balls which hit the
+/// body bounce off of it as if it were pinned, and turn it.
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+pub struct PinJoint {
+    /// Point of the world the body is pinned to.
+    pub anchor: Vec2,
+    /// Pinned point of the body, relative to its center and turned along
+    /// with it.
+    pub offset: Vec2,
+}
+
+impl PinJoint {
+    /// Pin the center of a body to `anchor`.
+    pub fn new(anchor: Vec2) -> Self {
+        Self { anchor, offset: Vec2::ZERO }
+    }
+
+    /// Pin the point of the body at `offset` from its center instead.
+    #[allow(dead_code)]
+    pub fn with_offset(self, offset: Vec2) -> Self {
+        Self { offset, ..self }
+    }
+
+    /// Center of the pinned body when it is turned by `rotation`.
+    #[inline]
+    pub fn center(&self, rotation: Quat) -> Vec2 {
+        self.anchor - (rotation * self.offset.extend(0.)).truncate()
+    }
+}
+
+/// Move pinned bodies back onto their anchor after they moved and turned,
+/// and keep only the part of their velocity which turns them around it.
+pub fn solve_pin_joints(mut query: Query<(&PinJoint, &mut Transform, &mut Velocity, Option<&AngularVelocity>)>) {
+    for (joint, mut transform, mut velocity, spin) in query.iter_mut() {
+        let center = joint.center(transform.rotation);
+        transform.translation = center.extend(transform.translation.z);
+        velocity.0 = (center - joint.anchor).perp() * spin.map_or(0., |spin| spin.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pinned_bars_turn_around_their_anchor() {
+        let (mut world, entities) = headless_world(1673, 0, Bounds::new(Vec2::ZERO, 400., 300.));
+        assert!(entities.is_empty());
+
+        // a bar pinned at its left end, and a ball which drops onto its right
+        // end
+        let anchor = Vec2::new(-40., 0.);
+        let bar = world.spawn()
+            .insert_bundle(BallBundle::builder(50.).with_style(BallStyle::fill(Color::GRAY)).with_mass(10.).build())
+            .insert(BoxBody { half_extents: Vec2::new(40., 4.) })
+            .insert(AngularVelocity::default())
+            .insert(PinJoint::new(anchor).with_offset(Vec2::new(-40., 0.)))
+            .id();
+        let ball = world.spawn()
+            .insert_bundle(BallBundle::builder(5.).with_mass(1.).with_velocity(Vec2::new(0., -100.)).with_position(Vec2::new(30., 12.)).build())
+            .id();
+
+        let mut stage = physics_stage();
+        for _ in 0..30 {
+            stage.run(&mut world);
+        }
+
+        // the bar swings down clockwise, and its pinned end stays put
+        let spin = world.get::<AngularVelocity>(bar).unwrap().0;
+        assert!(spin < 0., "{}", spin);
+        let transform = world.get::<Transform>(bar).unwrap();
+        let pinned = transform.translation.truncate() + (transform.rotation * Vec3::new(-40., 0., 0.)).truncate();
+        assert!(pinned.abs_diff_eq(anchor, 1e-3), "{}", pinned);
+        assert!(world.get::<Velocity>(ball).unwrap().0.y > -100.);
+    }
+
+    #[test]
+    fn overlapping_pinned_bars_stay_on_their_anchors() {
+        let (mut world, entities) = headless_world(1673, 0, Bounds::new(Vec2::ZERO, 400., 300.));
+        assert!(entities.is_empty());
+
+        // two crossed bars, pinned at their centers
+        let mut pinned_bar = |anchor: Vec2, half_extents: Vec2| world.spawn()
+            .insert_bundle(BallBundle::builder(40.).with_mass(10.).with_position(anchor).build())
+            .insert(BoxBody { half_extents })
+            .insert(AngularVelocity::default())
+            .insert(PinJoint::new(anchor))
+            .id();
+        let bars = [
+            pinned_bar(Vec2::ZERO, Vec2::new(40., 4.)),
+            pinned_bar(Vec2::new(10., 5.), Vec2::new(4., 40.)),
+        ];
+
+        // separating the bars would push them off their anchors until the
+        // joints are solved, so only the narrow phase is run
+        let mut stage = SystemStage::single_threaded()
+            .with_system(find_candidate_pairs.label(PhysicsSystem::BroadPhase))
+            .with_system(check_candidate_pairs.after(PhysicsSystem::BroadPhase));
+        stage.run(&mut world);
+        assert_eq!(world.resource::<PairBuffer>().shaped.len(), 1);
+        for (bar, anchor) in bars.into_iter().zip([Vec2::ZERO, Vec2::new(10., 5.)]) {
+            assert_eq!(world.get::<Transform>(bar).unwrap().translation.truncate(), anchor);
+        }
+    }
+}
@@ -0,0 +1,504 @@
+use std::collections::HashSet;
+
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::*;
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::rngs::StdRng;
+#[cfg(feature = "scene")]
+use serde::{Deserialize, Serialize};
+
+use crate::*;
+
+/// Adds the behaviors of the kinds of balls, which are registered in the
+/// `BallKinds` resource.
+pub struct KindsPlugin {
+    split_speed: f32,
+}
+
+impl KindsPlugin {
+    /// Balls which split do so when they collide at a relative speed of at
+    /// least `split_speed`.
+    pub fn with_split_speed(split_speed: f32) -> Self {
+        Self { split_speed }
+    }
+}
+
+impl Default for KindsPlugin {
+    fn default() -> Self { Self::with_split_speed(150.) }
+}
+
+impl Plugin for KindsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(SplitSpeed(self.split_speed))
+            .init_resource::<KindCounts>()
+            .add_system_to_stage(CoreStage::PreUpdate, count_kinds)
+            .add_system_to_stage(PhysicsStage, merge_and_split.after(PhysicsSystem::Resolve))
+            .add_system_to_stage(CoreStage::PostUpdate, emit_heat);
+    }
+}
+
+struct SplitSpeed(f32);
+
+/// Behaviors of a kind of ball.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "scene", derive(Serialize, Deserialize), serde(default))]
+pub struct KindFlags {
+    pub affected_by_gravity: bool,
+    /// Colliding balls of this kind merge into a single ball, when the merged
+    /// ball is not larger than the largest radius of the kind.
+    pub merges: bool,
+    /// Balls of this kind split in two when they collide hard enough, when
+    /// the halves are not smaller than the smallest radius of the kind.
+    pub splits: bool,
+    /// Balls of this kind stay hot, when balls have a temperature.
+    pub emits_heat: bool,
+}
+
+impl Default for KindFlags {
+    fn default() -> Self {
+        Self {
+            affected_by_gravity: true,
+            merges: false,
+            splits: false,
+            emits_heat: false,
+        }
+    }
+}
+
+/// Kind of ball, which determines how balls of the kind are spawned and how
+/// they behave.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "scene", derive(Serialize, Deserialize), serde(default))]
+pub struct BallKind {
+    pub name: String,
+    /// Range of the radius of spawned balls.
+    pub radius: [f32; 2],
+    /// Density of the configured mass model, or `None` to use it as is.
+    pub density: Option<f32>,
+    pub restitution: f32,
+    /// Fill color, as linear rgba, or `None` to use the palette.
+    pub color: Option<[f32; 4]>,
+    /// Relative amount of randomly spawned balls of this kind.
+    pub weight: f32,
+    /// Maximum amount of balls of this kind which are spawned randomly or by
+    /// splitting, or `None` for no limit.
+    pub cap: Option<usize>,
+    /// Shape of the balls, which are circles by default.
+    pub body: BallShape,
+    pub flags: KindFlags,
+}
+
+impl Default for BallKind {
+    fn default() -> Self {
+        Self {
+            name: "ball".to_string(),
+            radius: [*BALL_RADIUS.start(), *BALL_RADIUS.end()],
+            density: None,
+            restitution: 1.,
+            color: None,
+            weight: 1.,
+            cap: None,
+            body: BallShape::Circle,
+            flags: KindFlags::default(),
+        }
+    }
+}
+
+impl BallKind {
+    #[inline]
+    pub fn mass_model(&self) -> MassModel {
+        match self.density {
+            Some(density) => MASS_MODEL.with_density(density),
+            None => MASS_MODEL,
+        }
+    }
+
+    /// Fill color of balls of this kind, `palette` is used when the kind has
+    /// no color of its own.
+    #[inline]
+    pub fn color(&self, palette: Color) -> Color {
+        match self.color {
+            Some([r, g, b, a]) => Color::rgba_linear(r, g, b, a),
+            None => palette,
+        }
+    }
+}
+
+/// Index of the kind of a ball within `BallKinds`.
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Reflect)]
+#[reflect(Component)]
+pub struct Kind(pub usize);
+
+/// Registry of the kinds of balls, there is always at least one kind.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BallKinds {
+    kinds: Vec<BallKind>,
+}
+
+impl BallKinds {
+    #[cfg_attr(not(feature = "scene"), allow(dead_code))]
+    pub fn new(kinds: Vec<BallKind>) -> Result<Self, String> {
+        if kinds.is_empty() {
+            return Err("there are no kinds".to_string());
+        }
+        for (i, kind) in kinds.iter().enumerate() {
+            if kinds[..i].iter().any(|other| other.name == kind.name) {
+                return Err(format!("kind `{}` is defined more than once", kind.name));
+            }
+            let [min, max] = kind.radius;
+            if !(min > 0. && min <= max && max.is_finite()) {
+                return Err(format!("kind `{}` has an invalid radius range {:?}", kind.name, kind.radius));
+            }
+            // the broad phase relies on the largest radius of any ball
+            if max > *BALL_RADIUS.end() {
+                return Err(format!("kind `{}` has a radius larger than {}", kind.name, BALL_RADIUS.end()));
+            }
+            if !(0. ..=1.).contains(&kind.restitution) {
+                return Err(format!("kind `{}` has a restitution of {}", kind.name, kind.restitution));
+            }
+            if kind.weight < 0. || !kind.weight.is_finite() {
+                return Err(format!("kind `{}` has a weight of {}", kind.name, kind.weight));
+            }
+        }
+        if kinds.iter().all(|kind| kind.weight == 0.) {
+            return Err("all kinds have a weight of 0".to_string());
+        }
+        Ok(Self { kinds })
+    }
+
+    /// Load the kinds from a RON file, holding a list of kinds.
+    #[cfg(feature = "scene")]
+    pub fn load(path: impl AsRef<std::path::Path>) -> Result<Self, SceneError> {
+        let text = std::fs::read_to_string(path).map_err(SceneError::Io)?;
+        let kinds = ron::from_str(&text).map_err(SceneError::Ron)?;
+        Self::new(kinds).map_err(SceneError::Invalid)
+    }
+
+    #[inline]
+    pub fn get(&self, kind: Kind) -> &BallKind {
+        &self.kinds[kind.0.min(self.kinds.len() - 1)]
+    }
+
+    /// Kind with the given name.
+    #[cfg_attr(not(feature = "scene"), allow(dead_code))]
+    #[inline]
+    pub fn find(&self, name: &str) -> Option<Kind> {
+        self.kinds.iter().position(|kind| kind.name == name).map(Kind)
+    }
+
+    #[cfg_attr(not(feature = "scene"), allow(dead_code))]
+    #[inline]
+    pub fn as_slice(&self) -> &[BallKind] { &self.kinds }
+
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = (Kind, &BallKind)> {
+        self.kinds.iter().enumerate().map(|(i, kind)| (Kind(i), kind))
+    }
+
+    /// Random kind, chosen by the weight of each kind which has not reached
+    /// its cap. Returns `None` when all kinds are at their cap.
+    pub fn choose(&self, rng: &mut StdRng, counts: &KindCounts) -> Option<Kind> {
+        if self.kinds.len() == 1 {
+            return Some(Kind(0)).filter(|kind| counts.has_room(self, *kind));
+        }
+        let weights = self.iter().map(|(kind, ball_kind)| {
+            if counts.has_room(self, kind) { ball_kind.weight } else { 0. }
+        });
+        WeightedIndex::new(weights)
+            .ok()
+            .map(|weights| Kind(weights.sample(rng)))
+    }
+
+    /// A ball of `kind` with `radius`.
+    #[inline]
+    pub fn ball(&self, kind: Kind, radius: f32) -> Ball {
+        let kind = self.get(kind);
+        Ball {
+            restitution: kind.restitution,
+            ..Ball::new(radius, kind.mass_model())
+        }
+    }
+
+    /// Indicates if gravity acts on balls of `kind`, balls without a kind are
+    /// always affected.
+    #[inline]
+    pub fn has_gravity(kinds: Option<&Self>, kind: Option<&Kind>) -> bool {
+        match (kinds, kind) {
+            (Some(kinds), Some(kind)) => kinds.get(*kind).flags.affected_by_gravity,
+            _ => true,
+        }
+    }
+}
+
+impl Default for BallKinds {
+    fn default() -> Self {
+        Self { kinds: vec![BallKind::default()] }
+    }
+}
+
+/// Amount of live balls of each kind. Recounted at the start of each frame,
+/// and kept up to date by the systems which enforce the caps of the kinds.
+#[derive(Default)]
+pub struct KindCounts {
+    counts: Vec<usize>,
+}
+
+impl KindCounts {
+    #[inline]
+    pub fn get(&self, kind: Kind) -> usize {
+        self.counts.get(kind.0).copied().unwrap_or(0)
+    }
+
+    #[inline]
+    pub fn add(&mut self, kind: Kind) {
+        if self.counts.len() <= kind.0 {
+            self.counts.resize(kind.0 + 1, 0);
+        }
+        self.counts[kind.0] += 1;
+    }
+
+    #[inline]
+    pub fn remove(&mut self, kind: Kind) {
+        if let Some(count) = self.counts.get_mut(kind.0) {
+            *count = count.saturating_sub(1);
+        }
+    }
+
+    /// Indicates if another ball of `kind` stays within its cap.
+    #[inline]
+    pub fn has_room(&self, kinds: &BallKinds, kind: Kind) -> bool {
+        match kinds.get(kind).cap {
+            Some(cap) => self.get(kind) < cap,
+            None => true,
+        }
+    }
+
+    /// Count of each kind, like `ball 990, rock 10`.
+    pub fn summary(&self, kinds: &BallKinds) -> String {
+        kinds.iter()
+            .map(|(kind, ball_kind)| format!("{} {}", ball_kind.name, self.get(kind)))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+fn count_kinds(mut counts: ResMut<KindCounts>, query: Query<&Kind, With<Ball>>) {
+    counts.counts.clear();
+    for kind in query.iter() {
+        counts.add(*kind);
+    }
+}
+
+// Change the radius of a ball, and with it its mass and its shape.
+fn resize_ball(cmd: &mut Commands, entity: Entity, kinds: &BallKinds, kind: Kind, ball: &mut Ball, radius: f32) {
+    *ball = kinds.ball(kind, radius);
+    kinds.get(kind).body.apply(&mut cmd.entity(entity), radius);
+}
+
+// Fraction of the collision speed with which the halves of a split ball move
+// apart.
+const SPLIT_SPREAD: f32 = 0.25;
+
+// Merge or split the balls of kinds which do so when they collide. Each ball
+// merges or splits at most once per tick.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn merge_and_split(
+    mut cmd: Commands,
+    mut pool: ResMut<BallPool>,
+    mut changed: Local<HashSet<Entity>>,
+    mut counts: ResMut<KindCounts>,
+    kinds: Res<BallKinds>,
+    split_speed: Res<SplitSpeed>,
+    mut collided: EventReader<BallCollided>,
+    mut query: Query<(Entity, &Kind, &mut Ball, &mut Transform, &mut Velocity, &DrawMode)>,
+) {
+    changed.clear();
+    for BallCollided(a, b) in collided.iter() {
+        if changed.contains(a) || changed.contains(b) {
+            continue;
+        }
+        let [mut first, mut second] = match query.get_many_mut([*a, *b]) {
+            Ok(balls) => balls,
+            Err(_) => continue,
+        };
+        // the larger ball merges the other into itself, or splits
+        if first.2.radius < second.2.radius {
+            std::mem::swap(&mut first, &mut second);
+        }
+        let (entity, kind, mut ball, mut transform, mut velocity, draw_mode) = first;
+        let (other, other_kind, other_ball, other_transform, other_velocity, _) = second;
+        let flags = kinds.get(*kind).flags;
+        let [min_radius, max_radius] = kinds.get(*kind).radius;
+
+        if kind == other_kind && flags.merges {
+            let radius = (ball.radius * ball.radius + other_ball.radius * other_ball.radius).sqrt();
+            if radius > max_radius {
+                continue;
+            }
+
+            // the merged ball keeps the momentum and the center of mass, its
+            // mass isn't the sum of both under every mass model
+            let momentum = velocity.0 * ball.mass + other_velocity.0 * other_ball.mass;
+            let mass = ball.mass + other_ball.mass;
+            transform.translation = (transform.translation * ball.mass + other_transform.translation * other_ball.mass) / mass;
+            resize_ball(&mut cmd, entity, &kinds, *kind, &mut ball, radius);
+            velocity.0 = momentum / ball.mass;
+
+            pool.release(&mut cmd, other, &other_ball);
+            counts.remove(*kind);
+            changed.insert(entity);
+            changed.insert(other);
+            continue;
+        }
+
+        let speed = (velocity.0 - other_velocity.0).length();
+        let radius = ball.radius * std::f32::consts::FRAC_1_SQRT_2;
+        if !flags.splits || speed < split_speed.0 || radius < min_radius || !counts.has_room(&kinds, *kind) {
+            continue;
+        }
+
+        // the halves touch, side by side across the direction of movement,
+        // and move apart while keeping the momentum of the ball
+        let across = velocity.0.perp().try_normalize().unwrap_or(Vec2::X);
+        let offset = across * radius;
+        let spread = across * speed * SPLIT_SPREAD;
+        let position = transform.translation.truncate();
+        let momentum = velocity.0 * ball.mass;
+        resize_ball(&mut cmd, entity, &kinds, *kind, &mut ball, radius);
+        transform.translation = (position + offset).extend(transform.translation.z);
+        velocity.0 = momentum / (2. * ball.mass);
+
+        // reuse a pooled ball for the other half, its shape is rebuilt for
+        // the new radius
+        let mass_model = kinds.get(*kind).mass_model();
+        let half = pool.acquire(&mut cmd, mass_model, velocity.0 - spread, position - offset);
+        let mut half = match half {
+            Some((half, _)) => {
+                let mut half = cmd.entity(half);
+                half.insert(*ball).insert(*kind).insert(*draw_mode);
+                half
+            }
+            None => BallBundle::builder(radius)
+                .with_kind(&kinds, *kind)
+                .with_draw_mode(*draw_mode)
+                .with_velocity(velocity.0 - spread)
+                .with_position(position - offset)
+                .spawn(&mut cmd),
+        };
+        kinds.get(*kind).body.apply(&mut half, radius);
+        counts.add(*kind);
+        velocity.0 += spread;
+        changed.insert(entity);
+    }
+}
+
+// Keep the balls of kinds which emit heat hot.
+fn emit_heat(kinds: Res<BallKinds>, mut query: Query<(&Kind, &mut Heat)>) {
+    for (kind, mut heat) in query.iter_mut() {
+        if kinds.get(*kind).flags.emits_heat && heat.0 != 1. {
+            heat.0 = 1.;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::event::Events;
+    use bevy::ecs::system::CommandQueue;
+
+    use super::*;
+
+    #[test]
+    fn kinds_are_validated_and_chosen_by_weight() {
+        let rock = BallKind {
+            name: "rock".to_string(),
+            radius: [4., 8.],
+            density: Some(3.),
+            restitution: 0.5,
+            weight: 0.,
+            ..default()
+        };
+        let kinds = BallKinds::new(vec![BallKind::default(), rock.clone()]).unwrap();
+        assert_eq!(kinds.find("rock"), Some(Kind(1)));
+        let ball = kinds.ball(Kind(1), 4.);
+        assert_eq!(ball.mass, MASS_MODEL.with_density(3.).mass(4.));
+        assert_eq!(ball.restitution, 0.5);
+
+        // kinds without weight are never chosen at random
+        let mut rng = SimRng::new(Some(1662));
+        let counts = KindCounts::default();
+        assert!((0..100).all(|_| kinds.choose(&mut rng, &counts) == Some(Kind(0))));
+
+        assert!(BallKinds::new(Vec::new()).is_err());
+        assert!(BallKinds::new(vec![rock.clone()]).is_err());
+        assert!(BallKinds::new(vec![rock.clone(), BallKind { weight: 1., ..rock.clone() }]).is_err());
+        assert!(BallKinds::new(vec![BallKind { radius: [8., 4.], ..default() }]).is_err());
+        assert!(BallKinds::new(vec![BallKind { radius: [1., BALL_RADIUS.end() + 1.], ..default() }]).is_err());
+    }
+
+    #[test]
+    fn kinds_at_their_cap_are_not_chosen() {
+        let capped = |name: &str, cap| BallKind { name: name.to_string(), cap: Some(cap), ..default() };
+        let kinds = BallKinds::new(vec![capped("ball", 2), capped("rock", 1)]).unwrap();
+        let mut rng = SimRng::new(Some(1663));
+        let mut counts = KindCounts::default();
+
+        while let Some(kind) = kinds.choose(&mut rng, &counts) {
+            counts.add(kind);
+        }
+        assert_eq!(counts.summary(&kinds), "ball 2, rock 1");
+
+        counts.remove(Kind(1));
+        assert_eq!(kinds.choose(&mut rng, &counts), Some(Kind(1)));
+    }
+
+    #[test]
+    fn merges_and_splits_keep_the_momentum() {
+        let flags = KindFlags { merges: true, splits: true, ..default() };
+        let kinds = BallKinds::new(vec![
+            BallKind { radius: [2., 10.], flags: KindFlags { merges: false, ..flags }, ..default() },
+            BallKind { name: "drop".to_string(), radius: [2., 10.], weight: 0., flags, ..default() },
+        ]).unwrap();
+        let mut world = World::new();
+        let mut pool = BallPool::default();
+        let pooled = world.spawn().id();
+        let mut queue = CommandQueue::default();
+        pool.release(&mut Commands::new(&mut queue, &world), pooled, &Ball::new(3., MASS_MODEL));
+        queue.apply(&mut world);
+        world.insert_resource(pool);
+        world.insert_resource(KindCounts::default());
+        world.insert_resource(SplitSpeed(10.));
+        world.insert_resource(Events::<BallCollided>::default());
+
+        let spawn = |world: &mut World, kind, radius, velocity, position| world.spawn()
+            .insert_bundle(BallBundle::builder(radius).with_kind(&kinds, kind).with_velocity(velocity).with_position(position).build())
+            .insert(kind)
+            .id();
+        let splits = spawn(&mut world, Kind(0), 8., Vec2::new(100., 0.), Vec2::ZERO);
+        let wall = spawn(&mut world, Kind(0), 2., Vec2::new(-100., 0.), Vec2::new(9., 0.));
+        let merges = spawn(&mut world, Kind(1), 4., Vec2::new(0., 50.), Vec2::new(0., 50.));
+        let merged = spawn(&mut world, Kind(1), 3., Vec2::new(0., -20.), Vec2::new(0., 56.));
+        world.insert_resource(kinds);
+        let momentum = |world: &mut World| world.query::<(&Ball, &Velocity)>()
+            .iter(world)
+            .map(|(ball, velocity)| velocity.0 * ball.mass)
+            .fold(Vec2::ZERO, |sum, momentum| sum + momentum);
+        let before = momentum(&mut world);
+
+        let mut events = world.resource_mut::<Events<BallCollided>>();
+        events.send(BallCollided(splits, wall));
+        events.send(BallCollided(merges, merged));
+        SystemStage::single_threaded().with_system(merge_and_split).run(&mut world);
+
+        // the pooled ball became the other half of the split ball, and the
+        // merged ball took its place in the pool
+        assert_eq!(world.resource::<BallPool>().len(), 1);
+        assert_eq!(world.query::<&Ball>().iter(&world).count(), 4);
+        assert!(world.get::<Ball>(merged).is_none());
+        assert_eq!(world.get::<Kind>(pooled), Some(&Kind(0)));
+        assert_eq!(world.get::<Ball>(pooled).unwrap().radius, world.get::<Ball>(splits).unwrap().radius);
+        assert_eq!(world.get::<Ball>(merges).unwrap().radius, 5.);
+        let after = momentum(&mut world);
+        assert!(after.abs_diff_eq(before, 1e-2), "{} != {}", after, before);
+    }
+}
@@ -0,0 +1,255 @@
+use std::f32::consts::TAU;
+
+use bevy::prelude::*;
+use bevy_prototype_lyon::entity::Path;
+use bevy_prototype_lyon::prelude::*;
+
+use crate::*;
+
+/// Makes a few balls light up their surroundings, while the other balls cast
+/// shadows. The lit area of a light is found by casting rays from it against
+/// the `BallTree`, or against all balls before the first physics tick built
+/// it. Each light is drawn from a few points spread
+/// around its center, which softens the edges of the shadows. Lights which are
+/// despawned are replaced by other balls.
+pub struct BallLightsPlugin {
+    count: usize,
+    range: f32,
+}
+
+impl BallLightsPlugin {
+    pub fn with_count(count: usize) -> Self {
+        Self { count, range: 240. }
+    }
+}
+
+impl Default for BallLightsPlugin {
+    fn default() -> Self { Self::with_count(3) }
+}
+
+impl Plugin for BallLightsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(BallLights { count: self.count, range: self.range })
+            .add_system(assign_ball_lights)
+            .add_system_to_stage(CoreStage::PostUpdate, update_light_shapes);
+    }
+}
+
+pub struct BallLights {
+    /// Amount of balls which are lights.
+    pub count: usize,
+    /// Distance light reaches from the center of a light.
+    pub range: f32,
+}
+
+/// Ball which lights up its surroundings.
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+pub struct BallLight {
+    pub color: Color,
+}
+
+// Lit area of a light as seen from one of its sample points, drawn behind the
+// balls.
+#[derive(Component)]
+struct LightShape {
+    light: Entity,
+    sample: usize,
+}
+
+// Colors lights are given in turn.
+const LIGHT_COLORS: [Color; 4] = [
+    Color::rgb(1., 0.85, 0.5),
+    Color::rgb(0.5, 0.8, 1.),
+    Color::rgb(1., 0.5, 0.7),
+    Color::rgb(0.6, 1., 0.6),
+];
+
+// Amount of rays cast from each sample point.
+const RAYS: usize = 128;
+
+// Amount of points within a light which it is drawn from, as if it's an area
+// of light. More points give smoother penumbras.
+const SAMPLES: usize = 3;
+
+// Opacity of the lit area of all samples together.
+const LIGHT_ALPHA: f32 = 0.3;
+
+const LIGHT_Z: f32 = -0.5;
+
+/// Points where `RAYS` rays from `origin` stop, either at the first ball they
+/// hit in `tree` or at `range`, in counter clockwise order. Balls for which
+/// `ignore` returns true don't block the rays.
+pub fn visibility_polygon(
+    tree: &QuadTree,
+    origin: Vec2,
+    range: f32,
+    ignore: impl Fn(Entity) -> bool,
+) -> Vec<Vec2> {
+    cast_rays(origin, range, |direction| {
+        let mut hit = |location: Location, entity| match location {
+            Location::Area(bounds) if !ignore(entity) => {
+                ray_circle(origin, direction, bounds.center(), bounds.width() / 2.)
+            }
+            _ => None,
+        };
+        tree.raycast(origin, direction, range, &mut hit).map(|(distance, _)| distance)
+    })
+}
+
+/// Like `visibility_polygon`, but the rays are checked against each of
+/// `balls`, by their entity, center and radius, instead of a tree.
+pub fn visibility_polygon_among(
+    balls: &[(Entity, Vec2, f32)],
+    origin: Vec2,
+    range: f32,
+    ignore: impl Fn(Entity) -> bool,
+) -> Vec<Vec2> {
+    cast_rays(origin, range, |direction| {
+        balls.iter()
+            .filter(|(entity, ..)| !ignore(*entity))
+            .filter_map(|(_, center, radius)| ray_circle(origin, direction, *center, *radius))
+            .filter(|distance| *distance <= range)
+            .min_by(|a, b| a.total_cmp(b))
+    })
+}
+
+// Points where `RAYS` rays from `origin` stop, at the distance `hit` returns
+// for their direction or at `range`.
+fn cast_rays(origin: Vec2, range: f32, mut hit: impl FnMut(Vec2) -> Option<f32>) -> Vec<Vec2> {
+    (0..RAYS)
+        .map(|i| {
+            let angle = i as f32 / RAYS as f32 * TAU;
+            let direction = Vec2::new(angle.cos(), angle.sin());
+            origin + direction * hit(direction).unwrap_or(range)
+        })
+        .collect()
+}
+
+// Distance along the ray from `origin` in the unit `direction` at which it
+// enters the circle at `center`, 0 when `origin` is within it.
+fn ray_circle(origin: Vec2, direction: Vec2, center: Vec2, radius: f32) -> Option<f32> {
+    let offset = origin - center;
+    let b = offset.dot(direction);
+    let c = offset.length_squared() - radius * radius;
+    if c > 0. && b > 0. {
+        return None;
+    }
+    let discriminant = b * b - c;
+    if discriminant < 0. {
+        return None;
+    }
+    Some((-b - discriminant.sqrt()).max(0.))
+}
+
+// Point within the light at `center` with `radius` which `sample` is drawn
+// from, the first one is its center.
+fn sample_point(center: Vec2, radius: f32, sample: usize) -> Vec2 {
+    if sample == 0 {
+        return center;
+    }
+    let angle = sample as f32 / (SAMPLES - 1) as f32 * TAU;
+    center + Vec2::new(angle.cos(), angle.sin()) * radius * 0.5
+}
+
+// Turn balls into lights until there are as many as requested.
+fn assign_ball_lights(
+    mut cmd: Commands,
+    lights: Res<BallLights>,
+    existing: Query<(), With<BallLight>>,
+    balls: Query<Entity, (With<Ball>, Without<BallLight>)>,
+    mut assigned: Local<usize>,
+) {
+    let missing = lights.count.saturating_sub(existing.iter().count());
+    for entity in balls.iter().take(missing) {
+        let color = LIGHT_COLORS[*assigned % LIGHT_COLORS.len()];
+        *assigned += 1;
+        cmd.entity(entity).insert(BallLight { color });
+
+        let fill = Color::rgba(color.r(), color.g(), color.b(), LIGHT_ALPHA / SAMPLES as f32);
+        for sample in 0..SAMPLES {
+            cmd.spawn_bundle(GeometryBuilder::build_as(
+                &shapes::Polygon::default(),
+                DrawMode::Fill(FillMode::color(fill)),
+                Transform::default(),
+            ))
+                .insert(LightShape { light: entity, sample });
+        }
+    }
+}
+
+// Balls which are lights, rather than their shapes.
+type Lights = (With<BallLight>, Without<LightShape>);
+
+// Reshape the lit areas to the balls around the lights, shapes of lights
+// which are gone are despawned.
+fn update_light_shapes(
+    mut cmd: Commands,
+    settings: Res<BallLights>,
+    ball_tree: Res<BallTree>,
+    lights: Query<(&Transform, &Ball), Lights>,
+    balls: Query<(Entity, &Transform, &Ball), Without<LightShape>>,
+    mut shapes: Query<(Entity, &LightShape, &mut Path, &mut Transform)>,
+) {
+    // before the first physics tick there is no tree, all balls are checked
+    let all_balls: Vec<(Entity, Vec2, f32)> = if ball_tree.0.is_empty() {
+        balls.iter()
+            .map(|(entity, transform, ball)| (entity, transform.translation.truncate(), ball.radius))
+            .collect()
+    } else {
+        Vec::new()
+    };
+    for (entity, shape, mut path, mut transform) in shapes.iter_mut() {
+        let (light_transform, ball) = match lights.get(shape.light) {
+            Ok(light) => light,
+            Err(_) => {
+                cmd.entity(entity).despawn();
+                continue;
+            }
+        };
+        let origin = sample_point(light_transform.translation.truncate(), ball.radius, shape.sample);
+        let ignore = |entity| entity == shape.light;
+        let points = if ball_tree.0.is_empty() {
+            visibility_polygon_among(&all_balls, origin, settings.range, ignore)
+        } else {
+            visibility_polygon(&ball_tree.0, origin, settings.range, ignore)
+        };
+        *path = ShapePath::build_as(&shapes::Polygon {
+            points: points.into_iter().map(|point| point - origin).collect(),
+            closed: true,
+        });
+        transform.translation = origin.extend(LIGHT_Z);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn balls_cast_shadows_from_lights() {
+        let mut tree = QuadTree::new(Bounds::new(Vec2::ZERO, 400., 400.), Options::default());
+        let light = Entity::from_raw(0);
+        tree.insert(Location::new(Vec2::ZERO, 10., 10.), light).unwrap();
+        tree.insert(Location::new(Vec2::new(50., 0.), 20., 20.), Entity::from_raw(1)).unwrap();
+
+        let points = visibility_polygon(&tree, Vec2::ZERO, 100., |entity| entity == light);
+        assert_eq!(points.len(), RAYS);
+        // the ray to the right stops at the ball, the one to the left doesn't
+        assert!(points[0].abs_diff_eq(Vec2::new(40., 0.), 1e-4));
+        assert!(points[RAYS / 2].abs_diff_eq(Vec2::new(-100., 0.), 1e-3));
+        // without ignoring it, the light is in its own shadow
+        let points = visibility_polygon(&tree, Vec2::ZERO, 100., |_| false);
+        assert!(points.iter().all(|point| *point == Vec2::ZERO));
+
+        // without a tree, the rays stop at the same balls
+        let balls = [(light, Vec2::ZERO, 5.), (Entity::from_raw(1), Vec2::new(50., 0.), 10.)];
+        let among = visibility_polygon_among(&balls, Vec2::ZERO, 100., |entity| entity == light);
+        let points = visibility_polygon(&tree, Vec2::ZERO, 100., |entity| entity == light);
+        for (a, b) in among.iter().zip(points.iter()) {
+            assert!(a.abs_diff_eq(*b, 1e-3), "{} != {}", a, b);
+        }
+
+        assert_eq!(ray_circle(Vec2::ZERO, Vec2::Y, Vec2::new(50., 0.), 10.), None);
+        assert_eq!(sample_point(Vec2::ONE, 4., 0), Vec2::ONE);
+    }
+}
@@ -0,0 +1,359 @@
+//! Lets two instances simulate the same world in lockstep over UDP. Each
+//! player controls a paddle, and only the targets of the paddles are
+//! exchanged: a tick is simulated once the inputs of both players for it are
+//! known. Both instances start from the same seed, so with the
+//! `strict-math` feature they stay identical, even across platforms. Other
+//! interactions, like spawning balls, only happen locally and make the worlds
+//! diverge, which is detected by exchanging checksums of the state.
+
+use std::collections::BTreeMap;
+use std::io::ErrorKind as IoErrorKind;
+use std::net::{SocketAddr, UdpSocket};
+
+use bevy::prelude::*;
+
+use crate::*;
+
+pub struct LockstepPlugin {
+    local: SocketAddr,
+    peer: SocketAddr,
+    seed: u64,
+}
+
+impl LockstepPlugin {
+    pub fn with_peer(local: SocketAddr, peer: SocketAddr) -> Self {
+        Self { local, peer, seed: 0 }
+    }
+}
+
+impl Plugin for LockstepPlugin {
+    fn build(&self, app: &mut App) {
+        let socket = UdpSocket::bind(self.local)
+            .unwrap_or_else(|err| panic!("unable to bind {}: {}", self.local, err));
+        socket.set_nonblocking(true)
+            .unwrap_or_else(|err| panic!("unable to use {} without blocking: {}", self.local, err));
+
+        // the players agree on who is who by their addresses
+        let player = if self.local < self.peer { 0 } else { 1 };
+        let size = Vec2::new(120., 14.);
+        let starts = [
+            Vec2::new(0., -HEIGHT / 2. + PADDLE_HEIGHT),
+            Vec2::new(0., HEIGHT / 2. - PADDLE_HEIGHT),
+        ];
+        println!("lockstep: player {} on {}, waiting for {}", player + 1, self.local, self.peer);
+
+        app.world.get_resource_or_insert_with(SimulationHooks::default)
+            .add(HookPoint::PrePhysics, apply_inputs)
+            .add(HookPoint::PostPhysics, exchange_checksums);
+        app.insert_resource(SimRng::new(Some(self.seed)))
+            .insert_resource(Lockstep {
+                socket,
+                peer: self.peer,
+                player,
+                inputs: LockstepInputs::new(INPUT_DELAY, starts.map(|start| start.x)),
+                target: starts[player].x,
+                checksums: [BTreeMap::new(), BTreeMap::new()],
+                diverged: false,
+            })
+            .add_startup_system(move |mut cmd: Commands| {
+                for (player, start) in starts.into_iter().enumerate() {
+                    let paddle = spawn_paddle(&mut cmd, size, start);
+                    cmd.entity(paddle).insert(LockstepPlayer(player));
+                }
+            })
+            .bind_axis(AxisControl::PaddleMovement, [KeyCode::Q, KeyCode::E])
+            .add_system(control_local_paddle)
+            .add_system(receive_packets)
+            .add_plugin(PaddlePhysicsPlugin);
+    }
+}
+
+// Ticks between the moment a local input is given and the tick it is
+// simulated at, which gives it time to reach the other player.
+const INPUT_DELAY: u64 = 6;
+
+// Amount of the latest local inputs which are sent along with each input, so
+// lost packets don't stall the simulation.
+const RESENT_INPUTS: usize = 8;
+
+// Ticks between the checksums which are compared.
+const CHECKSUM_INTERVAL: u64 = 120;
+
+/// Marks the paddle of a player, the first or second.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LockstepPlayer(pub usize);
+
+/// Inputs of both players for the ticks which are not simulated yet, an input
+/// being the target of the player's paddle.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LockstepInputs {
+    delay: u64,
+    tick: u64,
+    inputs: [BTreeMap<u64, f32>; 2],
+}
+
+impl LockstepInputs {
+    /// The inputs of the first `delay` ticks are the starting `targets`.
+    pub fn new(delay: u64, targets: [f32; 2]) -> Self {
+        let inputs = targets.map(|target| (0..delay).map(|tick| (tick, target)).collect());
+        Self { delay, tick: 0, inputs }
+    }
+
+    /// Tick which is simulated next.
+    #[inline]
+    pub fn tick(&self) -> u64 { self.tick }
+
+    /// Whether the inputs of both players for the next tick are known.
+    #[inline]
+    pub fn ready(&self) -> bool {
+        self.inputs.iter().all(|inputs| inputs.contains_key(&self.tick))
+    }
+
+    /// Add the input of `player` for `tick`, inputs of ticks which were
+    /// simulated already are ignored.
+    pub fn insert(&mut self, player: usize, tick: u64, target: f32) {
+        if tick >= self.tick {
+            self.inputs[player].entry(tick).or_insert(target);
+        }
+    }
+
+    /// Inputs of both players for the next tick, which is then simulated, or
+    /// `None` when they're not known yet. `target` is the input of the local
+    /// `player` for the tick `delay` ticks later.
+    pub fn advance(&mut self, player: usize, target: f32) -> Option<[f32; 2]> {
+        if !self.ready() {
+            return None;
+        }
+        let tick = self.tick;
+        let targets = [0, 1].map(|i| self.inputs[i].remove(&tick).unwrap());
+        self.tick += 1;
+        self.insert(player, tick + self.delay, target);
+        Some(targets)
+    }
+
+    /// Latest inputs of `player` which are not simulated yet.
+    pub fn pending(&self, player: usize) -> Vec<(u64, f32)> {
+        let inputs = &self.inputs[player];
+        inputs.iter()
+            .skip(inputs.len().saturating_sub(RESENT_INPUTS))
+            .map(|(tick, target)| (*tick, *target))
+            .collect()
+    }
+}
+
+/// Message between the players.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Packet {
+    /// Inputs of the sender by tick.
+    Inputs(Vec<(u64, f32)>),
+    /// Checksum of the sender's state after a tick.
+    Checksum(u64, u64),
+}
+
+impl Packet {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        match self {
+            Packet::Inputs(inputs) => {
+                bytes.push(0);
+                for (tick, target) in inputs {
+                    bytes.extend(tick.to_le_bytes());
+                    bytes.extend(target.to_le_bytes());
+                }
+            }
+            Packet::Checksum(tick, checksum) => {
+                bytes.push(1);
+                bytes.extend(tick.to_le_bytes());
+                bytes.extend(checksum.to_le_bytes());
+            }
+        }
+        bytes
+    }
+
+    /// Packet in `bytes`, or `None` when they're not a packet.
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        let (kind, rest) = bytes.split_first()?;
+        let u64_at = |at: usize| rest[at..at + 8].try_into().ok().map(u64::from_le_bytes);
+        match kind {
+            0 if rest.len() % 12 == 0 => Some(Packet::Inputs(
+                (0..rest.len()).step_by(12)
+                    .map(|at| Some((u64_at(at)?, f32::from_le_bytes(rest[at + 8..at + 12].try_into().ok()?))))
+                    .collect::<Option<_>>()?,
+            )),
+            1 if rest.len() == 16 => Some(Packet::Checksum(u64_at(0)?, u64_at(8)?)),
+            _ => None,
+        }
+    }
+}
+
+pub struct Lockstep {
+    socket: UdpSocket,
+    peer: SocketAddr,
+    player: usize,
+    pub inputs: LockstepInputs,
+    /// Latest target of the local player's paddle.
+    pub target: f32,
+    // checksums of both players by tick, until they're compared
+    checksums: [BTreeMap<u64, u64>; 2],
+    diverged: bool,
+}
+
+impl Lockstep {
+    fn send(&self, packet: Packet) {
+        match self.socket.send_to(&packet.encode(), self.peer) {
+            Ok(_) => {}
+            Err(err) if err.kind() == IoErrorKind::WouldBlock => {}
+            Err(err) => println!("lockstep: unable to send to {}: {}", self.peer, err),
+        }
+    }
+
+    fn send_inputs(&self) {
+        self.send(Packet::Inputs(self.inputs.pending(self.player)));
+    }
+
+    // Compare the checksums which both players recorded.
+    fn compare_checksums(&mut self) {
+        let [local, remote] = &mut self.checksums;
+        let common: Vec<u64> = local.keys().filter(|tick| remote.contains_key(tick)).copied().collect();
+        for tick in common {
+            if local.remove(&tick) != remote.remove(&tick) && !self.diverged {
+                println!("lockstep: the worlds diverged before tick {}", tick);
+                self.diverged = true;
+            }
+        }
+    }
+}
+
+fn control_local_paddle(
+    mut lockstep: ResMut<Lockstep>,
+    picker: Picker,
+    axes: Res<ActionAxes>,
+    edge: Res<EdgeCollider>,
+    time: Res<Time>,
+    mut last_cursor: Local<Option<Vec2>>,
+    query: Query<(&Paddle, &LockstepPlayer)>,
+) {
+    let cursor = picker.cursor();
+    let cursor_moved = cursor.is_some() && cursor != *last_cursor;
+    *last_cursor = cursor;
+
+    let player = lockstep.player;
+    if let Some((paddle, _)) = query.iter().find(|(_, paddle_player)| paddle_player.0 == player) {
+        let paddle = Paddle { target: lockstep.target, ..*paddle };
+        let cursor = cursor.filter(|_| cursor_moved);
+        lockstep.target = paddle.next_target(cursor, axes.paddle_movement, edge.bounds, time.delta_seconds());
+    }
+}
+
+// Receive the packets of the other player. While stalled, the pending inputs
+// are sent again in case they were lost.
+fn receive_packets(mut lockstep: ResMut<Lockstep>) {
+    let mut buffer = [0; 1 + 12 * RESENT_INPUTS];
+    let remote = 1 - lockstep.player;
+    loop {
+        let packet = match lockstep.socket.recv_from(&mut buffer) {
+            Ok((len, from)) if from == lockstep.peer => Packet::decode(&buffer[..len]),
+            Ok(_) => continue,
+            Err(err) if err.kind() == IoErrorKind::WouldBlock => break,
+            // the other player is not there yet
+            Err(err) if err.kind() == IoErrorKind::ConnectionReset => continue,
+            Err(err) => {
+                println!("lockstep: unable to receive: {}", err);
+                break;
+            }
+        };
+        match packet {
+            Some(Packet::Inputs(inputs)) => {
+                for (tick, target) in inputs {
+                    lockstep.inputs.insert(remote, tick, target);
+                }
+            }
+            Some(Packet::Checksum(tick, checksum)) => {
+                lockstep.checksums[remote].insert(tick, checksum);
+                lockstep.compare_checksums();
+            }
+            None => println!("lockstep: invalid packet"),
+        }
+    }
+
+    if !lockstep.inputs.ready() {
+        lockstep.send_inputs();
+    }
+}
+
+// Move the paddles towards the targets of the tick, and send the local target
+// for a later tick.
+fn apply_inputs(world: &mut World) {
+    let targets = match world.get_resource_mut::<Lockstep>() {
+        Some(mut lockstep) => {
+            let (player, target) = (lockstep.player, lockstep.target);
+            let targets = lockstep.inputs.advance(player, target);
+            lockstep.send_inputs();
+            targets
+        }
+        None => return,
+    };
+    if let Some(targets) = targets {
+        for (mut paddle, player) in world.query::<(&mut Paddle, &LockstepPlayer)>().iter_mut(world) {
+            paddle.target = targets[player.0];
+        }
+    }
+}
+
+fn exchange_checksums(world: &mut World) {
+    let tick = match world.get_resource::<Lockstep>() {
+        Some(lockstep) => lockstep.inputs.tick(),
+        None => return,
+    };
+    if tick % CHECKSUM_INTERVAL != 0 {
+        return;
+    }
+    let checksum = state_checksum(world);
+    let mut lockstep = world.resource_mut::<Lockstep>();
+    let player = lockstep.player;
+    lockstep.checksums[player].insert(tick, checksum);
+    lockstep.send(Packet::Checksum(tick, checksum));
+    lockstep.compare_checksums();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn players_simulate_the_same_inputs() {
+        let mut players = [LockstepInputs::new(2, [0., 10.]), LockstepInputs::new(2, [0., 10.])];
+        let mut simulated: [Vec<[f32; 2]>; 2] = Default::default();
+
+        // the first player runs ahead until it runs out of inputs
+        for target in [1., 2., 3.] {
+            if let Some(targets) = players[0].advance(0, target) {
+                simulated[0].push(targets);
+            }
+        }
+        assert_eq!(players[0].tick(), 2);
+        assert!(!players[0].ready());
+
+        for target in [11., 12., 13.] {
+            simulated[1].extend(players[1].advance(1, target));
+        }
+        // the packets cross, the second player misses the newest input
+        let packet = Packet::decode(&Packet::Inputs(players[0].pending(0)).encode()).unwrap();
+        assert_eq!(packet, Packet::Inputs(vec![(2, 1.), (3, 2.)]));
+        for (tick, target) in players[1].pending(1) {
+            players[0].insert(1, tick, target);
+        }
+        players[1].insert(0, 2, 1.);
+
+        simulated[0].extend(players[0].advance(0, 4.));
+        simulated[0].extend(players[0].advance(0, 5.));
+        assert_eq!(simulated[0], vec![[0., 10.], [0., 10.], [1., 11.], [2., 12.]]);
+        assert_eq!(simulated[1], vec![[0., 10.], [0., 10.]]);
+        assert_eq!(players[1].advance(1, 14.), Some([1., 11.]));
+        assert_eq!(players[1].advance(1, 15.), None);
+
+        assert_eq!(Packet::decode(&Packet::Checksum(120, u64::MAX).encode()), Some(Packet::Checksum(120, u64::MAX)));
+        assert_eq!(Packet::decode(&[0, 1, 2]), None);
+        assert_eq!(Packet::decode(&[]), None);
+    }
+}
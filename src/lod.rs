@@ -0,0 +1,173 @@
+use bevy::prelude::*;
+use bevy::render::camera::Camera2d;
+
+use crate::*;
+
+/// Simulates balls in leaves of the `BallTree` which are far outside the
+/// camera's view at a lower tick rate. Far balls all move during the same
+/// ticks, and are left out of the broad phase during the ticks in between.
+/// Once a ball comes back into view, the skipped time is caught up gradually
+/// instead of in a single large step.
+pub struct LodPlugin {
+    tick_interval: u32,
+    margin: f32,
+}
+
+impl LodPlugin {
+    pub fn with_tick_interval(tick_interval: u32) -> Self {
+        Self {
+            tick_interval,
+            margin: 100.,
+        }
+    }
+}
+
+impl Default for LodPlugin {
+    fn default() -> Self { Self::with_tick_interval(8) }
+}
+
+impl Plugin for LodPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(LodSettings {
+            tick_interval: self.tick_interval.max(1),
+            margin: self.margin,
+            tick: 0,
+        })
+            .add_system(add_lod_to_spawned_balls)
+            .add_system(update_lod)
+            .add_system_to_stage(PhysicsStage, advance_lod_tick.before(PhysicsSystem::Integrate));
+    }
+}
+
+pub struct LodSettings {
+    /// Balls outside the view are only moved once per this amount of ticks.
+    pub tick_interval: u32,
+    /// Distance outside the view within which balls are still fully simulated.
+    pub margin: f32,
+    tick: u32,
+}
+
+impl LodSettings {
+    /// Indicates if far balls are moved during this tick.
+    #[inline]
+    pub fn is_due(&self) -> bool { self.tick % self.tick_interval == 0 }
+}
+
+// Fraction of a tick of skipped time which is caught up each tick, once a ball
+// is back in view.
+const CATCH_UP_RATE: f32 = 1.;
+
+/// Level of detail with which a ball is simulated.
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct Lod {
+    pub far: bool,
+    /// Whether the ball is not moved during this tick, so the broad phase can
+    /// leave it out.
+    pub skipped: bool,
+    // simulated time which the ball still has to catch up on
+    pending: f32,
+}
+
+impl Lod {
+    /// Time step to integrate the ball with during this tick, or `None` when
+    /// it is skipped. Far balls are only moved when they're `due`.
+    #[inline]
+    pub fn step(&mut self, dt: f32, due: bool) -> Option<f32> {
+        self.skipped = false;
+        if self.far {
+            self.pending += dt;
+            if !due {
+                self.skipped = true;
+                return None;
+            }
+
+            let step = self.pending;
+            self.pending = 0.;
+            return Some(step);
+        }
+
+        let catch_up = self.pending.min(dt * CATCH_UP_RATE);
+        self.pending -= catch_up;
+        Some(dt + catch_up)
+    }
+}
+
+fn add_lod_to_spawned_balls(mut cmd: Commands, mut spawned: EventReader<BallSpawned>) {
+    for BallSpawned(entity) in spawned.iter() {
+        cmd.entity(*entity).insert(Lod::default());
+    }
+}
+
+fn advance_lod_tick(mut settings: ResMut<LodSettings>) {
+    settings.tick = settings.tick.wrapping_add(1);
+}
+
+fn update_lod(
+    settings: Res<LodSettings>,
+    ball_tree: Res<BallTree>,
+    windows: Res<Windows>,
+    cameras: Query<&Transform, With<Camera2d>>,
+    mut query: Query<(&Transform, &mut Lod)>,
+) {
+    let view = match (windows.get_primary(), cameras.get_single()) {
+        (Some(window), Ok(camera)) => view_bounds(window, camera).expanded(settings.margin),
+        _ => return,
+    };
+
+    for (transform, mut lod) in query.iter_mut() {
+        let far = is_far(&ball_tree.0, view, transform.translation.truncate());
+        if lod.far != far {
+            lod.far = far;
+        }
+    }
+}
+
+// Indicates if the leaf of `tree` at `position` is outside of `view`, so all
+// balls within it are far together. Without a split tree, as with broad phases
+// which don't build one, only the position itself is checked.
+fn is_far(tree: &QuadTree, view: Bounds, position: Vec2) -> bool {
+    match tree.leaf_at(position).filter(|id| *id != NodeId::ROOT).and_then(|id| tree.node(id)) {
+        Some(leaf) => !view.intersects(leaf.bounds()),
+        None => !view.contains(position),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn far_balls_catch_up_when_back_in_view() {
+        let mut lod = Lod {
+            far: true,
+            ..default()
+        };
+
+        let steps: Vec<Option<f32>> = (1..=4).map(|tick| lod.step(1., tick % 4 == 0)).collect();
+        assert_eq!(steps, vec![None, None, None, Some(4.)]);
+
+        lod.step(1., false);
+        assert!(lod.skipped);
+        lod.step(1., false);
+        lod.far = false;
+        assert_eq!(lod.step(1., false), Some(2.));
+        assert!(!lod.skipped);
+        assert_eq!(lod.step(1., false), Some(2.));
+        assert_eq!(lod.step(1., false), Some(1.));
+    }
+
+    #[test]
+    fn balls_are_far_by_their_leaf() {
+        let mut tree = QuadTree::new(Bounds::new(Vec2::ZERO, 400., 400.), Options { capacity: 1, ..default() });
+        tree.insert(Location::new(Vec2::new(-150., 150.), 10., 10.), Entity::from_raw(0)).unwrap();
+        tree.insert(Location::new(Vec2::new(150., -150.), 10., 10.), Entity::from_raw(1)).unwrap();
+        let view = Bounds::new(Vec2::new(-150., 150.), 40., 40.);
+
+        // the north west leaf overlaps the view, even where the ball doesn't
+        assert!(!is_far(&tree, view, Vec2::new(-20., 20.)));
+        assert!(is_far(&tree, view, Vec2::new(150., -150.)));
+        // without a split tree, only the position counts
+        let tree = QuadTree::new(Bounds::new(Vec2::ZERO, 400., 400.), Options::default());
+        assert!(is_far(&tree, view, Vec2::new(-20., 20.)));
+    }
+}
@@ -1,78 +1,418 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+use std::f32::consts::SQRT_2;
+use std::net::SocketAddr;
 use std::ops::{Deref, RangeInclusive};
+use std::time::{Duration, Instant};
 
-use bevy::diagnostic::FrameTimeDiagnosticsPlugin;
+use bevy::core::FixedTimestep;
+use bevy::ecs::schedule::ShouldRun;
+use bevy::diagnostic::{Diagnostics, FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin};
 use bevy::math::*;
 use bevy::prelude::*;
+use bevy::tasks::ComputeTaskPool;
 use bevy::window::PresentMode;
 use bevy_prototype_lyon::prelude::*;
 use rand::distributions::{Distribution, Uniform};
+use rand::rngs::StdRng;
+use rand::Rng;
 
+use crate::aging::*;
+use crate::attractor::*;
+#[cfg(feature = "bloom")]
+use crate::bloom::*;
+use crate::bodies::*;
+use crate::capacity::*;
+use crate::checksum::*;
+use crate::chunks::*;
+use crate::cli::*;
+use crate::compound::*;
+use crate::boids::*;
+use crate::brownian::*;
+use crate::burst::*;
 use crate::collision::*;
 use crate::components::*;
+use crate::contacts::*;
+use crate::conveyor::*;
 use crate::debug::*;
+use crate::density::*;
+use crate::depth::*;
+#[cfg(feature = "scene")]
+use crate::dynamic_scene::*;
+use crate::events::*;
+use crate::goal::*;
+#[cfg(feature = "gpu")]
+use crate::gpu_broad_phase::*;
+use crate::headless::*;
+use crate::heat::*;
+use crate::histogram::*;
+use crate::joints::*;
+use crate::hooks::*;
+use crate::input::*;
+#[cfg(feature = "instancing")]
+use crate::instancing::*;
+use crate::integration::*;
+use crate::islands::*;
+use crate::kinds::*;
+use crate::lights::*;
+use crate::lockstep::*;
+use crate::lod::*;
+use crate::metrics::*;
+use crate::nbody::*;
+#[cfg(feature = "net")]
+use crate::net::*;
+#[cfg(feature = "scripting")]
+use crate::scripting::*;
 use crate::quadtree::*;
+use crate::pair_cache::*;
+use crate::paddle::*;
+use crate::palette::*;
+use crate::phosphor::*;
+use crate::picking::*;
+use crate::pool::*;
+use crate::portal::*;
+use crate::pressure::*;
+use crate::prewarm::*;
+use crate::replay::*;
+use crate::rewind::*;
+use crate::rng::*;
+use crate::rolling::*;
+use crate::scale::*;
+use crate::scenario::*;
+#[cfg(feature = "scene")]
+use crate::scene::*;
+use crate::selection::*;
+use crate::separation::*;
+use crate::slow_motion::*;
+use crate::soak::*;
+use crate::sprites::*;
+#[allow(unused_imports)]
+use crate::spatial::*;
+use crate::tessellation::*;
+use crate::undo::*;
+use crate::wind::*;
 
+mod aging;
+mod attractor;
+#[cfg(feature = "bloom")]
+mod bloom;
+mod bodies;
+mod boids;
+mod brownian;
+mod burst;
+mod capacity;
+mod checksum;
+mod chunks;
+mod compound;
+mod cli;
 mod collision;
 mod components;
+mod contacts;
+mod conveyor;
 mod quadtree;
 mod debug;
+mod density;
+mod depth;
+#[cfg(feature = "scene")]
+mod dynamic_scene;
+mod events;
+mod goal;
+#[cfg(feature = "gpu")]
+mod gpu_broad_phase;
+mod headless;
+mod heat;
+mod histogram;
+mod joints;
+mod hooks;
+mod input;
+#[cfg(feature = "instancing")]
+mod instancing;
+mod integration;
+mod islands;
+mod kinds;
+mod lights;
+mod lockstep;
+mod lod;
+mod metrics;
+mod nbody;
+#[cfg(feature = "net")]
+mod net;
+mod pair_cache;
+mod paddle;
+mod palette;
+mod phosphor;
+mod picking;
+mod pool;
+mod portal;
+mod pressure;
+mod prewarm;
+mod replay;
+mod rewind;
+mod rng;
+mod rolling;
+mod scale;
+mod scenario;
+#[cfg(feature = "scene")]
+mod scene;
+#[cfg(feature = "scripting")]
+mod scripting;
+mod selection;
+mod separation;
+mod slow_motion;
+mod soak;
+mod spatial;
+mod sprites;
+mod strict;
+mod tessellation;
+mod undo;
+mod wind;
+#[cfg(test)]
+mod regression_tests;
 
 pub const WIDTH: f32 = 1024.;
 pub const HEIGHT: f32 = 768.;
 
 const BALLS: u64 = 1000;
 
+// Duration of a single physics tick, in seconds.
+const TIMESTEP: f32 = 1. / 120.;
+
+// Print the checksum of the state of all balls every this many ticks of a
+// headless benchmark, or `None` to only print it at the end.
+const CHECKSUM_INTERVAL: Option<usize> = Some(600);
+
+// A headless run with `--until-settled` stops once the kinetic energy of the
+// balls stayed below this much per ball for `SETTLED_TICKS` ticks in a row.
+const SETTLED_ENERGY: f32 = 1.;
+const SETTLED_TICKS: usize = 120;
+
+// Script which is run alongside the simulation, see `src/scripting.rs`.
+#[cfg(feature = "scripting")]
+const SCRIPT: &str = "assets/scripts/main.rhai";
+
+// Acceleration applied to all balls.
+const GRAVITY: Vec2 = Vec2::ZERO;
+
+// Acceleration added to gravity when it is tilted at full strength.
+const GRAVITY_TILT: f32 = 400.;
+
+// Pixels per meter scenes are saved with, so their quantities are in meters and
+// seconds, or `None` to save them in pixels. Scenes which are loaded with a
+// scale are saved with their own scale.
+const PIXELS_PER_METER: Option<f32> = None;
+
+// Numerical method used to move the balls.
+const INTEGRATOR: Integrator = Integrator::SemiImplicitEuler;
+
+// Fraction of their velocity balls lose per second.
+const DRAG: f32 = 0.;
+
+// Amount of balls spawned or despawned per second while the action is held.
+const SPAWN_RATE: f32 = 50.;
+
+// Amount of balls in a burst, which is spawned around the cursor.
+const BURST_SIZE: usize = 24;
+
+// Maximum amount of despawned balls which are kept around for reuse.
+const BALL_POOL_SIZE: usize = 1000;
+
+// Seed of the simulation's random number generator, use `None` for a random
+// seed on each run.
+const SEED: Option<u64> = None;
+
+// Temperature of the optional thermal jitter, use `None` to disable it.
+const BROWNIAN_TEMPERATURE: Option<f32> = None;
+
+// Let all balls flock together like boids.
+const BOIDS: bool = false;
+
+// Give balls a temperature which spreads when they collide.
+const HEAT: bool = false;
+
 // Min/max radius range of balls.
 const BALL_RADIUS: RangeInclusive<f32> = 2.0..=16.0;
 
+// Determines the mass of a ball based on its radius.
+const MASS_MODEL: MassModel = MassModel::Area(1.);
+
+// Determines the leaf capacity of the quadtree used for collision checks.
+const QUADTREE_CAPACITY: CapacityModel = CapacityModel::Fixed(4);
+
+// Fraction of the speed which is preserved when a ball bounces off a wall.
+const WALL_RESTITUTION: f32 = 1.;
+
+// Coefficient of friction between the walls and the balls, which slows down
+// balls which slide along a wall.
+const WALL_FRICTION: f32 = 0.;
+
+// Coefficient of friction with which balls roll along the walls, instead of
+// sliding, or `None` to disable it.
+const ROLLING: Option<f32> = None;
+
+// Determines how candidate pairs of colliding balls are found.
+const BROAD_PHASE: BroadPhase = BroadPhase::QuadTree;
+
+// Move balls far outside of the view only once per this amount of ticks, use
+// `None` to always simulate all balls fully.
+const LOD_TICK_INTERVAL: Option<u32> = None;
+
+// Seconds of history which can be played backwards by holding T, or `None` to
+// keep no history.
+const REWIND_DURATION: Option<f32> = None;
+
+// Collisions per tick above which time is slowed down for a moment, or `None`
+// to keep time at normal speed.
+const AUTO_SLOW_MOTION: Option<f32> = None;
+
+// Draw balls as sprites of a texture atlas in the assets directory, with the
+// size of a sprite and the columns and rows of the atlas, or `None` to draw
+// them as shapes.
+const BALL_SPRITES: Option<(&str, Vec2, usize, usize)> = None;
+
+// Range of segments circles are drawn with depending on their size on screen,
+// or `None` to draw all circles at full detail.
+const CIRCLE_SEGMENTS: Option<RangeInclusive<usize>> = Some(8..=64);
+
+// Resolve separate groups of colliding balls in parallel.
+const PARALLEL_ISLANDS: bool = false;
+
+// Build the quadtree on multiple threads once it holds at least this many
+// balls, or `None` to always build it on a single thread.
+const PARALLEL_TREE_BUILD: Option<usize> = None;
+
+// Open a second window with live charts of the simulation. The fps is then
+// shown in that window, instead of in the title of the main window.
+const METRICS_WINDOW: bool = false;
+
+// Log the fps and the diagnostics of the collision pipeline to the console
+// each second.
+const LOG_DIAGNOSTICS: bool = false;
+
+// Plot the distribution of ball speeds in the corner of the view.
+const SPEED_HISTOGRAM: bool = false;
+
+// Distance between the lines of the background grid, which is toggled with G.
+const GRID_SPACING: f32 = 50.;
+
+// Show the density of balls as a colored grid behind them.
+const DENSITY_GRID: bool = false;
+
+// Draw trails behind the balls, which fade to half their brightness in this
+// many seconds, or `None` to draw no trails.
+const PHOSPHOR_TRAILS: Option<f32> = None;
+
+// Amount of balls which light up their surroundings while the other balls
+// cast shadows, or `None` for no lights.
+const BALL_LIGHTS: Option<usize> = None;
+
+// Measure the pressure balls exert on each wall, shown as bars along them.
+const WALL_PRESSURE: bool = false;
+
+// Fraction of the overlap of balls which stay in contact that is resolved each
+// tick, or `None` to always separate overlapping balls at once.
+const SEPARATION_BIAS: Option<f32> = None;
+
+// Iterations of the solver which keeps contacts between balls across ticks,
+// or `None` to bounce colliding balls off of each other once.
+const CONTACT_ITERATIONS: Option<usize> = None;
+
+// Determines how balls bounce off of each other.
+const COLLISION_MODEL: CollisionModel = CollisionModel::Elastic;
+
+// Outline drawn around balls, use `None` to only fill them.
+const BALL_OUTLINE: Option<Outline> = None;
+
+// Determines which balls are drawn in front, use `None` for arbitrary order.
+const DEPTH_SORT: Option<DepthSort> = None;
+
 // Initial random speed of ball.
 const BALL_INIT_SPEED: RangeInclusive<f32> = 10.0..=50.;
 
-// Possible ball colors.
-const BALL_COLORS: [Color; 36] = [
-    Color::ALICE_BLUE,
-    Color::ANTIQUE_WHITE,
-    Color::AQUAMARINE,
-    Color::AZURE,
-    Color::BEIGE,
-    Color::BISQUE,
-    Color::BLUE,
-    Color::CRIMSON,
-    Color::CYAN,
-    Color::DARK_GRAY,
-    Color::DARK_GREEN,
-    Color::FUCHSIA,
-    Color::GOLD,
-    Color::GRAY,
-    Color::GREEN,
-    Color::INDIGO,
-    Color::LIME_GREEN,
-    Color::MAROON,
-    Color::MIDNIGHT_BLUE,
-    Color::NAVY,
-    Color::OLIVE,
-    Color::ORANGE,
-    Color::ORANGE_RED,
-    Color::PINK,
-    Color::PURPLE,
-    Color::RED,
-    Color::SALMON,
-    Color::SEA_GREEN,
-    Color::SILVER,
-    Color::TEAL,
-    Color::TOMATO,
-    Color::TURQUOISE,
-    Color::VIOLET,
-    Color::WHITE,
-    Color::YELLOW,
-    Color::YELLOW_GREEN,
-];
+// Balls shrink and fade out over this many seconds, after which they are
+// removed. Hold the spawn action to keep a steady stream of balls.
+const BALL_LIFESPAN: Option<f32> = None;
+
+// Add regions which push balls along, like conveyor belts.
+const CONVEYORS: bool = false;
+
+// Add a pair of portals, balls which enter one leave through the other.
+const PORTALS: bool = false;
+
+// Add goal zones which capture balls and keep score.
+const GOAL_ZONES: bool = false;
+
+// Add a paddle which follows the cursor, or is moved with Q and E.
+const PADDLE: bool = false;
+
+// Strength of a wind which varies over the arena and over time, or `None` to
+// disable it.
+const WIND: Option<f32> = None;
+
+// Gravitational constant with which all balls attract each other, or `None` to
+// disable mutual gravity.
+const MUTUAL_GRAVITY: Option<f32> = None;
+
+// Amount of peanut and snowman shaped balls, made of several circles, which
+// are spawned next to the regular balls, or `None` to spawn none.
+const COMPOUND_BALLS: Option<usize> = None;
+
+// Add bars which are pinned at their center, and are turned by balls.
+const PINWHEELS: bool = false;
+
+// Palette balls are colored with, either the name of a built-in palette or the
+// path to a palette file. Can be overridden with `--palette <name or path>`.
+const PALETTE: &str = "default";
+
+// Determines which color of the palette a ball is given when it spawns. Can be
+// overridden with `--colors <strategy>`.
+const COLOR_STRATEGY: ColorStrategy = ColorStrategy::RoundRobin;
 
 fn main() {
-    App::new()
-        .insert_resource(ClearColor(Color::rgb(0.1, 0.1, 0.1)))
+    let command = match Command::from_args() {
+        Ok(command) => command,
+        Err(err) => {
+            println!("{}\n\n{}", err, USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    match command {
+        Command::Run { config, prewarm } => run(config, prewarm),
+        Command::Bench { ticks, balls, seed, scenario } => bench(ticks, balls, seed, scenario),
+        Command::BenchCompare { ticks, balls, seed, scenario } => bench_compare(ticks, balls, seed, scenario),
+        Command::Headless { exit, physics, balls, seed, scenario } => headless(exit, physics, balls, seed, scenario),
+        Command::Soak { duration, balls, seed, scenario } => {
+            soak(Duration::from_secs(duration), balls, seed, scenario)
+        }
+        Command::Replay(path) => replay(&path),
+        Command::Validate(path) => validate(&path),
+    }
+}
+
+// Check the scene at `path`, exits with an error when it is invalid.
+fn validate(path: &str) {
+    #[cfg(feature = "scene")]
+    match SceneConfig::load(path).and_then(|scene| scene.validate().map(|_| scene)) {
+        Ok(scene) => println!("{}: ok, {} balls", path, scene.balls.len()),
+        Err(err) => {
+            println!("{}: {}", path, err);
+            std::process::exit(1);
+        }
+    }
+
+    #[cfg(not(feature = "scene"))]
+    {
+        println!("{}: unable to validate, scenes require the `scene` feature", path);
+        std::process::exit(1);
+    }
+}
+
+// Run the interactive simulation, starting from the scene at `config` when
+// it is given, after running `prewarm` physics ticks.
+#[cfg_attr(not(feature = "scene"), allow(unused_variables))]
+fn run(config: Option<String>, prewarm: usize) {
+    let mut app = App::new();
+    app.insert_resource(ClearColor(Color::rgb(0.1, 0.1, 0.1)))
         .insert_resource(WindowDescriptor {
             title: "Bevy Balls".to_string(),
             width: WIDTH,
@@ -86,14 +426,398 @@ fn main() {
         .add_plugin(ShapePlugin)
         .add_plugin(DebugLinesPlugin::default())
         .add_plugin(FrameTimeDiagnosticsPlugin::default())
-        .add_plugin(WindowTitleFpsPlugin::default())
+        .add_plugin(DebugBudgetPlugin::default())
+        .add_plugin(PhysicsDiagnosticsPlugin)
+        .add_plugin(BallEventsPlugin)
+        .add_plugin(ActionInputPlugin)
+        .add_plugin(UndoPlugin::default())
+        .add_plugin(AttractorPlugin::default())
+        .add_plugin(SlowMotionPlugin::default())
+        .add_plugin(SelectionPlugin::default())
+        .add_plugin(BurstPlugin::with_size(BURST_SIZE))
+        .add_plugin(KindsPlugin::default())
+        .add_plugin(BallLabelsPlugin)
+        .add_plugin(BackgroundGridPlugin::with_spacing(GRID_SPACING))
+        .add_plugin(HelpOverlayPlugin::with_language(load_language()))
+        .add_plugin(StageTimingsPlugin)
         .add_startup_system(setup)
         .add_startup_system(spawn_balls)
         .add_system(bevy::input::system::exit_on_esc_system)
-        .add_system(check_collisions_quadtree.after(apply_velocity))
-        // .add_system(check_collisions.after(apply_velocity))
-        .add_system(apply_velocity)
-        .run();
+        .add_system(spawn_despawn_balls)
+        .add_system(reset_balls)
+        .add_system(toggle_pause)
+        .add_system(toggle_physics_debug)
+        .add_system(tilt_gravity)
+        .add_stage_after(
+            CoreStage::Update,
+            PhysicsStage,
+            physics_stage().with_run_criteria(
+                FixedTimestep::step(TIMESTEP as f64).chain(skip_when_paused).chain(run_prewarm_ticks)
+            ),
+        )
+        .insert_resource(Paused(false))
+        .insert_resource(PhysicsDebug(true))
+        .insert_resource(Gravity(GRAVITY))
+        .insert_resource(INTEGRATOR)
+        .insert_resource(Drag(DRAG))
+        .insert_resource(SimRng::new(SEED))
+        .insert_resource(load_palette())
+        .insert_resource(SpawnColors::new(load_color_strategy(), BALL_RADIUS, init_speeds()))
+        .insert_resource(load_kinds())
+        .insert_resource(BallPool::with_capacity(BALL_POOL_SIZE))
+        .insert_resource(BROAD_PHASE)
+        .init_resource::<PairBuffer>()
+        .init_resource::<BallTree>()
+        .init_resource::<ChunkedWorld>()
+        .init_resource::<SimulationHooks>()
+        .insert_resource(COLLISION_MODEL)
+        .insert_resource(TreeCapacity::new(QUADTREE_CAPACITY))
+        .register_type::<Ball>()
+        .register_type::<Velocity>()
+        .register_type::<AngularVelocity>()
+        .register_type::<Force>()
+        .register_type::<Impulse>()
+        .register_type::<BallVisual>()
+        .register_type::<BoxBody>()
+        .register_type::<CapsuleBody>()
+        .register_type::<Kind>()
+        .register_type::<Frozen>()
+        .register_type::<Location>();
+
+    if prewarm > 0 {
+        app.insert_resource(Prewarm::with_ticks(prewarm));
+    }
+    if let Some(temperature) = BROWNIAN_TEMPERATURE {
+        app.add_plugin(BrownianMotionPlugin::with_temperature(temperature));
+    }
+
+    if let Some(count) = COMPOUND_BALLS {
+        app.add_plugin(CompoundPlugin::with_count(count));
+    }
+    if BOIDS {
+        app.add_plugin(BoidsPlugin::default());
+    }
+    if HEAT {
+        app.add_plugin(HeatPlugin::default());
+    }
+    if matches!(BROAD_PHASE, BroadPhase::Chunked(_)) {
+        app.add_plugin(ChunkedWorldPlugin::default());
+    }
+    if let Some(tick_interval) = LOD_TICK_INTERVAL {
+        app.add_plugin(LodPlugin::with_tick_interval(tick_interval));
+    }
+    if let Some(duration) = REWIND_DURATION {
+        app.add_plugin(RewindPlugin::with_duration(duration));
+    }
+    if let Some(threshold) = AUTO_SLOW_MOTION {
+        app.add_plugin(AutoSlowMotionPlugin::with_threshold(threshold));
+    }
+    if let Some((path, tile_size, columns, rows)) = BALL_SPRITES {
+        app.add_plugin(BallSpritesPlugin::with_atlas(path, tile_size, columns, rows));
+    }
+    if let Some(segments) = CIRCLE_SEGMENTS {
+        app.add_plugin(CircleDetailPlugin::with_segments(segments));
+    }
+    if let Some(pixels_per_meter) = PIXELS_PER_METER {
+        app.insert_resource(WorldScale::new(pixels_per_meter));
+    }
+    if PARALLEL_ISLANDS {
+        app.insert_resource(CollisionIslands);
+    }
+    if let Some(min_balls) = PARALLEL_TREE_BUILD {
+        app.insert_resource(ParallelTreeBuild { min_balls });
+    }
+    if let Some(gravity) = MUTUAL_GRAVITY {
+        app.add_plugin(NBodyPlugin::with_gravity(gravity));
+    }
+    if let Some(bias) = SEPARATION_BIAS {
+        app.insert_resource(Separation::with_bias(bias));
+    }
+    if let Some(iterations) = CONTACT_ITERATIONS {
+        app.insert_resource(ContactCache::with_iterations(iterations));
+    }
+    if let Some(friction) = ROLLING {
+        app.add_plugin(RollingPlugin::with_friction(friction));
+    }
+    if let Some(strength) = WIND {
+        app.add_plugin(WindPlugin::with_strength(strength));
+    }
+    // in lockstep, both players have a paddle of their own
+    let lockstep = load_lockstep();
+    if PADDLE && lockstep.is_none() {
+        app.add_plugin(PaddlePlugin::default());
+    }
+    if let Some((local, peer)) = lockstep {
+        app.add_plugin(LockstepPlugin::with_peer(local, peer));
+    }
+    if let Some(path) = cli_option("record") {
+        app.add_plugin(ReplayRecorderPlugin::to_file(path));
+    }
+    if PINWHEELS {
+        app.add_plugin(PinwheelPlugin::default());
+    }
+    if GOAL_ZONES {
+        app.add_plugin(GoalPlugin::default());
+    }
+    if PORTALS {
+        app.add_plugin(PortalPlugin::default());
+    }
+    if CONVEYORS {
+        app.add_plugin(ConveyorPlugin::default());
+    }
+    if let Some(lifespan) = BALL_LIFESPAN {
+        app.add_plugin(AgingPlugin::with_lifespan(lifespan));
+    }
+    if let Some(key) = DEPTH_SORT {
+        app.add_plugin(DepthSortPlugin::with_key(key));
+    }
+    if LOG_DIAGNOSTICS {
+        app.add_plugin(LogDiagnosticsPlugin::default());
+    }
+    if SPEED_HISTOGRAM {
+        app.add_plugin(SpeedHistogramPlugin::default());
+    }
+    if DENSITY_GRID {
+        app.add_plugin(DensityGridPlugin::default());
+    }
+    if let Some(half_life) = PHOSPHOR_TRAILS {
+        app.add_plugin(PhosphorPlugin::with_half_life(half_life));
+    }
+    if let Some(count) = BALL_LIGHTS {
+        app.add_plugin(BallLightsPlugin::with_count(count));
+    }
+    if WALL_PRESSURE {
+        app.add_plugin(WallPressurePlugin::default());
+    }
+    if METRICS_WINDOW {
+        app.add_plugin(MetricsWindowPlugin::default());
+    } else {
+        app.add_plugin(WindowTitleFpsPlugin::default());
+    }
+
+    #[cfg(feature = "bloom")]
+    app.add_plugin(BloomPlugin::default());
+
+    #[cfg(feature = "gpu")]
+    app.add_plugin(GpuBroadPhasePlugin::default());
+
+    #[cfg(feature = "inspector")]
+    app.add_plugin(QuadTreeInspectorPlugin);
+
+    #[cfg(feature = "instancing")]
+    app.add_plugin(InstancedBallsPlugin::default());
+
+    #[cfg(feature = "net")]
+    app.add_plugin(NetPlugin::default());
+
+    #[cfg(feature = "scripting")]
+    app.add_plugin(ScriptingPlugin::with_path(SCRIPT));
+
+    #[cfg(feature = "scene")]
+    app.add_plugin(ScenePlugin::with_config(config));
+
+    // rebind once all plugins declared the keys of their actions
+    let mut bindings = app.world.resource_mut::<InputMap>();
+    for (control, keys) in load_bindings() {
+        bindings.rebind(control, keys);
+    }
+
+    run_prewarm(&mut app);
+    app.run();
+}
+
+// Bindings loaded from the file passed with `--input <path>`, which replace
+// the keys of their actions.
+fn load_bindings() -> Vec<(Control, Vec<Binding>)> {
+    let path = match cli_option("input") {
+        Some(path) => path,
+        None => return Vec::new(),
+    };
+
+    #[cfg(feature = "scene")]
+    match InputMap::load_bindings(&path) {
+        Ok(bindings) => return bindings,
+        Err(err) => println!("input: unable to load {}: {}", path, err),
+    }
+    #[cfg(not(feature = "scene"))]
+    println!("input: unable to load {}: requires the `scene` feature", path);
+    Vec::new()
+}
+
+// Language of the help overlay selected on the command line, or else English.
+fn load_language() -> Language {
+    let code = match cli_option("lang") {
+        Some(code) => code,
+        None => return Language::English,
+    };
+    code.parse().unwrap_or_else(|_| {
+        println!("lang: unknown language {}, expected en or nl", code);
+        Language::English
+    })
+}
+
+// Strategy balls are colored with selected on the command line, or else
+// `COLOR_STRATEGY`.
+fn load_color_strategy() -> ColorStrategy {
+    let name = match cli_option("colors") {
+        Some(name) => name,
+        None => return COLOR_STRATEGY,
+    };
+    name.parse().unwrap_or_else(|_| {
+        println!("colors: unknown strategy {}, expected round-robin, random, radius, speed or quadrant", name);
+        COLOR_STRATEGY
+    })
+}
+
+// Address of this instance and of the other player when playing in lockstep,
+// selected on the command line.
+fn load_lockstep() -> Option<(SocketAddr, SocketAddr)> {
+    let local = cli_option("lockstep")?;
+    let peer = match cli_option("peer") {
+        Some(peer) => peer,
+        None => {
+            println!("lockstep: the address of the other player is missing, pass it with --peer");
+            return None;
+        }
+    };
+    match (local.parse(), peer.parse()) {
+        (Ok(local), Ok(peer)) => Some((local, peer)),
+        _ => {
+            println!("lockstep: invalid address {} or {}", local, peer);
+            None
+        }
+    }
+}
+
+// Palette selected on the command line, or else `PALETTE`.
+fn load_palette() -> Palette {
+    let name = cli_option("palette").unwrap_or_else(|| PALETTE.to_string());
+    match Palette::named_or_load(&name) {
+        Ok(palette) => palette,
+        Err(err) => {
+            println!("palette: unable to load {}: {}", name, err);
+            Palette::default()
+        }
+    }
+}
+
+// Kinds of balls to spawn, loaded from the file passed with `--kinds <path>`.
+// Without it, all balls are of a single kind.
+fn load_kinds() -> BallKinds {
+    let path = match cli_option("kinds") {
+        Some(path) => path,
+        None => return BallKinds::default(),
+    };
+
+    #[cfg(feature = "scene")]
+    match BallKinds::load(&path) {
+        Ok(kinds) => return kinds,
+        Err(err) => println!("kinds: unable to load {}: {}", path, err),
+    }
+    #[cfg(not(feature = "scene"))]
+    println!("kinds: unable to load {}: requires the `scene` feature", path);
+    BallKinds::default()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, StageLabel)]
+pub struct PhysicsStage;
+
+/// Steps of a physics tick, in order. Systems which extend the simulation can
+/// be placed relative to them within the `PhysicsStage`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, SystemLabel)]
+pub enum PhysicsSystem {
+    /// Balls are moved by their velocity and the forces acting on them.
+    Integrate,
+    /// Balls bounce off the walls, and candidate pairs of balls are found.
+    BroadPhase,
+    /// Overlapping candidate pairs are moved apart.
+    NarrowPhase,
+    /// Colliding balls bounce off of each other.
+    Resolve,
+    /// Joints move their bodies back into place.
+    Constraints,
+    /// Debug lines of the walls and the quadtree are drawn.
+    DebugDraw,
+}
+
+/// Pauses the simulation when set to `true`.
+pub struct Paused(pub bool);
+
+/// Draws the walls, the regions of the quadtree and the candidate pairs when
+/// set to `true`.
+pub struct PhysicsDebug(pub bool);
+
+/// Acceleration which is applied to all balls.
+pub struct Gravity(pub Vec2);
+
+// Prevent the physics stage from running while paused, or while waiting for
+// the inputs of the other player in lockstep. The fixed timestep criteria is
+// still checked, so no ticks pile up during the pause.
+fn skip_when_paused(
+    In(should_run): In<ShouldRun>,
+    paused: Res<Paused>,
+    lockstep: Option<Res<Lockstep>>,
+) -> ShouldRun {
+    let stalled = lockstep.map_or(false, |lockstep| !lockstep.inputs.ready());
+    if !paused.0 && !stalled {
+        return should_run;
+    }
+
+    match should_run {
+        ShouldRun::YesAndCheckAgain | ShouldRun::NoAndCheckAgain => ShouldRun::NoAndCheckAgain,
+        ShouldRun::Yes | ShouldRun::No => ShouldRun::No,
+    }
+}
+
+// Skip the collision systems while paused, without any balls, or when no ball
+// moved since the last tick. Criteria are checked before the tick's movement,
+// so a ball which starts moving in an idle scene is checked one tick later.
+fn collisions_needed(
+    paused: Res<Paused>,
+    balls: Query<(), With<Ball>>,
+    moved: Query<(), (With<Ball>, Changed<Transform>)>,
+) -> ShouldRun {
+    if paused.0 || balls.is_empty() || moved.is_empty() {
+        return ShouldRun::No;
+    }
+    ShouldRun::Yes
+}
+
+// Systems which advance the simulation by a single tick of `TIMESTEP`.
+fn physics_stage() -> SystemStage {
+    SystemStage::parallel()
+        .with_system(run_pre_physics_hooks.exclusive_system().at_start())
+        .with_system(apply_velocity.label(PhysicsSystem::Integrate))
+        .with_system_set(
+            SystemSet::new()
+                .with_run_criteria(collisions_needed)
+                .with_system(
+                    find_candidate_pairs
+                        .label(PhysicsSystem::BroadPhase)
+                        .after(PhysicsSystem::Integrate)
+                )
+                .with_system(
+                    check_candidate_pairs
+                        .label(PhysicsSystem::NarrowPhase)
+                        .after(PhysicsSystem::BroadPhase)
+                )
+                .with_system(
+                    resolve_collisions
+                        .label(PhysicsSystem::Resolve)
+                        .after(PhysicsSystem::NarrowPhase)
+                )
+        )
+        .with_system(tune_tree_capacity.after(PhysicsSystem::Resolve))
+        .with_system(apply_spin.after(PhysicsSystem::Resolve).before(PhysicsSystem::Constraints))
+        .with_system(solve_pin_joints.label(PhysicsSystem::Constraints).after(PhysicsSystem::Resolve))
+        .with_system(
+            draw_physics_debug
+                .label(PhysicsSystem::DebugDraw)
+                .after(PhysicsSystem::BroadPhase)
+        )
+        .with_system(run_post_resolve_hooks.exclusive_system().before_commands())
+        .with_system(run_post_physics_hooks.exclusive_system().at_end())
+        // .with_system(check_collisions.after(apply_velocity))
 }
 
 fn setup(mut cmd: Commands) {
@@ -111,60 +835,252 @@ fn display_fps(
     }
 }
 
-fn spawn_balls(mut cmd: Commands) {
-    let rand_radius = Uniform::from(BALL_RADIUS);
+fn spawn_balls(
+    mut cmd: Commands,
+    mut rng: ResMut<SimRng>,
+    mut counts: ResMut<KindCounts>,
+    mut colors: ResMut<SpawnColors>,
+    palette: Res<Palette>,
+    kinds: Res<BallKinds>,
+) {
+    let edge = EdgeCollider::with_restitution(Bounds::new(Vec2::ZERO, WIDTH, HEIGHT), WALL_RESTITUTION)
+        .with_friction(WALL_FRICTION);
+    spawn_initial_balls(&mut cmd, &mut rng, &mut counts, &mut colors, &palette, &kinds, &edge);
+    cmd.insert_resource(edge);
+}
+
+// Spawn the `BALLS` balls the simulation starts with, within `edge`.
+fn spawn_initial_balls(
+    cmd: &mut Commands,
+    rng: &mut StdRng,
+    counts: &mut KindCounts,
+    colors: &mut SpawnColors,
+    palette: &Palette,
+    kinds: &BallKinds,
+    edge: &EdgeCollider,
+) -> Vec<Entity> {
+    let mut spawned = Vec::with_capacity(BALLS as usize);
+    for _ in 0..BALLS as usize {
+        let kind = match kinds.choose(rng, counts) {
+            Some(kind) => kind,
+            None => break,
+        };
+        let ball = random_ball(rng, kinds, kind, edge, colors, palette);
+        let mut entity = ball.spawn(cmd);
+        kinds.get(kind).body.apply(&mut entity, ball.radius());
+        counts.add(kind);
+        spawned.push(entity.id());
+    }
+    spawned
+}
+
+// Create a ball of `kind` with a random size, velocity and position within
+// `edge`. The ball is colored by `colors` when its kind has no color.
+fn random_ball(
+    rng: &mut StdRng,
+    kinds: &BallKinds,
+    kind: Kind,
+    edge: &EdgeCollider,
+    colors: &mut SpawnColors,
+    palette: &Palette,
+) -> BallBuilder {
+    let velocity = random_velocity(rng);
+    let position = random_position(rng, edge);
+    new_ball(rng, kinds, kind, edge, colors, palette, velocity, position)
+}
+
+// Create a ball of `kind` with a random size, which moves with `velocity` at
+// `position`. The ball is colored by `colors` when its kind has no color.
+#[allow(clippy::too_many_arguments)]
+fn new_ball(
+    rng: &mut StdRng,
+    kinds: &BallKinds,
+    kind: Kind,
+    edge: &EdgeCollider,
+    colors: &mut SpawnColors,
+    palette: &Palette,
+    velocity: Vec2,
+    position: Vec2,
+) -> BallBuilder {
+    let [min, max] = kinds.get(kind).radius;
+    let radius = Uniform::from(min..=max).sample(rng);
+    let color = colors.next(palette, rng, radius, velocity, position, edge.bounds);
+
+    BallBundle::builder(radius)
+        .with_kind(kinds, kind)
+        .with_style(ball_style(kinds.get(kind).color(color)))
+        .with_velocity(velocity)
+        .with_position(position)
+}
+
+// Style of a ball with the given fill color.
+#[inline]
+fn ball_style(color: Color) -> BallStyle {
+    BallStyle {
+        fill: color,
+        outline: BALL_OUTLINE,
+    }
+}
+
+// Speeds a ball can have initially, as each direction is within
+// `BALL_INIT_SPEED`.
+fn init_speeds() -> RangeInclusive<f32> {
+    BALL_INIT_SPEED.start() * SQRT_2..=BALL_INIT_SPEED.end() * SQRT_2
+}
+
+// Random initial velocity of a ball.
+fn random_velocity(rng: &mut StdRng) -> Vec2 {
     let rand_velocity = Uniform::from(BALL_INIT_SPEED);
 
-    let edge = EdgeCollider::new(Bounds::new(Vec2::ZERO, WIDTH, HEIGHT));
-    let rand_pos_x = Uniform::from(edge.range_x(*BALL_RADIUS.end()));
-    let rand_pos_y = Uniform::from(edge.range_y(*BALL_RADIUS.end()));
-    cmd.insert_resource(edge);
+    let mut velocity = Vec2::new(
+        rand_velocity.sample(rng),
+        rand_velocity.sample(rng),
+    );
+    if rng.gen() {
+        velocity.x *= -1.;
+    }
+    if rng.gen() {
+        velocity.y *= -1.;
+    }
+    velocity
+}
 
-    let mut rng = rand::thread_rng();
-    let mut ball_color_index: usize = 0;
-
-    for _ in 0..BALLS {
-        let radius = rand_radius.sample(&mut rng);
-        let mut velocity = Vec2::new(
-            rand_velocity.sample(&mut rng),
-            rand_velocity.sample(&mut rng),
-        );
-        if rand::random() {
-            velocity.x *= -1.;
-        }
-        if rand::random() {
-            velocity.y *= -1.;
-        }
-
-        cmd.spawn_bundle(BallBundle::new(
-            BALL_COLORS[ball_color_index],
-            radius,
-            velocity,
-            Vec2::new(
-                rand_pos_x.sample(&mut rng),
-                rand_pos_y.sample(&mut rng),
-            ),
-        ));
+// Random position within `edge` at which a ball of any radius fits.
+fn random_position(rng: &mut StdRng, edge: &EdgeCollider) -> Vec2 {
+    random_point_in(rng, edge.spawn_area(*BALL_RADIUS.end()))
+}
 
-        ball_color_index += 1;
-        if ball_color_index == BALL_COLORS.len() {
-            ball_color_index = 0;
+// Spawn or despawn balls while the corresponding action is held. Each batch
+// is recorded as a single edit once the action is released.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn spawn_despawn_balls(
+    mut cmd: Commands,
+    mut rng: ResMut<SimRng>,
+    mut pool: ResMut<BallPool>,
+    mut counts: ResMut<KindCounts>,
+    mut pending: Local<f32>,
+    mut batch: Local<(Vec<Entity>, Vec<BallSnapshot>)>,
+    mut edits: EventWriter<Edit>,
+    mut colors: ResMut<SpawnColors>,
+    palette: Res<Palette>,
+    kinds: Res<BallKinds>,
+    actions: Res<Input<Action>>,
+    edge: Res<EdgeCollider>,
+    time: Res<Time>,
+    query: Query<(Entity, &Ball, &Transform, &Velocity, &DrawMode, Option<&Frozen>)>,
+) {
+    let spawn = actions.pressed(Action::SpawnBalls);
+    let despawn = actions.pressed(Action::DespawnBalls);
+    if spawn == despawn {
+        *pending = 0.;
+        if !batch.0.is_empty() {
+            edits.send(Edit::Spawned(std::mem::take(&mut batch.0)));
+        }
+        if !batch.1.is_empty() {
+            edits.send(Edit::Despawned(std::mem::take(&mut batch.1)));
+        }
+        return;
+    }
+
+    *pending += SPAWN_RATE * time.delta_seconds();
+    let count = *pending as usize;
+    *pending -= count as f32;
+
+    let rng = &mut **rng;
+    if spawn {
+        for _ in 0..count {
+            // reuse pooled balls which fit the kind before spawning new ones
+            let kind = match kinds.choose(rng, &counts) {
+                Some(kind) => kind,
+                None => break,
+            };
+            counts.add(kind);
+            let [min, max] = kinds.get(kind).radius;
+            let velocity = random_velocity(rng);
+            let position = random_position(rng, &edge);
+            let mass_model = kinds.get(kind).mass_model();
+            if let Some((entity, radius)) = pool.acquire_within(&mut cmd, min..=max, mass_model, velocity, position) {
+                let mut entity = cmd.entity(entity);
+                entity.insert(kinds.ball(kind, radius)).insert(kind);
+                kinds.get(kind).body.apply(&mut entity, radius);
+                let color = colors.next(&palette, rng, radius, velocity, position, edge.bounds);
+                entity.insert(ball_style(kinds.get(kind).color(color)).draw_mode());
+                batch.0.push(entity.id());
+                continue;
+            }
+
+            let ball = new_ball(rng, &kinds, kind, &edge, &mut colors, &palette, velocity, position);
+            let mut entity = ball.spawn(&mut cmd);
+            kinds.get(kind).body.apply(&mut entity, ball.radius());
+            batch.0.push(entity.id());
+        }
+    } else {
+        for (entity, ball, transform, velocity, draw_mode, frozen) in query.iter().take(count) {
+            batch.1.push(BallSnapshot::new(entity, ball, transform, velocity, draw_mode, frozen.is_some()));
+            pool.release(&mut cmd, entity, ball);
         }
     }
 }
 
-fn apply_velocity(mut query: Query<(&mut Transform, &mut Velocity)>, time: Res<Time>) {
-    for (mut transform, mut velocity) in query.iter_mut() {
-        // apply friction
-        // velocity.0.x -= velocity.0.x * 0.03 * time.delta_seconds();
-        // velocity.0.y -= velocity.0.y * 0.03 * time.delta_seconds();
+// Replace all balls with the balls the simulation starts with. Removing the
+// old balls and spawning the new ones are recorded as separate edits.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn reset_balls(
+    mut cmd: Commands,
+    mut rng: ResMut<SimRng>,
+    mut pool: ResMut<BallPool>,
+    mut counts: ResMut<KindCounts>,
+    mut edits: EventWriter<Edit>,
+    mut colors: ResMut<SpawnColors>,
+    palette: Res<Palette>,
+    kinds: Res<BallKinds>,
+    actions: Res<Input<Action>>,
+    edge: Res<EdgeCollider>,
+    query: Query<(Entity, &Ball, &Transform, &Velocity, &DrawMode, Option<&Frozen>)>,
+) {
+    if !actions.just_pressed(Action::Reset) {
+        return;
+    }
+
+    let mut despawned = Vec::new();
+    for (entity, ball, transform, velocity, draw_mode, frozen) in query.iter() {
+        despawned.push(BallSnapshot::new(entity, ball, transform, velocity, draw_mode, frozen.is_some()));
+        pool.release(&mut cmd, entity, ball);
+    }
+    edits.send(Edit::Despawned(despawned));
+
+    *counts = KindCounts::default();
+    let spawned = spawn_initial_balls(&mut cmd, &mut rng, &mut counts, &mut colors, &palette, &kinds, &edge);
+    edits.send(Edit::Spawned(spawned));
+}
+
+fn toggle_pause(actions: Res<Input<Action>>, mut paused: ResMut<Paused>) {
+    if actions.just_pressed(Action::Pause) {
+        paused.0 = !paused.0;
+    }
+}
 
-        // apply velocity
-        transform.translation.x += velocity.0.x * time.delta_seconds();
-        transform.translation.y += velocity.0.y * time.delta_seconds();
+fn toggle_physics_debug(actions: Res<Input<Action>>, mut debug: ResMut<PhysicsDebug>) {
+    if actions.just_pressed(Action::ToggleDebug) {
+        debug.0 = !debug.0;
     }
 }
 
+// Tilt gravity while there is input for it.
+fn tilt_gravity(axes: Res<ActionAxes>, mut gravity: ResMut<Gravity>, mut tilting: Local<bool>) {
+    if axes.gravity_tilt == Vec2::ZERO {
+        if *tilting {
+            // restore gravity after tilting
+            gravity.0 = GRAVITY;
+            *tilting = false;
+        }
+        return;
+    }
+
+    gravity.0 = GRAVITY + axes.gravity_tilt * GRAVITY_TILT;
+    *tilting = true;
+}
+
 #[allow(dead_code)]
 // fn check_collisions(edge: Res<EdgeCollider>, mut query: Query<(Entity, &mut Transform, &mut Velocity, &Ball)>) {
 //     for (_, mut transform, mut velocity, ball) in query.iter_mut() {
@@ -197,47 +1113,154 @@ fn apply_velocity(mut query: Query<(&mut Transform, &mut Velocity)>, time: Res<T
 //         (_, transform_b, mut velocity_b, ball_b)
 //         ] = query.many_mut(balls);
 //
-//         balls_bounce_after_collision([
+//         balls_bounce_after_collision(*model, [
 //             (transform_a.deref(), &mut *velocity_a, ball_a),
 //             (transform_b.deref(), &mut *velocity_b, ball_b),
 //         ]);
 //     }
 // }
+// Bounce balls off the walls and find all candidate pairs of balls which might
+// collide, using the configured broad phase.
 #[allow(dead_code)]
-fn check_collisions_quadtree(
+#[allow(clippy::too_many_arguments)]
+fn find_candidate_pairs(
     edge: Res<EdgeCollider>,
-    mut debug_lines: ResMut<DebugLines>,
+    broad_phase: Res<BroadPhase>,
+    mut capacity: ResMut<TreeCapacity>,
+    mut wall_hits: EventWriter<BallHitWall>,
+    mut pair_cache: Local<PairCache>,
+    mut pair_buffer: ResMut<PairBuffer>,
+    mut ball_tree: ResMut<BallTree>,
+    mut chunks: ResMut<ChunkedWorld>,
+    #[cfg(feature = "gpu")] gpu_pairs: Option<Res<GpuPairs>>,
+    timings: Option<ResMut<PhysicsTimings>>,
+    pool: Option<Res<ComputeTaskPool>>,
+    parallel_build: Option<Res<ParallelTreeBuild>>,
+    lods: Query<&Lod>,
     mut query: Query<(Entity, &mut Transform, &mut Velocity, &Ball)>,
 ) {
-    let debug_lines = &mut *debug_lines;
-    edge.bounds.debug_draw_lines(debug_lines, Some(Color::WHITE));
+    let pair_buffer = &mut *pair_buffer;
+    pair_buffer.clear();
 
-    let mut tree = QuadTree::new(
-        edge.bounds,
-        Options {
-            capacity: 4,
-            min_size: Some(Vec2::splat(BALL_RADIUS.end() * 2.)),
-            ..default()
-        },
+    let build_start = Instant::now();
+    let options = capacity.options(
+        Options::builder()
+            .min_size(Vec2::splat(BALL_RADIUS.end() * 2.))
+            .build()
+            .expect("invalid quadtree options")
     );
+    let deferred = matches!(*broad_phase, BroadPhase::Deferred(_));
+    let mut tree = match *broad_phase {
+        // the tree of the previous tick is kept while its options stay the
+        // same, it then continues with the splits it deferred
+        BroadPhase::Deferred(max_splits) => {
+            let options = Options { max_splits: Some(max_splits), ..options };
+            options.validate().expect("invalid deferred broad phase");
+            let mut previous = std::mem::replace(&mut ball_tree.0, QuadTree::new(edge.bounds, options));
+            if previous.options() == options && previous.bounds() == edge.bounds {
+                previous.next_frame();
+                previous
+            } else {
+                QuadTree::new(edge.bounds, options)
+            }
+        }
+        _ => QuadTree::new(edge.bounds, options),
+    };
+    let mut linear = match *broad_phase {
+        BroadPhase::Linear(depth) => Some(LinearQuadTree::new(edge.bounds, depth)),
+        _ => None,
+    };
+    // balls are stored by their center, so the leaves don't need a minimum
+    // size. Centers on the edge between leaves are stored in each of them.
+    let mut reach = match *broad_phase {
+        BroadPhase::Reach => Some(QuadTree::<MaxRadius>::with_aggregate(edge.bounds, capacity.options(Options::default()))),
+        _ => None,
+    };
+    let cached = match *broad_phase {
+        BroadPhase::Cached(_) | BroadPhase::Chunked(_) | BroadPhase::BruteForce => true,
+        #[cfg(feature = "gpu")]
+        BroadPhase::Gpu(_) => true,
+        _ => false,
+    };
+    // balls are collected first when the tree might be built in parallel
+    let mut elements = match (&pool, &parallel_build) {
+        (Some(_), Some(_)) if !deferred => Some(Vec::new()),
+        _ => None,
+    };
 
     for (entity, mut transform, mut velocity, ball) in query.iter_mut() {
-        let transform = &mut *transform;
-        let velocity = &mut *velocity;
-
-        let _ = edge.check_left(ball, transform, velocity)
-            || edge.check_right(ball, transform, velocity);
-
-        let _ = edge.check_top(ball, transform, velocity)
-            || edge.check_bottom(ball, transform, velocity);
-
-        if let Err(err) = tree.insert(
-            Location::new(transform.translation.truncate(), ball.radius * 2., ball.radius * 2.),
-            entity,
-        ) {
-            match err {
-                ErrorKind::OutOfBounds(bounds, location) => {
-                    println!("err: {}: {}, {:?} not in {:?}", entity.id(), err, location, bounds)
+        // far balls which didn't move this tick are left out, they are still
+        // in the tree which is kept by the deferred broad phase
+        if lods.get(entity).map_or(false, |lod| lod.skipped) {
+            continue;
+        }
+        // only mutably borrow balls near a wall, so other balls are not
+        // marked as changed
+        if edge.touches_wall(ball, &transform) {
+            let transform = &mut *transform;
+            let velocity = &mut *velocity;
+
+            let hit_x = edge.check_left(ball, transform, velocity)
+                .or_else(|| edge.check_right(ball, transform, velocity));
+
+            let hit_y = edge.check_top(ball, transform, velocity)
+                .or_else(|| edge.check_bottom(ball, transform, velocity));
+
+            for hit in [hit_x, hit_y].into_iter().flatten() {
+                wall_hits.send(BallHitWall(entity, hit));
+            }
+        }
+
+        if let Some(linear) = &mut linear {
+            let _ = linear.insert(transform.translation.truncate(), entity);
+            continue;
+        }
+        if let Some(reach) = &mut reach {
+            let position = transform.translation.truncate();
+            if let Err(err) = reach.insert_with(Location::Point(position), entity, ball.radius) {
+                println!("err: {}", err);
+            }
+            pair_buffer.balls.push((entity, position, ball.radius));
+            continue;
+        }
+        if cached {
+            pair_buffer.balls.push((entity, transform.translation.truncate(), ball.radius));
+            continue;
+        }
+        let location = Location::new(transform.translation.truncate(), ball.radius * 2., ball.radius * 2.);
+        if let Some(elements) = &mut elements {
+            elements.push((location, entity));
+            continue;
+        }
+        if deferred {
+            if let Err(err) = tree.relocate(entity, location) {
+                println!("err: {}", err);
+            }
+            continue;
+        }
+        if let Err(err) = tree.insert(location, entity) {
+            println!("err: {}", err);
+        }
+    }
+    if deferred {
+        let despawned: Vec<Entity> = tree.entities()
+            .filter(|entity| query.get(*entity).is_err())
+            .collect();
+        for entity in despawned {
+            tree.remove_entity(entity);
+        }
+    }
+    if let (Some(elements), Some(pool), Some(parallel_build)) = (elements, &pool, &parallel_build) {
+        if elements.len() >= parallel_build.min_balls {
+            let (built, errors) = QuadTree::build_parallel(edge.bounds, options, &elements, pool);
+            tree = built;
+            for err in errors {
+                println!("err: {}", err);
+            }
+        } else {
+            for (location, entity) in elements {
+                if let Err(err) = tree.insert(location, entity) {
+                    println!("err: {}", err);
                 }
             }
         }
@@ -248,56 +1271,329 @@ fn check_collisions_quadtree(
     // query.for_each(|(x, y, z)| {});
     // query.par_for_each(pool, 8, |(x, y, z)| {});
 
-    for region in tree.regions() {
-        region.bounds().debug_draw_lines(debug_lines, None);
-        let elems = region.elements().unwrap();
-        if elems.len() < 2 {
-            continue;
+    let build_time = build_start.elapsed();
+    capacity.record_build(build_time);
+    let pair_start = Instant::now();
+    tree.for_each_leaf(&mut |leaf| {
+        let elems = leaf.leaf_elements().unwrap();
+        for (i, (_, a)) in elems.iter().enumerate() {
+            for (_, b) in elems[i + 1..].iter() {
+                pair_buffer.push(*a, *b);
+            }
         }
+    });
+    if let Some(linear) = &mut linear {
+        linear.sort();
+        linear_pairs(linear, &query, pair_buffer);
+    }
+    if let Some(reach) = &reach {
+        reach_pairs(reach, pair_buffer);
+    }
+    if let BroadPhase::Cached(margin) = *broad_phase {
+        if !pair_cache.is_valid(margin, &pair_buffer.balls) {
+            pair_cache.rebuild(edge.bounds, options, margin, &pair_buffer.balls);
+        }
+        pair_buffer.extend(pair_cache.pairs());
+    }
+    if let BroadPhase::Chunked(size) = *broad_phase {
+        for err in chunks.rebuild(size, options, &pair_buffer.balls) {
+            println!("err: {}", err);
+        }
+        pair_buffer.extend(chunks.pairs());
+    }
+    if let BroadPhase::BruteForce = *broad_phase {
+        brute_force_pairs(pair_buffer);
+    }
+    #[cfg(feature = "gpu")]
+    if let (BroadPhase::Gpu(_), Some(gpu_pairs)) = (*broad_phase, &gpu_pairs) {
+        for [a, b] in gpu_pairs.latest() {
+            // the pairs are a frame old, their balls may be gone by now
+            if query.get(a).is_ok() && query.get(b).is_ok() {
+                pair_buffer.push(a, b);
+            }
+        }
+    }
+    pair_buffer.dedup();
+    capacity.record_pairs(pair_start.elapsed());
+    if let Some(mut timings) = timings {
+        timings.record_broad_phase(build_time, pair_buffer.pairs().len());
+        timings.record_stage(PhysicsSystem::BroadPhase, build_start.elapsed());
+    }
 
-        let mut collisions = BallCollisions::new(Some(elems.capacity() * 2));
-        for (_, a) in elems.clone() {
-            for (_, b) in elems.clone() {
-                if a == b {
-                    continue;
-                }
+    // keep the tree around for picking balls, debug drawing and the other
+    // users of `BallTree`. Broad phases which find their pairs without it
+    // leave it empty, so it's filled here.
+    if !matches!(*broad_phase, BroadPhase::QuadTree | BroadPhase::Deferred(_)) {
+        for (entity, transform, _, ball) in query.iter() {
+            let location = Location::new(transform.translation.truncate(), ball.radius * 2., ball.radius * 2.);
+            if let Err(err) = tree.insert(location, entity) {
+                println!("err: {}", err);
+            }
+        }
+    }
+    ball_tree.0 = tree;
+}
+
+// Find all pairs of balls of which the bounding circles touch, by checking
+// each ball against every other ball.
+fn brute_force_pairs(buffer: &mut PairBuffer) {
+    for (i, (a, position_a, radius_a)) in buffer.balls.iter().enumerate() {
+        for (b, position_b, radius_b) in buffer.balls[i + 1..].iter() {
+            let reach = radius_a + radius_b;
+            if position_a.distance_squared(*position_b) <= reach * reach {
+                buffer.pairs.push(if a < b { [*a, *b] } else { [*b, *a] });
+            }
+        }
+    }
+}
 
-                let [
-                (a, mut transform_a, _, ball_a),
-                (b, mut transform_b, _, ball_b)
-                ] = query.many_mut([a, b]);
+// Find all pairs of balls within reach of each other, using the largest
+// possible radius. Each pair is added once, by the ball with the lowest entity.
+fn linear_pairs(
+    tree: &LinearQuadTree,
+    query: &Query<(Entity, &mut Transform, &mut Velocity, &Ball)>,
+    buffer: &mut PairBuffer,
+) {
+    for (position, a) in tree.elements() {
+        let radius = match query.get(a) {
+            Ok((_, _, _, ball)) => ball.radius,
+            Err(_) => continue,
+        };
 
-                debug_lines.line(transform_a.translation, transform_b.translation, 0.);
+        for (_, b) in tree.query_circle(position, radius + BALL_RADIUS.end()) {
+            if a < b {
+                buffer.push(a, b);
+            }
+        }
+    }
+}
 
-                collisions.check([
-                    (a, &mut *transform_a, ball_a),
-                    (b, &mut *transform_b, ball_b),
-                ]);
+// Find all pairs of balls which overlap, using the largest radius near each
+// ball. Each pair is added once, by the ball with the lowest entity.
+fn reach_pairs(tree: &QuadTree<MaxRadius>, buffer: &mut PairBuffer) {
+    let mut found = std::mem::take(&mut buffer.found);
+    for i in 0..buffer.balls.len() {
+        let (a, position, radius) = buffer.balls[i];
+        found.clear();
+        tree.query_reach(position, radius, &mut found);
+        // balls found in multiple leaves are paired more than once, those
+        // pairs are deduplicated with all others
+        for (_, b) in found.iter() {
+            if a < *b {
+                buffer.push(a, *b);
             }
         }
+    }
+    buffer.found = found;
+}
 
-        for balls in collisions {
-            let [
-            (_, transform_a, mut velocity_a, ball_a),
-            (_, transform_b, mut velocity_b, ball_b)
-            ] = query.many_mut(balls);
+// Check all candidate pairs and move apart the balls which overlap. Separate
+// islands of balls are checked and resolved at once during `Resolve` instead,
+// when they are resolved in parallel.
+#[allow(clippy::too_many_arguments)]
+fn check_candidate_pairs(
+    mut capacity: ResMut<TreeCapacity>,
+    mut pair_buffer: ResMut<PairBuffer>,
+    islands: Option<Res<CollisionIslands>>,
+    pool: Option<Res<ComputeTaskPool>>,
+    mut separation: Option<ResMut<Separation>>,
+    timings: Option<ResMut<PhysicsTimings>>,
+    mut query: Query<(Entity, &mut Transform, &mut Velocity, &Ball)>,
+    bodies: Query<(Option<&BoxBody>, Option<&CapsuleBody>, Option<&CompoundCollider>)>,
+    pins: Query<&PinJoint>,
+) {
+    if islands.is_some() && pool.is_some() {
+        return;
+    }
+
+    let start = Instant::now();
+    if let Some(separation) = &mut separation {
+        separation.begin_tick();
+    }
+    let buffer = &mut *pair_buffer;
+    for pair in buffer.pairs.iter() {
+        let [
+        (a, transform_a, _, ball_a),
+        (b, transform_b, _, ball_b)
+        ] = query.many_mut(*pair);
+
+        let body_a = bodies.get(a).unwrap_or_default();
+        let body_b = bodies.get(b).unwrap_or_default();
+        let pinned = [pins.get(a).is_ok(), pins.get(b).is_ok()];
+        if body_a != (None, None, None) || body_b != (None, None, None) || pinned.contains(&true) {
+            let shapes_a = body_shapes(&transform_a, ball_a, body_a);
+            let shapes_b = body_shapes(&transform_b, ball_b, body_b);
+            let contact = match body_contact(&shapes_a, &shapes_b) {
+                Some(contact) => contact,
+                None => continue,
+            };
+            let fraction = separation.as_mut().map_or(1., |separation| separation.contact(*pair));
+            let offset = (contact.normal * contact.depth * fraction).extend(0.);
+            // pinned bodies stay in place, the other body is moved all the way.
+            // Two pinned bodies both stay, and keep overlapping.
+            let [share_a, share_b] = match pinned {
+                [true, true] => [0., 0.],
+                [true, false] => [0., 1.],
+                [false, true] => [1., 0.],
+                [false, false] => [0.5, 0.5],
+            };
+            if offset != Vec3::ZERO {
+                let (mut transform_a, mut transform_b) = (transform_a, transform_b);
+                transform_a.translation -= offset * share_a;
+                transform_b.translation += offset * share_b;
+            }
+            buffer.shaped.push(([a, b], contact));
+            continue;
+        }
 
-            balls_bounce_after_collision([
-                (transform_a.deref(), &mut *velocity_a, ball_a),
-                (transform_b.deref(), &mut *velocity_b, ball_b),
-            ]);
+        let balls = [(&*transform_a, ball_a), (&*transform_b, ball_b)];
+        let mut positions = match separated_positions(balls) {
+            Some(positions) => positions,
+            None => continue,
+        };
+        if let Some(separation) = &mut separation {
+            let fraction = separation.contact(*pair);
+            if fraction < 1. {
+                positions = separated_positions_by(balls, fraction).unwrap_or(positions);
+            }
+        }
+        let [position_a, position_b] = positions;
+        // balls which merely touch are not moved, so they aren't marked as
+        // changed either
+        for (mut transform, position) in [(transform_a, position_a), (transform_b, position_b)] {
+            if transform.translation.truncate() != position {
+                transform.translation.x = position.x;
+                transform.translation.y = position.y;
+            }
         }
+        buffer.collisions.push(a, b);
     }
+    capacity.record_pairs(start.elapsed());
+    if let Some(mut timings) = timings {
+        timings.record_stage(PhysicsSystem::NarrowPhase, start.elapsed());
+    }
+}
 
-    // query.iter_combinations();
-    // for (a, b) in tree.iter_combinations() {
-    //     query.
-    // }
+// Bounce off the colliding balls.
+#[allow(clippy::too_many_arguments)]
+fn resolve_collisions(
+    model: Res<CollisionModel>,
+    mut capacity: ResMut<TreeCapacity>,
+    mut collided: EventWriter<BallCollided>,
+    pair_buffer: Res<PairBuffer>,
+    islands: Option<Res<CollisionIslands>>,
+    pool: Option<Res<ComputeTaskPool>>,
+    mut timings: Option<ResMut<PhysicsTimings>>,
+    contacts: Option<ResMut<ContactCache>>,
+    mut query: Query<(Entity, &mut Transform, &mut Velocity, &Ball)>,
+    bodies: Query<(Option<&BoxBody>, Option<&CapsuleBody>, Option<&CompoundCollider>)>,
+    mut spins: Query<&mut AngularVelocity>,
+    pins: Query<&PinJoint>,
+) {
+    let start = Instant::now();
+    for (balls, contact) in pair_buffer.shaped.iter() {
+        collided.send(BallCollided(balls[0], balls[1]));
+
+        let mut bodies = balls.map(|entity| {
+            let (_, transform, velocity, ball) = query.get(entity).unwrap();
+            ContactBody {
+                center: transform.translation.truncate(),
+                velocity: velocity.0,
+                spin: spins.get(entity).ok().map(|spin| spin.0),
+                mass: ball.mass,
+                inertia: moment_of_inertia(ball, bodies.get(entity).unwrap_or_default()),
+                restitution: ball.restitution,
+                pivot: pins.get(entity).ok().map(|joint| joint.anchor),
+            }
+        });
+        bounce_at_contact(*model, contact, &mut bodies);
+        for (entity, body) in balls.iter().zip(bodies) {
+            query.get_mut(*entity).unwrap().2.0 = body.velocity;
+            if let (Ok(mut spin), Some(body_spin)) = (spins.get_mut(*entity), body.spin) {
+                spin.0 = body_spin;
+            }
+        }
+    }
+    if let (Some(_), Some(pool)) = (islands, &pool) {
+        resolve_islands(&pool.0, pair_buffer.pairs(), *model, &mut collided, &mut query);
+        capacity.record_pairs(start.elapsed());
+        if let Some(timings) = &mut timings {
+            timings.record_resolve(start.elapsed());
+        }
+        return;
+    }
+    if let Some(mut contacts) = contacts {
+        let collisions = pair_buffer.collisions.as_slice();
+        for balls in collisions {
+            collided.send(BallCollided(balls[0], balls[1]));
+        }
+        contacts.solve(*model, collisions, &mut query);
+        capacity.record_pairs(start.elapsed());
+        if let Some(timings) = &mut timings {
+            timings.record_resolve(start.elapsed());
+        }
+        return;
+    }
 
-    // for q in query.iter_mut() {}
-    // for partitions in qt.iter() {
-    //     let mut combinations = .iter_combinations_mut();
-    //     for q in partitions.iter_combinations_mut() {}
-    // }
-    // print!("w:{}, h:{}, l:{}\n", qt.width(), qt.height(), qt.len())
+    for balls in pair_buffer.collisions.as_slice() {
+        collided.send(BallCollided(balls[0], balls[1]));
+
+        let [
+        (_, transform_a, mut velocity_a, ball_a),
+        (_, transform_b, mut velocity_b, ball_b)
+        ] = query.many_mut(*balls);
+
+        balls_bounce_after_collision(*model, [
+            (transform_a.deref(), &mut *velocity_a, ball_a),
+            (transform_b.deref(), &mut *velocity_b, ball_b),
+        ]);
+    }
+    capacity.record_pairs(start.elapsed());
+    if let Some(timings) = &mut timings {
+        timings.record_resolve(start.elapsed());
+    }
 }
+
+// Draw the walls, the leaves of the quadtree and the candidate pairs within
+// each leaf.
+fn draw_physics_debug(
+    edge: Res<EdgeCollider>,
+    ball_tree: Res<BallTree>,
+    detail: Option<Res<DebugDetail>>,
+    debug: Option<Res<PhysicsDebug>>,
+    timings: Option<ResMut<PhysicsTimings>>,
+    mut debug_lines: ResMut<DebugLines>,
+) {
+    if debug.map_or(false, |debug| !debug.0) {
+        return;
+    }
+    let start = Instant::now();
+    let debug_lines = &mut *debug_lines;
+    edge.bounds.debug_draw_lines(debug_lines, Some(Color::WHITE));
+
+    let (region_outlines, pair_lines) = match detail {
+        Some(detail) => (detail.region_outlines(), detail.pair_lines()),
+        None => (true, true),
+    };
+    if !region_outlines && !pair_lines {
+        return;
+    }
+
+    ball_tree.0.for_each_leaf(&mut |leaf| {
+        if region_outlines {
+            leaf.bounds().debug_draw_lines(debug_lines, None);
+        }
+        if !pair_lines {
+            return;
+        }
+        let elems = leaf.leaf_elements().unwrap();
+        for (i, (location_a, _)) in elems.iter().enumerate() {
+            for (location_b, _) in elems[i + 1..].iter() {
+                debug_lines.line(location_a.center().extend(0.), location_b.center().extend(0.), 0.);
+            }
+        }
+    });
+    if let Some(mut timings) = timings {
+        timings.record_stage(PhysicsSystem::DebugDraw, start.elapsed());
+    }
+}
\ No newline at end of file
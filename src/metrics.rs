@@ -0,0 +1,333 @@
+use std::collections::VecDeque;
+
+use bevy::core::FixedTimestep;
+use bevy::core_pipeline::{self, Transparent2d};
+use bevy::diagnostic::{Diagnostics, FrameTimeDiagnosticsPlugin};
+use bevy::math::const_vec2;
+use bevy::prelude::*;
+use bevy::render::camera::{ActiveCamera, CameraTypePlugin, RenderTarget};
+use bevy::render::render_graph::{self, NodeRunError, RenderGraph, RenderGraphContext, SlotValue};
+use bevy::render::render_phase::RenderPhase;
+use bevy::render::renderer::RenderContext;
+use bevy::render::view::RenderLayers;
+use bevy::render::{RenderApp, RenderStage};
+use bevy::window::{CreateWindow, WindowId};
+use bevy_prototype_lyon::entity::Path;
+
+use crate::*;
+
+/// Opens a second window with live charts of the frame time, the amount of
+/// candidate pairs and the total kinetic energy of the simulation. The latest
+/// values, including the fps, are shown in the title of that window.
+pub struct MetricsWindowPlugin {
+    history: usize,
+    rate: f64,
+}
+
+impl MetricsWindowPlugin {
+    pub fn with_history(history: usize) -> Self {
+        Self { history, rate: 20. }
+    }
+}
+
+impl Default for MetricsWindowPlugin {
+    fn default() -> Self { Self::with_history(240) }
+}
+
+impl Plugin for MetricsWindowPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugin(CameraTypePlugin::<MetricsCamera>::default())
+            .insert_resource(Metrics::with_history(self.history))
+            .add_startup_system(open_metrics_window)
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(FixedTimestep::steps_per_second(self.rate))
+                    .with_system(record_metrics)
+                    .with_system(draw_charts.after(record_metrics))
+                    .with_system(display_metrics.after(record_metrics)),
+            );
+
+        // the metrics camera is not a regular 2d camera, so it needs its own
+        // render phase and a node which draws the 2d graph for it
+        let render_app = app.sub_app_mut(RenderApp);
+        render_app.add_system_to_stage(RenderStage::Extract, extract_metrics_camera_phase);
+
+        let mut render_graph = render_app.world.resource_mut::<RenderGraph>();
+        let node = render_graph.add_node("metrics_window_cam", MetricsDriverNode);
+        render_graph
+            .add_node_edge(core_pipeline::node::MAIN_PASS_DEPENDENCIES, node)
+            .unwrap();
+        render_graph
+            .add_node_edge(core_pipeline::node::CLEAR_PASS_DRIVER, node)
+            .unwrap();
+    }
+}
+
+/// Most recent samples of a single metric, oldest first.
+pub struct History {
+    samples: VecDeque<f32>,
+    capacity: usize,
+}
+
+impl History {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            samples: VecDeque::with_capacity(capacity),
+            capacity: capacity.max(2),
+        }
+    }
+
+    /// Add a sample, dropping the oldest one when the history is full.
+    #[inline]
+    pub fn push(&mut self, value: f32) {
+        if self.samples.len() == self.capacity {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(value);
+    }
+
+    #[inline]
+    pub fn latest(&self) -> Option<f32> { self.samples.back().copied() }
+
+    /// Points of a line chart of the samples, which spans `size` with its
+    /// bottom left corner at the origin. The full history always spans the
+    /// width, while the height is scaled to the samples' range.
+    pub fn sparkline(&self, size: Vec2) -> Vec<Vec2> {
+        let min = self.samples.iter().copied().fold(f32::INFINITY, f32::min);
+        let max = self.samples.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+        let range = if max > min { max - min } else { 1. };
+        let step = size.x / (self.capacity - 1) as f32;
+
+        self.samples.iter()
+            .enumerate()
+            .map(|(i, value)| Vec2::new(i as f32 * step, (value - min) / range * size.y))
+            .collect()
+    }
+}
+
+pub struct Metrics {
+    /// Average frame time, in milliseconds.
+    pub frame_time: History,
+    /// Candidate pairs checked during the last physics tick.
+    pub pairs: History,
+    /// Total kinetic energy of all balls.
+    pub energy: History,
+    /// Memory used by the quadtree of the last physics tick, in KiB.
+    pub tree_memory: History,
+}
+
+impl Metrics {
+    pub fn with_history(history: usize) -> Self {
+        Self {
+            frame_time: History::with_capacity(history),
+            pairs: History::with_capacity(history),
+            energy: History::with_capacity(history),
+            tree_memory: History::with_capacity(history),
+        }
+    }
+}
+
+/// Marks the camera which renders the charts into the metrics window.
+#[derive(Component, Default)]
+pub struct MetricsCamera;
+
+/// Window in which the metrics are shown.
+pub struct MetricsWindow(pub WindowId);
+
+#[derive(Component, Clone, Copy, Debug)]
+enum Chart {
+    FrameTime,
+    Pairs,
+    Energy,
+    TreeMemory,
+}
+
+impl Chart {
+    const ALL: [Chart; 4] = [Chart::FrameTime, Chart::Pairs, Chart::Energy, Chart::TreeMemory];
+
+    #[inline]
+    fn history(self, metrics: &Metrics) -> &History {
+        match self {
+            Chart::FrameTime => &metrics.frame_time,
+            Chart::Pairs => &metrics.pairs,
+            Chart::Energy => &metrics.energy,
+            Chart::TreeMemory => &metrics.tree_memory,
+        }
+    }
+
+    #[inline]
+    fn color(self) -> Color {
+        match self {
+            Chart::FrameTime => Color::YELLOW,
+            Chart::Pairs => Color::CYAN,
+            Chart::Energy => Color::ORANGE,
+            Chart::TreeMemory => Color::GREEN,
+        }
+    }
+}
+
+const WINDOW_SIZE: Vec2 = const_vec2!([420., 420.]);
+const CHART_SIZE: Vec2 = const_vec2!([380., 80.]);
+const CHART_GAP: f32 = 20.;
+
+// Only entities on this layer are rendered into the metrics window.
+const METRICS_LAYER: u8 = 1;
+
+fn open_metrics_window(mut cmd: Commands, mut create_window: EventWriter<CreateWindow>) {
+    let id = WindowId::new();
+    create_window.send(CreateWindow {
+        id,
+        descriptor: WindowDescriptor {
+            title: "Metrics".to_string(),
+            width: WINDOW_SIZE.x,
+            height: WINDOW_SIZE.y,
+            ..default()
+        },
+    });
+
+    let camera = OrthographicCameraBundle::new_2d();
+    cmd.spawn_bundle(OrthographicCameraBundle {
+        camera: Camera {
+            target: RenderTarget::Window(id),
+            ..camera.camera
+        },
+        orthographic_projection: camera.orthographic_projection,
+        visible_entities: camera.visible_entities,
+        frustum: camera.frustum,
+        transform: camera.transform,
+        global_transform: camera.global_transform,
+        marker: MetricsCamera,
+    })
+        .insert(RenderLayers::layer(METRICS_LAYER));
+
+    // charts are stacked from top to bottom, each within a dim frame
+    let total_height = Chart::ALL.len() as f32 * (CHART_SIZE.y + CHART_GAP) - CHART_GAP;
+    for (i, chart) in Chart::ALL.into_iter().enumerate() {
+        let bottom = total_height / 2. - CHART_SIZE.y - i as f32 * (CHART_SIZE.y + CHART_GAP);
+        let transform = Transform::from_xyz(-CHART_SIZE.x / 2., bottom, 0.);
+
+        cmd.spawn_bundle(GeometryBuilder::build_as(
+            &shapes::Rectangle {
+                extents: CHART_SIZE,
+                origin: shapes::RectangleOrigin::BottomLeft,
+            },
+            DrawMode::Stroke(StrokeMode::new(Color::DARK_GRAY, 1.)),
+            transform,
+        ))
+            .insert(RenderLayers::layer(METRICS_LAYER));
+
+        cmd.spawn_bundle(GeometryBuilder::build_as(
+            &shapes::Polygon {
+                points: vec![Vec2::ZERO, Vec2::new(CHART_SIZE.x, 0.)],
+                closed: false,
+            },
+            DrawMode::Stroke(StrokeMode::new(chart.color(), 1.5)),
+            transform.with_translation(transform.translation + Vec3::Z),
+        ))
+            .insert(chart)
+            .insert(RenderLayers::layer(METRICS_LAYER));
+    }
+
+    cmd.insert_resource(MetricsWindow(id));
+}
+
+fn record_metrics(
+    mut metrics: ResMut<Metrics>,
+    diagnostics: Res<Diagnostics>,
+    pair_buffer: Res<PairBuffer>,
+    ball_tree: Res<BallTree>,
+    query: Query<(&Ball, &Velocity)>,
+) {
+    let frame_time = diagnostics.get(FrameTimeDiagnosticsPlugin::FRAME_TIME)
+        .and_then(|frame_time| frame_time.average())
+        .unwrap_or_default();
+    let energy: f32 = query.iter()
+        .map(|(ball, velocity)| 0.5 * ball.mass * velocity.0.length_squared())
+        .sum();
+
+    metrics.frame_time.push(frame_time as f32 * 1000.);
+    metrics.pairs.push(pair_buffer.pairs.len() as f32);
+    metrics.energy.push(energy);
+    metrics.tree_memory.push(ball_tree.0.memory_usage() as f32 / 1024.);
+}
+
+fn draw_charts(metrics: Res<Metrics>, mut query: Query<(&mut Path, &Chart)>) {
+    for (mut path, chart) in query.iter_mut() {
+        let points = chart.history(&metrics).sparkline(CHART_SIZE);
+        if points.len() < 2 {
+            continue;
+        }
+
+        *path = ShapePath::build_as(&shapes::Polygon { points, closed: false });
+    }
+}
+
+fn display_metrics(
+    mut windows: ResMut<Windows>,
+    window: Option<Res<MetricsWindow>>,
+    metrics: Res<Metrics>,
+    diagnostics: Res<Diagnostics>,
+) {
+    // the window might not be created yet, or already closed
+    let window = match window.and_then(|window| windows.get_mut(window.0)) {
+        Some(window) => window,
+        None => return,
+    };
+
+    let fps = diagnostics.get_measurement(FrameTimeDiagnosticsPlugin::FPS)
+        .map(|fps| fps.value)
+        .unwrap_or_default();
+    window.set_title(format!(
+        "Metrics: {:.0} fps, {:.2} ms, {} pairs, {:.0} energy, {:.0} KiB tree",
+        fps,
+        metrics.frame_time.latest().unwrap_or_default(),
+        metrics.pairs.latest().unwrap_or_default(),
+        metrics.energy.latest().unwrap_or_default(),
+        metrics.tree_memory.latest().unwrap_or_default(),
+    ));
+}
+
+// Add the phase in which the 2d shapes are drawn to the metrics camera.
+fn extract_metrics_camera_phase(mut cmd: Commands, active: Res<ActiveCamera<MetricsCamera>>) {
+    if let Some(entity) = active.get() {
+        cmd.get_or_spawn(entity)
+            .insert(RenderPhase::<Transparent2d>::default());
+    }
+}
+
+struct MetricsDriverNode;
+
+impl render_graph::Node for MetricsDriverNode {
+    fn run(
+        &self,
+        graph: &mut RenderGraphContext,
+        _: &mut RenderContext,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        if let Some(camera) = world.resource::<ActiveCamera<MetricsCamera>>().get() {
+            graph.run_sub_graph(core_pipeline::draw_2d_graph::NAME, vec![SlotValue::Entity(camera)])?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn history_drops_oldest_samples_and_spans_the_chart() {
+        let mut history = History::with_capacity(3);
+        for value in [5., 1., 2., 3.] {
+            history.push(value);
+        }
+
+        assert_eq!(history.latest(), Some(3.));
+        assert_eq!(history.sparkline(Vec2::new(10., 4.)), vec![
+            Vec2::new(0., 0.),
+            Vec2::new(5., 2.),
+            Vec2::new(10., 4.),
+        ]);
+    }
+}
@@ -0,0 +1,155 @@
+use bevy::prelude::*;
+
+use crate::*;
+
+/// Makes every ball attract every other ball, like planets do. Attraction is
+/// approximated with the Barnes-Hut algorithm: balls in a distant region of a
+/// quadtree attract as a single body at their center of mass.
+pub struct NBodyPlugin {
+    gravity: f32,
+    theta: f32,
+}
+
+impl NBodyPlugin {
+    pub fn with_gravity(gravity: f32) -> Self {
+        Self { gravity, theta: 0.5 }
+    }
+}
+
+impl Default for NBodyPlugin {
+    fn default() -> Self { Self::with_gravity(50.) }
+}
+
+impl Plugin for NBodyPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(NBody {
+            gravity: self.gravity,
+            theta: self.theta,
+            softening: 10.,
+        })
+            .add_system_to_stage(PhysicsStage, apply_mutual_gravity.before(PhysicsSystem::Integrate));
+    }
+}
+
+pub struct NBody {
+    /// Gravitational constant.
+    pub gravity: f32,
+    /// Regions which appear smaller than this ratio of their size to their
+    /// distance are approximated as a single body. Zero computes the exact
+    /// attraction between all balls.
+    pub theta: f32,
+    /// Distance added to all distances, which prevents extreme accelerations
+    /// of balls which are very close together.
+    pub softening: f32,
+}
+
+/// Acceleration of `entity` at `position`, caused by all other balls in `tree`,
+/// where balls are stored as points with their mass.
+pub fn acceleration(nbody: &NBody, tree: &QuadTree<Mass>, entity: Entity, position: Vec2) -> Vec2 {
+    region_acceleration(nbody, tree, tree, NodeId::ROOT, entity, position)
+}
+
+// Acceleration caused by the balls within `region` of `tree`, which is at
+// `id`. A region which appears small enough from `position` attracts as a
+// single body at its center of mass.
+fn region_acceleration(
+    nbody: &NBody,
+    tree: &QuadTree<Mass>,
+    region: &QuadTree<Mass>,
+    id: NodeId,
+    entity: Entity,
+    position: Vec2,
+) -> Vec2 {
+    let mass = region.aggregate();
+    let center = match mass.center() {
+        Some(center) => center,
+        None => return Vec2::ZERO,
+    };
+
+    match region.children() {
+        Some(children) => {
+            let bounds = region.bounds();
+            let size = bounds.width().max(bounds.height());
+            if !bounds.contains(position) && size < nbody.theta * center.distance(position) {
+                return attraction(nbody, position, center, mass.total);
+            }
+
+            children.iter()
+                .enumerate()
+                .map(|(i, child)| region_acceleration(nbody, tree, child, id.child(i), entity, position))
+                .fold(Vec2::ZERO, |sum, acceleration| sum + acceleration)
+        }
+        None => region.leaf_elements().unwrap_or_default().iter()
+            // points on the edge between leaves are stored in each of them,
+            // they only attract from the first one
+            .filter(|(_, other)| *other != entity && tree.leaves_of(*other).first() == Some(&id))
+            .map(|(location, other)| attraction(nbody, position, location.center(), tree.item(*other).unwrap_or_default()))
+            .fold(Vec2::ZERO, |sum, acceleration| sum + acceleration),
+    }
+}
+
+// Acceleration towards a body with `mass` at `center`.
+#[inline]
+fn attraction(nbody: &NBody, position: Vec2, center: Vec2, mass: f32) -> Vec2 {
+    let delta = center - position;
+    let distance_sq = delta.length_squared() + nbody.softening * nbody.softening;
+    delta * (nbody.gravity * mass / (distance_sq * distance_sq.sqrt()))
+}
+
+fn apply_mutual_gravity(
+    nbody: Res<NBody>,
+    edge: Res<EdgeCollider>,
+    mut query: Query<(Entity, &Transform, &mut Force, &Ball)>,
+) {
+    if nbody.gravity == 0. {
+        return;
+    }
+
+    let mut tree = QuadTree::<Mass>::with_aggregate(edge.bounds, Options::default());
+    for (entity, transform, _, ball) in query.iter() {
+        let _ = tree.insert_with(Location::Point(transform.translation.truncate()), entity, ball.mass);
+    }
+
+    for (entity, transform, mut force, ball) in query.iter_mut() {
+        force.0 += acceleration(&nbody, &tree, entity, transform.translation.truncate()) * ball.mass;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn barnes_hut_approximates_direct_sum() {
+        let bounds = Bounds::new(Vec2::ZERO, 1000., 1000.);
+        let mut tree = QuadTree::<Mass>::with_aggregate(bounds, Options { capacity: 1, ..Default::default() });
+        let balls: Vec<(Entity, Vec2, f32)> = (0..100)
+            .map(|i| (
+                Entity::from_raw(i),
+                Vec2::new((i * 37 % 100) as f32 * 9. - 450., (i * 61 % 100) as f32 * 9. - 450.),
+                1. + (i % 5) as f32,
+            ))
+            .collect();
+        for (entity, position, mass) in &balls {
+            tree.insert_with(Location::Point(*position), *entity, *mass).unwrap();
+        }
+        // some balls lie on the edges between regions
+        assert!(balls.iter().any(|(entity, ..)| tree.leaves_of(*entity).len() > 1));
+        let total: f32 = balls.iter().map(|(.., mass)| mass).sum();
+        assert_eq!(tree.aggregate().total, total);
+
+        let entity = Entity::from_raw(0);
+        let position = Vec2::new(-450., -450.);
+        let exact = NBody { gravity: 1., theta: 0., softening: 1. };
+        let approximate = NBody { theta: 0.5, ..exact };
+
+        let direct: Vec2 = balls.iter()
+            .filter(|(other, ..)| *other != entity)
+            .map(|(_, center, mass)| attraction(&exact, position, *center, *mass))
+            .fold(Vec2::ZERO, |sum, acceleration| sum + acceleration);
+        let a = acceleration(&exact, &tree, entity, position);
+        let b = acceleration(&approximate, &tree, entity, position);
+        assert!(a.abs_diff_eq(direct, direct.length() * 1e-4), "{} != {}", a, direct);
+        assert!(b.abs_diff_eq(direct, direct.length() * 0.05), "{} != {}", b, direct);
+    }
+}
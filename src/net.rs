@@ -0,0 +1,196 @@
+//! Streams the state of the simulation over a local WebSocket and accepts
+//! commands from connected clients, so external tools can observe and drive
+//! the simulation.
+
+use std::io::ErrorKind as IoErrorKind;
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use std::time::Duration;
+
+use bevy::prelude::*;
+use crossbeam_channel::{bounded, unbounded, Receiver, Sender, TrySendError};
+use serde::{Deserialize, Serialize};
+use tungstenite::{Message, WebSocket};
+
+use crate::*;
+
+pub struct NetPlugin {
+    addr: String,
+}
+
+impl NetPlugin {
+    pub fn with_addr(addr: impl Into<String>) -> Self {
+        Self { addr: addr.into() }
+    }
+}
+
+impl Default for NetPlugin {
+    fn default() -> Self { Self::with_addr("127.0.0.1:9001") }
+}
+
+impl Plugin for NetPlugin {
+    fn build(&self, app: &mut App) {
+        let listener = match TcpListener::bind(&self.addr) {
+            Ok(listener) => listener,
+            Err(err) => {
+                println!("net: unable to listen on {}, streaming is disabled: {}", self.addr, err);
+                return;
+            }
+        };
+
+        let (clients_tx, clients_rx) = unbounded();
+        let (commands_tx, commands_rx) = unbounded();
+        thread::spawn(move || accept_clients(listener, clients_tx, commands_tx));
+
+        app.insert_resource(NetServer {
+            tick: 0,
+            clients: Vec::new(),
+            new_clients: clients_rx,
+            commands: commands_rx,
+        })
+            .add_system(handle_commands)
+            .add_system_to_stage(PhysicsStage, broadcast_state.after(PhysicsSystem::Resolve));
+    }
+}
+
+/// Command sent by a client, for example `{"command": "pause", "paused": true}`.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum NetCommand {
+    Spawn { position: [f32; 2], velocity: [f32; 2], radius: f32 },
+    Pause { paused: bool },
+    SetGravity { gravity: [f32; 2] },
+}
+
+/// State of the simulation, sent to all clients after each physics tick.
+#[derive(Debug, Serialize)]
+pub struct NetState {
+    pub tick: u64,
+    pub balls: Vec<NetBall>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct NetBall {
+    pub id: u32,
+    pub position: [f32; 2],
+    pub velocity: [f32; 2],
+    pub radius: f32,
+}
+
+pub struct NetServer {
+    tick: u64,
+    clients: Vec<Sender<String>>,
+    new_clients: Receiver<Sender<String>>,
+    commands: Receiver<NetCommand>,
+}
+
+fn accept_clients(listener: TcpListener, clients: Sender<Sender<String>>, commands: Sender<NetCommand>) {
+    for stream in listener.incoming().flatten() {
+        // states are dropped instead of queued when a client can't keep up
+        let (state_tx, state_rx) = bounded(8);
+        if clients.send(state_tx).is_err() {
+            return;
+        }
+
+        let commands = commands.clone();
+        thread::spawn(move || {
+            if let Err(err) = handle_client(stream, state_rx, commands) {
+                println!("net: client disconnected: {}", err);
+            }
+        });
+    }
+}
+
+fn handle_client(stream: TcpStream, states: Receiver<String>, commands: Sender<NetCommand>) -> tungstenite::Result<()> {
+    let mut socket: WebSocket<TcpStream> = tungstenite::accept(stream)
+        .map_err(|err| match err {
+            tungstenite::HandshakeError::Failure(err) => err,
+            tungstenite::HandshakeError::Interrupted(_) => tungstenite::Error::ConnectionClosed,
+        })?;
+    socket.get_mut().set_nonblocking(true)?;
+
+    loop {
+        match socket.read_message() {
+            Ok(Message::Text(text)) => match serde_json::from_str::<NetCommand>(&text) {
+                Ok(command) => { let _ = commands.send(command); }
+                Err(err) => println!("net: invalid command: {}", err),
+            },
+            Ok(Message::Close(_)) => return Ok(()),
+            Ok(_) => {}
+            Err(tungstenite::Error::Io(err)) if err.kind() == IoErrorKind::WouldBlock => {}
+            Err(err) => return Err(err),
+        }
+
+        let mut idle = true;
+        for state in states.try_iter() {
+            idle = false;
+            match socket.write_message(Message::Text(state)) {
+                Ok(_) => {}
+                Err(tungstenite::Error::Io(err)) if err.kind() == IoErrorKind::WouldBlock => {}
+                Err(err) => return Err(err),
+            }
+        }
+        if idle {
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+}
+
+fn handle_commands(
+    mut cmd: Commands,
+    server: Res<NetServer>,
+    mut paused: ResMut<Paused>,
+    mut gravity: ResMut<Gravity>,
+    mut rng: ResMut<SimRng>,
+    palette: Res<Palette>,
+    edge: Res<EdgeCollider>,
+) {
+    for command in server.commands.try_iter() {
+        match command {
+            NetCommand::Spawn { position, velocity, radius } => {
+                let velocity = Vec2::from(velocity);
+                let position = match edge.check_spawn(Vec2::from(position), velocity, radius) {
+                    Ok(position) => position,
+                    Err(err) => {
+                        println!("net: invalid spawn: {}", err);
+                        continue;
+                    }
+                };
+                BallBundle::builder(radius)
+                    .with_style(ball_style(palette.pick(&mut rng)))
+                    .with_velocity(velocity)
+                    .with_position(position)
+                    .spawn(&mut cmd);
+            }
+            NetCommand::Pause { paused: p } => paused.0 = p,
+            NetCommand::SetGravity { gravity: g } => gravity.0 = Vec2::from(g),
+        }
+    }
+}
+
+fn broadcast_state(mut server: ResMut<NetServer>, query: Query<(Entity, &Transform, &Velocity, &Ball)>) {
+    let server = &mut *server;
+    server.tick += 1;
+    server.clients.extend(server.new_clients.try_iter());
+    if server.clients.is_empty() {
+        return;
+    }
+
+    let state = NetState {
+        tick: server.tick,
+        balls: query.iter()
+            .map(|(entity, transform, velocity, ball)| NetBall {
+                id: entity.id(),
+                position: transform.translation.truncate().to_array(),
+                velocity: velocity.0.to_array(),
+                radius: ball.radius,
+            })
+            .collect(),
+    };
+
+    let json = serde_json::to_string(&state).unwrap();
+    server.clients.retain(|client| match client.try_send(json.clone()) {
+        Ok(_) | Err(TrySendError::Full(_)) => true,
+        Err(TrySendError::Disconnected(_)) => false,
+    });
+}
@@ -0,0 +1,155 @@
+use bevy::prelude::*;
+
+use crate::*;
+
+/// Adds a paddle near the bottom of the arena, which follows the cursor or is
+/// moved with the `ActionAxes::paddle_movement` axis. Balls bounce off of it,
+/// and pick up part of its sideways movement.
+pub struct PaddlePlugin {
+    size: Vec2,
+}
+
+impl PaddlePlugin {
+    pub fn with_size(size: Vec2) -> Self {
+        Self { size }
+    }
+}
+
+impl Default for PaddlePlugin {
+    fn default() -> Self { Self::with_size(Vec2::new(120., 14.)) }
+}
+
+impl Plugin for PaddlePlugin {
+    fn build(&self, app: &mut App) {
+        let size = self.size;
+        app.add_startup_system(move |mut cmd: Commands| {
+            spawn_paddle(&mut cmd, size, Vec2::new(0., -HEIGHT / 2. + PADDLE_HEIGHT));
+        })
+            .bind_axis(AxisControl::PaddleMovement, [KeyCode::Q, KeyCode::E])
+            .add_system(control_paddle)
+            .add_plugin(PaddlePhysicsPlugin);
+    }
+}
+
+/// Moves paddles towards their target and bounces balls off of them, without
+/// spawning or controlling any paddle.
+pub struct PaddlePhysicsPlugin;
+
+impl Plugin for PaddlePhysicsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_to_stage(PhysicsStage, move_paddle.before(PhysicsSystem::Integrate))
+            .add_system_to_stage(
+                PhysicsStage,
+                bounce_off_paddle
+                    .after(PhysicsSystem::Integrate)
+                    .before(PhysicsSystem::BroadPhase),
+            );
+    }
+}
+
+/// Kinematic box which is moved by the player, and is not affected by balls.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Paddle {
+    pub size: Vec2,
+    /// Horizontal position the paddle moves towards.
+    pub target: f32,
+    /// Velocity during the last tick.
+    pub velocity: Vec2,
+}
+
+impl Paddle {
+    /// Target of the paddle after `dt` seconds of moving it along `movement`,
+    /// or to the cursor when it moved to `cursor`. The paddle stays within
+    /// `bounds`.
+    pub fn next_target(&self, cursor: Option<Vec2>, movement: f32, bounds: Bounds, dt: f32) -> f32 {
+        let target = match cursor {
+            Some(cursor) if movement == 0. => cursor.x,
+            _ => self.target + movement * PADDLE_SPEED * dt,
+        };
+        let half_width = self.size.x / 2.;
+        target.clamp(bounds.left() + half_width, bounds.right() - half_width)
+    }
+}
+
+/// Spawn a paddle of `size` at `position`.
+pub fn spawn_paddle(cmd: &mut Commands, size: Vec2, position: Vec2) -> Entity {
+    cmd.spawn_bundle(GeometryBuilder::build_as(
+        &shapes::Rectangle {
+            extents: size,
+            origin: shapes::RectangleOrigin::Center,
+        },
+        DrawMode::Fill(FillMode::color(Color::WHITE)),
+        Transform::from_translation(position.extend(1.)),
+    ))
+        .insert(Paddle {
+            size,
+            target: position.x,
+            velocity: Vec2::ZERO,
+        })
+        .id()
+}
+
+/// Distance of the paddle's center from the bottom wall.
+pub const PADDLE_HEIGHT: f32 = 40.;
+// Maximum speed of the paddle, also the speed when moved at full tilt.
+const PADDLE_SPEED: f32 = 900.;
+// Fraction of the paddle's sideways velocity which balls pick up on a bounce.
+const PADDLE_INFLUENCE: f32 = 0.5;
+const PADDLE_RESTITUTION: f32 = 1.;
+
+fn control_paddle(
+    picker: Picker,
+    axes: Res<ActionAxes>,
+    edge: Res<EdgeCollider>,
+    time: Res<Time>,
+    mut last_cursor: Local<Option<Vec2>>,
+    mut query: Query<&mut Paddle>,
+) {
+    let cursor = picker.cursor();
+    let cursor_moved = cursor.is_some() && cursor != *last_cursor;
+    *last_cursor = cursor;
+
+    for mut paddle in query.iter_mut() {
+        let cursor = cursor.filter(|_| cursor_moved);
+        let target = paddle.next_target(cursor, axes.paddle_movement, edge.bounds, time.delta_seconds());
+        if paddle.target != target {
+            paddle.target = target;
+        }
+    }
+}
+
+fn move_paddle(mut query: Query<(&mut Paddle, &mut Transform)>) {
+    for (mut paddle, mut transform) in query.iter_mut() {
+        let max_step = PADDLE_SPEED * TIMESTEP;
+        let step = (paddle.target - transform.translation.x).clamp(-max_step, max_step);
+
+        paddle.velocity = Vec2::new(step / TIMESTEP, 0.);
+        if step != 0. {
+            transform.translation.x += step;
+        }
+    }
+}
+
+fn bounce_off_paddle(
+    paddles: Query<(&Paddle, &Transform), Without<Ball>>,
+    mut balls: Query<(&mut Transform, &mut Velocity, &Ball)>,
+) {
+    for (paddle, paddle_transform) in paddles.iter() {
+        let area = Bounds::new(paddle_transform.translation.truncate(), paddle.size.x, paddle.size.y);
+        let reach = area.expanded(*BALL_RADIUS.end());
+
+        for (mut transform, mut velocity, ball) in balls.iter_mut() {
+            // only mutably borrow balls near the paddle, so other balls are
+            // not marked as changed
+            if !reach.contains(transform.translation.truncate()) {
+                continue;
+            }
+
+            let transform = &mut *transform;
+            let velocity = &mut *velocity;
+            if bounce_off_box(area, paddle.velocity, PADDLE_RESTITUTION, ball, transform, velocity).is_some() {
+                velocity.0.x += paddle.velocity.x * PADDLE_INFLUENCE;
+            }
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use std::collections::HashMap;
+
+use crate::*;
+
+/// Candidate pairs of colliding balls, which are reused for as long as no
+/// ball has moved far enough to be able to touch a ball outside of its pairs.
+/// Pairs are found using bounding circles which are grown by a margin, so the
+/// cache remains valid until a ball moves more than half the margin.
+#[derive(Default)]
+pub struct PairCache {
+    pairs: Vec<[Entity; 2]>,
+    // position of each ball when the pairs were found
+    positions: HashMap<Entity, Vec2>,
+}
+
+impl PairCache {
+    #[inline]
+    pub fn pairs(&self) -> &[[Entity; 2]] { &self.pairs }
+
+    /// Indicates if the cached pairs still contain all possible collisions
+    /// between `balls`.
+    pub fn is_valid(&self, margin: f32, balls: &[(Entity, Vec2, f32)]) -> bool {
+        if balls.len() != self.positions.len() {
+            return false;
+        }
+
+        let max_distance = margin * 0.5;
+        balls.iter().all(|(entity, position, _)| match self.positions.get(entity) {
+            Some(cached) => cached.distance_squared(*position) < max_distance * max_distance,
+            None => false,
+        })
+    }
+
+    /// Find all pairs of `balls` whose bounding circles, grown by `margin`,
+    /// overlap.
+    pub fn rebuild(&mut self, bounds: Bounds, options: Options, margin: f32, balls: &[(Entity, Vec2, f32)]) {
+        self.pairs.clear();
+        self.positions.clear();
+
+        let mut tree = QuadTree::new(bounds, options);
+        for (entity, position, radius) in balls {
+            let size = (radius + margin * 0.5) * 2.;
+            let _ = tree.insert(Location::new(*position, size, size), *entity);
+            self.positions.insert(*entity, *position);
+        }
+
+        for region in tree.regions() {
+            let elems = region.elements().unwrap();
+            for (i, (location_a, a)) in elems.iter().enumerate() {
+                for (location_b, b) in elems[i + 1..].iter() {
+                    if let (Location::Area(area_a), Location::Area(area_b)) = (location_a, location_b) {
+                        let reach = (area_a.width() + area_b.width()) * 0.5;
+                        if area_a.center().distance_squared(area_b.center()) <= reach * reach {
+                            self.pairs.push(if a < b { [*a, *b] } else { [*b, *a] });
+                        }
+                    }
+                }
+            }
+        }
+
+        // balls which overlap multiple regions are paired more than once
+        self.pairs.sort_unstable();
+        self.pairs.dedup();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pair_cache_is_valid_until_balls_move_half_the_margin() {
+        let bounds = Bounds::new(Vec2::ZERO, 100., 100.);
+        let mut balls = vec![
+            (Entity::from_raw(0), Vec2::new(-10., 0.), 4.),
+            (Entity::from_raw(1), Vec2::new(0., 0.), 4.),
+            (Entity::from_raw(2), Vec2::new(30., 30.), 4.),
+        ];
+
+        let mut cache = PairCache::default();
+        assert!(!cache.is_valid(4., &balls));
+
+        cache.rebuild(bounds, Options::default(), 4., &balls);
+        assert_eq!(cache.pairs(), &[[Entity::from_raw(0), Entity::from_raw(1)]]);
+        assert!(cache.is_valid(4., &balls));
+
+        balls[2].1.x -= 1.9;
+        assert!(cache.is_valid(4., &balls));
+        balls[2].1.x -= 0.2;
+        assert!(!cache.is_valid(4., &balls));
+
+        balls.pop();
+        cache.rebuild(bounds, Options::default(), 4., &balls);
+        assert!(!cache.is_valid(4., &balls[..1]));
+    }
+}
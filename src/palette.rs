@@ -0,0 +1,304 @@
+use std::fmt::{self, Formatter};
+use std::ops::RangeInclusive;
+use std::{fs, io};
+use std::path::Path;
+use std::str::FromStr;
+
+use bevy::prelude::*;
+use rand::Rng;
+use rand::rngs::StdRng;
+#[cfg(feature = "scene")]
+use serde::{Deserialize, Serialize};
+
+use crate::quadtree::Bounds;
+
+/// Colors which balls are given when they spawn.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Palette {
+    colors: Vec<Color>,
+}
+
+// Colors of the `default` palette.
+const DEFAULT_COLORS: [Color; 36] = [
+    Color::ALICE_BLUE,
+    Color::ANTIQUE_WHITE,
+    Color::AQUAMARINE,
+    Color::AZURE,
+    Color::BEIGE,
+    Color::BISQUE,
+    Color::BLUE,
+    Color::CRIMSON,
+    Color::CYAN,
+    Color::DARK_GRAY,
+    Color::DARK_GREEN,
+    Color::FUCHSIA,
+    Color::GOLD,
+    Color::GRAY,
+    Color::GREEN,
+    Color::INDIGO,
+    Color::LIME_GREEN,
+    Color::MAROON,
+    Color::MIDNIGHT_BLUE,
+    Color::NAVY,
+    Color::OLIVE,
+    Color::ORANGE,
+    Color::ORANGE_RED,
+    Color::PINK,
+    Color::PURPLE,
+    Color::RED,
+    Color::SALMON,
+    Color::SEA_GREEN,
+    Color::SILVER,
+    Color::TEAL,
+    Color::TOMATO,
+    Color::TURQUOISE,
+    Color::VIOLET,
+    Color::WHITE,
+    Color::YELLOW,
+    Color::YELLOW_GREEN,
+];
+
+#[derive(Debug)]
+pub enum PaletteError {
+    Io(io::Error),
+    /// A line of a palette file is not a valid hex color.
+    InvalidColor(usize, String),
+    Empty,
+}
+
+impl fmt::Display for PaletteError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        use PaletteError::*;
+        match self {
+            Io(err) => write!(f, "{}", err),
+            InvalidColor(line, text) => write!(f, "invalid color `{}` on line {}", text, line),
+            Empty => write!(f, "palette has no colors"),
+        }
+    }
+}
+
+impl Palette {
+    pub fn new(colors: Vec<Color>) -> Result<Self, PaletteError> {
+        if colors.is_empty() {
+            return Err(PaletteError::Empty);
+        }
+        Ok(Self { colors })
+    }
+
+    /// `count` colors with evenly spaced hues and the same saturation and
+    /// value, in HSV color space.
+    pub fn hsv(count: usize, saturation: f32, value: f32) -> Self {
+        let colors = (0..count.max(1))
+            .map(|i| hsv_color(i as f32 * 360. / count.max(1) as f32, saturation, value))
+            .collect();
+        Self { colors }
+    }
+
+    /// Built-in palette with the given name. Besides `default`, `pastel` and
+    /// `rainbow`, any size of HSV palette is available as `hsv:<count>`.
+    pub fn named(name: &str) -> Option<Self> {
+        match name {
+            "default" => Some(Self { colors: DEFAULT_COLORS.to_vec() }),
+            "pastel" => Some(Self::hsv(12, 0.35, 1.)),
+            "rainbow" => Some(Self::hsv(36, 1., 1.)),
+            _ => {
+                let count = name.strip_prefix("hsv:")?.parse().ok()?;
+                Some(Self::hsv(count, 0.8, 1.))
+            }
+        }
+    }
+
+    /// Built-in palette named `name`, or else the palette file at that path.
+    pub fn named_or_load(name: &str) -> Result<Self, PaletteError> {
+        match Self::named(name) {
+            Some(palette) => Ok(palette),
+            None => Self::load(name),
+        }
+    }
+
+    /// Load a palette file, see `Palette::parse`.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, PaletteError> {
+        let text = fs::read_to_string(path).map_err(PaletteError::Io)?;
+        Self::parse(&text)
+    }
+
+    /// Parse a palette with a hex color (`#rrggbb` or `#rrggbbaa`) on each
+    /// line. Empty lines and lines starting with `//` are skipped.
+    pub fn parse(text: &str) -> Result<Self, PaletteError> {
+        let mut colors = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with("//") {
+                continue;
+            }
+
+            let hex = line.strip_prefix('#').unwrap_or(line);
+            match Color::hex(hex) {
+                Ok(color) => colors.push(color),
+                Err(_) => return Err(PaletteError::InvalidColor(i + 1, line.to_string())),
+            }
+        }
+        Self::new(colors)
+    }
+
+    #[inline]
+    pub fn colors(&self) -> &[Color] { &self.colors }
+
+    /// Color at `index`, wrapping around at the end of the palette.
+    #[inline]
+    pub fn get(&self, index: usize) -> Color { self.colors[index % self.colors.len()] }
+
+    #[inline]
+    pub fn pick(&self, rng: &mut StdRng) -> Color { self.colors[rng.gen_range(0..self.colors.len())] }
+}
+
+impl Default for Palette {
+    fn default() -> Self { Self { colors: DEFAULT_COLORS.to_vec() } }
+}
+
+/// Determines which color of the palette a ball is given when it spawns, so
+/// the colors of a scene can tell something about its balls from the start.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "scene", derive(Serialize, Deserialize))]
+pub enum ColorStrategy {
+    /// Each ball is given the next color of the palette.
+    #[default]
+    RoundRobin,
+    Random,
+    /// Balls of a similar size share a color, from the smallest to the
+    /// largest balls along the palette.
+    Radius,
+    /// Balls with a similar initial speed share a color, from the slowest to
+    /// the fastest balls along the palette.
+    Speed,
+    /// Balls are given the first four colors by the quadrant of the arena
+    /// they spawn in, from the top left to the bottom right.
+    Quadrant,
+}
+
+impl FromStr for ColorStrategy {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "round-robin" => Ok(Self::RoundRobin),
+            "random" => Ok(Self::Random),
+            "radius" => Ok(Self::Radius),
+            "speed" => Ok(Self::Speed),
+            "quadrant" => Ok(Self::Quadrant),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Gives spawning balls a color of the palette, using a `ColorStrategy`.
+pub struct SpawnColors {
+    pub strategy: ColorStrategy,
+    /// Radii which are spread over the palette by `ColorStrategy::Radius`.
+    pub radius: RangeInclusive<f32>,
+    /// Speeds which are spread over the palette by `ColorStrategy::Speed`.
+    pub speed: RangeInclusive<f32>,
+    spawned: usize,
+}
+
+impl SpawnColors {
+    pub fn new(strategy: ColorStrategy, radius: RangeInclusive<f32>, speed: RangeInclusive<f32>) -> Self {
+        Self { strategy, radius, speed, spawned: 0 }
+    }
+
+    /// Color of the next ball, which spawns with `radius` and `velocity` at
+    /// `position` within `arena`.
+    pub fn next(
+        &mut self,
+        palette: &Palette,
+        rng: &mut StdRng,
+        radius: f32,
+        velocity: Vec2,
+        position: Vec2,
+        arena: Bounds,
+    ) -> Color {
+        let len = palette.colors().len();
+        // index of the part of `range` which `value` is in, when it is split
+        // into a part for each color
+        let bucket = |value: f32, range: &RangeInclusive<f32>| {
+            let t = (value - range.start()) / (range.end() - range.start()).max(f32::EPSILON);
+            ((t * len as f32) as usize).min(len - 1)
+        };
+
+        self.spawned += 1;
+        match self.strategy {
+            ColorStrategy::RoundRobin => palette.get(self.spawned - 1),
+            ColorStrategy::Random => palette.pick(rng),
+            ColorStrategy::Radius => palette.get(bucket(radius, &self.radius)),
+            ColorStrategy::Speed => palette.get(bucket(velocity.length(), &self.speed)),
+            ColorStrategy::Quadrant => {
+                let center = arena.center();
+                let right = (position.x >= center.x) as usize;
+                let bottom = (position.y < center.y) as usize;
+                palette.get(bottom * 2 + right)
+            }
+        }
+    }
+}
+
+// Convert from HSV to the HSL color space of bevy.
+#[inline]
+fn hsv_color(hue: f32, saturation: f32, value: f32) -> Color {
+    let lightness = value * (1. - saturation / 2.);
+    let saturation = match lightness {
+        l if l <= 0. || l >= 1. => 0.,
+        l => (value - l) / l.min(1. - l),
+    };
+    Color::hsl(hue, saturation, lightness)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn palettes_are_named_generated_or_parsed() {
+        assert_eq!(Palette::named("default").unwrap().colors().len(), 36);
+        assert_eq!(Palette::named("hsv:100").unwrap().colors().len(), 100);
+        assert_eq!(Palette::named("unknown"), None);
+
+        let red = Palette::hsv(1, 1., 1.).get(0).as_rgba_f32();
+        assert!(red.iter().zip([1., 0., 0., 1.]).all(|(a, b)| (a - b).abs() < 1e-5), "{:?}", red);
+
+        let palette = Palette::parse("// reds\n#ff0000\n\n00ff0080\n").unwrap();
+        assert_eq!(palette.colors(), &[Color::rgb(1., 0., 0.), Color::rgba(0., 1., 0., 128. / 255.)]);
+        assert_eq!(palette.get(3), palette.get(1));
+
+        assert!(matches!(Palette::parse("#ff0000\nred"), Err(PaletteError::InvalidColor(2, _))));
+        assert!(matches!(Palette::parse("// nothing"), Err(PaletteError::Empty)));
+    }
+
+    #[test]
+    fn spawn_colors_follow_the_strategy() {
+        let palette = Palette::hsv(4, 1., 1.);
+        let mut rng = rand::SeedableRng::seed_from_u64(0);
+        let arena = Bounds::new(Vec2::new(100., 0.), 200., 100.);
+        let mut colors = SpawnColors::new(ColorStrategy::RoundRobin, 2.0..=10., 0.0..=100.);
+        let mut next = |colors: &mut SpawnColors, radius: f32, speed: f32, position: Vec2| {
+            colors.next(&palette, &mut rng, radius, Vec2::new(speed, 0.), position, arena)
+        };
+
+        assert_eq!(next(&mut colors, 2., 0., Vec2::ZERO), palette.get(0));
+        assert_eq!(next(&mut colors, 2., 0., Vec2::ZERO), palette.get(1));
+
+        colors.strategy = ColorStrategy::Radius;
+        assert_eq!(next(&mut colors, 2., 0., Vec2::ZERO), palette.get(0));
+        assert_eq!(next(&mut colors, 5., 0., Vec2::ZERO), palette.get(1));
+        assert_eq!(next(&mut colors, 10., 0., Vec2::ZERO), palette.get(3));
+
+        colors.strategy = ColorStrategy::Speed;
+        assert_eq!(next(&mut colors, 2., 60., Vec2::ZERO), palette.get(2));
+
+        // quadrants are relative to the center of the arena
+        colors.strategy = ColorStrategy::Quadrant;
+        assert_eq!(next(&mut colors, 2., 0., Vec2::new(50., 10.)), palette.get(0));
+        assert_eq!(next(&mut colors, 2., 0., Vec2::new(150., -10.)), palette.get(3));
+
+        assert_eq!("speed".parse(), Ok(ColorStrategy::Speed));
+    }
+}
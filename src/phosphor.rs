@@ -0,0 +1,158 @@
+use bevy::prelude::*;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+
+use crate::*;
+
+/// Draws fading trails behind the balls, like the phosphor of an old
+/// oscilloscope. Each frame the texture of the trails is faded, after which
+/// the balls are drawn into it. This gives long exposure trails, without
+/// keeping a history of the positions of each ball.
+pub struct PhosphorPlugin {
+    half_life: f32,
+    resolution: f32,
+}
+
+impl PhosphorPlugin {
+    pub fn with_half_life(half_life: f32) -> Self {
+        Self { half_life, resolution: 0.5 }
+    }
+}
+
+impl Default for PhosphorPlugin {
+    fn default() -> Self { Self::with_half_life(0.5) }
+}
+
+impl Plugin for PhosphorPlugin {
+    fn build(&self, app: &mut App) {
+        let bounds = Bounds::new(Vec2::ZERO, WIDTH, HEIGHT);
+        app.insert_resource(PhosphorScreen::new(bounds, self.resolution, self.half_life))
+            .add_startup_system(spawn_phosphor_screen)
+            .add_system(expose_phosphor_screen);
+    }
+}
+
+/// Texture which covers the arena, in which the balls leave their trails.
+pub struct PhosphorScreen {
+    pub image: Handle<Image>,
+    pub bounds: Bounds,
+    /// Pixels of the texture per unit of the world.
+    pub resolution: f32,
+    /// Seconds in which trails fade to half their brightness.
+    pub half_life: f32,
+}
+
+impl PhosphorScreen {
+    pub fn new(bounds: Bounds, resolution: f32, half_life: f32) -> Self {
+        Self {
+            image: Handle::default(),
+            bounds,
+            resolution: resolution.max(0.01),
+            half_life: half_life.max(0.01),
+        }
+    }
+
+    /// Empty texture of the screen, which is transparent until balls are
+    /// drawn into it.
+    pub fn blank_image(&self) -> Image {
+        let size = Extent3d {
+            width: (self.bounds.width() * self.resolution).ceil().max(1.) as u32,
+            height: (self.bounds.height() * self.resolution).ceil().max(1.) as u32,
+            depth_or_array_layers: 1,
+        };
+        Image::new_fill(size, TextureDimension::D2, &[0; 4], TextureFormat::Rgba8UnormSrgb)
+    }
+
+    /// Fade all pixels of `image` for `delta` seconds.
+    pub fn fade(&self, image: &mut Image, delta: f32) {
+        let factor = 0.5f32.powf(delta / self.half_life);
+        for byte in image.data.iter_mut() {
+            *byte = (*byte as f32 * factor) as u8;
+        }
+    }
+
+    /// Draw a ball at `center` with `radius` into `image`. Pixels keep the
+    /// brightest of their current color and `color`, so crossing trails
+    /// don't saturate.
+    pub fn expose(&self, image: &mut Image, center: Vec2, radius: f32, color: Color) {
+        let (width, height) = (image.texture_descriptor.size.width as i32, image.texture_descriptor.size.height as i32);
+        // image rows run from the top down
+        let x = (center.x - self.bounds.left()) * self.resolution;
+        let y = (self.bounds.top() - center.y) * self.resolution;
+        let r = (radius * self.resolution).max(0.5);
+
+        let [red, green, blue, _] = color.as_rgba_f32();
+        let exposed = [red, green, blue, red.max(green).max(blue)].map(|c| (c.clamp(0., 1.) * 255.) as u8);
+        for row in ((y - r).floor() as i32).max(0)..((y + r).ceil() as i32).min(height) {
+            for column in ((x - r).floor() as i32).max(0)..((x + r).ceil() as i32).min(width) {
+                let pixel = Vec2::new(column as f32 + 0.5, row as f32 + 0.5);
+                if pixel.distance_squared(Vec2::new(x, y)) > r * r {
+                    continue;
+                }
+                let i = (row * width + column) as usize * 4;
+                for (byte, exposed) in image.data[i..i + 4].iter_mut().zip(exposed) {
+                    *byte = (*byte).max(exposed);
+                }
+            }
+        }
+    }
+}
+
+// Behind the balls and the density grid.
+const SCREEN_Z: f32 = -2.;
+
+fn spawn_phosphor_screen(mut cmd: Commands, mut screen: ResMut<PhosphorScreen>, mut images: ResMut<Assets<Image>>) {
+    screen.image = images.add(screen.blank_image());
+    cmd.spawn_bundle(SpriteBundle {
+        sprite: Sprite {
+            custom_size: Some(Vec2::new(screen.bounds.width(), screen.bounds.height())),
+            ..Default::default()
+        },
+        texture: screen.image.clone(),
+        transform: Transform::from_translation(screen.bounds.center().extend(SCREEN_Z)),
+        ..Default::default()
+    });
+}
+
+fn expose_phosphor_screen(
+    time: Res<Time>,
+    screen: Res<PhosphorScreen>,
+    mut images: ResMut<Assets<Image>>,
+    balls: Query<(&Transform, &Ball, &DrawMode)>,
+) {
+    let image = match images.get_mut(&screen.image) {
+        Some(image) => image,
+        None => return,
+    };
+    screen.fade(image, time.delta_seconds());
+    for (transform, ball, draw_mode) in balls.iter() {
+        screen.expose(image, transform.translation.truncate(), ball.radius, fill_color(draw_mode));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trails_are_exposed_and_fade() {
+        let screen = PhosphorScreen::new(Bounds::new(Vec2::ZERO, 20., 10.), 1., 1.);
+        let mut image = screen.blank_image();
+        assert_eq!(image.data.len(), 20 * 10 * 4);
+        let pixel = |image: &Image, column: usize, row: usize| {
+            let i = (row * 20 + column) * 4;
+            [image.data[i], image.data[i + 1], image.data[i + 2], image.data[i + 3]]
+        };
+
+        // a red ball in the top left quarter
+        screen.expose(&mut image, Vec2::new(-5., 2.5), 2., Color::RED);
+        assert_eq!(pixel(&image, 5, 2), [255, 0, 0, 255]);
+        assert_eq!(pixel(&image, 15, 7), [0; 4]);
+
+        // a dimmer ball doesn't darken the trail it crosses
+        screen.expose(&mut image, Vec2::new(-4., 2.5), 2., Color::rgb(0.2, 0., 0.));
+        assert_eq!(pixel(&image, 5, 2), [255, 0, 0, 255]);
+
+        screen.fade(&mut image, 1.);
+        assert_eq!(pixel(&image, 5, 2), [127, 0, 0, 127]);
+    }
+}
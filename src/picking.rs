@@ -0,0 +1,87 @@
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy::render::camera::Camera2d;
+
+use crate::*;
+
+/// Quadtree of all balls, as built during the last physics tick, whichever
+/// broad phase is used. Empty until the first tick.
+pub struct BallTree(pub QuadTree);
+
+impl Default for BallTree {
+    fn default() -> Self { Self(QuadTree::new(Bounds::new(Vec2::ZERO, 0., 0.), Options::default())) }
+}
+
+/// Finds balls at a position in the world, or under the cursor.
+#[derive(SystemParam)]
+pub struct Picker<'w, 's> {
+    windows: Res<'w, Windows>,
+    tree: Res<'w, BallTree>,
+    cameras: Query<'w, 's, &'static Transform, With<Camera2d>>,
+    balls: Query<'w, 's, (Entity, &'static Transform, &'static Ball), Without<Camera2d>>,
+}
+
+impl<'w, 's> Picker<'w, 's> {
+    /// Position of the cursor in the world, when it is within the primary
+    /// window.
+    pub fn cursor(&self) -> Option<Vec2> {
+        match (self.windows.get_primary(), self.cameras.get_single()) {
+            (Some(window), Ok(camera)) => cursor_to_world(window, camera),
+            _ => None,
+        }
+    }
+
+    /// Topmost ball under the cursor.
+    #[allow(dead_code)]
+    pub fn ball_under_cursor(&self) -> Option<Entity> { self.ball_at(self.cursor()?) }
+
+    /// Topmost ball which contains `point`. Of overlapping balls, the one with
+    /// the highest z-translation is drawn on top, or else the one whose
+    /// center is closest.
+    pub fn ball_at(&self, point: Vec2) -> Option<Entity> {
+        let hit = |entity: Entity| {
+            let (_, transform, ball) = self.balls.get(entity).ok()?;
+            let distance = transform.translation.truncate().distance(point);
+            if distance > ball.radius {
+                return None;
+            }
+            Some((entity, transform.translation.z, distance))
+        };
+
+        // the tree might be empty or a tick old, so candidates are checked
+        // against their current position
+        let hits: Vec<(Entity, f32, f32)> = if self.tree.0.is_empty() {
+            self.balls.iter().filter_map(|(entity, _, _)| hit(entity)).collect()
+        } else {
+            self.tree.0.query_circle(point, 0.)
+                .into_iter()
+                .filter_map(|(_, entity)| hit(entity))
+                .collect()
+        };
+
+        topmost(hits)
+    }
+}
+
+// Entity with the highest z, and the smallest distance among equal z.
+#[inline]
+fn topmost(hits: Vec<(Entity, f32, f32)>) -> Option<Entity> {
+    hits.into_iter()
+        .max_by(|(_, z_a, distance_a), (_, z_b, distance_b)| {
+            z_a.total_cmp(z_b).then(distance_b.total_cmp(distance_a))
+        })
+        .map(|(entity, _, _)| entity)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn topmost_prefers_highest_z_then_closest() {
+        let [a, b, c] = [Entity::from_raw(1), Entity::from_raw(2), Entity::from_raw(3)];
+        assert_eq!(topmost(vec![]), None);
+        assert_eq!(topmost(vec![(a, 0., 1.), (b, 0.5, 4.), (c, 0., 0.)]), Some(b));
+        assert_eq!(topmost(vec![(a, 0., 3.), (b, 0., 1.), (c, 0., 2.)]), Some(b));
+    }
+}
@@ -0,0 +1,108 @@
+use bevy::prelude::*;
+
+use crate::*;
+
+/// Keeps despawned balls around as hidden entities, so they can be reused
+/// when spawning new balls. Reused balls keep their radius and color, which
+/// avoids tessellating a new circle mesh for every spawned ball.
+pub struct BallPool {
+    /// Maximum amount of pooled balls, balls released beyond this are
+    /// despawned.
+    pub capacity: usize,
+    balls: Vec<(Entity, f32)>,
+}
+
+impl BallPool {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            capacity,
+            balls: Vec::with_capacity(capacity),
+        }
+    }
+
+    #[inline]
+    #[allow(dead_code)]
+    pub fn len(&self) -> usize { self.balls.len() }
+
+    #[inline]
+    #[allow(dead_code)]
+    pub fn is_empty(&self) -> bool { self.balls.is_empty() }
+
+    /// Removes the ball from the simulation and hides it, or despawns it when
+    /// the pool is full. Removing the `Ball` component still sends a
+    /// `BallDespawned` event.
+    pub fn release(&mut self, cmd: &mut Commands, entity: Entity, ball: &Ball) {
+        if self.balls.len() >= self.capacity {
+            cmd.entity(entity).despawn_recursive();
+            return;
+        }
+
+        // pooled balls are plain circles, until they are given a body again
+        BallShape::Circle.apply(&mut cmd.entity(entity), ball.radius);
+        cmd.entity(entity)
+            .remove::<Ball>()
+            .remove::<Velocity>()
+            .insert(Visibility { is_visible: false });
+        self.balls.push((entity, ball.radius));
+    }
+
+    /// Returns a pooled ball to the simulation with a new velocity and
+    /// position, or `None` when the pool is empty. The returned value is the
+    /// entity and its radius.
+    pub fn acquire(
+        &mut self,
+        cmd: &mut Commands,
+        mass_model: MassModel,
+        velocity: Vec2,
+        position: Vec2,
+    ) -> Option<(Entity, f32)> {
+        let (entity, radius) = self.balls.pop()?;
+        cmd.entity(entity)
+            .insert(Ball::new(radius, mass_model))
+            .insert(Velocity(velocity))
+            .insert(Force::default())
+            .insert(Impulse::default())
+            .insert(Transform::from_translation(Vec3::from((position, 0.))))
+            .insert(Visibility { is_visible: true });
+
+        Some((entity, radius))
+    }
+
+    /// Like `acquire`, but only returns a pooled ball with a radius within
+    /// `radius`.
+    pub fn acquire_within(
+        &mut self,
+        cmd: &mut Commands,
+        radius: RangeInclusive<f32>,
+        mass_model: MassModel,
+        velocity: Vec2,
+        position: Vec2,
+    ) -> Option<(Entity, f32)> {
+        let index = self.balls.iter().rposition(|(_, pooled)| radius.contains(pooled))?;
+        let ball = self.balls.remove(index);
+        self.balls.push(ball);
+        self.acquire(cmd, mass_model, velocity, position)
+    }
+
+    /// Returns `entity` to the simulation with a new velocity and position,
+    /// or `None` when it is not pooled.
+    pub fn acquire_entity(
+        &mut self,
+        cmd: &mut Commands,
+        entity: Entity,
+        mass_model: MassModel,
+        velocity: Vec2,
+        position: Vec2,
+    ) -> Option<Entity> {
+        let index = self.balls.iter().position(|(pooled, _)| *pooled == entity)?;
+        // move it to the top, so it is the next to be acquired
+        let ball = self.balls.remove(index);
+        self.balls.push(ball);
+        self.acquire(cmd, mass_model, velocity, position)
+            .map(|(entity, _)| entity)
+    }
+}
+
+impl Default for BallPool {
+    fn default() -> Self { Self::with_capacity(1000) }
+}
@@ -0,0 +1,152 @@
+use bevy::prelude::*;
+
+use crate::*;
+
+/// Adds pairs of portals. A ball which enters a portal leaves its paired
+/// portal at the same speed, moving in the same direction. Balls are moved
+/// before the broad phase, so the quadtree of that tick already contains them
+/// at their new position.
+pub struct PortalPlugin {
+    pairs: Vec<[Vec2; 2]>,
+    radius: f32,
+}
+
+impl PortalPlugin {
+    pub fn with_pairs(pairs: Vec<[Vec2; 2]>) -> Self {
+        Self { pairs, radius: 30. }
+    }
+}
+
+impl Default for PortalPlugin {
+    fn default() -> Self { Self::with_pairs(vec![[Vec2::new(-WIDTH / 3., 0.), Vec2::new(WIDTH / 3., 0.)]]) }
+}
+
+impl Plugin for PortalPlugin {
+    fn build(&self, app: &mut App) {
+        let pairs = self.pairs.clone();
+        let radius = self.radius;
+        app.add_startup_system(move |mut cmd: Commands| {
+            for (i, pair) in pairs.iter().enumerate() {
+                spawn_portal_pair(&mut cmd, *pair, radius, PORTAL_COLORS[i % PORTAL_COLORS.len()]);
+            }
+        })
+            .add_system_to_stage(
+                PhysicsStage,
+                teleport_balls
+                    .after(PhysicsSystem::Integrate)
+                    .before(PhysicsSystem::BroadPhase),
+            );
+    }
+}
+
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Portal {
+    pub radius: f32,
+    /// Portal balls which enter this portal are moved to.
+    pub exit: Entity,
+}
+
+/// Prevents a ball which just went through a portal from being teleported
+/// again, for the remaining amount of seconds.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct PortalCooldown(pub f32);
+
+const PORTAL_COOLDOWN: f32 = 0.5;
+
+pub(crate) const PORTAL_COLORS: [Color; 3] = [Color::CYAN, Color::ORANGE, Color::PINK];
+
+/// Spawn two portals at `centers` which lead to each other.
+pub fn spawn_portal_pair(cmd: &mut Commands, centers: [Vec2; 2], radius: f32, color: Color) -> [Entity; 2] {
+    let entities = [cmd.spawn().id(), cmd.spawn().id()];
+    for (i, center) in centers.into_iter().enumerate() {
+        cmd.entity(entities[i])
+            .insert_bundle(GeometryBuilder::build_as(
+                &shapes::Circle {
+                    radius,
+                    center: Vec2::ZERO,
+                },
+                DrawMode::Stroke(StrokeMode::new(color, 2.)),
+                Transform::from_translation(center.extend(0.)),
+            ))
+            .insert(Portal {
+                radius,
+                exit: entities[1 - i],
+            });
+    }
+    entities
+}
+
+/// Position at which a ball leaves the portal at `center`, just outside of it
+/// in the direction the ball is moving.
+#[inline]
+pub fn exit_position(center: Vec2, radius: f32, ball_radius: f32, velocity: Vec2) -> Vec2 {
+    center + velocity.normalize_or_zero() * (radius + ball_radius)
+}
+
+fn teleport_balls(
+    mut cmd: Commands,
+    edge: Res<EdgeCollider>,
+    tree: Res<BallTree>,
+    portals: Query<(&Portal, &Transform), Without<Ball>>,
+    mut cooldowns: Query<(Entity, &mut PortalCooldown)>,
+    mut balls: Query<(Entity, &mut Transform, &Velocity, &Ball), Without<PortalCooldown>>,
+) {
+    for (entity, mut cooldown) in cooldowns.iter_mut() {
+        cooldown.0 -= TIMESTEP;
+        if cooldown.0 <= 0. {
+            cmd.entity(entity).remove::<PortalCooldown>();
+        }
+    }
+
+    // cooldowns are only inserted at the end of the stage
+    let mut teleported = Vec::new();
+    for (portal, portal_transform) in portals.iter() {
+        let center = portal_transform.translation.truncate();
+        let exit_center = match portals.get(portal.exit) {
+            Ok((_, exit_transform)) => exit_transform.translation.truncate(),
+            Err(_) => continue,
+        };
+
+        // the tree is a tick old, so candidates are checked against their
+        // current position; without a tree all balls are checked
+        let candidates: Vec<Entity> = if tree.0.is_empty() {
+            balls.iter().map(|(entity, ..)| entity).collect()
+        } else {
+            tree.0.query_circle(center, portal.radius)
+                .into_iter()
+                .map(|(_, entity)| entity)
+                .collect()
+        };
+
+        for entity in candidates {
+            let (_, mut transform, velocity, ball) = match balls.get_mut(entity) {
+                Ok(ball) => ball,
+                Err(_) => continue,
+            };
+            if teleported.contains(&entity) {
+                continue;
+            }
+            if transform.translation.truncate().distance_squared(center) > portal.radius * portal.radius {
+                continue;
+            }
+
+            let position = exit_position(exit_center, portal.radius, ball.radius, velocity.0);
+            let position = edge.bounds.shrunk(ball.radius).clamp_point(position);
+            transform.translation.x = position.x;
+            transform.translation.y = position.y;
+            cmd.entity(entity).insert(PortalCooldown(PORTAL_COOLDOWN));
+            teleported.push(entity);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn balls_exit_outside_of_the_portal() {
+        let position = exit_position(Vec2::new(100., 0.), 30., 5., Vec2::new(0., -20.));
+        assert_eq!(position, Vec2::new(100., -35.));
+    }
+}
@@ -0,0 +1,179 @@
+use bevy::prelude::*;
+
+use crate::*;
+
+/// Measures the pressure on each wall of the arena, from the impulses of balls
+/// bouncing off of it. The pressure is shown as a bar along each wall, which
+/// grows with the pressure relative to the other walls.
+pub struct WallPressurePlugin {
+    window: f32,
+}
+
+impl WallPressurePlugin {
+    pub fn with_window(window: f32) -> Self {
+        Self { window }
+    }
+}
+
+impl Default for WallPressurePlugin {
+    fn default() -> Self { Self::with_window(1.) }
+}
+
+impl Plugin for WallPressurePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(WallPressure::with_window(self.window))
+            .add_startup_system(spawn_pressure_bars)
+            .add_system(draw_pressure_bars)
+            .add_system_to_stage(PhysicsStage, measure_wall_pressure.after(PhysicsSystem::Resolve));
+    }
+}
+
+const SIDES: [WallSide; 4] = [WallSide::Left, WallSide::Right, WallSide::Top, WallSide::Bottom];
+
+pub struct WallPressure {
+    /// Simulated time over which impulses are summed, in seconds.
+    pub window: f32,
+    /// Impulse per second per unit of wall length, measured over the last
+    /// window. Indexed by `WallSide`.
+    pub pressure: [f32; 4],
+    impulse: [f32; 4],
+    elapsed: f32,
+}
+
+impl WallPressure {
+    pub fn with_window(window: f32) -> Self {
+        Self {
+            window,
+            pressure: [0.; 4],
+            impulse: [0.; 4],
+            elapsed: 0.,
+        }
+    }
+
+    #[inline]
+    pub fn get(&self, side: WallSide) -> f32 { self.pressure[side as usize] }
+
+    /// Add the impulse a ball of `mass` exerted on a wall with `restitution`.
+    #[inline]
+    pub fn record(&mut self, hit: WallHit, mass: f32, restitution: f32) {
+        self.impulse[hit.side as usize] += mass * hit.impact_speed * (1. + restitution);
+    }
+
+    /// Advance the measurement by `dt` seconds. Once the window has passed the
+    /// pressure is updated from the impulses recorded on the walls of `bounds`.
+    pub fn advance(&mut self, dt: f32, bounds: Bounds) {
+        self.elapsed += dt;
+        if self.elapsed < self.window {
+            return;
+        }
+
+        for side in SIDES {
+            let length = match side {
+                WallSide::Left | WallSide::Right => bounds.height(),
+                WallSide::Top | WallSide::Bottom => bounds.width(),
+            };
+            self.pressure[side as usize] = self.impulse[side as usize] / (self.elapsed * length);
+        }
+        self.impulse = [0.; 4];
+        self.elapsed = 0.;
+    }
+}
+
+#[derive(Component)]
+struct PressureBar(WallSide);
+
+// Thickness of the bars along the walls.
+const BAR_THICKNESS: f32 = 6.;
+
+fn measure_wall_pressure(
+    mut pressure: ResMut<WallPressure>,
+    mut wall_hits: EventReader<BallHitWall>,
+    edge: Res<EdgeCollider>,
+    query: Query<&Ball>,
+) {
+    for BallHitWall(entity, hit) in wall_hits.iter() {
+        if let Ok(ball) = query.get(*entity) {
+            pressure.record(*hit, ball.mass, edge.restitution * ball.restitution);
+        }
+    }
+    pressure.advance(TIMESTEP, edge.bounds);
+}
+
+fn spawn_pressure_bars(mut cmd: Commands) {
+    // bars are unit squares, which are scaled to their size
+    for side in SIDES {
+        cmd.spawn_bundle(GeometryBuilder::build_as(
+            &shapes::Rectangle {
+                extents: Vec2::ONE,
+                origin: shapes::RectangleOrigin::Center,
+            },
+            DrawMode::Fill(FillMode::color(Color::rgba(1., 0.3, 0.2, 0.6))),
+            Transform::default(),
+        ))
+            .insert(PressureBar(side));
+    }
+}
+
+fn draw_pressure_bars(
+    pressure: Res<WallPressure>,
+    edge: Option<Res<EdgeCollider>>,
+    mut query: Query<(&mut Transform, &PressureBar)>,
+) {
+    let bounds = match edge {
+        Some(edge) => edge.bounds,
+        None => return,
+    };
+
+    let max = pressure.pressure.iter().copied().fold(0., f32::max);
+    for (mut transform, bar) in query.iter_mut() {
+        let fraction = if max > 0. { pressure.get(bar.0) / max } else { 0. };
+
+        // bars are centered on their wall, just outside of the arena
+        let offset = BAR_THICKNESS / 2.;
+        let (center, size) = match bar.0 {
+            WallSide::Left => (
+                Vec2::new(bounds.left() - offset, bounds.center().y),
+                Vec2::new(BAR_THICKNESS, fraction * bounds.height()),
+            ),
+            WallSide::Right => (
+                Vec2::new(bounds.right() + offset, bounds.center().y),
+                Vec2::new(BAR_THICKNESS, fraction * bounds.height()),
+            ),
+            WallSide::Top => (
+                Vec2::new(bounds.center().x, bounds.top() + offset),
+                Vec2::new(fraction * bounds.width(), BAR_THICKNESS),
+            ),
+            WallSide::Bottom => (
+                Vec2::new(bounds.center().x, bounds.bottom() - offset),
+                Vec2::new(fraction * bounds.width(), BAR_THICKNESS),
+            ),
+        };
+        transform.translation = center.extend(1.);
+        transform.scale = size.extend(1.);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pressure_is_impulse_per_second_per_length() {
+        let bounds = Bounds::new(Vec2::ZERO, 100., 50.);
+        let mut pressure = WallPressure::with_window(0.5);
+
+        pressure.record(WallHit { side: WallSide::Left, impact_speed: 10. }, 2., 1.);
+        pressure.record(WallHit { side: WallSide::Top, impact_speed: 10. }, 2., 0.);
+        pressure.advance(0.25, bounds);
+        assert_eq!(pressure.get(WallSide::Left), 0.);
+
+        pressure.advance(0.25, bounds);
+        assert_eq!(pressure.get(WallSide::Left), 40. / (0.5 * 50.));
+        assert_eq!(pressure.get(WallSide::Top), 20. / (0.5 * 100.));
+        assert_eq!(pressure.get(WallSide::Bottom), 0.);
+
+        // a new window starts without any impulses
+        pressure.advance(0.5, bounds);
+        assert_eq!(pressure.get(WallSide::Left), 0.);
+    }
+}
@@ -0,0 +1,115 @@
+use std::time::Instant;
+
+use bevy::ecs::event::Events;
+use bevy::ecs::schedule::ShouldRun;
+use bevy::prelude::*;
+
+use crate::*;
+
+/// Physics ticks which are run as fast as possible by `run_prewarm`, before the
+/// window is opened, so a scene which takes long to settle starts out
+/// settled. The ticks run all systems of the `PhysicsStage`, including those
+/// added by plugins.
+pub struct Prewarm {
+    pub ticks: usize,
+    remaining: usize,
+    start: Option<Instant>,
+}
+
+impl Prewarm {
+    pub fn with_ticks(ticks: usize) -> Self {
+        Self { ticks, remaining: ticks, start: None }
+    }
+
+    /// Whether all ticks have been run.
+    #[allow(dead_code)]
+    #[inline]
+    pub fn is_done(&self) -> bool { self.remaining == 0 }
+}
+
+/// Run the startup systems and then the `Prewarm` ticks of `app`, when it has
+/// any. Called before `App::run`, so nothing is rendered and the window isn't
+/// opened until the ticks are done. The startup systems don't run again in
+/// the first frame.
+pub fn run_prewarm(app: &mut App) {
+    if !app.world.contains_resource::<Prewarm>() {
+        return;
+    }
+    if let Some(startup) = app.schedule.get_stage_mut::<Schedule>(&StartupSchedule) {
+        startup.run(&mut app.world);
+    }
+    if let Some(physics) = app.schedule.get_stage_mut::<SystemStage>(&PhysicsStage) {
+        physics.run(&mut app.world);
+    }
+}
+
+/// Run criteria of the `PhysicsStage` which keeps running it until the
+/// `Prewarm` ticks are done, and leaves `should_run` as is afterwards.
+pub(crate) fn run_prewarm_ticks(
+    In(should_run): In<ShouldRun>,
+    prewarm: Option<ResMut<Prewarm>>,
+    mut collided: ResMut<Events<BallCollided>>,
+    mut wall_hits: ResMut<Events<BallHitWall>>,
+) -> ShouldRun {
+    let mut prewarm = match prewarm {
+        Some(prewarm) => prewarm,
+        None => return should_run,
+    };
+    if prewarm.remaining == 0 {
+        if let Some(start) = prewarm.start.take() {
+            println!("prewarm: ran {} ticks in {:.1} s", prewarm.ticks, start.elapsed().as_secs_f64());
+        }
+        return should_run;
+    }
+
+    prewarm.start.get_or_insert_with(Instant::now);
+    prewarm.remaining -= 1;
+    // events are only cleared once per frame, they'd pile up over the ticks
+    collided.update();
+    wall_hits.update();
+    ShouldRun::YesAndCheckAgain
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use bevy::app::AppExit;
+
+    use super::*;
+
+    #[test]
+    fn prewarm_ticks_run_before_the_first_frame() {
+        let (mut world, _) = headless_world(1701, 8, Bounds::new(Vec2::ZERO, 200., 200.));
+        world.insert_resource(Prewarm::with_ticks(5));
+        let ticks = Arc::new(Mutex::new(0));
+        let mut hooks = SimulationHooks::default();
+        let counter = ticks.clone();
+        hooks.add(HookPoint::PostPhysics, move |_| *counter.lock().unwrap() += 1);
+        world.insert_resource(hooks);
+
+        let mut app = App::new();
+        app.world = world;
+        // added by `App::new` to the world which was replaced
+        app.world.init_resource::<Events<AppExit>>();
+        let startups = Arc::new(Mutex::new(0));
+        let counter = startups.clone();
+        app.add_startup_system(move || *counter.lock().unwrap() += 1)
+            .add_stage_after(
+                CoreStage::Update,
+                PhysicsStage,
+                physics_stage().with_run_criteria((|| ShouldRun::No).chain(run_prewarm_ticks)),
+            );
+
+        run_prewarm(&mut app);
+        assert_eq!(*startups.lock().unwrap(), 1);
+        assert_eq!(*ticks.lock().unwrap(), 5);
+        assert!(app.world.resource::<Prewarm>().is_done());
+
+        // the first frame doesn't run the startup systems again, and the
+        // stage runs as usual afterwards, which is never here
+        app.update();
+        assert_eq!(*startups.lock().unwrap(), 1);
+        assert_eq!(*ticks.lock().unwrap(), 5);
+    }
+}
@@ -0,0 +1,126 @@
+use bevy::math::Vec2;
+
+use super::Location;
+
+/// Summary of the elements within a region of a `QuadTree`, which the tree
+/// keeps up to date while elements are inserted, relocated and removed. Each
+/// region includes every element stored within it exactly once, even when the
+/// element is stored in multiple of its sub-regions.
+pub trait Aggregate: Default {
+    /// Data of an element, next to its location, that the summary is
+    /// computed from.
+    type Item: Copy + Default;
+
+    /// Trees without a summary skip all bookkeeping.
+    const TRACKED: bool = true;
+
+    /// Include an element in the summary.
+    fn add(&mut self, location: Location, item: Self::Item);
+
+    /// Exclude an element which was added before. Returns `false` when the
+    /// summary can't be updated this way, in which case it is computed again
+    /// from the remaining elements.
+    fn remove(&mut self, _location: Location, _item: Self::Item) -> bool { false }
+}
+
+/// No summary, which is the default for a `QuadTree`.
+impl Aggregate for () {
+    type Item = ();
+    const TRACKED: bool = false;
+
+    #[inline(always)]
+    fn add(&mut self, _location: Location, _item: ()) {}
+
+    #[inline(always)]
+    fn remove(&mut self, _location: Location, _item: ()) -> bool { true }
+}
+
+/// Combination of two summaries.
+impl<A: Aggregate, B: Aggregate> Aggregate for (A, B) {
+    type Item = (A::Item, B::Item);
+    const TRACKED: bool = A::TRACKED || B::TRACKED;
+
+    #[inline]
+    fn add(&mut self, location: Location, item: Self::Item) {
+        self.0.add(location, item.0);
+        self.1.add(location, item.1);
+    }
+
+    #[inline]
+    fn remove(&mut self, location: Location, item: Self::Item) -> bool {
+        // both have to be computed again when either one fails, so stop early
+        self.0.remove(location, item.0) && self.1.remove(location, item.1)
+    }
+}
+
+/// Amount of elements.
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Count(pub usize);
+
+impl Aggregate for Count {
+    type Item = ();
+
+    #[inline]
+    fn add(&mut self, _location: Location, _item: ()) { self.0 += 1; }
+
+    #[inline]
+    fn remove(&mut self, _location: Location, _item: ()) -> bool {
+        self.0 -= 1;
+        true
+    }
+}
+
+/// Total mass and center of mass of the elements, where the item is the mass
+/// of an element.
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Mass {
+    pub total: f32,
+    // sum of the centers of the elements, weighted by their mass
+    weighted: Vec2,
+}
+
+#[allow(dead_code)]
+impl Mass {
+    /// Center of mass, or `None` without any mass.
+    #[inline]
+    pub fn center(&self) -> Option<Vec2> {
+        if self.total > 0. { Some(self.weighted / self.total) } else { None }
+    }
+}
+
+impl Aggregate for Mass {
+    type Item = f32;
+
+    #[inline]
+    fn add(&mut self, location: Location, mass: f32) {
+        self.total += mass;
+        self.weighted += location.center() * mass;
+    }
+
+    #[inline]
+    fn remove(&mut self, location: Location, mass: f32) -> bool {
+        self.total -= mass;
+        self.weighted -= location.center() * mass;
+        true
+    }
+}
+
+/// Largest radius of the elements, where the item is the radius of an
+/// element. Elements stored by their area count with at least half of their
+/// largest extent.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MaxRadius(pub f32);
+
+impl Aggregate for MaxRadius {
+    type Item = f32;
+
+    #[inline]
+    fn add(&mut self, location: Location, radius: f32) {
+        self.0 = self.0.max(radius);
+        if let Location::Area(area) = location {
+            self.0 = self.0.max(area.width().max(area.height()) * 0.5);
+        }
+    }
+}
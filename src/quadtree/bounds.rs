@@ -19,24 +19,30 @@ impl Bounds {
         }
     }
 
+    /// Create bounds from any two opposite corners. Corners which are on the
+    /// same horizontal or vertical line result in bounds without width or
+    /// height.
     #[inline]
-    pub fn from_corners(min: Vec2, max: Vec2) -> Self {
-        assert!(min.x < max.x);
-        let mut center = Vec2::ZERO;
-        let mut half_extents = Vec2::new((max.x - min.x) * 0.5, 0.0);
-
-        if min.y > max.y {
-            // min = top left
-            half_extents.y = (min.y - max.y) * 0.5;
-            center.y = min.y - half_extents.y;
-        } else {
-            // min = bottom left
-            half_extents.y = (max.y - min.y) * 0.5;
-            center.y = min.y + half_extents.y;
+    pub fn from_corners(a: Vec2, b: Vec2) -> Self {
+        let min = a.min(b);
+        let max = a.max(b);
+        let half_extents = (max - min) * 0.5;
+
+        Self {
+            center: min + half_extents,
+            half_extents,
+        }
+    }
+
+    /// Create bounds from any two opposite corners, returns an error when the
+    /// resulting bounds would not have an area.
+    #[inline]
+    pub fn try_from_corners(a: Vec2, b: Vec2) -> Result<Self, QuadTreeError> {
+        if !a.is_finite() || !b.is_finite() || a.x == b.x || a.y == b.y {
+            return Err(ErrorKind::DegenerateBounds(a, b).into());
         }
 
-        center.x = min.x + half_extents.x;
-        Self { center, half_extents }
+        Ok(Self::from_corners(a, b))
     }
 
     #[inline(always)]
@@ -86,12 +92,14 @@ impl Bounds {
         Vec2::new(self.right(), self.bottom())
     }
 
+    /// Indicates if `area` overlaps with the bounds. This includes areas that
+    /// are larger than the bounds and cover it entirely.
     #[inline]
     pub fn intersects(&self, area: Bounds) -> bool {
-        self.contains(area.top_left())
-            || self.contains(area.top_right())
-            || self.contains(area.bottom_left())
-            || self.contains(area.bottom_right())
+        area.left() <= self.right()
+            && area.right() >= self.left()
+            && area.bottom() <= self.top()
+            && area.top() >= self.bottom()
     }
 
     #[inline]
@@ -101,6 +109,92 @@ impl Bounds {
             && point.y <= self.top()
             && point.y >= self.bottom()
     }
+
+    /// Indicates if `area` lies entirely within the bounds.
+    #[inline]
+    pub fn contains_area(&self, area: Bounds) -> bool {
+        area.left() >= self.left()
+            && area.right() <= self.right()
+            && area.bottom() >= self.bottom()
+            && area.top() <= self.top()
+    }
+
+    /// Indicates if the circle at `center` with `radius` overlaps with the
+    /// bounds.
+    #[inline]
+    pub fn intersects_circle(&self, center: Vec2, radius: f32) -> bool {
+        self.clamp_point(center).distance_squared(center) <= radius * radius
+    }
+
+    /// Distance along the ray from `origin` in `direction` at which it enters
+    /// the bounds, 0 when `origin` is within them, or `None` when the ray
+    /// misses them. The distance is in lengths of `direction`.
+    #[inline]
+    pub fn ray_entry(&self, origin: Vec2, direction: Vec2) -> Option<f32> {
+        let mut near = 0.0f32;
+        let mut far = f32::INFINITY;
+        let axes = [
+            (origin.x, direction.x, self.left(), self.right()),
+            (origin.y, direction.y, self.bottom(), self.top()),
+        ];
+        for (origin, direction, min, max) in axes {
+            // parallel to the axis, the ray is either always or never between
+            // the sides
+            if direction == 0.0 {
+                if origin < min || origin > max {
+                    return None;
+                }
+                continue;
+            }
+            let (a, b) = ((min - origin) / direction, (max - origin) / direction);
+            near = near.max(a.min(b));
+            far = far.min(a.max(b));
+        }
+        (near <= far).then_some(near)
+    }
+
+    /// Returns the point within the bounds which is closest to `point`.
+    #[inline]
+    pub fn clamp_point(&self, point: Vec2) -> Vec2 {
+        point.clamp(self.min(), self.max())
+    }
+
+    /// Returns a copy of the bounds which is grown by `by` on all sides.
+    #[inline]
+    pub fn expanded(&self, by: f32) -> Self {
+        Self {
+            center: self.center,
+            half_extents: self.half_extents + Vec2::splat(by),
+        }
+    }
+
+    /// Returns a copy of the bounds which is shrunk by `by` on all sides. The
+    /// width and height will not drop below zero.
+    #[inline]
+    pub fn shrunk(&self, by: f32) -> Self {
+        Self {
+            center: self.center,
+            half_extents: (self.half_extents - Vec2::splat(by)).max(Vec2::ZERO),
+        }
+    }
+
+    /// Returns the smallest bounds that contain both bounds.
+    #[inline]
+    pub fn union(&self, other: Bounds) -> Self {
+        Self::from_corners(self.min().min(other.min()), self.max().max(other.max()))
+    }
+
+    /// Returns the overlapping area of both bounds, if any.
+    #[inline]
+    pub fn intersection(&self, other: Bounds) -> Option<Self> {
+        let min = self.min().max(other.min());
+        let max = self.max().min(other.max());
+        if min.x > max.x || min.y > max.y {
+            return None;
+        }
+
+        Some(Self::from_corners(min, max))
+    }
 }
 
 // impl From<Aabb> for Bounds {
@@ -133,6 +227,17 @@ mod tests {
         let bounds = Bounds::new(Vec2::new(5.0, 5.0), 10.0, 10.0);
         assert_eq!(Bounds::from_corners(Vec2::new(0.0, 0.0), Vec2::new(10.0, 10.0)), bounds);
         assert_eq!(Bounds::from_corners(Vec2::new(0.0, 10.0), Vec2::new(10.0, 0.0)), bounds);
+        assert_eq!(Bounds::from_corners(Vec2::new(10.0, 10.0), Vec2::new(0.0, 0.0)), bounds);
+        assert_eq!(Bounds::from_corners(Vec2::new(10.0, 0.0), Vec2::new(0.0, 10.0)), bounds);
+    }
+
+    #[test]
+    fn bounds_from_degenerate_corners() {
+        let bounds = Bounds::from_corners(Vec2::new(5.0, 0.0), Vec2::new(5.0, 10.0));
+        assert_eq!(bounds, Bounds::new(Vec2::new(5.0, 5.0), 0.0, 10.0));
+        assert!(Bounds::try_from_corners(Vec2::new(5.0, 0.0), Vec2::new(5.0, 10.0)).is_err());
+        assert!(Bounds::try_from_corners(Vec2::new(0.0, 0.0), Vec2::new(f32::NAN, 10.0)).is_err());
+        assert!(Bounds::try_from_corners(Vec2::new(0.0, 0.0), Vec2::new(10.0, 10.0)).is_ok());
     }
 
     #[test]
@@ -142,4 +247,59 @@ mod tests {
         assert!(bounds.contains(Vec2::new(-2.0, -2.0)));
         assert!(!bounds.contains(Vec2::new(10.0, 10.0)))
     }
+
+    #[test]
+    fn bounds_intersects_area() {
+        let bounds = Bounds::new(Vec2::ZERO, 4.0, 4.0);
+        assert!(bounds.intersects(Bounds::new(Vec2::new(2.0, 2.0), 2.0, 2.0)));
+        assert!(bounds.intersects(Bounds::new(Vec2::ZERO, 10.0, 1.0)));
+        assert!(bounds.intersects(Bounds::new(Vec2::ZERO, 10.0, 10.0)));
+        assert!(!bounds.intersects(Bounds::new(Vec2::new(4.0, 0.0), 2.0, 2.0)));
+    }
+
+    #[test]
+    fn bounds_intersects_circle() {
+        let bounds = Bounds::new(Vec2::ZERO, 4.0, 4.0);
+        assert!(bounds.intersects_circle(Vec2::ZERO, 1.0));
+        assert!(bounds.intersects_circle(Vec2::new(3.0, 0.0), 1.0));
+        assert!(!bounds.intersects_circle(Vec2::new(3.0, 3.0), 1.0));
+    }
+
+    #[test]
+    fn bounds_ray_entry() {
+        let bounds = Bounds::new(Vec2::new(10.0, 0.0), 4.0, 4.0);
+        assert_eq!(bounds.ray_entry(Vec2::ZERO, Vec2::X), Some(8.0));
+        assert_eq!(bounds.ray_entry(Vec2::ZERO, Vec2::X * 2.0), Some(4.0));
+        assert_eq!(bounds.ray_entry(Vec2::new(10.0, 0.0), Vec2::Y), Some(0.0));
+        assert_eq!(bounds.ray_entry(Vec2::ZERO, -Vec2::X), None);
+        assert_eq!(bounds.ray_entry(Vec2::ZERO, Vec2::Y), None);
+        // along a side
+        assert_eq!(bounds.ray_entry(Vec2::new(0.0, 2.0), Vec2::X), Some(8.0));
+    }
+
+    #[test]
+    fn bounds_expanded_shrunk() {
+        let bounds = Bounds::new(Vec2::ONE, 4.0, 4.0);
+        assert_eq!(bounds.expanded(1.0), Bounds::new(Vec2::ONE, 6.0, 6.0));
+        assert_eq!(bounds.shrunk(1.0), Bounds::new(Vec2::ONE, 2.0, 2.0));
+        assert_eq!(bounds.shrunk(3.0), Bounds::new(Vec2::ONE, 0.0, 0.0));
+    }
+
+    #[test]
+    fn bounds_union_intersection() {
+        let a = Bounds::from_corners(Vec2::new(0.0, 0.0), Vec2::new(4.0, 4.0));
+        let b = Bounds::from_corners(Vec2::new(2.0, 2.0), Vec2::new(6.0, 8.0));
+        assert_eq!(a.union(b), Bounds::from_corners(Vec2::new(0.0, 0.0), Vec2::new(6.0, 8.0)));
+        assert_eq!(a.intersection(b), Some(Bounds::from_corners(Vec2::new(2.0, 2.0), Vec2::new(4.0, 4.0))));
+
+        let c = Bounds::from_corners(Vec2::new(5.0, 5.0), Vec2::new(6.0, 6.0));
+        assert_eq!(a.intersection(c), None);
+    }
+
+    #[test]
+    fn bounds_clamp_point() {
+        let bounds = Bounds::new(Vec2::ZERO, 4.0, 4.0);
+        assert_eq!(bounds.clamp_point(Vec2::new(1.0, -1.0)), Vec2::new(1.0, -1.0));
+        assert_eq!(bounds.clamp_point(Vec2::new(10.0, -10.0)), Vec2::new(2.0, -2.0));
+    }
 }
@@ -0,0 +1,134 @@
+use std::collections::HashMap;
+use std::mem;
+
+use bevy::ecs::entity::Entity;
+
+/// Maximum depth of a `QuadTree`, deeper nodes can't be addressed by a
+/// `NodeId`.
+pub const MAX_DEPTH: u8 = 32;
+
+/// Address of a node within a `QuadTree`, as the path of region indices
+/// starting from the root. A `NodeId` stays valid while the tree is not split
+/// or merged at or above the node, so callers can revisit a leaf cheaply
+/// instead of searching for it from the root.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct NodeId {
+    path: u64,
+    depth: u8,
+}
+
+impl NodeId {
+    pub const ROOT: Self = Self { path: 0, depth: 0 };
+
+    #[inline(always)]
+    pub fn depth(&self) -> u8 { self.depth }
+
+    /// Handle of the child node in region `index`.
+    #[inline]
+    pub fn child(&self, index: usize) -> Self {
+        debug_assert!(self.depth < MAX_DEPTH);
+        Self {
+            path: self.path | ((index as u64) << (2 * self.depth as u64)),
+            depth: self.depth + 1,
+        }
+    }
+
+    /// Handle of the parent node, or `None` for the root.
+    #[inline]
+    pub fn parent(&self) -> Option<Self> {
+        if self.depth == 0 {
+            return None;
+        }
+
+        let depth = self.depth - 1;
+        Some(Self {
+            path: self.path & !(0b11 << (2 * depth as u64)),
+            depth,
+        })
+    }
+
+    /// Region index taken at `level` of the path.
+    #[inline]
+    pub fn region_at(&self, level: u8) -> usize {
+        ((self.path >> (2 * level as u64)) & 0b11) as usize
+    }
+}
+
+/// Keeps track of the leaves in which each entity is stored.
+#[derive(Default)]
+pub(crate) struct EntityMap(HashMap<Entity, Vec<NodeId>>);
+
+impl EntityMap {
+    #[inline]
+    pub fn add(&mut self, entity: Entity, handle: NodeId) {
+        let handles = self.0.entry(entity).or_default();
+        if !handles.contains(&handle) {
+            handles.push(handle);
+        }
+    }
+
+    #[inline]
+    pub fn remove(&mut self, entity: Entity, handle: NodeId) {
+        if let Some(handles) = self.0.get_mut(&entity) {
+            handles.retain(|h| *h != handle);
+            if handles.is_empty() {
+                self.0.remove(&entity);
+            }
+        }
+    }
+
+    /// Add the leaves of all entities in `other`.
+    pub fn extend(&mut self, other: EntityMap) {
+        for (entity, handles) in other.0 {
+            for handle in handles {
+                self.add(entity, handle);
+            }
+        }
+    }
+
+    #[inline]
+    pub fn take(&mut self, entity: Entity) -> Option<Vec<NodeId>> {
+        self.0.remove(&entity)
+    }
+
+    #[inline]
+    pub fn get(&self, entity: Entity) -> Option<&[NodeId]> {
+        self.0.get(&entity).map(|handles| handles.as_slice())
+    }
+
+    #[inline]
+    pub fn entities(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.0.keys().copied()
+    }
+
+    #[allow(dead_code)]
+    #[inline]
+    pub fn len(&self) -> usize { self.0.len() }
+
+    /// Approximate amount of bytes allocated by the map.
+    pub fn memory_usage(&self) -> usize {
+        self.0.capacity() * mem::size_of::<(Entity, Vec<NodeId>)>()
+            + self.0.values().map(|handles| handles.capacity() * mem::size_of::<NodeId>()).sum::<usize>()
+    }
+
+    pub fn shrink_to_fit(&mut self) {
+        self.0.shrink_to_fit();
+        self.0.values_mut().for_each(Vec::shrink_to_fit);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn node_handle_path() {
+        let handle = NodeId::ROOT.child(2).child(1).child(3);
+        assert_eq!(handle.depth(), 3);
+        assert_eq!(handle.region_at(0), 2);
+        assert_eq!(handle.region_at(1), 1);
+        assert_eq!(handle.region_at(2), 3);
+        assert_eq!(handle.parent(), Some(NodeId::ROOT.child(2).child(1)));
+        assert_eq!(NodeId::ROOT.parent(), None);
+    }
+}
@@ -0,0 +1,139 @@
+use std::error::Error;
+use std::fmt;
+use std::fmt::Formatter;
+
+use bevy::ecs::entity::Entity;
+use bevy::math::Vec2;
+
+use super::{Bounds, Location, OptionsError};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ErrorKind {
+    OutOfBounds(Bounds, Location),
+    DegenerateBounds(Vec2, Vec2),
+    /// A leaf which can't be split any further holds more than this amount
+    /// of elements.
+    #[allow(dead_code)]
+    CapacityExceeded(usize),
+    InvalidOptions(OptionsError),
+}
+
+impl ErrorKind {
+    #[allow(dead_code)]
+    pub fn as_str(&self) -> &'static str {
+        use ErrorKind::*;
+        match *self {
+            OutOfBounds(_, _) => "out of bounds",
+            DegenerateBounds(_, _) => "degenerate bounds",
+            CapacityExceeded(_) => "capacity exceeded",
+            InvalidOptions(_) => "invalid options",
+        }
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// Error of a `QuadTree` operation, with the entity and depth at which it
+/// occurred, when known.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct QuadTreeError {
+    kind: ErrorKind,
+    entity: Option<Entity>,
+    depth: Option<u8>,
+}
+
+impl QuadTreeError {
+    #[inline]
+    pub fn new(kind: ErrorKind) -> Self {
+        Self {
+            kind,
+            entity: None,
+            depth: None,
+        }
+    }
+
+    #[inline]
+    pub fn with_entity(mut self, entity: Entity) -> Self {
+        self.entity = Some(entity);
+        self
+    }
+
+    #[inline]
+    pub fn with_depth(mut self, depth: u8) -> Self {
+        self.depth = Some(depth);
+        self
+    }
+
+    #[allow(dead_code)]
+    #[inline]
+    pub fn kind(&self) -> ErrorKind { self.kind }
+
+    #[allow(dead_code)]
+    #[inline]
+    pub fn entity(&self) -> Option<Entity> { self.entity }
+
+    #[allow(dead_code)]
+    #[inline]
+    pub fn depth(&self) -> Option<u8> { self.depth }
+}
+
+impl From<ErrorKind> for QuadTreeError {
+    fn from(kind: ErrorKind) -> Self { Self::new(kind) }
+}
+
+impl From<OptionsError> for QuadTreeError {
+    fn from(err: OptionsError) -> Self { Self::new(ErrorKind::InvalidOptions(err)) }
+}
+
+impl fmt::Display for QuadTreeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if let Some(entity) = self.entity {
+            write!(f, "entity {}: ", entity.id())?;
+        }
+        if let Some(depth) = self.depth {
+            write!(f, "depth {}: ", depth)?;
+        }
+
+        use ErrorKind::*;
+        match self.kind {
+            OutOfBounds(bounds, location) => write!(f, "{}, {:?} not in {:?}", self.kind, location, bounds),
+            DegenerateBounds(a, b) => write!(f, "{}, corners {} and {} don't span an area", self.kind, a, b),
+            CapacityExceeded(capacity) => write!(f, "{}, leaf holds more than {} elements", self.kind, capacity),
+            InvalidOptions(err) => write!(f, "{}, {}", self.kind, err),
+        }
+    }
+}
+
+impl Error for QuadTreeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match &self.kind {
+            ErrorKind::InvalidOptions(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_describes_its_context() {
+        let bounds = Bounds::new(Vec2::ZERO, 10., 10.);
+        let err = QuadTreeError::new(ErrorKind::OutOfBounds(bounds, Location::Point(Vec2::new(20., 0.))))
+            .with_entity(Entity::from_raw(7))
+            .with_depth(0);
+        assert_eq!(err.entity(), Some(Entity::from_raw(7)));
+        assert_eq!(err.depth(), Some(0));
+        assert!(err.to_string().starts_with("entity 7: depth 0: out of bounds, "), "{}", err);
+        assert!(err.source().is_none());
+
+        let err = QuadTreeError::from(OptionsError::ZeroCapacity);
+        assert_eq!(err.to_string(), "invalid options, capacity must be at least 1");
+        assert!(err.source().is_some());
+    }
+}
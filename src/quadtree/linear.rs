@@ -0,0 +1,208 @@
+use bevy::ecs::entity::Entity;
+
+use crate::*;
+
+/// Maximum depth of a `LinearQuadTree`, at which Morton codes use all 32 bits.
+pub const LINEAR_MAX_DEPTH: u8 = 16;
+
+/// Quadtree of points which is stored as a flat array, sorted by the Morton
+/// code (Z-order) of each point. Rebuilding it is a matter of computing codes
+/// and sorting, which is a lot cheaper than allocating nodes. Call `sort`
+/// after inserting, before querying the tree.
+pub struct LinearQuadTree {
+    bounds: Bounds,
+    depth: u8,
+    elems: Vec<(u32, Vec2, Entity)>,
+    sorted: bool,
+}
+
+#[allow(dead_code)]
+impl LinearQuadTree {
+    /// Create a tree which divides `bounds` in a grid of `2^depth` by
+    /// `2^depth` cells.
+    #[inline]
+    pub fn new(bounds: Bounds, depth: u8) -> Self {
+        Self {
+            bounds,
+            depth: depth.min(LINEAR_MAX_DEPTH),
+            elems: Vec::new(),
+            sorted: true,
+        }
+    }
+
+    /// Bounds, or area, in which the `LinearQuadTree` operates.
+    #[inline(always)]
+    pub fn bounds(&self) -> Bounds { self.bounds }
+
+    #[inline(always)]
+    pub fn depth(&self) -> u8 { self.depth }
+
+    #[inline(always)]
+    pub fn len(&self) -> usize { self.elems.len() }
+
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool { self.elems.is_empty() }
+
+    #[inline]
+    pub fn clear(&mut self) {
+        self.elems.clear();
+        self.sorted = true;
+    }
+
+    /// Insert `entity` at `point`.
+    pub fn insert(&mut self, point: Vec2, entity: Entity) -> Result<(), QuadTreeError> {
+        if !self.bounds.contains(point) {
+            return Err(QuadTreeError::new(ErrorKind::OutOfBounds(self.bounds, Location::Point(point)))
+                .with_entity(entity)
+                .with_depth(self.depth));
+        }
+
+        self.elems.push((self.code(point), point, entity));
+        self.sorted = false;
+        return Ok(());
+    }
+
+    /// Sort the inserted elements by their Morton code.
+    #[inline]
+    pub fn sort(&mut self) {
+        if !self.sorted {
+            self.elems.sort_unstable_by_key(|(code, _, entity)| (*code, *entity));
+            self.sorted = true;
+        }
+    }
+
+    /// All elements, in Z-order once sorted.
+    #[inline]
+    pub fn elements(&self) -> impl Iterator<Item = (Vec2, Entity)> + '_ {
+        self.elems.iter().map(|(_, point, entity)| (*point, *entity))
+    }
+
+    /// Find all elements within `area`.
+    pub fn query_rect(&self, area: Bounds) -> Vec<(Vec2, Entity)> {
+        debug_assert!(self.sorted, "LinearQuadTree must be sorted before it is queried");
+
+        let mut vec = Vec::new();
+        self.query_cell(&mut vec, area, 0, 0, 0, 0);
+        return vec;
+    }
+
+    /// Find all elements within the circle at `center` with `radius`.
+    pub fn query_circle(&self, center: Vec2, radius: f32) -> Vec<(Vec2, Entity)> {
+        let mut vec = self.query_rect(Bounds::new(center, radius * 2.0, radius * 2.0));
+        vec.retain(|(point, _)| point.distance_squared(center) <= radius * radius);
+        return vec;
+    }
+
+    // Morton code of the cell which contains `point`.
+    #[inline]
+    fn code(&self, point: Vec2) -> u32 {
+        let cells = (1u32 << self.depth) as f32;
+        let cell = (point - self.bounds.min()) / Vec2::new(self.bounds.width(), self.bounds.height()) * cells;
+        let max = (1u32 << self.depth) - 1;
+
+        let x = (cell.x.max(0.0) as u32).min(max);
+        let y = (cell.y.max(0.0) as u32).min(max);
+        return spread_bits(x) | (spread_bits(y) << 1);
+    }
+
+    // Bounds of the cell at `level`, with its grid coordinates `x` and `y`.
+    #[inline]
+    fn cell_bounds(&self, level: u8, x: u32, y: u32) -> Bounds {
+        let cells = (1u32 << level) as f32;
+        let size = Vec2::new(self.bounds.width(), self.bounds.height()) / cells;
+        let min = self.bounds.min() + Vec2::new(x as f32, y as f32) * size;
+        Bounds::from_corners(min, min + size)
+    }
+
+    // Decompose `area` into cells, which each cover a continuous interval of
+    // Morton codes. Cells that are entirely inside `area` are added at once,
+    // partially covered cells are subdivided until the deepest level.
+    fn query_cell(&self, dest: &mut Vec<(Vec2, Entity)>, area: Bounds, level: u8, x: u32, y: u32, prefix: u32) {
+        let cell = self.cell_bounds(level, x, y);
+        if !area.intersects(cell) {
+            return;
+        }
+
+        let shift = 2 * (self.depth - level) as u32;
+        let start = (prefix as u64) << shift;
+        let end = (prefix as u64 + 1) << shift;
+        let from = self.elems.partition_point(|(code, _, _)| (*code as u64) < start);
+        let to = self.elems.partition_point(|(code, _, _)| (*code as u64) < end);
+        if from == to {
+            return;
+        }
+
+        let covered = area.contains_area(cell);
+        if covered || level == self.depth {
+            for (_, point, entity) in &self.elems[from..to] {
+                if covered || area.contains(*point) {
+                    dest.push((*point, *entity));
+                }
+            }
+            return;
+        }
+
+        for child in 0..4 {
+            let (cx, cy) = (child & 1, child >> 1);
+            self.query_cell(dest, area, level + 1, x * 2 + cx, y * 2 + cy, (prefix << 2) | child);
+        }
+    }
+}
+
+// Spread the lower 16 bits of `v` so there is a zero bit between each of them.
+#[inline(always)]
+fn spread_bits(v: u32) -> u32 {
+    let mut v = v & 0x0000_ffff;
+    v = (v | (v << 8)) & 0x00ff_00ff;
+    v = (v | (v << 4)) & 0x0f0f_0f0f;
+    v = (v | (v << 2)) & 0x3333_3333;
+    v = (v | (v << 1)) & 0x5555_5555;
+    v
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use proptest::prelude::*;
+
+    use super::*;
+
+    #[test]
+    fn spread_bits_interleaves() {
+        assert_eq!(spread_bits(0b1011), 0b1000101);
+        assert_eq!(spread_bits(0xffff), 0x5555_5555);
+    }
+
+    fn point() -> impl Strategy<Value = Vec2> {
+        (-64.0f32..64.0, -64.0f32..64.0).prop_map(|(x, y)| Vec2::new(x, y))
+    }
+
+    proptest! {
+        #[test]
+        fn query_circle_matches_brute_force(
+            depth in 0u8..=LINEAR_MAX_DEPTH,
+            points in prop::collection::vec(point(), 0..200),
+            center in point(),
+            radius in 0.0f32..64.0,
+        ) {
+            let mut tree = LinearQuadTree::new(Bounds::new(Vec2::ZERO, 128.0, 128.0), depth);
+            for (i, point) in points.iter().enumerate() {
+                tree.insert(*point, Entity::from_raw(i as u32)).unwrap();
+            }
+            tree.sort();
+
+            let found: HashSet<Entity> = tree.query_circle(center, radius)
+                .into_iter()
+                .map(|(_, entity)| entity)
+                .collect();
+            let expected: HashSet<Entity> = points.iter()
+                .enumerate()
+                .filter(|(_, point)| point.distance_squared(center) <= radius * radius)
+                .map(|(i, _)| Entity::from_raw(i as u32))
+                .collect();
+
+            prop_assert_eq!(found, expected);
+        }
+    }
+}
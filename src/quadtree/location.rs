@@ -2,12 +2,17 @@ use bevy::ecs::component::Component;
 
 use crate::*;
 
-#[derive(Component, Copy, Clone, Debug, PartialEq)]
+#[derive(Component, Copy, Clone, Debug, PartialEq, Reflect)]
+#[reflect_value(Component, PartialEq)]
 pub enum Location {
     Point(Vec2),
     Area(Bounds),
 }
 
+impl Default for Location {
+    fn default() -> Self { Self::Point(Vec2::ZERO) }
+}
+
 impl Location {
     #[inline]
     pub fn new(center: Vec2, width: f32, height: f32) -> Self {
@@ -18,6 +23,33 @@ impl Location {
         }
     }
 
+    /// Indicates if the location overlaps with the circle at `center` with
+    /// `radius`.
+    #[inline]
+    pub fn intersects_circle(&self, center: Vec2, radius: f32) -> bool {
+        match self {
+            Self::Point(point) => point.distance_squared(center) <= radius * radius,
+            Self::Area(bounds) => bounds.intersects_circle(center, radius),
+        }
+    }
+
+    /// Indicates if the location overlaps with `area`.
+    #[inline]
+    pub fn intersects(&self, area: Bounds) -> bool {
+        match self {
+            Self::Point(point) => area.contains(*point),
+            Self::Area(bounds) => bounds.intersects(area),
+        }
+    }
+
+    #[inline]
+    pub fn center(&self) -> Vec2 {
+        match self {
+            Self::Point(point) => *point,
+            Self::Area(bounds) => bounds.center(),
+        }
+    }
+
     #[allow(dead_code)]
     #[inline]
     pub fn set_center(&mut self, center: Vec2) {
@@ -1,58 +1,29 @@
 use std::borrow::Borrow;
-use std::fmt;
-use std::fmt::Formatter;
+use std::collections::{HashMap, HashSet};
+use std::mem;
 use std::ops::{Deref, DerefMut};
 
 use bevy::ecs::entity::Entity;
+use bevy::tasks::TaskPool;
 pub use bevy::math::Vec2;
 
+pub use aggregate::*;
 pub use bounds::*;
+pub use entity_map::*;
+pub use error::*;
+pub use linear::*;
 pub use location::*;
+pub use options::*;
+pub(crate) use splits::*;
 
+mod aggregate;
 mod bounds;
-pub mod iter;
+mod entity_map;
+mod error;
+mod linear;
 mod location;
-
-#[derive(Clone, Copy, Debug, PartialEq)]
-pub enum ErrorKind {
-    OutOfBounds(Bounds, Location),
-}
-
-impl ErrorKind {
-    #[allow(dead_code)]
-    pub fn as_str(&self) -> &'static str {
-        use ErrorKind::*;
-        match *self {
-            OutOfBounds(_, _) => "out of bounds",
-        }
-    }
-}
-
-impl fmt::Display for ErrorKind {
-    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.as_str())
-    }
-}
-
-#[derive(Clone, Copy, PartialEq)]
-pub struct Options {
-    /// Target capacity of a leaf before it is split in nodes. Note that a leaf
-    /// may contain more items when `max_depth` is reached.
-    pub capacity: usize,
-
-    pub max_depth: Option<u8>,
-    pub min_size: Option<Vec2>,
-}
-
-impl Default for Options {
-    fn default() -> Self {
-        Self {
-            capacity: 4,
-            max_depth: None,
-            min_size: None,
-        }
-    }
-}
+mod options;
+mod splits;
 
 #[allow(dead_code)]
 pub enum Region {
@@ -80,28 +51,61 @@ impl Into<usize> for Region {
     fn into(self) -> usize { self.index() }
 }
 
-pub(crate) enum Body {
+pub(crate) enum Body<A: Aggregate> {
     Empty,
     Leaf(Vec<(Location, Entity)>),
-    Node([QuadTree; 4]), // 4 regions
+    Node([QuadTree<A>; 4]), // 4 regions
 }
 
-pub struct QuadTree {
+/// Quadtree of entities, where each region maintains an `Aggregate` summary
+/// of the elements within it. By default no summary is maintained.
+pub struct QuadTree<A: Aggregate = ()> {
     pub(crate) bounds: Bounds,
-    pub(crate) body: Box<Body>,
+    pub(crate) body: Box<Body<A>>,
     options: Options,
     depth: u8,
+    aggregate: A,
+    // only maintained by the root
+    entities: EntityMap,
+    items: HashMap<Entity, A::Item>,
+    splits: SplitBudget,
 }
 
 impl QuadTree {
     #[inline]
     pub fn new(bounds: Bounds, options: Options) -> Self {
-        Self {
-            bounds,
-            options,
-            body: Box::new(Body::Empty),
-            depth: 0,
-        }
+        Self::with_aggregate(bounds, options)
+    }
+
+    /// Create a `QuadTree`, after checking if `options` make sense for
+    /// `bounds`.
+    #[allow(dead_code)]
+    #[inline]
+    pub fn try_new(bounds: Bounds, options: Options) -> Result<Self, QuadTreeError> {
+        options.validate_for(bounds)?;
+        Ok(Self::new(bounds, options))
+    }
+
+    /// Build a `QuadTree` of `elements` on the threads of `pool`, see
+    /// `build_parallel_with`.
+    pub fn build_parallel(
+        bounds: Bounds,
+        options: Options,
+        elements: &[(Location, Entity)],
+        pool: &TaskPool,
+    ) -> (Self, Vec<QuadTreeError>) {
+        let elements: Vec<(Location, Entity, ())> = elements.iter()
+            .map(|(location, value)| (*location, *value, ()))
+            .collect();
+        Self::build_parallel_with(bounds, options, &elements, pool)
+    }
+}
+
+impl<A: Aggregate> QuadTree<A> {
+    /// Create a `QuadTree` which maintains an `A` summary in each region.
+    #[inline]
+    pub fn with_aggregate(bounds: Bounds, options: Options) -> Self {
+        Self::new_region(bounds, options, 0)
     }
 
     #[inline]
@@ -110,10 +114,81 @@ impl QuadTree {
             bounds,
             options,
             body: Box::new(Body::Empty),
-            depth: depth + 1,
+            depth,
+            aggregate: A::default(),
+            entities: EntityMap::default(),
+            items: HashMap::new(),
+            splits: SplitBudget::new(options.max_splits),
         }
     }
 
+    /// Build a `QuadTree` of `elements` on the threads of `pool`. Once the
+    /// root is split, each of its four regions is built by a task of its own,
+    /// which results in the same tree as inserting the elements one by one.
+    /// Elements outside of `bounds` are left out, and returned as errors.
+    pub fn build_parallel_with(
+        bounds: Bounds,
+        options: Options,
+        elements: &[(Location, Entity, A::Item)],
+        pool: &TaskPool,
+    ) -> (Self, Vec<QuadTreeError>)
+    where
+        A: Send,
+        A::Item: Send + Sync,
+    {
+        let mut tree = Self::with_aggregate(bounds, options);
+        let mut errors = Vec::new();
+        let mut inside = Vec::with_capacity(elements.len());
+        for (location, value, item) in elements.iter().copied() {
+            if tree.contains(location) {
+                inside.push((location, value, item));
+            } else {
+                errors.push(QuadTreeError::new(ErrorKind::OutOfBounds(bounds, location))
+                    .with_entity(value)
+                    .with_depth(0));
+            }
+        }
+
+        // a root which is never split is not worth the tasks, and a budget of
+        // splits can't be shared between them
+        if options.max_splits.is_some() || !Self::splits_with(bounds, options, 0, inside.len()) {
+            for (location, value, item) in inside {
+                if let Err(err) = tree.insert_with(location, value, item) {
+                    errors.push(err);
+                }
+            }
+            return (tree, errors);
+        }
+
+        if A::TRACKED {
+            for (location, value, item) in inside.iter() {
+                tree.aggregate.add(*location, *item);
+                tree.items.insert(*value, *item);
+            }
+        }
+        let mut regions = Self::child_regions(bounds, options, 0);
+        let (inside, items) = (&inside, &tree.items);
+        let maps = pool.scope(|scope| {
+            for (i, region) in regions.iter_mut().enumerate() {
+                scope.spawn(async move {
+                    // elements which don't overlap with the region are skipped
+                    let mut entities = EntityMap::default();
+                    let mut splits = SplitBudget::default();
+                    for (location, value, item) in inside.iter() {
+                        region.insert_tracked(*location, *value, *item, NodeId::ROOT.child(i), &mut entities, items, &mut splits);
+                    }
+                    entities
+                });
+            }
+        });
+
+        for entities in maps {
+            tree.entities.extend(entities);
+        }
+        tree.body = Box::new(Body::Node(regions));
+        (tree, errors)
+    }
+
     /// Bounds, or area, in which the `QuadTree` operates.
     #[inline(always)]
     pub fn bounds(&self) -> Bounds { self.bounds }
@@ -122,6 +197,11 @@ impl QuadTree {
     #[inline(always)]
     pub fn options(&self) -> Options { self.options }
 
+    /// Summary of all elements within the region.
+    #[allow(dead_code)]
+    #[inline(always)]
+    pub fn aggregate(&self) -> &A { &self.aggregate }
+
     /// Indicates if the `QuadTree` contains any inserted elements.
     #[allow(dead_code)]
     #[inline(always)]
@@ -153,80 +233,399 @@ impl QuadTree {
     }
 
     /// Insert `entity` at `location`.
-    pub fn insert(&mut self, location: Location, value: Entity) -> Result<(), ErrorKind> {
+    #[inline]
+    pub fn insert(&mut self, location: Location, value: Entity) -> Result<(), QuadTreeError> {
+        self.insert_with(location, value, A::Item::default())
+    }
+
+    /// Insert `entity` at `location`, with the `item` the summaries of the
+    /// regions are computed from.
+    pub fn insert_with(&mut self, location: Location, value: Entity, item: A::Item) -> Result<(), QuadTreeError> {
         if !self.contains(location) {
-            return Err(ErrorKind::OutOfBounds(self.bounds, location));
+            return Err(QuadTreeError::new(ErrorKind::OutOfBounds(self.bounds, location))
+                .with_entity(value)
+                .with_depth(self.depth));
+        }
+
+        let mut entities = std::mem::take(&mut self.entities);
+        if A::TRACKED {
+            self.items.insert(value, item);
+        }
+        let items = std::mem::take(&mut self.items);
+        let mut splits = std::mem::take(&mut self.splits);
+        self.insert_tracked(location, value, item, NodeId::ROOT, &mut entities, &items, &mut splits);
+        self.entities = entities;
+        self.items = items;
+        self.splits = splits;
+        return Ok(());
+    }
+
+    // Insert in this region, which is located at `handle`, while keeping track
+    // of the leaves the elements end up in. `items` contains the item of each
+    // element, for when a leaf is split.
+    #[allow(clippy::too_many_arguments)]
+    fn insert_tracked(
+        &mut self,
+        location: Location,
+        value: Entity,
+        item: A::Item,
+        handle: NodeId,
+        entities: &mut EntityMap,
+        items: &HashMap<Entity, A::Item>,
+        splits: &mut SplitBudget,
+    ) {
+        if !self.contains(location) {
+            return;
+        }
+        if A::TRACKED {
+            self.aggregate.add(location, item);
         }
 
         match self.body.deref_mut() {
             // quadtree is empty, make it a leaf
             Body::Empty => {
-                let mut elems = Vec::with_capacity(self.options.capacity);
+                let mut elems = Vec::with_capacity(self.options.capacity_at(self.depth));
                 elems.push((location, value));
                 self.body = Box::new(Body::Leaf(elems));
+                entities.add(value, handle);
             }
 
-            // quadtree is a leaf, make it a node
+            // quadtree is a leaf, make it a node when it's over capacity
             Body::Leaf(elems) => {
                 elems.push((location, value));
-                if elems.len() <= self.options.capacity
-                    || self.depth >= self.options.max_depth.unwrap_or(255) {
-                    // return when map is not over capacity or when max depth is reached
-                    return Ok(());
+                entities.add(value, handle);
+                self.split_leaf(handle, entities, items, splits);
+            }
+
+            // quadtree is already a node, try to insert in any of its the regions
+            Body::Node(regions) => {
+                for (i, region) in regions.iter_mut().enumerate() {
+                    region.insert_tracked(location, value, item, handle.child(i), entities, items, splits);
+                }
+            }
+        };
+    }
+
+    // Split this leaf, which is located at `handle`, into regions when it is
+    // over capacity. When `splits` is used up, the split is deferred instead.
+    fn split_leaf(
+        &mut self,
+        handle: NodeId,
+        entities: &mut EntityMap,
+        items: &HashMap<Entity, A::Item>,
+        splits: &mut SplitBudget,
+    ) {
+        let elems = match self.body.deref() {
+            Body::Leaf(elems) => elems,
+            _ => return,
+        };
+        if !Self::splits_with(self.bounds, self.options, self.depth, elems.len()) {
+            return;
+        }
+        if !splits.take() {
+            splits.defer(handle);
+            return;
+        }
+
+        let mut regions = Self::child_regions(self.bounds, self.options, self.depth);
+        for (loc, val) in elems.iter() {
+            entities.remove(*val, handle);
+            let item = items.get(val).copied().unwrap_or_default();
+            for (i, region) in regions.iter_mut().enumerate() {
+                region.insert_tracked(*loc, *val, item, handle.child(i), entities, items, splits);
+            }
+        }
+
+        self.body = Box::new(Body::Node(regions));
+    }
+
+    /// Start a new frame, which restores the budget of `max_splits` of the
+    /// options. The budget is spent on the leaves of which the split was
+    /// deferred first, oldest first. Returns the amount of leaves of which
+    /// the split is still deferred.
+    #[allow(dead_code)]
+    pub fn next_frame(&mut self) -> usize {
+        let pending = self.splits.restore(self.options.max_splits);
+        if pending.is_empty() {
+            return 0;
+        }
+
+        let mut entities = std::mem::take(&mut self.entities);
+        let items = std::mem::take(&mut self.items);
+        let mut splits = std::mem::take(&mut self.splits);
+        for handle in pending {
+            // leaves might have been split or merged since, or have lost
+            // elements, in which case they're skipped
+            if let Some(leaf) = self.node_mut(handle) {
+                leaf.split_leaf(handle, &mut entities, &items, &mut splits);
+            }
+        }
+        self.entities = entities;
+        self.items = items;
+        self.splits = splits;
+        self.splits.pending()
+    }
+
+    /// Amount of leaves which are over capacity, of which the split is
+    /// deferred to a later frame.
+    #[allow(dead_code)]
+    #[inline]
+    pub fn pending_splits(&self) -> usize { self.splits.pending() }
+
+    // Indicates if a leaf of the region at `bounds` and `depth` is split into
+    // regions once it holds `len` elements: when it is over capacity, and
+    // neither the maximum depth nor the minimum size is reached.
+    #[inline]
+    fn splits_with(bounds: Bounds, options: Options, depth: u8, len: usize) -> bool {
+        if len <= options.capacity_at(depth)
+            || depth >= options.max_depth.unwrap_or(MAX_DEPTH).min(MAX_DEPTH) {
+            return false;
+        }
+        if let Some(min_size) = options.min_size {
+            if bounds.width() <= (min_size.x * 2.0) || bounds.height() <= (min_size.y * 2.0) {
+                return false;
+            }
+        }
+        true
+    }
+
+    // Empty regions the region at `bounds` and `depth` is split into, in the
+    // order of `Region`.
+    fn child_regions(bounds: Bounds, options: Options, depth: u8) -> [Self; 4] {
+        let center = bounds.center();
+        [
+            // Region::NorthWest
+            Self::new_region(
+                Bounds::from_corners(bounds.top_left(), center),
+                options,
+                depth + 1,
+            ),
+            // Region::NorthEast
+            Self::new_region(
+                Bounds::from_corners(center, bounds.top_right()),
+                options,
+                depth + 1,
+            ),
+            // Region::SouthEast
+            Self::new_region(
+                Bounds::from_corners(center, bounds.bottom_right()),
+                options,
+                depth + 1,
+            ),
+            // Region::SouthWest
+            Self::new_region(
+                Bounds::from_corners(bounds.bottom_left(), center),
+                options,
+                depth + 1,
+            ),
+        ]
+    }
+
+    /// Entities stored in the `QuadTree`, in no particular order.
+    #[inline]
+    pub fn entities(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.entities.entities()
+    }
+
+    /// Indicates if `entity` is stored in the `QuadTree`.
+    #[allow(dead_code)]
+    #[inline]
+    pub fn contains_entity(&self, entity: Entity) -> bool {
+        self.entities.get(entity).is_some()
+    }
+
+    /// Remove `entity` from all leaves it is stored in. Leaves which end up
+    /// with few enough elements are merged back into their parent. Returns
+    /// `false` when the entity was not stored.
+    #[allow(dead_code)]
+    pub fn remove_entity(&mut self, entity: Entity) -> bool {
+        let handles = match self.entities.take(entity) {
+            Some(handles) => handles,
+            None => return false,
+        };
+
+        // the entity leaves all of its leaves and the summaries are updated
+        // before any regions are merged, while all handles are still valid and
+        // no sibling can put the entity back
+        let item = self.items.remove(&entity).unwrap_or_default();
+        let mut location = None;
+        for handle in handles.iter() {
+            if let Some(leaf) = self.node_mut(*handle) {
+                location = location.or(leaf.remove_from_leaf(entity));
+            }
+        }
+        if let (true, Some(location)) = (A::TRACKED, location) {
+            let mut updated = HashSet::new();
+            for handle in handles.iter() {
+                self.update_aggregates(*handle, &mut updated, |aggregate| aggregate.remove(location, item));
+            }
+        }
+
+        let mut entities = std::mem::take(&mut self.entities);
+        for handle in handles {
+            let mut parent = handle.parent();
+            while let Some(handle) = parent {
+                let merged = match self.node_mut(handle) {
+                    Some(node) => node.try_merge(handle, &mut entities),
+                    None => false,
+                };
+                if !merged {
+                    break;
                 }
-                if let Some(min_size) = self.options.min_size {
-                    if self.bounds.width() <= (min_size.x * 2.0) || self.bounds.height() <= (min_size.y * 2.0) {
-                        return Ok(());
+                parent = handle.parent();
+            }
+        }
+        self.entities = entities;
+        return true;
+    }
+
+    /// Move `entity` to `location`. When it stays within the only leaf it is
+    /// stored in, the element is updated in place. Otherwise it is removed
+    /// and inserted again.
+    #[allow(dead_code)]
+    pub fn relocate(&mut self, entity: Entity, location: Location) -> Result<(), QuadTreeError> {
+        if !self.contains(location) {
+            return Err(QuadTreeError::new(ErrorKind::OutOfBounds(self.bounds, location))
+                .with_entity(entity)
+                .with_depth(self.depth));
+        }
+
+        if let Some(&[handle]) = self.entities.get(entity) {
+            if let Some(leaf) = self.node_mut(handle) {
+                let fits = match location {
+                    Location::Point(point) => leaf.bounds.contains(point),
+                    Location::Area(area) => leaf.bounds.contains_area(area),
+                };
+                if let (true, Body::Leaf(elems)) = (fits, leaf.body.deref_mut()) {
+                    let mut previous = location;
+                    for elem in elems.iter_mut().filter(|(_, e)| *e == entity) {
+                        previous = elem.0;
+                        elem.0 = location;
+                    }
+
+                    if A::TRACKED {
+                        let item = self.items.get(&entity).copied().unwrap_or_default();
+                        self.update_aggregates(handle, &mut HashSet::new(), |aggregate| {
+                            if !aggregate.remove(previous, item) {
+                                return false;
+                            }
+                            aggregate.add(location, item);
+                            true
+                        });
                     }
+                    return Ok(());
                 }
+            }
+        }
 
-                let center = self.bounds.center();
-                let mut regions = [
-                    // Region::NorthWest
-                    Self::new_region(
-                        Bounds::from_corners(self.bounds.top_left(), center),
-                        self.options,
-                        self.depth + 1,
-                    ),
-                    // Region::NorthEast
-                    Self::new_region(
-                        Bounds::from_corners(center, self.bounds.top_right()),
-                        self.options,
-                        self.depth + 1,
-                    ),
-                    // Region::SouthEast
-                    Self::new_region(
-                        Bounds::from_corners(center, self.bounds.bottom_right()),
-                        self.options,
-                        self.depth + 1,
-                    ),
-                    // Region::SouthWest
-                    Self::new_region(
-                        Bounds::from_corners(self.bounds.bottom_left(), center),
-                        self.options,
-                        self.depth + 1,
-                    ),
-                ];
-
-                for (loc, val) in elems.iter() {
-                    let _ = regions[0].insert(*loc, *val);
-                    let _ = regions[1].insert(*loc, *val);
-                    let _ = regions[2].insert(*loc, *val);
-                    let _ = regions[3].insert(*loc, *val);
+        let item = self.items.get(&entity).copied().unwrap_or_default();
+        self.remove_entity(entity);
+        return self.insert_with(location, entity, item);
+    }
+
+    // Update the summaries of the regions from the root down to `handle`, which
+    // are not in `updated` yet. Summaries for which `update` returns `false`
+    // are computed again from their elements.
+    fn update_aggregates<F>(&mut self, handle: NodeId, updated: &mut HashSet<NodeId>, mut update: F)
+    where
+        F: FnMut(&mut A) -> bool,
+    {
+        let items = std::mem::take(&mut self.items);
+        let mut path = handle;
+        loop {
+            if updated.insert(path) {
+                if let Some(node) = self.node_mut(path) {
+                    if !update(&mut node.aggregate) {
+                        node.recompute_aggregate(&items);
+                    }
                 }
+            }
+            path = match path.parent() {
+                Some(parent) => parent,
+                None => break,
+            };
+        }
+        self.items = items;
+    }
 
-                self.body = Box::new(Body::Node(regions));
+    // Compute the summary from all elements within the region.
+    fn recompute_aggregate(&mut self, items: &HashMap<Entity, A::Item>) {
+        let mut aggregate = A::default();
+        let mut seen = HashSet::new();
+        self.for_each_leaf(&mut |leaf| {
+            for (location, entity) in leaf.leaf_elements().unwrap() {
+                if seen.insert(*entity) {
+                    aggregate.add(*location, items.get(entity).copied().unwrap_or_default());
+                }
             }
+        });
+        self.aggregate = aggregate;
+    }
 
-            // quadtree is already a node, try to insert in any of its the regions
-            Body::Node(regions) => {
-                let _ = regions[0].insert(location, value);
-                let _ = regions[1].insert(location, value);
-                let _ = regions[2].insert(location, value);
-                let _ = regions[3].insert(location, value);
+    // Find the region at `handle`.
+    fn node_mut(&mut self, handle: NodeId) -> Option<&mut QuadTree<A>> {
+        let mut node = self;
+        for level in 0..handle.depth() {
+            node = match node.body.deref_mut() {
+                Body::Node(regions) => &mut regions[handle.region_at(level)],
+                _ => return None,
+            };
+        }
+        Some(node)
+    }
+
+    // Remove `entity` from the leaf, returns its location when it was stored.
+    fn remove_from_leaf(&mut self, entity: Entity) -> Option<Location> {
+        let mut location = None;
+        if let Body::Leaf(elems) = self.body.deref_mut() {
+            location = elems.iter().find(|(_, e)| *e == entity).map(|(location, _)| *location);
+            elems.retain(|(_, e)| *e != entity);
+            if elems.is_empty() {
+                self.body = Box::new(Body::Empty);
             }
+        }
+        location
+    }
+
+    // Turn a node, located at `handle`, back into a leaf when all its regions
+    // are leaves which together hold no more elements than its capacity.
+    fn try_merge(&mut self, handle: NodeId, entities: &mut EntityMap) -> bool {
+        let regions = match self.body.deref() {
+            Body::Node(regions) => regions,
+            _ => return false,
         };
-        return Ok(());
+
+        let mut elems: Vec<(Location, Entity)> = Vec::new();
+        for region in regions {
+            match region.body.deref() {
+                Body::Empty => {}
+                Body::Leaf(region_elems) => {
+                    for elem in region_elems {
+                        if !elems.iter().any(|(_, e)| *e == elem.1) {
+                            elems.push(*elem);
+                        }
+                    }
+                }
+                Body::Node(_) => return false,
+            }
+        }
+        if elems.len() > self.options.capacity_at(self.depth) {
+            return false;
+        }
+
+        for (i, region) in regions.iter().enumerate() {
+            if let Body::Leaf(region_elems) = region.body.deref() {
+                for (_, entity) in region_elems {
+                    entities.remove(*entity, handle.child(i));
+                }
+            }
+        }
+        for (_, entity) in elems.iter() {
+            entities.add(*entity, handle);
+        }
+
+        self.body = Box::new(if elems.is_empty() { Body::Empty } else { Body::Leaf(elems) });
+        return true;
     }
 
     /// Count and return the amount of inserted items among all leafs.
@@ -260,7 +659,7 @@ impl QuadTree {
 
     #[allow(dead_code)]
     #[inline]
-    pub fn region(&self, region: Region) -> Option<&QuadTree> {
+    pub fn region(&self, region: Region) -> Option<&QuadTree<A>> {
         return match self.body.deref() {
             Body::Node(regions) => {
                 Some(&regions[region.index()])
@@ -269,13 +668,169 @@ impl QuadTree {
         };
     }
 
+    /// Regions of the `QuadTree` when it is split, in the order of `Region`.
+    #[allow(dead_code)]
+    #[inline]
+    pub fn children(&self) -> Option<&[QuadTree<A>; 4]> {
+        match self.body.deref() {
+            Body::Node(regions) => Some(regions),
+            _ => None,
+        }
+    }
+
+    /// Elements of the `QuadTree` when it is a leaf.
+    #[inline]
+    pub fn leaf_elements(&self) -> Option<&[(Location, Entity)]> {
+        return match self.body.deref() {
+            Body::Leaf(elems) => Some(elems),
+            _ => None,
+        };
+    }
+
+    /// Approximate amount of bytes used by the `QuadTree`, including all
+    /// regions and elements it allocated room for.
+    pub fn memory_usage(&self) -> usize {
+        mem::size_of::<Self>() + self.heap_usage()
+    }
+
+    // Bytes allocated by this region and its sub-regions, which are stored
+    // inline in the body of their parent.
+    fn heap_usage(&self) -> usize {
+        let body = match self.body.deref() {
+            Body::Empty => 0,
+            Body::Leaf(elems) => elems.capacity() * mem::size_of::<(Location, Entity)>(),
+            Body::Node(regions) => regions.iter().map(|region| region.heap_usage()).sum(),
+        };
+        mem::size_of::<Body<A>>()
+            + body
+            + self.entities.memory_usage()
+            + self.items.capacity() * mem::size_of::<(Entity, A::Item)>()
+    }
+
+    /// Release the memory which is allocated for more elements than are
+    /// stored, like after many elements were removed.
+    #[allow(dead_code)]
+    pub fn shrink_to_fit(&mut self) {
+        match self.body.deref_mut() {
+            Body::Empty => {}
+            Body::Leaf(elems) => elems.shrink_to_fit(),
+            Body::Node(regions) => regions.iter_mut().for_each(QuadTree::shrink_to_fit),
+        }
+        self.entities.shrink_to_fit();
+        self.items.shrink_to_fit();
+    }
+
+    /// Node at `id`, or `None` when the tree no longer has a node there. A
+    /// node that was split since is returned as the node it became.
+    #[allow(dead_code)]
+    pub fn node(&self, id: NodeId) -> Option<&QuadTree<A>> {
+        let mut node = self;
+        for level in 0..id.depth() {
+            node = match node.body.deref() {
+                Body::Node(regions) => &regions[id.region_at(level)],
+                _ => return None,
+            };
+        }
+        Some(node)
+    }
+
+    /// Item `entity` was inserted with, or `None` when it is not stored or the
+    /// tree has no summary. Only maintained by the root.
+    #[allow(dead_code)]
+    #[inline]
+    pub fn item(&self, entity: Entity) -> Option<A::Item> {
+        self.items.get(&entity).copied()
+    }
+
+    /// Leaves `entity` is stored in. Only maintained by the root.
+    #[allow(dead_code)]
+    #[inline]
+    pub fn leaves_of(&self, entity: Entity) -> &[NodeId] {
+        self.entities.get(entity).unwrap_or_default()
+    }
+
+    /// Leaf, or empty region, which contains `point`.
+    #[allow(dead_code)]
+    pub fn leaf_at(&self, point: Vec2) -> Option<NodeId> {
+        if !self.bounds.contains(point) {
+            return None;
+        }
+
+        let mut node = self;
+        let mut id = NodeId::ROOT;
+        while let Body::Node(regions) = node.body.deref() {
+            let index = regions.iter().position(|region| region.bounds.contains(point))?;
+            node = &regions[index];
+            id = id.child(index);
+        }
+        Some(id)
+    }
+
+    /// Call `f` with the id of each leaf and the leaf itself.
+    #[allow(dead_code)]
+    pub fn for_each_leaf_id<F: FnMut(NodeId, &QuadTree<A>)>(&self, f: &mut F) {
+        for_each_leaf_id(self, NodeId::ROOT, f);
+    }
+
+    /// Call `f` for each leaf, without collecting them first.
+    pub fn for_each_leaf<F: FnMut(&QuadTree<A>)>(&self, f: &mut F) {
+        match self.body.deref() {
+            Body::Empty => {}
+            Body::Leaf(_) => f(self),
+            Body::Node(regions) => {
+                for region in regions {
+                    region.for_each_leaf(f);
+                }
+            }
+        };
+    }
+
     #[inline]
-    pub fn regions(&self) -> Vec<&QuadTree> {
+    pub fn regions(&self) -> Vec<&QuadTree<A>> {
         let mut vec = Vec::new();
         get_regions(&mut vec, self);
         return vec;
     }
 
+    /// Find all elements which overlap with the circle at `center` with
+    /// `radius`. Each element is returned only once, even when it is stored in
+    /// multiple regions.
+    #[allow(dead_code)]
+    pub fn query_circle(&self, center: Vec2, radius: f32) -> Vec<(Location, Entity)> {
+        let mut vec = Vec::new();
+        query_circle(&mut vec, self, center, radius);
+
+        vec.sort_unstable_by_key(|(_, entity)| *entity);
+        vec.dedup_by_key(|(_, entity)| *entity);
+        return vec;
+    }
+
+    /// Find all elements which overlap with `area`. Each element is returned
+    /// only once, even when it is stored in multiple regions.
+    pub fn query_area(&self, area: Bounds) -> Vec<(Location, Entity)> {
+        let mut vec = Vec::new();
+        query_area(&mut vec, self, area);
+
+        vec.sort_unstable_by_key(|(_, entity)| *entity);
+        vec.dedup_by_key(|(_, entity)| *entity);
+        return vec;
+    }
+
+    /// Nearest element along the ray from `origin` in `direction` within
+    /// `max_distance`, and the distance at which it is hit. `hit` returns that
+    /// distance for an element, or `None` when the ray misses it. Regions are
+    /// searched front to back, and skipped when they start beyond the nearest
+    /// hit so far.
+    #[allow(dead_code)]
+    pub fn raycast<F>(&self, origin: Vec2, direction: Vec2, max_distance: f32, hit: &mut F) -> Option<(f32, Entity)>
+    where
+        F: FnMut(Location, Entity) -> Option<f32>,
+    {
+        let mut nearest = None;
+        raycast(&mut nearest, self, origin, direction, max_distance, hit);
+        nearest
+    }
+
     // pub fn iter(&self) -> CombinationIterator {
     //     let mut vec = Vec::<Combination>::new();
     //     fill_combination_iterator(&mut vec, self);
@@ -291,7 +846,141 @@ impl QuadTree {
     // }
 }
 
-fn get_regions<'a>(dest: &mut Vec<&'a QuadTree>, tree: &'a QuadTree) {
+impl QuadTree<MaxRadius> {
+    /// Find all elements whose circle overlaps with the circle at `center`,
+    /// where the circle of an element has the radius it was inserted with.
+    /// Regions are only searched when they are within reach of the largest
+    /// radius stored in them, instead of the largest radius overall. The
+    /// elements are pushed onto `dest`, once for each leaf they are found in.
+    pub fn query_reach(&self, center: Vec2, radius: f32, dest: &mut Vec<(Location, Entity)>) {
+        query_reach(dest, self, &self.items, center, radius);
+    }
+}
+
+fn query_reach(
+    dest: &mut Vec<(Location, Entity)>,
+    tree: &QuadTree<MaxRadius>,
+    radii: &HashMap<Entity, f32>,
+    center: Vec2,
+    radius: f32,
+) {
+    if !tree.bounds.intersects_circle(center, radius + tree.aggregate.0) {
+        return;
+    }
+
+    match tree.body.deref() {
+        Body::Empty => {}
+        Body::Leaf(elems) => {
+            for (location, entity) in elems {
+                let reach = radius + radii.get(entity).copied().unwrap_or_default();
+                if location.center().distance_squared(center) <= reach * reach {
+                    dest.push((*location, *entity));
+                }
+            }
+        }
+        Body::Node(regions) => {
+            for region in regions {
+                query_reach(dest, region, radii, center, radius);
+            }
+        }
+    };
+}
+
+fn query_circle<A: Aggregate>(dest: &mut Vec<(Location, Entity)>, tree: &QuadTree<A>, center: Vec2, radius: f32) {
+    if !tree.bounds.intersects_circle(center, radius) {
+        return;
+    }
+
+    match tree.body.deref() {
+        Body::Empty => {}
+        Body::Leaf(elems) => {
+            for (location, entity) in elems {
+                if location.intersects_circle(center, radius) {
+                    dest.push((*location, *entity));
+                }
+            }
+        }
+        Body::Node(regions) => {
+            for region in regions {
+                query_circle(dest, region, center, radius);
+            }
+        }
+    };
+}
+
+fn query_area<A: Aggregate>(dest: &mut Vec<(Location, Entity)>, tree: &QuadTree<A>, area: Bounds) {
+    if !tree.bounds.intersects(area) {
+        return;
+    }
+
+    match tree.body.deref() {
+        Body::Empty => {}
+        Body::Leaf(elems) => {
+            for (location, entity) in elems {
+                if location.intersects(area) {
+                    dest.push((*location, *entity));
+                }
+            }
+        }
+        Body::Node(regions) => {
+            for region in regions {
+                query_area(dest, region, area);
+            }
+        }
+    };
+}
+
+fn raycast<A: Aggregate, F: FnMut(Location, Entity) -> Option<f32>>(
+    nearest: &mut Option<(f32, Entity)>,
+    tree: &QuadTree<A>,
+    origin: Vec2,
+    direction: Vec2,
+    max_distance: f32,
+    hit: &mut F,
+) {
+    let limit = nearest.map_or(max_distance, |(distance, _)| distance);
+    if !tree.bounds.ray_entry(origin, direction).map_or(false, |entry| entry <= limit) {
+        return;
+    }
+
+    match tree.body.deref() {
+        Body::Empty => {}
+        Body::Leaf(elems) => {
+            for (location, entity) in elems {
+                let distance = match hit(*location, *entity) {
+                    Some(distance) => distance,
+                    None => continue,
+                };
+                if nearest.map_or(distance <= max_distance, |(nearest, _)| distance < nearest) {
+                    *nearest = Some((distance, *entity));
+                }
+            }
+        }
+        Body::Node(regions) => {
+            let mut order = [0, 1, 2, 3].map(|i| {
+                (regions[i].bounds.ray_entry(origin, direction).unwrap_or(f32::INFINITY), i)
+            });
+            order.sort_unstable_by(|(a, _), (b, _)| a.total_cmp(b));
+            for (_, i) in order {
+                raycast(nearest, &regions[i], origin, direction, max_distance, hit);
+            }
+        }
+    };
+}
+
+fn for_each_leaf_id<A: Aggregate, F: FnMut(NodeId, &QuadTree<A>)>(tree: &QuadTree<A>, id: NodeId, f: &mut F) {
+    match tree.body.deref() {
+        Body::Empty => {}
+        Body::Leaf(_) => f(id, tree),
+        Body::Node(regions) => {
+            for (i, region) in regions.iter().enumerate() {
+                for_each_leaf_id(region, id.child(i), f);
+            }
+        }
+    };
+}
+
+fn get_regions<'a, A: Aggregate>(dest: &mut Vec<&'a QuadTree<A>>, tree: &'a QuadTree<A>) {
     match tree.body.deref() {
         Body::Empty => {}
         Body::Leaf(_) => { dest.push(tree); }
@@ -302,4 +991,496 @@ fn get_regions<'a>(dest: &mut Vec<&'a QuadTree>, tree: &'a QuadTree) {
             get_regions(dest, regions[3].borrow());
         }
     };
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quadtree_query_circle() {
+        let mut tree = QuadTree::new(Bounds::new(Vec2::ZERO, 100.0, 100.0), Options {
+            capacity: 1,
+            ..Default::default()
+        });
+        tree.insert(Location::from(Vec2::new(10.0, 10.0)), Entity::from_raw(0)).unwrap();
+        tree.insert(Location::from(Vec2::new(-30.0, 20.0)), Entity::from_raw(1)).unwrap();
+        tree.insert(Location::new(Vec2::new(20.0, -10.0), 10.0, 10.0), Entity::from_raw(2)).unwrap();
+        tree.insert(Location::new(Vec2::ZERO, 4.0, 4.0), Entity::from_raw(3)).unwrap();
+
+        let found: Vec<Entity> = tree.query_circle(Vec2::new(10.0, 0.0), 15.0)
+            .into_iter()
+            .map(|(_, entity)| entity)
+            .collect();
+
+        assert_eq!(found, vec![Entity::from_raw(0), Entity::from_raw(2), Entity::from_raw(3)]);
+    }
+
+    #[test]
+    fn quadtree_query_area() {
+        let mut tree = QuadTree::new(Bounds::new(Vec2::ZERO, 100.0, 100.0), Options {
+            capacity: 1,
+            ..Default::default()
+        });
+        tree.insert(Location::from(Vec2::new(10.0, 10.0)), Entity::from_raw(0)).unwrap();
+        tree.insert(Location::from(Vec2::new(-30.0, 20.0)), Entity::from_raw(1)).unwrap();
+        tree.insert(Location::new(Vec2::new(20.0, -10.0), 10.0, 10.0), Entity::from_raw(2)).unwrap();
+        tree.insert(Location::new(Vec2::ZERO, 4.0, 4.0), Entity::from_raw(3)).unwrap();
+
+        let found: Vec<Entity> = tree.query_area(Bounds::from_corners(Vec2::new(1.0, -6.0), Vec2::new(40.0, 40.0)))
+            .into_iter()
+            .map(|(_, entity)| entity)
+            .collect();
+
+        assert_eq!(found, vec![Entity::from_raw(0), Entity::from_raw(2), Entity::from_raw(3)]);
+    }
+
+    #[test]
+    fn quadtree_raycast() {
+        let mut tree = QuadTree::new(Bounds::new(Vec2::ZERO, 100.0, 100.0), Options {
+            capacity: 1,
+            ..Default::default()
+        });
+        tree.insert(Location::new(Vec2::new(30.0, 0.0), 10.0, 10.0), Entity::from_raw(0)).unwrap();
+        tree.insert(Location::new(Vec2::new(10.0, 1.0), 4.0, 4.0), Entity::from_raw(1)).unwrap();
+        tree.insert(Location::new(Vec2::new(-20.0, 0.0), 4.0, 4.0), Entity::from_raw(2)).unwrap();
+        tree.insert(Location::from(Vec2::new(0.0, 20.0)), Entity::from_raw(3)).unwrap();
+
+        let mut hit = |location: Location, _| match location {
+            Location::Area(bounds) => bounds.ray_entry(Vec2::ZERO, Vec2::X),
+            Location::Point(_) => None,
+        };
+        assert_eq!(tree.raycast(Vec2::ZERO, Vec2::X, 100.0, &mut hit), Some((8.0, Entity::from_raw(1))));
+        assert_eq!(tree.raycast(Vec2::ZERO, Vec2::X, 5.0, &mut hit), None);
+
+        // elements can be skipped, the next one along the ray is hit instead
+        let mut skip = |location: Location, entity| hit(location, entity).filter(|_| entity != Entity::from_raw(1));
+        assert_eq!(tree.raycast(Vec2::ZERO, Vec2::X, 100.0, &mut skip), Some((25.0, Entity::from_raw(0))));
+    }
+
+    #[test]
+    fn quadtree_aggregates_elements_once() {
+        let mut tree = QuadTree::<(Count, Mass)>::with_aggregate(Bounds::new(Vec2::ZERO, 100.0, 100.0), Options {
+            capacity: 1,
+            ..Default::default()
+        });
+        tree.insert_with(Location::from(Vec2::new(10.0, 10.0)), Entity::from_raw(0), ((), 1.0)).unwrap();
+        tree.insert_with(Location::from(Vec2::new(-30.0, 20.0)), Entity::from_raw(1), ((), 3.0)).unwrap();
+        // stored in all four regions, but only counted once by the root
+        tree.insert_with(Location::new(Vec2::ZERO, 4.0, 4.0), Entity::from_raw(2), ((), 4.0)).unwrap();
+
+        let (count, mass) = tree.aggregate();
+        assert_eq!(count.0, 3);
+        assert_eq!(mass.total, 8.0);
+        assert_eq!(mass.center(), Some(Vec2::new(-10.0, 8.75)));
+
+        tree.relocate(Entity::from_raw(0), Location::from(Vec2::new(40.0, 10.0))).unwrap();
+        tree.remove_entity(Entity::from_raw(1));
+        let (count, mass) = tree.aggregate();
+        assert_eq!(count.0, 2);
+        assert_eq!(mass.center(), Some(Vec2::new(8.0, 2.0)));
+    }
+
+    #[test]
+    fn quadtree_query_reach() {
+        let mut tree = QuadTree::<MaxRadius>::with_aggregate(Bounds::new(Vec2::ZERO, 100.0, 100.0), Options {
+            capacity: 1,
+            ..Default::default()
+        });
+        tree.insert_with(Location::from(Vec2::new(10.0, 10.0)), Entity::from_raw(0), 2.0).unwrap();
+        // far away, but large enough to reach the query
+        tree.insert_with(Location::from(Vec2::new(-40.0, -40.0)), Entity::from_raw(1), 50.0).unwrap();
+        tree.insert_with(Location::from(Vec2::new(30.0, -30.0)), Entity::from_raw(2), 2.0).unwrap();
+        tree.insert_with(Location::from(Vec2::new(-2.0, 4.0)), Entity::from_raw(3), 1.0).unwrap();
+        // on the edge between two leaves
+        tree.insert_with(Location::from(Vec2::new(0.0, -8.0)), Entity::from_raw(4), 1.0).unwrap();
+
+        let mut found = vec![(Location::from(Vec2::ZERO), Entity::from_raw(5))];
+        tree.query_reach(Vec2::ZERO, 10.0, &mut found);
+        let mut found: Vec<Entity> = found.into_iter().map(|(_, entity)| entity).collect();
+        assert_eq!(found.iter().filter(|entity| **entity == Entity::from_raw(4)).count(), 2);
+
+        found.sort_unstable();
+        found.dedup();
+        assert_eq!(found, vec![Entity::from_raw(1), Entity::from_raw(3), Entity::from_raw(4), Entity::from_raw(5)]);
+        assert_eq!(tree.aggregate().0, 50.0);
+    }
+
+    #[test]
+    fn quadtree_shrinks_to_fit() {
+        let mut tree = QuadTree::new(Bounds::new(Vec2::ZERO, 100.0, 100.0), Options {
+            capacity: 64,
+            ..Default::default()
+        });
+        let empty = tree.memory_usage();
+        for i in 0..64 {
+            tree.insert(Location::from(Vec2::new(i as f32 - 32.0, 0.0)), Entity::from_raw(i)).unwrap();
+        }
+        for i in 1..64 {
+            tree.remove_entity(Entity::from_raw(i));
+        }
+
+        let peak = tree.memory_usage();
+        assert!(peak > empty);
+        tree.shrink_to_fit();
+        assert!(tree.memory_usage() < peak, "{} < {}", tree.memory_usage(), peak);
+        assert_eq!(tree.count(), 1);
+    }
+
+    #[test]
+    fn quadtree_defers_splits_over_budget() {
+        let mut tree = QuadTree::new(Bounds::new(Vec2::ZERO, 100.0, 100.0), Options {
+            capacity: 1,
+            max_splits: Some(1),
+            ..Default::default()
+        });
+        tree.insert(Location::from(Vec2::new(10.0, 10.0)), Entity::from_raw(0)).unwrap();
+        tree.insert(Location::from(Vec2::new(-30.0, 20.0)), Entity::from_raw(1)).unwrap();
+        assert_eq!(tree.regions().len(), 2);
+
+        // the budget is used up, so the leaf stays over capacity
+        tree.insert(Location::from(Vec2::new(40.0, 40.0)), Entity::from_raw(2)).unwrap();
+        assert_eq!(tree.pending_splits(), 1);
+        let leaf = tree.leaf_at(Vec2::new(10.0, 10.0)).unwrap();
+        assert_eq!(tree.node(leaf).unwrap().leaf_elements().unwrap().len(), 2);
+
+        // and is split in the next frame
+        assert_eq!(tree.next_frame(), 0);
+        assert_eq!(tree.pending_splits(), 0);
+        assert_ne!(tree.leaf_at(Vec2::new(10.0, 10.0)), tree.leaf_at(Vec2::new(40.0, 40.0)));
+        assert_eq!(tree.count(), 3);
+    }
+
+    #[test]
+    fn quadtree_node_ids_revisit_leaves() {
+        let mut tree = QuadTree::new(Bounds::new(Vec2::ZERO, 100.0, 100.0), Options {
+            capacity: 1,
+            ..Default::default()
+        });
+        tree.insert(Location::from(Vec2::new(10.0, 10.0)), Entity::from_raw(0)).unwrap();
+        tree.insert(Location::from(Vec2::new(-30.0, -20.0)), Entity::from_raw(1)).unwrap();
+        // straddles the center, so it's stored in all four leaves
+        tree.insert(Location::new(Vec2::ZERO, 4.0, 4.0), Entity::from_raw(2)).unwrap();
+
+        let id = tree.leaf_at(Vec2::new(10.0, 10.0)).unwrap();
+        assert_eq!(tree.leaves_of(Entity::from_raw(0)), &[id]);
+        assert!(tree.node(id).unwrap().leaf_elements().unwrap().iter().any(|(_, e)| *e == Entity::from_raw(0)));
+        assert_eq!(tree.leaves_of(Entity::from_raw(2)).len(), 4);
+        assert_eq!(tree.leaves_of(Entity::from_raw(3)), &[]);
+        assert_eq!(tree.leaf_at(Vec2::new(80.0, 0.0)), None);
+
+        let mut ids = Vec::new();
+        tree.for_each_leaf_id(&mut |id, leaf| {
+            assert!(std::ptr::eq(tree.node(id).unwrap(), leaf));
+            ids.push(id);
+        });
+        assert!(ids.contains(&id));
+
+        // once the leaf is merged into its parent, its id no longer resolves
+        tree.remove_entity(Entity::from_raw(1));
+        tree.remove_entity(Entity::from_raw(2));
+        assert!(tree.node(id).is_none());
+        assert_eq!(tree.leaf_at(Vec2::new(10.0, 10.0)), Some(NodeId::ROOT));
+    }
+
+    #[test]
+    fn quadtree_remove_entity_spanning_leaves() {
+        let mut tree = QuadTree::new(Bounds::new(Vec2::ZERO, 100.0, 100.0), Options {
+            capacity: 1,
+            ..Default::default()
+        });
+        tree.insert(Location::from(Vec2::new(-25.0, 25.0)), Entity::from_raw(0)).unwrap();
+        tree.insert(Location::from(Vec2::new(25.0, -25.0)), Entity::from_raw(1)).unwrap();
+        // overlaps all four regions of the root
+        tree.insert(Location::new(Vec2::ZERO, 20.0, 20.0), Entity::from_raw(2)).unwrap();
+        assert!(tree.count() > 3);
+
+        assert!(tree.remove_entity(Entity::from_raw(2)));
+        assert!(!tree.contains_entity(Entity::from_raw(2)));
+        assert_eq!(tree.leaves_of(Entity::from_raw(2)), &[]);
+        assert_eq!(tree.count(), 2);
+        let found: Vec<Entity> = tree.query_circle(Vec2::ZERO, 70.0)
+            .into_iter()
+            .map(|(_, entity)| entity)
+            .collect();
+        assert_eq!(found, vec![Entity::from_raw(0), Entity::from_raw(1)]);
+    }
+}
+
+#[cfg(test)]
+mod proptests {
+    use std::collections::HashSet;
+
+    use proptest::prelude::*;
+
+    use super::*;
+
+    const SIZE: f32 = 128.0;
+
+    fn tree_bounds() -> Bounds { Bounds::new(Vec2::ZERO, SIZE, SIZE) }
+
+    // Points are placed between whole units, so they never end up exactly on
+    // the edge between two regions when `min_size` is at least 1.
+    fn point() -> impl Strategy<Value = Vec2> {
+        let half = (SIZE * 0.5) as i32;
+        (-half..half, -half..half).prop_map(|(x, y)| Vec2::new(x as f32 + 0.5, y as f32 + 0.5))
+    }
+
+    fn location() -> impl Strategy<Value = Location> {
+        prop_oneof![
+            point().prop_map(Location::Point),
+            (point(), 0.5f32..40.0, 0.5f32..40.0).prop_map(|(center, w, h)| Location::new(center, w, h)),
+        ]
+    }
+
+    fn options() -> impl Strategy<Value = Options> {
+        (1usize..8, 0usize..4, proptest::option::of(0u8..12), proptest::option::of(1.0f32..16.0))
+            .prop_map(|(capacity, capacity_growth, max_depth, min_size)| Options {
+                capacity,
+                capacity_growth,
+                max_depth,
+                min_size: Some(Vec2::splat(min_size.unwrap_or(1.0))),
+                max_splits: None,
+            })
+    }
+
+    fn build(options: Options, locations: &[Location]) -> QuadTree {
+        let mut tree = QuadTree::new(tree_bounds(), options);
+        for (i, location) in locations.iter().enumerate() {
+            tree.insert(*location, Entity::from_raw(i as u32)).unwrap();
+        }
+        tree
+    }
+
+    fn entities(elems: Vec<(Location, Entity)>) -> HashSet<Entity> {
+        elems.into_iter().map(|(_, entity)| entity).collect()
+    }
+
+    fn walk<'a, A: Aggregate>(tree: &'a QuadTree<A>, dest: &mut Vec<&'a QuadTree<A>>) {
+        dest.push(tree);
+        if let Body::Node(regions) = tree.body.deref() {
+            for region in regions {
+                walk(region, dest);
+            }
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn count_matches_inserted_points(options in options(), points in prop::collection::vec(point(), 0..200)) {
+            let locations: Vec<Location> = points.into_iter().map(Location::Point).collect();
+            let tree = build(options, &locations);
+            prop_assert_eq!(tree.count(), locations.len());
+        }
+
+        #[test]
+        fn every_element_is_stored(options in options(), locations in prop::collection::vec(location(), 0..200)) {
+            let tree = build(options, &locations);
+            let mut stored = HashSet::new();
+            for region in tree.regions() {
+                stored.extend(entities(region.elements().unwrap()));
+            }
+            prop_assert_eq!(stored.len(), locations.len());
+        }
+
+        #[test]
+        fn query_circle_matches_brute_force(
+            options in options(),
+            locations in prop::collection::vec(location(), 0..200),
+            center in point(),
+            radius in 0.0f32..64.0,
+        ) {
+            let tree = build(options, &locations);
+            let found = tree.query_circle(center, radius);
+            for (location, _) in found.iter() {
+                prop_assert!(location.intersects_circle(center, radius));
+            }
+
+            let expected: HashSet<Entity> = locations.iter()
+                .enumerate()
+                .filter(|(_, location)| location.intersects_circle(center, radius))
+                .map(|(i, _)| Entity::from_raw(i as u32))
+                .collect();
+
+            prop_assert_eq!(entities(found), expected);
+        }
+
+        #[test]
+        fn regions_respect_max_depth_and_min_size(options in options(), points in prop::collection::vec(point(), 0..200)) {
+            let locations: Vec<Location> = points.into_iter().map(Location::Point).collect();
+            let tree = build(options, &locations);
+
+            let mut regions = Vec::new();
+            walk(&tree, &mut regions);
+            for region in regions {
+                if let Some(max_depth) = options.max_depth {
+                    prop_assert!(region.depth <= max_depth);
+                }
+                if region.depth > 0 {
+                    let min_size = options.min_size.unwrap();
+                    prop_assert!(region.bounds.width() >= min_size.x);
+                    prop_assert!(region.bounds.height() >= min_size.y);
+                }
+            }
+        }
+
+        #[test]
+        fn parallel_build_matches_sequential(options in options(), locations in prop::collection::vec(location(), 0..200)) {
+            let elements: Vec<(Location, Entity, ((), f32))> = locations.iter()
+                .enumerate()
+                .map(|(i, location)| (*location, Entity::from_raw(i as u32), ((), (i % 7) as f32)))
+                .collect();
+            let mut expected = QuadTree::<(Count, MaxRadius)>::with_aggregate(tree_bounds(), options);
+            for (location, entity, item) in elements.iter() {
+                expected.insert_with(*location, *entity, *item).unwrap();
+            }
+            let (tree, errors) = QuadTree::<(Count, MaxRadius)>::build_parallel_with(tree_bounds(), options, &elements, &TaskPool::new());
+            prop_assert!(errors.is_empty());
+
+            let (mut regions, mut expected_regions) = (Vec::new(), Vec::new());
+            walk(&tree, &mut regions);
+            walk(&expected, &mut expected_regions);
+            prop_assert_eq!(regions.len(), expected_regions.len());
+            for (region, expected) in regions.into_iter().zip(expected_regions) {
+                prop_assert!(region.bounds == expected.bounds);
+                prop_assert_eq!(region.depth, expected.depth);
+                prop_assert_eq!(region.aggregate(), expected.aggregate());
+                prop_assert_eq!(region.leaf_elements(), expected.leaf_elements());
+            }
+            for (_, entity, _) in elements.iter() {
+                let leaves: HashSet<NodeId> = tree.leaves_of(*entity).iter().copied().collect();
+                let expected_leaves: HashSet<NodeId> = expected.leaves_of(*entity).iter().copied().collect();
+                prop_assert_eq!(leaves, expected_leaves);
+            }
+        }
+
+        #[test]
+        fn deferred_splits_catch_up(
+            options in options(),
+            max_splits in 1usize..4,
+            locations in prop::collection::vec(location(), 0..200),
+        ) {
+            let expected = build(options, &locations);
+            let mut expected_regions = Vec::new();
+            walk(&expected, &mut expected_regions);
+            let splits = expected_regions.iter()
+                .filter(|region| matches!(region.body.deref(), Body::Node(_)))
+                .count();
+
+            // each frame spends all of its splits until none are left
+            let mut tree = build(Options { max_splits: Some(max_splits), ..options }, &locations);
+            let mut frames = 0;
+            while tree.next_frame() > 0 {
+                frames += 1;
+                prop_assert!(frames <= (splits + max_splits - 1) / max_splits);
+            }
+
+            // once all splits are done, the leaves match those of a tree
+            // which split them at once
+            let mut regions = Vec::new();
+            walk(&tree, &mut regions);
+            prop_assert_eq!(regions.len(), expected_regions.len());
+            for (region, expected) in regions.into_iter().zip(expected_regions) {
+                prop_assert!(region.bounds == expected.bounds);
+                prop_assert_eq!(
+                    region.leaf_elements().map(|elems| entities(elems.to_vec())),
+                    expected.leaf_elements().map(|elems| entities(elems.to_vec())),
+                );
+            }
+        }
+
+        #[test]
+        fn aggregates_match_elements(
+            options in options(),
+            locations in prop::collection::vec(location(), 1..100),
+            changes in prop::collection::vec((any::<prop::sample::Index>(), proptest::option::of(location())), 0..100),
+        ) {
+            let mut items: Vec<(f32, f32)> = (0..locations.len())
+                .map(|i| (1.0 + (i % 5) as f32, (i % 7) as f32))
+                .collect();
+            let mut tree = QuadTree::<(Count, (Mass, MaxRadius))>::with_aggregate(tree_bounds(), options);
+            for (i, location) in locations.iter().enumerate() {
+                tree.insert_with(*location, Entity::from_raw(i as u32), ((), items[i])).unwrap();
+            }
+            for (index, change) in changes {
+                let i = index.index(locations.len());
+                match change {
+                    Some(location) => tree.relocate(Entity::from_raw(i as u32), location).unwrap(),
+                    None => {
+                        // relocating a removed entity inserts it without any mass
+                        tree.remove_entity(Entity::from_raw(i as u32));
+                        items[i] = (0.0, 0.0);
+                    }
+                }
+            }
+
+            let mut regions = Vec::new();
+            walk(&tree, &mut regions);
+            for region in regions {
+                let mut expected = (Count::default(), (Mass::default(), MaxRadius::default()));
+                let mut seen = HashSet::new();
+                region.for_each_leaf(&mut |leaf| {
+                    for (location, entity) in leaf.leaf_elements().unwrap() {
+                        if seen.insert(*entity) {
+                            expected.add(*location, ((), items[entity.id() as usize]));
+                        }
+                    }
+                });
+
+                let (count, (total, max_radius)) = region.aggregate();
+                prop_assert_eq!(count, &expected.0);
+                prop_assert!((total.total - (expected.1).0.total).abs() < 1e-2);
+                prop_assert_eq!(max_radius, &(expected.1).1);
+            }
+        }
+
+        #[test]
+        fn relocate_and_remove_keep_tree_consistent(
+            options in options(),
+            locations in prop::collection::vec(location(), 1..100),
+            changes in prop::collection::vec((any::<prop::sample::Index>(), proptest::option::of(location())), 0..100),
+        ) {
+            let mut tree = build(options, &locations);
+            let mut expected: Vec<Option<Location>> = locations.iter().copied().map(Some).collect();
+
+            for (index, change) in changes {
+                let i = index.index(expected.len());
+                let entity = Entity::from_raw(i as u32);
+                match change {
+                    Some(location) => {
+                        tree.relocate(entity, location).unwrap();
+                        expected[i] = Some(location);
+                    }
+                    None => {
+                        prop_assert_eq!(tree.remove_entity(entity), expected[i].is_some());
+                        expected[i] = None;
+                    }
+                }
+            }
+
+            let mut stored = HashSet::new();
+            for region in tree.regions() {
+                stored.extend(entities(region.elements().unwrap()));
+            }
+            let present: HashSet<Entity> = expected.iter()
+                .enumerate()
+                .filter(|(_, location)| location.is_some())
+                .map(|(i, _)| Entity::from_raw(i as u32))
+                .collect();
+            prop_assert_eq!(&stored, &present);
+            prop_assert_eq!(tree.entities.len(), present.len());
+
+            // every handle points to a leaf which holds the entity at its
+            // current location
+            for entity in present {
+                let location = expected[entity.id() as usize].unwrap();
+                for handle in tree.entities.get(entity).unwrap().to_vec() {
+                    match tree.node_mut(handle).unwrap().body.deref() {
+                        Body::Leaf(elems) => prop_assert!(elems.contains(&(location, entity))),
+                        _ => prop_assert!(false, "handle does not point to a leaf"),
+                    }
+                }
+            }
+        }
+    }
+}
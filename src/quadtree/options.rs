@@ -0,0 +1,194 @@
+use std::fmt;
+use std::fmt::Formatter;
+
+use bevy::math::Vec2;
+
+use super::{Bounds, MAX_DEPTH};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Options {
+    /// Target capacity of a leaf before it is split in nodes. Note that a leaf
+    /// may contain more items when `max_depth` is reached.
+    pub capacity: usize,
+    /// Additional capacity of leaves for each level of depth, so deeper leaves
+    /// in dense areas are split less eagerly.
+    pub capacity_growth: usize,
+
+    pub max_depth: Option<u8>,
+    pub min_size: Option<Vec2>,
+    /// Maximum amount of leaves which are split per frame, at least 1, see
+    /// `QuadTree::next_frame`. Leaves which are over capacity after that are
+    /// split in later frames, so a dense cluster which forms suddenly doesn't
+    /// cause a spike in frame time.
+    pub max_splits: Option<usize>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            capacity: 4,
+            capacity_growth: 0,
+            max_depth: None,
+            min_size: None,
+            max_splits: None,
+        }
+    }
+}
+
+impl Options {
+    #[inline]
+    pub fn builder() -> OptionsBuilder { OptionsBuilder(Self::default()) }
+
+    /// Target capacity of a leaf at `depth`.
+    #[inline]
+    pub fn capacity_at(&self, depth: u8) -> usize {
+        self.capacity + self.capacity_growth * depth as usize
+    }
+
+    /// Check if the options result in a sensible tree.
+    pub fn validate(&self) -> Result<(), OptionsError> {
+        if self.capacity == 0 {
+            return Err(OptionsError::ZeroCapacity);
+        }
+        if let Some(max_depth) = self.max_depth {
+            if max_depth > MAX_DEPTH {
+                return Err(OptionsError::MaxDepthTooLarge(max_depth));
+            }
+        }
+        if let Some(min_size) = self.min_size {
+            if !min_size.is_finite() || min_size.x <= 0. || min_size.y <= 0. {
+                return Err(OptionsError::InvalidMinSize(min_size));
+            }
+        }
+        if self.max_splits == Some(0) {
+            return Err(OptionsError::ZeroMaxSplits);
+        }
+        return Ok(());
+    }
+
+    /// Check if the options result in a sensible tree within `bounds`.
+    pub fn validate_for(&self, bounds: Bounds) -> Result<(), OptionsError> {
+        self.validate()?;
+        if let Some(min_size) = self.min_size {
+            if min_size.x > bounds.width() || min_size.y > bounds.height() {
+                return Err(OptionsError::MinSizeExceedsBounds(min_size, bounds));
+            }
+        }
+        return Ok(());
+    }
+}
+
+/// Builds validated `Options`, starting from the defaults.
+#[derive(Clone, Copy, Debug)]
+pub struct OptionsBuilder(Options);
+
+impl OptionsBuilder {
+    #[allow(dead_code)]
+    #[inline]
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.0.capacity = capacity;
+        self
+    }
+
+    #[allow(dead_code)]
+    #[inline]
+    pub fn capacity_growth(mut self, capacity_growth: usize) -> Self {
+        self.0.capacity_growth = capacity_growth;
+        self
+    }
+
+    #[allow(dead_code)]
+    #[inline]
+    pub fn max_depth(mut self, max_depth: u8) -> Self {
+        self.0.max_depth = Some(max_depth);
+        self
+    }
+
+    #[inline]
+    pub fn min_size(mut self, min_size: Vec2) -> Self {
+        self.0.min_size = Some(min_size);
+        self
+    }
+
+    #[allow(dead_code)]
+    #[inline]
+    pub fn max_splits(mut self, max_splits: usize) -> Self {
+        self.0.max_splits = Some(max_splits);
+        self
+    }
+
+    #[inline]
+    pub fn build(self) -> Result<Options, OptionsError> {
+        self.0.validate()?;
+        Ok(self.0)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OptionsError {
+    /// Leaves must be able to hold at least one element.
+    ZeroCapacity,
+    /// Trees can't be deeper than `MAX_DEPTH`.
+    MaxDepthTooLarge(u8),
+    /// The minimum size of a region must be positive and finite.
+    InvalidMinSize(Vec2),
+    /// The minimum size of a region is larger than the bounds of the tree.
+    MinSizeExceedsBounds(Vec2, Bounds),
+    /// Leaves which are deferred must be split eventually.
+    ZeroMaxSplits,
+}
+
+impl fmt::Display for OptionsError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        use OptionsError::*;
+        match self {
+            ZeroCapacity => write!(f, "capacity must be at least 1"),
+            MaxDepthTooLarge(depth) => write!(f, "max depth {} exceeds the limit of {}", depth, MAX_DEPTH),
+            InvalidMinSize(size) => write!(f, "min size {} must be positive and finite", size),
+            MinSizeExceedsBounds(size, bounds) => write!(
+                f,
+                "min size {} exceeds the bounds of {}x{}",
+                size, bounds.width(), bounds.height(),
+            ),
+            ZeroMaxSplits => write!(f, "max splits must be at least 1"),
+        }
+    }
+}
+
+impl std::error::Error for OptionsError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builder_validates_options() {
+        let options = Options::builder()
+            .capacity(8)
+            .max_depth(10)
+            .min_size(Vec2::splat(4.))
+            .max_splits(16)
+            .build()
+            .unwrap();
+        assert_eq!(options, Options {
+            capacity: 8,
+            capacity_growth: 0,
+            max_depth: Some(10),
+            min_size: Some(Vec2::splat(4.)),
+            max_splits: Some(16),
+        });
+
+        assert_eq!(Options::builder().capacity(0).build(), Err(OptionsError::ZeroCapacity));
+        assert_eq!(Options::builder().max_depth(40).build(), Err(OptionsError::MaxDepthTooLarge(40)));
+        assert_eq!(
+            Options::builder().min_size(Vec2::new(1., -1.)).build(),
+            Err(OptionsError::InvalidMinSize(Vec2::new(1., -1.))),
+        );
+        assert_eq!(Options::builder().max_splits(0).build(), Err(OptionsError::ZeroMaxSplits));
+
+        let bounds = Bounds::new(Vec2::ZERO, 10., 10.);
+        assert!(options.validate_for(bounds).is_ok());
+        let options = Options::builder().min_size(Vec2::new(4., 20.)).build().unwrap();
+        assert_eq!(options.validate_for(bounds), Err(OptionsError::MinSizeExceedsBounds(Vec2::new(4., 20.), bounds)));
+    }
+}
@@ -0,0 +1,77 @@
+use std::mem;
+
+use super::NodeId;
+
+/// Keeps track of the splits a `QuadTree` may still perform during the
+/// current frame, and of the leaves of which the split is deferred to a later
+/// frame.
+#[derive(Default)]
+pub(crate) struct SplitBudget {
+    // `None` when the amount of splits is not limited
+    remaining: Option<usize>,
+    pending: Vec<NodeId>,
+}
+
+impl SplitBudget {
+    #[inline]
+    pub fn new(limit: Option<usize>) -> Self {
+        Self { remaining: limit, pending: Vec::new() }
+    }
+
+    /// Use up a split, returns `false` when there are none left.
+    #[inline]
+    pub fn take(&mut self) -> bool {
+        match &mut self.remaining {
+            None => true,
+            Some(0) => false,
+            Some(remaining) => {
+                *remaining -= 1;
+                true
+            }
+        }
+    }
+
+    /// Split the leaf at `handle` in a later frame.
+    #[inline]
+    pub fn defer(&mut self, handle: NodeId) {
+        if !self.pending.contains(&handle) {
+            self.pending.push(handle);
+        }
+    }
+
+    /// Start a new frame with `limit` splits, returns the leaves which were
+    /// deferred, oldest first.
+    #[inline]
+    pub fn restore(&mut self, limit: Option<usize>) -> Vec<NodeId> {
+        self.remaining = limit;
+        mem::take(&mut self.pending)
+    }
+
+    /// Amount of leaves of which the split is deferred.
+    #[inline]
+    pub fn pending(&self) -> usize { self.pending.len() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_budget_defers_once_used_up() {
+        let mut budget = SplitBudget::new(Some(1));
+        assert!(budget.take());
+        assert!(!budget.take());
+
+        let handle = NodeId::ROOT.child(1);
+        budget.defer(handle);
+        budget.defer(handle);
+        assert_eq!(budget.pending(), 1);
+
+        assert_eq!(budget.restore(Some(1)), vec![handle]);
+        assert_eq!(budget.pending(), 0);
+        assert!(budget.take());
+
+        let mut unlimited = SplitBudget::default();
+        assert!((0..100).all(|_| unlimited.take()));
+    }
+}
@@ -0,0 +1,89 @@
+//! Physics regression tests which run a small, seeded scene for a fixed amount
+//! of ticks and compare the end result with a stored golden snapshot. Run the
+//! tests with `UPDATE_GOLDEN=1` to (re)write the snapshots after an intended
+//! change in behavior.
+
+use std::fs;
+
+use crate::*;
+
+const TICKS: usize = 600;
+const TOLERANCE: f32 = 1e-3;
+
+fn golden_path(name: &str) -> String {
+    format!("{}/tests/golden/{}.txt", env!("CARGO_MANIFEST_DIR"), name)
+}
+
+// Spawn a small scene inside a world, without any rendering or windowing.
+fn small_scene(seed: u64, balls: usize) -> (World, Vec<Entity>) {
+    headless_world(seed, balls, Bounds::new(Vec2::ZERO, 200., 200.))
+}
+
+// Positions and velocities of the balls, one ball per line.
+fn snapshot(world: &World, entities: &[Entity]) -> Vec<[f32; 4]> {
+    entities.iter()
+        .map(|entity| {
+            let translation = world.get::<Transform>(*entity).unwrap().translation;
+            let velocity = world.get::<Velocity>(*entity).unwrap().0;
+            [translation.x, translation.y, velocity.x, velocity.y]
+        })
+        .collect()
+}
+
+fn assert_golden(name: &str, state: Vec<[f32; 4]>) {
+    let path = golden_path(name);
+    if std::env::var("UPDATE_GOLDEN").is_ok() {
+        let contents: Vec<String> = state.iter()
+            .map(|s| format!("{} {} {} {}", s[0], s[1], s[2], s[3]))
+            .collect();
+        fs::write(&path, contents.join("\n") + "\n").unwrap();
+        return;
+    }
+
+    let contents = fs::read_to_string(&path)
+        .unwrap_or_else(|err| panic!("unable to read {}: {}", path, err));
+    let golden: Vec<Vec<f32>> = contents.lines()
+        .map(|line| line.split(' ').map(|v| v.parse().unwrap()).collect())
+        .collect();
+
+    assert_eq!(golden.len(), state.len(), "amount of balls differs from {}", path);
+    for (i, (expected, actual)) in golden.iter().zip(state.iter()).enumerate() {
+        for (e, a) in expected.iter().zip(actual.iter()) {
+            assert!(
+                (e - a).abs() <= TOLERANCE,
+                "ball {} differs from {}: expected {:?}, got {:?}", i, path, expected, actual,
+            );
+        }
+    }
+}
+
+#[test]
+fn small_scene_elastic() {
+    let (mut world, entities) = small_scene(1603, 24);
+    let mut stage = physics_stage();
+    for _ in 0..TICKS {
+        stage.run(&mut world);
+    }
+
+    assert_golden("small_scene_elastic", snapshot(&world, &entities));
+}
+
+#[test]
+fn idle_scene_skips_collisions() {
+    let (mut world, entities) = small_scene(1640, 24);
+    for entity in &entities {
+        world.get_mut::<Velocity>(*entity).unwrap().0 = Vec2::ZERO;
+    }
+
+    // let overlapping balls settle, after which nothing moves anymore
+    let mut stage = physics_stage();
+    for _ in 0..20 {
+        stage.run(&mut world);
+    }
+    let settled = snapshot(&world, &entities);
+
+    world.resource_mut::<PairBuffer>().clear();
+    stage.run(&mut world);
+    assert!(world.resource::<PairBuffer>().pairs().is_empty());
+    assert_eq!(snapshot(&world, &entities), settled);
+}
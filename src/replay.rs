@@ -0,0 +1,600 @@
+//! Records the interactive simulation to a file, so it can be played back
+//! headless with `replay <file>`. Rather than the keys and clicks, each tick
+//! records what they changed since the tick before: the balls which were
+//! spawned, changed or despawned, the walls, gravity, slow motion and the
+//! enabled attractors. Each tick ends with the `state_checksum` of the balls
+//! after it, which the replay checks its own state against.
+//!
+//! Only what changes between ticks is recorded, systems which change balls
+//! within the `PhysicsStage`, like those of most optional plugins, are not.
+//! Neither are the kinds of balls, or bodies other than circles. Runs which
+//! use those diverge from their replay, which is reported at the first tick
+//! which plays out differently.
+//!
+//! A recording has a change on each line, and each tick ends with a line
+//! with its checksum, in hex. Empty lines and lines starting with `//` are
+//! skipped.
+//!
+//! ```text
+//! walls <x> <y> <width> <height> <restitution> <friction>
+//! gravity <x> <y>
+//! slow-motion <time scale> <global time scale> [<x> <y> <width> <height>]
+//! attractors [<x> <y> <strength>]...
+//! ball <entity> <radius> <mass> <restitution> <x> <y> <vx> <vy> <fx> <fy> <ix> <iy>
+//! despawn <entity>
+//! checksum <checksum>
+//! ```
+
+use std::collections::HashMap;
+use std::fmt::{self, Formatter};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Instant;
+
+use bevy::prelude::*;
+
+use crate::*;
+
+/// Records each physics tick to the file at `path`, see the module
+/// documentation.
+pub struct ReplayRecorderPlugin {
+    path: String,
+}
+
+impl ReplayRecorderPlugin {
+    pub fn to_file(path: impl Into<String>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl Plugin for ReplayRecorderPlugin {
+    fn build(&self, app: &mut App) {
+        let file = File::create(&self.path)
+            .unwrap_or_else(|err| panic!("unable to create {}: {}", self.path, err));
+        let mut writer = BufWriter::new(file);
+        if let Err(err) = writeln!(writer, "// bevy-collision-balls replay") {
+            println!("replay: unable to write {}: {}", self.path, err);
+        }
+        println!("replay: recording to {}", self.path);
+
+        app.world.get_resource_or_insert_with(SimulationHooks::default)
+            .add(HookPoint::PrePhysics, record_changes)
+            .add(HookPoint::PostPhysics, record_checksum);
+        app.insert_resource(ReplayRecording {
+            path: self.path.clone(),
+            writer,
+            recorder: ReplayRecorder::default(),
+            failed: false,
+        });
+    }
+}
+
+/// State of a ball which is recorded.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BallState {
+    pub radius: f32,
+    pub mass: f32,
+    pub restitution: f32,
+    pub position: Vec2,
+    pub velocity: Vec2,
+    pub force: Vec2,
+    pub impulse: Vec2,
+}
+
+/// Change to the world between two ticks.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Change {
+    Walls { bounds: Bounds, restitution: f32, friction: f32 },
+    Gravity(Vec2),
+    SlowMotion { region: Option<Bounds>, time_scale: f32, global_time_scale: f32 },
+    /// Position and strength of each enabled attractor.
+    Attractors(Vec<(Vec2, f32)>),
+    /// A ball which was spawned or changed.
+    Ball(Entity, BallState),
+    Despawn(Entity),
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let bounds = |f: &mut Formatter<'_>, bounds: &Bounds| {
+            let center = bounds.center();
+            write!(f, " {} {} {} {}", center.x, center.y, bounds.width(), bounds.height())
+        };
+        match self {
+            Change::Walls { bounds: walls, restitution, friction } => {
+                write!(f, "walls")?;
+                bounds(f, walls)?;
+                write!(f, " {} {}", restitution, friction)
+            }
+            Change::Gravity(gravity) => write!(f, "gravity {} {}", gravity.x, gravity.y),
+            Change::SlowMotion { region, time_scale, global_time_scale } => {
+                write!(f, "slow-motion {} {}", time_scale, global_time_scale)?;
+                match region {
+                    Some(region) => bounds(f, region),
+                    None => Ok(()),
+                }
+            }
+            Change::Attractors(attractors) => {
+                write!(f, "attractors")?;
+                for (position, strength) in attractors {
+                    write!(f, " {} {} {}", position.x, position.y, strength)?;
+                }
+                Ok(())
+            }
+            Change::Ball(entity, ball) => write!(
+                f,
+                "ball {} {} {} {} {} {} {} {} {} {} {} {}",
+                entity.to_bits(),
+                ball.radius,
+                ball.mass,
+                ball.restitution,
+                ball.position.x,
+                ball.position.y,
+                ball.velocity.x,
+                ball.velocity.y,
+                ball.force.x,
+                ball.force.y,
+                ball.impulse.x,
+                ball.impulse.y,
+            ),
+            Change::Despawn(entity) => write!(f, "despawn {}", entity.to_bits()),
+        }
+    }
+}
+
+impl FromStr for Change {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let words: Vec<&str> = s.split_whitespace().collect();
+        let floats = |words: &[&str]| words.iter()
+            .map(|word| word.parse::<f32>().map_err(|_| ()))
+            .collect::<Result<Vec<_>, _>>();
+        let entity = |word: &str| word.parse().map(Entity::from_bits).map_err(|_| ());
+
+        match words[..] {
+            ["walls", ref values @ ..] => match floats(values)?[..] {
+                [x, y, width, height, restitution, friction] => Ok(Change::Walls {
+                    bounds: Bounds::new(Vec2::new(x, y), width, height),
+                    restitution,
+                    friction,
+                }),
+                _ => Err(()),
+            },
+            ["gravity", ref values @ ..] => match floats(values)?[..] {
+                [x, y] => Ok(Change::Gravity(Vec2::new(x, y))),
+                _ => Err(()),
+            },
+            ["slow-motion", ref values @ ..] => match floats(values)?[..] {
+                [time_scale, global_time_scale] => {
+                    Ok(Change::SlowMotion { region: None, time_scale, global_time_scale })
+                }
+                [time_scale, global_time_scale, x, y, width, height] => Ok(Change::SlowMotion {
+                    region: Some(Bounds::new(Vec2::new(x, y), width, height)),
+                    time_scale,
+                    global_time_scale,
+                }),
+                _ => Err(()),
+            },
+            ["attractors", ref values @ ..] => {
+                let values = floats(values)?;
+                if values.len() % 3 != 0 {
+                    return Err(());
+                }
+                Ok(Change::Attractors(values.chunks(3).map(|values| (Vec2::new(values[0], values[1]), values[2])).collect()))
+            }
+            ["ball", id, ref values @ ..] => match floats(values)?[..] {
+                [radius, mass, restitution, x, y, vx, vy, fx, fy, ix, iy] => Ok(Change::Ball(entity(id)?, BallState {
+                    radius,
+                    mass,
+                    restitution,
+                    position: Vec2::new(x, y),
+                    velocity: Vec2::new(vx, vy),
+                    force: Vec2::new(fx, fy),
+                    impulse: Vec2::new(ix, iy),
+                })),
+                _ => Err(()),
+            },
+            ["despawn", id] => Ok(Change::Despawn(entity(id)?)),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Changes to the world before a tick, and the checksum of the state after.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ReplayTick {
+    pub changes: Vec<Change>,
+    pub checksum: u64,
+}
+
+/// Recorded ticks of a run, in order.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Replay {
+    pub ticks: Vec<ReplayTick>,
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(io::Error),
+    /// A line of a recording is not a change or a checksum.
+    InvalidLine(usize, String),
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        use ReplayError::*;
+        match self {
+            Io(err) => write!(f, "{}", err),
+            InvalidLine(line, text) => write!(f, "invalid line `{}` on line {}", text, line),
+        }
+    }
+}
+
+/// First tick of a replay which played out differently than recorded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Divergence {
+    /// Tick which differs, the first tick is 1.
+    pub tick: usize,
+    pub expected: u64,
+    pub actual: u64,
+}
+
+impl Replay {
+    /// Load a recording, see `Replay::parse`.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ReplayError> {
+        let text = fs::read_to_string(path).map_err(ReplayError::Io)?;
+        Self::parse(&text)
+    }
+
+    /// Parse a recording, see the module documentation for its format.
+    /// Changes after the last checksum belong to a tick which didn't finish,
+    /// they're left out.
+    pub fn parse(text: &str) -> Result<Self, ReplayError> {
+        let mut ticks = Vec::new();
+        let mut changes = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with("//") {
+                continue;
+            }
+
+            let invalid = || ReplayError::InvalidLine(i + 1, line.to_string());
+            match line.strip_prefix("checksum ") {
+                Some(checksum) => ticks.push(ReplayTick {
+                    changes: std::mem::take(&mut changes),
+                    checksum: u64::from_str_radix(checksum.trim(), 16).map_err(|_| invalid())?,
+                }),
+                None => changes.push(line.parse().map_err(|_| invalid())?),
+            }
+        }
+        Ok(Self { ticks })
+    }
+
+    /// Play back all ticks in `world`, which starts without any balls.
+    /// Returns the amount of ticks, or the first one which played out
+    /// differently.
+    pub fn play(&self, world: &mut World) -> Result<usize, Divergence> {
+        let mut player = ReplayPlayer::default();
+        for (i, tick) in self.ticks.iter().enumerate() {
+            let actual = player.tick(world, tick);
+            if actual != tick.checksum {
+                return Err(Divergence { tick: i + 1, expected: tick.checksum, actual });
+            }
+        }
+        Ok(self.ticks.len())
+    }
+}
+
+/// Finds the changes to record, by comparing the world with its state at the
+/// end of the tick before.
+#[derive(Default)]
+pub struct ReplayRecorder {
+    balls: HashMap<Entity, BallState>,
+    // walls, gravity, slow motion and attractors as they were last recorded
+    environment: Vec<Change>,
+}
+
+impl ReplayRecorder {
+    /// Changes to `world` since the end of the tick before, or all of it
+    /// before the first tick.
+    pub fn changes(&mut self, world: &mut World) -> Vec<Change> {
+        let environment = environment(world);
+        let mut changes: Vec<Change> = environment.iter()
+            .enumerate()
+            .filter(|(i, change)| self.environment.get(*i) != Some(*change))
+            .map(|(_, change)| change.clone())
+            .collect();
+        self.environment = environment;
+
+        // despawns go first, the entity of a despawned ball may be reused by
+        // a new one
+        let balls = ball_states(world);
+        let mut despawned: Vec<Entity> = self.balls.keys()
+            .filter(|entity| balls.binary_search_by_key(*entity, |(entity, _)| *entity).is_err())
+            .copied()
+            .collect();
+        despawned.sort_unstable();
+        changes.extend(despawned.into_iter().map(Change::Despawn));
+        changes.extend(balls.iter()
+            .filter(|(entity, ball)| self.balls.get(entity) != Some(ball))
+            .map(|(entity, ball)| Change::Ball(*entity, *ball)));
+        self.balls = balls.into_iter().collect();
+        changes
+    }
+
+    /// Remember the state of `world` at the end of a tick, and return its
+    /// checksum.
+    pub fn end_tick(&mut self, world: &mut World) -> u64 {
+        self.balls = ball_states(world).into_iter().collect();
+        state_checksum(world)
+    }
+}
+
+/// Applies the recorded changes to a headless world, and runs its ticks.
+pub struct ReplayPlayer {
+    stage: SystemStage,
+    attractors: Vec<(Vec2, f32)>,
+}
+
+impl Default for ReplayPlayer {
+    fn default() -> Self {
+        Self { stage: physics_stage(), attractors: Vec::new() }
+    }
+}
+
+impl ReplayPlayer {
+    /// Apply the changes of `tick` to `world` and run it, returns the
+    /// checksum of the state after.
+    pub fn tick(&mut self, world: &mut World, tick: &ReplayTick) -> u64 {
+        for change in &tick.changes {
+            self.apply(world, change);
+        }
+        // attractors pull the balls right after the changes were recorded
+        for (transform, mut force, ball) in world.query::<(&Transform, &mut Force, &Ball)>().iter_mut(world) {
+            for (center, strength) in &self.attractors {
+                force.0 += attraction(*center, *strength, transform.translation.truncate(), ball.mass);
+            }
+        }
+        self.stage.run(world);
+        state_checksum(world)
+    }
+
+    fn apply(&mut self, world: &mut World, change: &Change) {
+        match change {
+            Change::Walls { bounds, restitution, friction } => {
+                world.insert_resource(EdgeCollider::with_restitution(*bounds, *restitution).with_friction(*friction));
+            }
+            Change::Gravity(gravity) => world.insert_resource(Gravity(*gravity)),
+            Change::SlowMotion { region, time_scale, global_time_scale } => world.insert_resource(SlowMotion {
+                region: *region,
+                time_scale: *time_scale,
+                global_time_scale: *global_time_scale,
+            }),
+            Change::Attractors(attractors) => self.attractors = attractors.clone(),
+            Change::Ball(entity, state) => {
+                // a ball which was despawned in the recording, but not here,
+                // can't be replaced; the checksum tells the replay diverged
+                let mut entity = match world.get_or_spawn(*entity) {
+                    Some(entity) => entity,
+                    None => return,
+                };
+                if !entity.contains::<Ball>() {
+                    entity.insert_bundle(BallBundle::builder(state.radius).build());
+                }
+                let mut ball = entity.get_mut::<Ball>().unwrap();
+                ball.radius = state.radius;
+                ball.mass = state.mass;
+                ball.restitution = state.restitution;
+                let mut transform = entity.get_mut::<Transform>().unwrap();
+                transform.translation.x = state.position.x;
+                transform.translation.y = state.position.y;
+                entity.get_mut::<Velocity>().unwrap().0 = state.velocity;
+                entity.get_mut::<Force>().unwrap().0 = state.force;
+                entity.get_mut::<Impulse>().unwrap().0 = state.impulse;
+            }
+            Change::Despawn(entity) => {
+                world.despawn(*entity);
+            }
+        }
+    }
+}
+
+/// Headless world without any balls, with the physics of the interactive
+/// simulation, to play back a recording in.
+pub fn replay_world() -> World {
+    let (mut world, _) = headless_world(0, 0, Bounds::new(Vec2::ZERO, WIDTH, HEIGHT));
+    world.insert_resource(BROAD_PHASE);
+    world.insert_resource(TreeCapacity::new(QUADTREE_CAPACITY));
+    world.insert_resource(INTEGRATOR);
+    world.insert_resource(Drag(DRAG));
+    if PARALLEL_ISLANDS {
+        world.insert_resource(CollisionIslands);
+    }
+    if let Some(min_balls) = PARALLEL_TREE_BUILD {
+        world.insert_resource(ParallelTreeBuild { min_balls });
+    }
+    if let Some(bias) = SEPARATION_BIAS {
+        world.insert_resource(Separation::with_bias(bias));
+    }
+    if let Some(iterations) = CONTACT_ITERATIONS {
+        world.insert_resource(ContactCache::with_iterations(iterations));
+    }
+    world
+}
+
+/// Play back the recording at `path` headless, and print whether each tick
+/// played out as recorded. Exits with an error when one didn't.
+pub fn replay(path: &str) {
+    let recording = match Replay::load(path) {
+        Ok(recording) => recording,
+        Err(err) => {
+            println!("{}: {}", path, err);
+            std::process::exit(1);
+        }
+    };
+
+    let mut world = replay_world();
+    let start = Instant::now();
+    match recording.play(&mut world) {
+        Ok(ticks) => {
+            println!("{}: ok, {} ticks in {:.3} s", path, ticks, start.elapsed().as_secs_f64());
+            println!("checksum: {:016x}", state_checksum(&mut world));
+        }
+        Err(divergence) => {
+            println!(
+                "{}: tick {} played out differently, checksum {:016x} instead of {:016x}",
+                path, divergence.tick, divergence.actual, divergence.expected,
+            );
+            std::process::exit(1);
+        }
+    }
+}
+
+struct ReplayRecording {
+    path: String,
+    writer: BufWriter<File>,
+    recorder: ReplayRecorder,
+    // only the first error is printed
+    failed: bool,
+}
+
+impl ReplayRecording {
+    fn write(&mut self, lines: impl IntoIterator<Item = String>) {
+        let result = lines.into_iter()
+            .try_for_each(|line| writeln!(self.writer, "{}", line))
+            .and_then(|_| self.writer.flush());
+        if let Err(err) = result {
+            if !self.failed {
+                println!("replay: unable to write {}: {}", self.path, err);
+            }
+            self.failed = true;
+        }
+    }
+}
+
+fn record_changes(world: &mut World) {
+    if world.contains_resource::<ReplayRecording>() {
+        world.resource_scope(|world, mut recording: Mut<ReplayRecording>| {
+            let changes = recording.recorder.changes(world);
+            recording.write(changes.iter().map(Change::to_string));
+        });
+    }
+}
+
+fn record_checksum(world: &mut World) {
+    if world.contains_resource::<ReplayRecording>() {
+        world.resource_scope(|world, mut recording: Mut<ReplayRecording>| {
+            let checksum = recording.recorder.end_tick(world);
+            recording.write([format!("checksum {:016x}", checksum)]);
+        });
+    }
+}
+
+// Walls, gravity, slow motion and enabled attractors of `world`, in that
+// order.
+fn environment(world: &mut World) -> Vec<Change> {
+    let edge = world.resource::<EdgeCollider>();
+    let walls = Change::Walls { bounds: edge.bounds, restitution: edge.restitution, friction: edge.friction };
+    let gravity = Change::Gravity(world.resource::<Gravity>().0);
+    let slow_motion = world.resource::<SlowMotion>();
+    let slow_motion = Change::SlowMotion {
+        region: slow_motion.region,
+        time_scale: slow_motion.time_scale,
+        global_time_scale: slow_motion.global_time_scale,
+    };
+    let attractors = world.query_filtered::<(&Attractor, &Transform), Without<Ball>>()
+        .iter(world)
+        .filter(|(attractor, _)| attractor.enabled)
+        .map(|(attractor, transform)| (transform.translation.truncate(), attractor.strength))
+        .collect();
+    vec![walls, gravity, slow_motion, Change::Attractors(attractors)]
+}
+
+// State of all balls in `world`, ordered by entity.
+fn ball_states(world: &mut World) -> Vec<(Entity, BallState)> {
+    let mut balls: Vec<_> = world
+        .query::<(Entity, &Ball, &Transform, &Velocity, Option<&Force>, Option<&Impulse>)>()
+        .iter(world)
+        .map(|(entity, ball, transform, velocity, force, impulse)| (entity, BallState {
+            radius: ball.radius,
+            mass: ball.mass,
+            restitution: ball.restitution,
+            position: transform.translation.truncate(),
+            velocity: velocity.0,
+            force: force.map_or(Vec2::ZERO, |force| force.0),
+            impulse: impulse.map_or(Vec2::ZERO, |impulse| impulse.0),
+        }))
+        .collect();
+    balls.sort_unstable_by_key(|(entity, _)| *entity);
+    balls
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Record `ticks` ticks of a seeded scene, in which a ball is spawned,
+    // pushed and despawned along the way, like the inputs of a player would.
+    fn record(ticks: usize) -> (Replay, u64) {
+        let (mut world, entities) = headless_world(1655, 30, Bounds::new(Vec2::ZERO, 400., 300.));
+        world.insert_resource(Gravity(Vec2::new(0., -100.)));
+        world.spawn().insert(Attractor { strength: 1000., enabled: true }).insert(Transform::default());
+        let mut recorder = ReplayRecorder::default();
+        let mut stage = physics_stage().with_system(apply_attraction.before(PhysicsSystem::Integrate));
+        let mut replay = Replay::default();
+        for tick in 0..ticks {
+            match tick {
+                10 => {
+                    world.spawn().insert_bundle(BallBundle::builder(6.).with_velocity(Vec2::new(50., 0.)).build());
+                }
+                20 => world.get_mut::<Impulse>(entities[4]).unwrap().0 = Vec2::new(0., 300.),
+                30 => {
+                    world.despawn(entities[7]);
+                    world.resource_mut::<SlowMotion>().region = Some(Bounds::new(Vec2::ZERO, 100., 100.));
+                }
+                _ => {}
+            }
+            let changes = recorder.changes(&mut world);
+            stage.run(&mut world);
+            replay.ticks.push(ReplayTick { changes, checksum: recorder.end_tick(&mut world) });
+        }
+        (replay, state_checksum(&mut world))
+    }
+
+    #[test]
+    fn recordings_play_out_the_same() {
+        let (replay, checksum) = record(60);
+        // only what changed is recorded
+        assert_eq!(replay.ticks[0].changes.len(), 4 + 30);
+        assert!(replay.ticks[20].changes.iter().any(|change| matches!(change, Change::Ball(_, ball) if ball.impulse.y == 300.)));
+        assert!(replay.ticks[30].changes.iter().any(|change| matches!(change, Change::Despawn(_))));
+
+        // the recording survives being written and parsed
+        let text: String = replay.ticks.iter()
+            .flat_map(|tick| tick.changes.iter().map(Change::to_string).chain([format!("checksum {:x}", tick.checksum)]))
+            .map(|line| line + "\n")
+            .collect();
+        let parsed = Replay::parse(&format!("// recording\n{}gravity 0 0", text)).unwrap();
+        assert_eq!(parsed, replay);
+
+        let mut world = replay_world();
+        world.insert_resource(BroadPhase::default());
+        assert_eq!(replay.play(&mut world), Ok(60));
+        assert_eq!(state_checksum(&mut world), checksum);
+
+        // a replay which doesn't apply the same changes plays out differently
+        let mut changed = replay.clone();
+        changed.ticks[20].changes.retain(|change| !matches!(change, Change::Ball(..)));
+        let mut world = replay_world();
+        world.insert_resource(BroadPhase::default());
+        let divergence = changed.play(&mut world).unwrap_err();
+        assert_eq!(divergence.tick, 21);
+        assert_eq!(divergence.expected, replay.ticks[20].checksum);
+
+        assert!(matches!(Replay::parse("gravity 1\nchecksum 0"), Err(ReplayError::InvalidLine(1, _))));
+        assert!(matches!(Replay::parse("checksum x"), Err(ReplayError::InvalidLine(1, _))));
+    }
+}
@@ -0,0 +1,165 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+
+use crate::*;
+
+/// Records the state of all balls after each physics tick, for the last
+/// `duration` seconds. While `Action::Rewind` is held the simulation is paused
+/// and played backwards from these records, once it is released the
+/// simulation continues forward from where the playback stopped.
+pub struct RewindPlugin {
+    duration: f32,
+}
+
+impl RewindPlugin {
+    pub fn with_duration(duration: f32) -> Self {
+        Self { duration }
+    }
+}
+
+impl Default for RewindPlugin {
+    fn default() -> Self { Self::with_duration(5.) }
+}
+
+impl Plugin for RewindPlugin {
+    fn build(&self, app: &mut App) {
+        let capacity = (self.duration / TIMESTEP).ceil() as usize;
+        app.insert_resource(History::with_capacity(capacity))
+            .bind_action(Action::Rewind, [Binding::Key(KeyCode::T)])
+            .add_system(rewind)
+            .add_system_to_stage(
+                PhysicsStage,
+                record_history.after(PhysicsSystem::Constraints).after(PhysicsSystem::Resolve),
+            );
+    }
+}
+
+/// State of a ball at the end of a physics tick.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BallState {
+    pub entity: Entity,
+    pub position: Vec2,
+    pub velocity: Vec2,
+}
+
+/// States of all balls for each of the last `capacity` physics ticks, from
+/// the oldest to the latest.
+pub struct History {
+    capacity: usize,
+    ticks: VecDeque<Vec<BallState>>,
+}
+
+impl History {
+    pub fn with_capacity(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        Self { capacity, ticks: VecDeque::with_capacity(capacity) }
+    }
+
+    /// Record the states of a tick, the oldest tick is dropped when the
+    /// history is full.
+    pub fn record(&mut self, states: Vec<BallState>) {
+        if self.ticks.len() == self.capacity {
+            self.ticks.pop_front();
+        }
+        self.ticks.push_back(states);
+    }
+
+    /// Step back `ticks` ticks, but no further than the oldest tick. The
+    /// ticks after it are dropped, so recording continues from there. Returns
+    /// the states of the tick stepped back to, or `None` when the history is
+    /// empty.
+    pub fn rewind(&mut self, ticks: usize) -> Option<&[BallState]> {
+        let keep = self.ticks.len().saturating_sub(ticks).max(1);
+        self.ticks.truncate(keep);
+        self.ticks.back().map(Vec::as_slice)
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize { self.ticks.len() }
+
+    #[inline]
+    #[allow(dead_code)]
+    pub fn is_empty(&self) -> bool { self.ticks.is_empty() }
+}
+
+fn record_history(mut history: ResMut<History>, query: Query<(Entity, &Transform, &Velocity), With<Ball>>) {
+    history.record(query.iter()
+        .map(|(entity, transform, velocity)| BallState {
+            entity,
+            position: transform.translation.truncate(),
+            velocity: velocity.0,
+        })
+        .collect());
+}
+
+// Play the history backwards at normal speed while the action is held. The
+// simulation is paused meanwhile, and restored to its previous state after.
+fn rewind(
+    mut history: ResMut<History>,
+    mut paused: ResMut<Paused>,
+    mut was_paused: Local<Option<bool>>,
+    mut pending: Local<f32>,
+    actions: Res<Input<Action>>,
+    time: Res<Time>,
+    mut query: Query<(&mut Transform, &mut Velocity), With<Ball>>,
+) {
+    if actions.just_released(Action::Rewind) {
+        if let Some(was_paused) = was_paused.take() {
+            paused.0 = was_paused;
+        }
+    }
+    if !actions.pressed(Action::Rewind) {
+        return;
+    }
+    if was_paused.is_none() {
+        *was_paused = Some(paused.0);
+        *pending = 0.;
+    }
+    paused.0 = true;
+
+    *pending += time.delta_seconds() / TIMESTEP;
+    let ticks = *pending as usize;
+    *pending -= ticks as f32;
+    if ticks == 0 || history.len() <= 1 {
+        return;
+    }
+
+    // balls which were despawned since are skipped, balls which were
+    // spawned since keep their state
+    let states = match history.rewind(ticks) {
+        Some(states) => states,
+        None => return,
+    };
+    for state in states {
+        if let Ok((mut transform, mut velocity)) = query.get_mut(state.entity) {
+            transform.translation = state.position.extend(transform.translation.z);
+            velocity.0 = state.velocity;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn history_keeps_the_last_ticks_and_rewinds() {
+        let state = |x: f32| vec![BallState { entity: Entity::from_raw(0), position: Vec2::new(x, 0.), velocity: Vec2::X }];
+        let mut history = History::with_capacity(3);
+        assert_eq!(history.rewind(1), None);
+        for x in 0..5 {
+            history.record(state(x as f32));
+        }
+        assert_eq!(history.len(), 3);
+
+        assert_eq!(history.rewind(1), Some(state(3.).as_slice()));
+        // the oldest tick is kept
+        assert_eq!(history.rewind(10), Some(state(2.).as_slice()));
+        assert_eq!(history.len(), 1);
+
+        // recording continues from the tick stepped back to
+        history.record(state(7.));
+        assert_eq!(history.rewind(1), Some(state(2.).as_slice()));
+    }
+}
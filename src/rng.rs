@@ -0,0 +1,43 @@
+use std::ops::{Deref, DerefMut};
+
+use bevy::math::Vec2;
+use rand::distributions::{Distribution, Uniform};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+use crate::quadtree::Bounds;
+
+/// Random number generator shared by all systems of the simulation. Using a
+/// fixed seed makes a run reproducible.
+pub struct SimRng(StdRng);
+
+impl SimRng {
+    #[inline]
+    pub fn new(seed: Option<u64>) -> Self {
+        Self(match seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        })
+    }
+}
+
+impl Deref for SimRng {
+    type Target = StdRng;
+
+    #[inline(always)]
+    fn deref(&self) -> &Self::Target { &self.0 }
+}
+
+impl DerefMut for SimRng {
+    #[inline(always)]
+    fn deref_mut(&mut self) -> &mut Self::Target { &mut self.0 }
+}
+
+/// Uniformly sampled point within `bounds`.
+#[inline]
+pub fn random_point_in(rng: &mut StdRng, bounds: Bounds) -> Vec2 {
+    Vec2::new(
+        Uniform::from(bounds.left()..=bounds.right()).sample(rng),
+        Uniform::from(bounds.bottom()..=bounds.top()).sample(rng),
+    )
+}
@@ -0,0 +1,126 @@
+use bevy::prelude::*;
+
+use crate::*;
+
+/// Makes balls roll along the walls instead of sliding. When a ball touches a
+/// wall, friction converts part of its speed along the wall into spin and vice
+/// versa, until the surface of the ball no longer slips. The spin of each ball
+/// is shown by a spoke from its center to its rim.
+pub struct RollingPlugin {
+    friction: f32,
+}
+
+impl RollingPlugin {
+    pub fn with_friction(friction: f32) -> Self {
+        Self { friction }
+    }
+}
+
+impl Default for RollingPlugin {
+    fn default() -> Self { Self::with_friction(0.3) }
+}
+
+impl Plugin for RollingPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Rolling { friction: self.friction })
+            .add_system(spin_spawned_balls)
+            .add_system(draw_spin)
+            .add_system_to_stage(
+                PhysicsStage,
+                roll_along_walls
+                    .after(PhysicsSystem::BroadPhase)
+                    .before(PhysicsSystem::NarrowPhase),
+            );
+    }
+}
+
+pub struct Rolling {
+    /// Coefficient of friction between the balls and the walls.
+    pub friction: f32,
+}
+
+fn spin_spawned_balls(mut cmd: Commands, mut spawned: EventReader<BallSpawned>) {
+    for BallSpawned(entity) in spawned.iter() {
+        cmd.entity(*entity).insert(AngularVelocity::default());
+    }
+}
+
+/// Change in velocity and spin of a ball with `radius` and `velocity`, which
+/// touches a surface with `normal` and bounced off of it at `impact_speed`.
+/// The friction impulse cancels the slip of the surface of the ball, but is
+/// limited by the impulse of the impact. Balls are solid disks, with a moment
+/// of inertia of `m r² / 2`.
+#[inline]
+pub fn rolling_impulse(
+    friction: f32,
+    restitution: f32,
+    impact_speed: f32,
+    normal: Vec2,
+    radius: f32,
+    velocity: Vec2,
+    spin: f32,
+) -> (Vec2, f32) {
+    let tangent = normal.perp();
+    let slip = velocity.dot(tangent) - spin * radius;
+    let limit = friction * (1. + restitution) * impact_speed;
+    let impulse = (-slip / 3.).clamp(-limit, limit);
+    (tangent * impulse, -2. * impulse / radius)
+}
+
+fn roll_along_walls(
+    rolling: Res<Rolling>,
+    edge: Res<EdgeCollider>,
+    mut wall_hits: EventReader<BallHitWall>,
+    mut query: Query<(&Ball, &mut Velocity, &mut AngularVelocity)>,
+) {
+    for BallHitWall(entity, hit) in wall_hits.iter() {
+        let (ball, mut velocity, mut spin) = match query.get_mut(*entity) {
+            Ok(ball) => ball,
+            Err(_) => continue,
+        };
+
+        let (velocity_change, spin_change) = rolling_impulse(
+            rolling.friction,
+            edge.restitution * ball.restitution,
+            hit.impact_speed,
+            hit.side.normal(),
+            ball.radius,
+            velocity.0,
+            spin.0,
+        );
+        velocity.0 += velocity_change;
+        spin.0 += spin_change;
+    }
+}
+
+fn draw_spin(mut debug_lines: ResMut<DebugLines>, query: Query<(&Transform, &Ball), With<AngularVelocity>>) {
+    for (transform, ball) in query.iter() {
+        let rim = transform.rotation * Vec3::new(ball.radius, 0., 0.);
+        debug_lines.line_colored(transform.translation, transform.translation + rim, 0., Color::BLACK);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sliding_ball_starts_rolling() {
+        // a ball sliding to the right along the floor, without spin
+        let (velocity, spin) = rolling_impulse(1., 0., 10., Vec2::Y, 2., Vec2::new(9., -10.), 0.);
+        assert_eq!(velocity, Vec2::new(-3., 0.));
+        assert_eq!(spin, -3.);
+        // its surface no longer slips, it rolls clockwise
+        assert_eq!(9. + velocity.x + spin * 2., 0.);
+
+        // friction limits the impulse of a light touch
+        let (velocity, spin) = rolling_impulse(0.5, 0., 1., Vec2::Y, 2., Vec2::new(9., -1.), 0.);
+        assert_eq!(velocity, Vec2::new(-0.5, 0.));
+        assert_eq!(spin, -0.5);
+
+        // a ball spinning counter clockwise is pushed to the left
+        let (velocity, spin) = rolling_impulse(1., 0., 10., Vec2::Y, 2., Vec2::ZERO, 3.);
+        assert_eq!(velocity, Vec2::new(-2., 0.));
+        assert_eq!(spin, -2.);
+    }
+}
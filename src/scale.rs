@@ -0,0 +1,30 @@
+#[cfg(feature = "scene")]
+use serde::{Deserialize, Serialize};
+
+/// Scale between physical units and the pixels the simulation runs in. With
+/// it, lengths, speeds and accelerations such as gravity can be configured in
+/// meters and seconds, independent of the size of the window.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "scene", derive(Serialize, Deserialize))]
+pub struct WorldScale {
+    pub pixels_per_meter: f32,
+}
+
+#[cfg_attr(not(feature = "scene"), allow(dead_code))]
+impl WorldScale {
+    pub fn new(pixels_per_meter: f32) -> Self {
+        Self { pixels_per_meter: pixels_per_meter.max(f32::EPSILON) }
+    }
+
+    /// Length, speed or acceleration in meters converted to pixels.
+    #[inline]
+    pub fn to_pixels(self, meters: f32) -> f32 {
+        meters * self.pixels_per_meter
+    }
+
+    /// Length, speed or acceleration in pixels converted to meters.
+    #[inline]
+    pub fn to_meters(self, pixels: f32) -> f32 {
+        pixels / self.pixels_per_meter
+    }
+}
@@ -0,0 +1,166 @@
+use std::fmt::{self, Formatter};
+use std::str::FromStr;
+
+use bevy::prelude::*;
+use rand::distributions::{Distribution, Uniform};
+use rand::rngs::StdRng;
+
+use crate::*;
+
+/// Distribution of the balls of a headless scene. Next to the uniform default,
+/// there are pathological distributions which push the broad phase to its
+/// limits, like the maximum depth and minimum size of the quadtree.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Scenario {
+    /// Random radii, velocities and positions.
+    #[default]
+    Uniform,
+    /// All balls start at the center of the arena.
+    SinglePoint,
+    /// All balls start on a horizontal line through the center.
+    DenseLine,
+    /// Balls are evenly spaced along the diagonal, all moving the same way.
+    DiagonalStream,
+    /// Radii halve from the largest radius down to a tiny fraction of it.
+    ExponentialRadii,
+}
+
+impl Scenario {
+    pub const ALL: [Scenario; 5] = [
+        Scenario::Uniform,
+        Scenario::SinglePoint,
+        Scenario::DenseLine,
+        Scenario::DiagonalStream,
+        Scenario::ExponentialRadii,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Scenario::Uniform => "uniform",
+            Scenario::SinglePoint => "point",
+            Scenario::DenseLine => "line",
+            Scenario::DiagonalStream => "diagonal",
+            Scenario::ExponentialRadii => "exponential",
+        }
+    }
+
+    /// Radius, velocity and position of ball `i` out of `balls`, which is
+    /// spawned within `area`.
+    pub fn ball(&self, rng: &mut StdRng, i: usize, balls: usize, area: Bounds) -> (f32, Vec2, Vec2) {
+        let rand_radius = Uniform::from(BALL_RADIUS);
+        let rand_velocity = Uniform::from(-50.0..=50.0);
+        // fraction of the way along the line or stream
+        let t = i as f32 / balls.max(2).saturating_sub(1) as f32;
+
+        match self {
+            Scenario::Uniform => (
+                rand_radius.sample(rng),
+                Vec2::new(rand_velocity.sample(rng), rand_velocity.sample(rng)),
+                random_point_in(rng, area),
+            ),
+            Scenario::SinglePoint => (
+                rand_radius.sample(rng),
+                Vec2::new(rand_velocity.sample(rng), rand_velocity.sample(rng)),
+                area.center(),
+            ),
+            Scenario::DenseLine => (
+                rand_radius.sample(rng),
+                Vec2::new(rand_velocity.sample(rng), rand_velocity.sample(rng)),
+                Vec2::new(area.left() + t * area.width(), area.center().y),
+            ),
+            Scenario::DiagonalStream => (
+                rand_radius.sample(rng),
+                Vec2::ONE.normalize() * 50.,
+                area.bottom_left() + t * (area.top_right() - area.bottom_left()),
+            ),
+            Scenario::ExponentialRadii => (
+                BALL_RADIUS.end() * 0.5f32.powi((i % 12) as i32),
+                Vec2::new(rand_velocity.sample(rng), rand_velocity.sample(rng)),
+                random_point_in(rng, area),
+            ),
+        }
+    }
+
+    /// Check that the balls of a headless scene survived the ticks it was run
+    /// for: they have finite positions and velocities, are within the arena,
+    /// and are all stored in the quadtree when it is used as broad phase.
+    pub fn check(world: &World, entities: &[Entity]) -> Result<(), ScenarioError> {
+        let bounds = world.resource::<EdgeCollider>().bounds;
+        for entity in entities {
+            let ball = world.get::<Ball>(*entity).unwrap();
+            let position = world.get::<Transform>(*entity).unwrap().translation.truncate();
+            let velocity = world.get::<Velocity>(*entity).unwrap().0;
+
+            if !position.is_finite() || !velocity.is_finite() {
+                return Err(ScenarioError::NotFinite(*entity));
+            }
+            if !bounds.expanded(ball.radius).contains(position) {
+                return Err(ScenarioError::OutOfBounds(*entity, position));
+            }
+        }
+
+        if matches!(*world.resource::<BroadPhase>(), BroadPhase::QuadTree | BroadPhase::Deferred(_)) {
+            let tree = &world.resource::<BallTree>().0;
+            let stored = tree.query_area(tree.bounds()).len();
+            if stored != entities.len() {
+                return Err(ScenarioError::MissingFromTree(entities.len() - stored));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Scenario {
+    type Err = ();
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Scenario::ALL.into_iter()
+            .find(|scenario| scenario.name() == name)
+            .ok_or(())
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ScenarioError {
+    NotFinite(Entity),
+    OutOfBounds(Entity, Vec2),
+    /// Amount of balls which are not stored in the quadtree.
+    MissingFromTree(usize),
+}
+
+impl fmt::Display for ScenarioError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        use ScenarioError::*;
+        match self {
+            NotFinite(entity) => write!(f, "ball {:?} has a position or velocity which is not finite", entity),
+            OutOfBounds(entity, position) => write!(f, "ball {:?} escaped the arena at {}", entity, position),
+            MissingFromTree(missing) => write!(f, "{} balls are missing from the quadtree", missing),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pathological_scenarios_keep_the_broad_phase_intact() {
+        for broad_phase in [BroadPhase::QuadTree, BroadPhase::Linear(6), BroadPhase::Reach, BroadPhase::Deferred(4), BroadPhase::Chunked(100.)] {
+            for scenario in Scenario::ALL {
+                let (mut world, entities) = scenario_world(scenario, 1665, 200, Bounds::new(Vec2::ZERO, 400., 300.));
+                world.insert_resource(broad_phase);
+
+                let mut stage = physics_stage();
+                for _ in 0..60 {
+                    stage.run(&mut world);
+                }
+                if let Err(err) = Scenario::check(&world, &entities) {
+                    panic!("{} with {:?}: {}", scenario.name(), broad_phase, err);
+                }
+            }
+        }
+
+        assert_eq!("diagonal".parse(), Ok(Scenario::DiagonalStream));
+        assert_eq!("spiral".parse::<Scenario>(), Err(()));
+    }
+}
@@ -0,0 +1,586 @@
+//! Saves the live scene to a RON file with `Action::SaveScene`, and loads such
+//! a file on startup when it is passed with `--config <path>`. A scene holds
+//! the arena and its settings, all balls and the obstacles within it. Paths
+//! ending in `.scn.ron` are Bevy scenes instead, see `src/dynamic_scene.rs`.
+
+use std::fmt::{self, Formatter};
+use std::path::Path;
+use std::{fs, io};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::*;
+
+pub struct ScenePlugin {
+    config: Option<String>,
+    export: String,
+}
+
+impl ScenePlugin {
+    pub fn with_config(config: Option<String>) -> Self {
+        Self {
+            config,
+            export: "scene.ron".to_string(),
+        }
+    }
+}
+
+impl Default for ScenePlugin {
+    fn default() -> Self { Self::with_config(None) }
+}
+
+impl Plugin for ScenePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(SceneExport(self.export.clone()))
+            .register_type::<SceneStyle>()
+            .bind_action(Action::SaveScene, [Binding::Ctrl(KeyCode::S)])
+            .bind_action(Action::ExportBevyScene, [Binding::Ctrl(KeyCode::E)])
+            .add_system(export_scene)
+            .add_system(export_bevy_scene.exclusive_system());
+
+        let path = match &self.config {
+            Some(path) => path,
+            None => return,
+        };
+        if path.ends_with(".scn.ron") {
+            app.insert_resource(BevySceneImport(path.clone()))
+                .add_startup_system_to_stage(StartupStage::PostStartup, spawn_bevy_scene.exclusive_system());
+            return;
+        }
+        let scene = match SceneConfig::load(path) {
+            Ok(scene) => scene,
+            Err(err) => {
+                println!("scene: unable to load {}: {}", path, err);
+                return;
+            }
+        };
+
+        // obstacles only work when their plugin is added, the plugins don't
+        // spawn any obstacles themselves as those are replaced by the scene
+        if !CONVEYORS && !scene.conveyors.is_empty() {
+            app.add_plugin(ConveyorPlugin::with_regions(Vec::new()));
+        }
+        if !PORTALS && !scene.portals.is_empty() {
+            app.add_plugin(PortalPlugin::with_pairs(Vec::new()));
+        }
+        if !GOAL_ZONES && !scene.goals.is_empty() {
+            app.add_plugin(GoalPlugin::with_zones(Vec::new()));
+        }
+        app.insert_resource(scene)
+            .add_startup_system_to_stage(StartupStage::PostStartup, spawn_scene);
+    }
+}
+
+/// Path the scene is saved to.
+pub struct SceneExport(pub String);
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct SceneConfig {
+    pub arena: ArenaConfig,
+    pub gravity: [f32; 2],
+    #[serde(default)]
+    pub drag: f32,
+    /// Kinds of balls, or none to keep the kinds which are already in use.
+    #[serde(default)]
+    pub kinds: Vec<BallKind>,
+    /// Strategy balls which spawn are colored with, or `None` to keep the
+    /// strategy which is already in use.
+    #[serde(default)]
+    pub colors: Option<ColorStrategy>,
+    #[serde(default)]
+    pub balls: Vec<BallConfig>,
+    #[serde(default)]
+    pub conveyors: Vec<ConveyorConfig>,
+    #[serde(default)]
+    pub portals: Vec<PortalConfig>,
+    #[serde(default)]
+    pub goals: Vec<GoalConfig>,
+    /// Scale of the scene when its quantities are in meters and seconds, or
+    /// `None` when they are in pixels.
+    #[serde(default)]
+    pub scale: Option<WorldScale>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ArenaConfig {
+    pub center: [f32; 2],
+    pub size: [f32; 2],
+    pub restitution: f32,
+    #[serde(default)]
+    pub friction: f32,
+}
+
+impl Default for ArenaConfig {
+    fn default() -> Self {
+        Self {
+            center: [0., 0.],
+            size: [WIDTH, HEIGHT],
+            restitution: WALL_RESTITUTION,
+            friction: WALL_FRICTION,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BallConfig {
+    pub position: [f32; 2],
+    pub velocity: [f32; 2],
+    pub radius: f32,
+    /// Fill color, as linear rgba.
+    pub fill: [f32; 4],
+    #[serde(default)]
+    pub outline: Option<OutlineConfig>,
+    #[serde(default)]
+    pub frozen: bool,
+    /// Name of the kind of the ball, or `None` for the first kind.
+    #[serde(default)]
+    pub kind: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct OutlineConfig {
+    pub color: [f32; 4],
+    pub width: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ConveyorConfig {
+    pub center: [f32; 2],
+    pub size: [f32; 2],
+    pub force: [f32; 2],
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PortalConfig {
+    pub centers: [[f32; 2]; 2],
+    pub radius: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GoalConfig {
+    pub center: [f32; 2],
+    pub size: [f32; 2],
+    pub capture: CaptureConfig,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum CaptureConfig {
+    Despawn,
+    Hold,
+    Respawn([f32; 2]),
+}
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io(io::Error),
+    Ron(ron::Error),
+    /// The scene is well-formed, but doesn't make sense.
+    Invalid(String),
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneError::Io(err) => write!(f, "{}", err),
+            SceneError::Ron(err) => write!(f, "{}", err),
+            SceneError::Invalid(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+impl SceneConfig {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SceneError> {
+        let text = fs::read_to_string(path).map_err(SceneError::Io)?;
+        Self::parse(&text)
+    }
+
+    #[inline]
+    pub fn parse(text: &str) -> Result<Self, SceneError> {
+        ron::from_str(text).map_err(SceneError::Ron)
+    }
+
+    /// Check if the scene can be simulated, every ball has to fit within the
+    /// arena.
+    pub fn validate(&self) -> Result<(), SceneError> {
+        let invalid = |reason: String| Err(SceneError::Invalid(reason));
+        let arena = self.arena;
+        if !(arena.size[0] > 0. && arena.size[1] > 0.) {
+            return invalid(format!("arena size {:?} is not positive", arena.size));
+        }
+        if !(0. ..=1.).contains(&arena.restitution) {
+            return invalid(format!("arena restitution {} is not within 0..=1", arena.restitution));
+        }
+        if arena.friction < 0. || !arena.friction.is_finite() {
+            return invalid(format!("arena friction {} is not zero or positive", arena.friction));
+        }
+
+        if !self.kinds.is_empty() {
+            let kinds = BallKinds::new(self.kinds.clone()).map_err(SceneError::Invalid)?;
+            for (i, ball) in self.balls.iter().enumerate() {
+                if let Some(name) = ball.kind.as_deref().filter(|name| kinds.find(name).is_none()) {
+                    return invalid(format!("ball {} is of unknown kind `{}`", i, name));
+                }
+            }
+        }
+
+        let bounds = Bounds::new(vec2(arena.center), arena.size[0], arena.size[1]);
+        for (i, ball) in self.balls.iter().enumerate() {
+            if ball.radius <= 0. || !ball.radius.is_finite() {
+                return invalid(format!("ball {} has a radius of {}", i, ball.radius));
+            }
+            if !bounds.contains_area(Bounds::new(vec2(ball.position), ball.radius * 2., ball.radius * 2.)) {
+                return invalid(format!("ball {} at {:?} is outside of the arena", i, ball.position));
+            }
+        }
+        for (i, portal) in self.portals.iter().enumerate() {
+            if portal.radius <= 0. || !portal.radius.is_finite() {
+                return invalid(format!("portal pair {} has a radius of {}", i, portal.radius));
+            }
+        }
+        Ok(())
+    }
+
+    /// The scene with all lengths, speeds and accelerations multiplied by
+    /// `factor`.
+    pub fn scaled(&self, factor: f32) -> Self {
+        let scale = |v: [f32; 2]| v.map(|x| x * factor);
+        let mut scene = self.clone();
+        scene.arena.center = scale(scene.arena.center);
+        scene.arena.size = scale(scene.arena.size);
+        scene.gravity = scale(scene.gravity);
+        for kind in scene.kinds.iter_mut() {
+            kind.radius = scale(kind.radius);
+        }
+        for ball in scene.balls.iter_mut() {
+            ball.position = scale(ball.position);
+            ball.velocity = scale(ball.velocity);
+            ball.radius *= factor;
+            if let Some(outline) = ball.outline.as_mut() {
+                outline.width *= factor;
+            }
+        }
+        for conveyor in scene.conveyors.iter_mut() {
+            conveyor.center = scale(conveyor.center);
+            conveyor.size = scale(conveyor.size);
+            conveyor.force = scale(conveyor.force);
+        }
+        for portal in scene.portals.iter_mut() {
+            portal.centers = portal.centers.map(scale);
+            portal.radius *= factor;
+        }
+        for goal in scene.goals.iter_mut() {
+            goal.center = scale(goal.center);
+            goal.size = scale(goal.size);
+            if let CaptureConfig::Respawn(position) = goal.capture {
+                goal.capture = CaptureConfig::Respawn(scale(position));
+            }
+        }
+        scene
+    }
+
+    /// The scene in pixels, converted from meters when it has a scale.
+    pub fn in_pixels(&self) -> Self {
+        match self.scale {
+            Some(scale) => self.scaled(scale.to_pixels(1.)),
+            None => self.clone(),
+        }
+    }
+
+    /// The scene, which is in pixels, converted to meters with `scale`.
+    pub fn in_meters(&self, scale: WorldScale) -> Self {
+        Self { scale: Some(scale), ..self.scaled(scale.to_meters(1.)) }
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), SceneError> {
+        let text = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()).map_err(SceneError::Ron)?;
+        fs::write(path, text).map_err(SceneError::Io)
+    }
+}
+
+#[inline]
+fn vec2(v: [f32; 2]) -> Vec2 { Vec2::from(v) }
+
+#[inline]
+fn color(c: [f32; 4]) -> Color { Color::rgba_linear(c[0], c[1], c[2], c[3]) }
+
+impl BallConfig {
+    pub fn new(ball: &Ball, transform: &Transform, velocity: &Velocity, draw_mode: &DrawMode, frozen: bool) -> Self {
+        let outline = match draw_mode {
+            DrawMode::Outlined { outline_mode, .. } => Some(OutlineConfig {
+                color: outline_mode.color.as_linear_rgba_f32(),
+                width: outline_mode.options.line_width,
+            }),
+            _ => None,
+        };
+
+        Self {
+            position: transform.translation.truncate().into(),
+            velocity: velocity.0.into(),
+            radius: ball.radius,
+            fill: fill_color(draw_mode).as_linear_rgba_f32(),
+            outline,
+            frozen,
+            kind: None,
+        }
+    }
+
+    #[inline]
+    pub fn style(&self) -> BallStyle {
+        BallStyle {
+            fill: color(self.fill),
+            outline: self.outline.map(|outline| Outline {
+                color: color(outline.color),
+                width: outline.width,
+            }),
+        }
+    }
+}
+
+impl From<ConveyorRegion> for ConveyorConfig {
+    fn from(region: ConveyorRegion) -> Self {
+        Self {
+            center: region.bounds.center().into(),
+            size: [region.bounds.width(), region.bounds.height()],
+            force: region.force.into(),
+        }
+    }
+}
+
+impl From<ConveyorConfig> for ConveyorRegion {
+    fn from(config: ConveyorConfig) -> Self {
+        Self {
+            bounds: Bounds::new(vec2(config.center), config.size[0], config.size[1]),
+            force: vec2(config.force),
+        }
+    }
+}
+
+impl From<GoalZone> for GoalConfig {
+    fn from(zone: GoalZone) -> Self {
+        Self {
+            center: zone.bounds.center().into(),
+            size: [zone.bounds.width(), zone.bounds.height()],
+            capture: match zone.capture {
+                GoalCapture::Despawn => CaptureConfig::Despawn,
+                GoalCapture::Hold => CaptureConfig::Hold,
+                GoalCapture::Respawn(position) => CaptureConfig::Respawn(position.into()),
+            },
+        }
+    }
+}
+
+impl From<GoalConfig> for GoalZone {
+    fn from(config: GoalConfig) -> Self {
+        let capture = match config.capture {
+            CaptureConfig::Despawn => GoalCapture::Despawn,
+            CaptureConfig::Hold => GoalCapture::Hold,
+            CaptureConfig::Respawn(position) => GoalCapture::Respawn(vec2(position)),
+        };
+        GoalZone::new(Bounds::new(vec2(config.center), config.size[0], config.size[1]), capture)
+    }
+}
+
+// Replace the randomly spawned balls, and any obstacles, with the scene.
+#[allow(clippy::too_many_arguments)]
+fn spawn_scene(
+    mut cmd: Commands,
+    config: Res<SceneConfig>,
+    mut gravity: ResMut<Gravity>,
+    mut drag: ResMut<Drag>,
+    mut kinds: ResMut<BallKinds>,
+    mut colors: ResMut<SpawnColors>,
+    balls: Query<Entity, With<Ball>>,
+    conveyors: Query<Entity, With<ConveyorRegion>>,
+    portals: Query<Entity, With<Portal>>,
+    goals: Query<Entity, With<GoalZone>>,
+) {
+    for entity in balls.iter().chain(conveyors.iter()).chain(portals.iter()).chain(goals.iter()) {
+        cmd.entity(entity).despawn_recursive();
+    }
+    // the scene is saved in the units it was loaded in
+    if let Some(scale) = config.scale {
+        cmd.insert_resource(scale);
+    }
+    let scene = config.in_pixels();
+
+    let arena = scene.arena;
+    cmd.insert_resource(EdgeCollider::with_restitution(
+        Bounds::new(vec2(arena.center), arena.size[0], arena.size[1]),
+        arena.restitution,
+    ).with_friction(arena.friction));
+    gravity.0 = vec2(scene.gravity);
+    drag.0 = scene.drag;
+    if let Ok(scene_kinds) = BallKinds::new(scene.kinds.clone()) {
+        *kinds = scene_kinds;
+    }
+    if let Some(strategy) = scene.colors {
+        colors.strategy = strategy;
+    }
+
+    for ball in scene.balls.iter() {
+        let kind = ball.kind.as_deref()
+            .and_then(|name| kinds.find(name))
+            .unwrap_or_default();
+        let mut entity = BallBundle::builder(ball.radius)
+            .with_kind(&kinds, kind)
+            .with_style(ball.style())
+            .with_velocity(vec2(ball.velocity))
+            .with_position(vec2(ball.position))
+            .spawn(&mut cmd);
+        if ball.frozen {
+            entity.insert(Frozen);
+        }
+    }
+    for conveyor in scene.conveyors.iter() {
+        cmd.spawn().insert(ConveyorRegion::from(*conveyor));
+    }
+    for (i, portal) in scene.portals.iter().enumerate() {
+        let color = PORTAL_COLORS[i % PORTAL_COLORS.len()];
+        spawn_portal_pair(&mut cmd, portal.centers.map(vec2), portal.radius, color);
+    }
+    for goal in scene.goals.iter() {
+        cmd.spawn().insert(GoalZone::from(*goal));
+    }
+
+    cmd.remove_resource::<SceneConfig>();
+}
+
+#[allow(clippy::too_many_arguments)]
+fn export_scene(
+    actions: Res<Input<Action>>,
+    export: Res<SceneExport>,
+    edge: Res<EdgeCollider>,
+    gravity: Res<Gravity>,
+    drag: Res<Drag>,
+    kinds: Res<BallKinds>,
+    colors: Res<SpawnColors>,
+    world_scale: Option<Res<WorldScale>>,
+    balls: Query<(&Ball, &Transform, &Velocity, &DrawMode, Option<&Frozen>, Option<&Kind>)>,
+    conveyors: Query<&ConveyorRegion>,
+    portals: Query<(Entity, &Portal, &Transform)>,
+    goals: Query<&GoalZone>,
+) {
+    if !actions.just_pressed(Action::SaveScene) {
+        return;
+    }
+
+    // each pair is saved once, by the portal with the lowest entity
+    let portals = portals.iter()
+        .filter(|(entity, portal, _)| *entity < portal.exit)
+        .filter_map(|(_, portal, transform)| {
+            let (_, _, exit) = portals.get(portal.exit).ok()?;
+            Some(PortalConfig {
+                centers: [transform.translation.truncate().into(), exit.translation.truncate().into()],
+                radius: portal.radius,
+            })
+        })
+        .collect();
+
+    let scene = SceneConfig {
+        arena: ArenaConfig {
+            center: edge.bounds.center().into(),
+            size: [edge.bounds.width(), edge.bounds.height()],
+            restitution: edge.restitution,
+            friction: edge.friction,
+        },
+        gravity: gravity.0.into(),
+        drag: drag.0,
+        kinds: kinds.as_slice().to_vec(),
+        colors: Some(colors.strategy),
+        balls: balls.iter()
+            .map(|(ball, transform, velocity, draw_mode, frozen, kind)| BallConfig {
+                kind: kind.map(|kind| kinds.get(*kind).name.clone()),
+                ..BallConfig::new(ball, transform, velocity, draw_mode, frozen.is_some())
+            })
+            .collect(),
+        conveyors: conveyors.iter().map(|region| ConveyorConfig::from(*region)).collect(),
+        portals,
+        goals: goals.iter().map(|zone| GoalConfig::from(*zone)).collect(),
+        scale: None,
+    };
+    let scene = match world_scale {
+        Some(world_scale) => scene.in_meters(*world_scale),
+        None => scene,
+    };
+
+    match scene.save(&export.0) {
+        Ok(()) => println!("scene: saved {} balls to {}", scene.balls.len(), export.0),
+        Err(err) => println!("scene: unable to save {}: {}", export.0, err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scene_survives_a_round_trip() {
+        let scene = SceneConfig {
+            arena: ArenaConfig::default(),
+            gravity: [0., -100.],
+            drag: 0.1,
+            kinds: vec![BallKind {
+                name: "rock".to_string(),
+                color: Some([0.5, 0.5, 0.5, 1.]),
+                flags: KindFlags { affected_by_gravity: false, ..default() },
+                ..default()
+            }],
+            colors: Some(ColorStrategy::Quadrant),
+            balls: vec![BallConfig {
+                position: [10., 20.],
+                velocity: [-5., 0.],
+                radius: 4.,
+                fill: [1., 0.5, 0., 1.],
+                outline: Some(OutlineConfig { color: [0., 0., 0., 1.], width: 1.5 }),
+                frozen: true,
+                kind: Some("rock".to_string()),
+            }],
+            conveyors: vec![ConveyorRegion {
+                bounds: Bounds::new(Vec2::ZERO, 100., 20.),
+                force: Vec2::new(50., 0.),
+            }.into()],
+            portals: vec![PortalConfig { centers: [[-100., 0.], [100., 0.]], radius: 30. }],
+            goals: vec![GoalZone::new(Bounds::new(Vec2::ZERO, 40., 40.), GoalCapture::Respawn(Vec2::Y)).into()],
+            scale: None,
+        };
+
+        let text = ron::ser::to_string_pretty(&scene, ron::ser::PrettyConfig::default()).unwrap();
+        assert_eq!(SceneConfig::parse(&text).unwrap(), scene);
+
+        assert!(scene.validate().is_ok());
+        let mut outside = scene.clone();
+        outside.balls[0].position = [WIDTH, 0.];
+        assert!(matches!(outside.validate(), Err(SceneError::Invalid(_))));
+        let mut unknown = scene.clone();
+        unknown.balls[0].kind = Some("pebble".to_string());
+        assert!(matches!(unknown.validate(), Err(SceneError::Invalid(_))));
+
+        // everything but the arena and gravity may be left out
+        let minimal = SceneConfig::parse("(arena: (center: (0, 0), size: (100, 100), restitution: 1), gravity: (0, 0))").unwrap();
+        assert!(minimal.balls.is_empty());
+    }
+
+    #[test]
+    fn scenes_in_meters_are_converted_to_pixels() {
+        let scene = SceneConfig::parse(
+            "(arena: (center: (0, 0), size: (10, 8), restitution: 1), gravity: (0, -9.5), \
+             balls: [(position: (1, 2), velocity: (0.5, 0), radius: 0.25, fill: (1, 1, 1, 1))], \
+             scale: Some((pixels_per_meter: 100)))",
+        ).unwrap();
+        assert!(scene.validate().is_ok());
+
+        let pixels = scene.in_pixels();
+        assert_eq!(pixels.arena.size, [1000., 800.]);
+        assert_eq!(pixels.gravity, [0., -950.]);
+        let ball = &pixels.balls[0];
+        assert_eq!((ball.position, ball.velocity, ball.radius), ([100., 200.], [50., 0.], 25.));
+
+        // saved scenes are converted back to the units they were loaded in
+        let meters = pixels.in_meters(WorldScale::new(100.));
+        assert_eq!(meters.balls[0].position, [1., 2.]);
+        assert_eq!(meters.scale, Some(WorldScale::new(100.)));
+        let unscaled = SceneConfig { scale: None, ..scene };
+        assert_eq!(unscaled.in_pixels(), unscaled);
+    }
+}
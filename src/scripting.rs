@@ -0,0 +1,215 @@
+//! Runs a Rhai script alongside the simulation. The script may define the
+//! hooks `on_tick(dt)` and `on_collision(a, b)`, and can call `balls`,
+//! `spawn_ball`, `apply_force` and `apply_impulse` to interact with the
+//! simulation. The script is reloaded whenever the file changes.
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use bevy::prelude::*;
+use rhai::{Array, Dynamic, Engine, Scope, AST, FLOAT, INT};
+
+use crate::*;
+
+pub struct ScriptingPlugin {
+    path: PathBuf,
+}
+
+impl ScriptingPlugin {
+    pub fn with_path(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl Plugin for ScriptingPlugin {
+    fn build(&self, app: &mut App) {
+        let shared = ScriptShared::default();
+        app.insert_resource(Script {
+            engine: create_engine(shared.clone()),
+            path: self.path.clone(),
+            ast: None,
+            modified: None,
+            shared,
+            reload_timer: Timer::from_seconds(0.5, true),
+        })
+            .add_system(reload_script)
+            .add_system_to_stage(PhysicsStage, run_tick_hook.before(PhysicsSystem::Integrate))
+            .add_system_to_stage(PhysicsStage, run_collision_hook.after(PhysicsSystem::Resolve));
+    }
+}
+
+enum ScriptCommand {
+    SpawnBall { position: Vec2, velocity: Vec2, radius: f32 },
+    ApplyForce(Entity, Vec2),
+    ApplyImpulse(Entity, Vec2),
+}
+
+// State shared between the script and the systems. Commands issued by the
+// script are applied to the world after the script has run.
+#[derive(Default)]
+struct SharedState {
+    balls: Array,
+    commands: Vec<ScriptCommand>,
+}
+
+type ScriptShared = Arc<Mutex<SharedState>>;
+
+pub struct Script {
+    engine: Engine,
+    path: PathBuf,
+    ast: Option<AST>,
+    modified: Option<SystemTime>,
+    shared: ScriptShared,
+    reload_timer: Timer,
+}
+
+impl Script {
+    #[inline]
+    fn has_fn(&self, name: &str) -> bool {
+        match &self.ast {
+            Some(ast) => ast.iter_functions().any(|f| f.name == name),
+            None => false,
+        }
+    }
+
+    fn call_fn(&self, name: &str, args: impl rhai::FuncArgs) {
+        if let Some(ast) = &self.ast {
+            if let Err(err) = self.engine.call_fn::<()>(&mut Scope::new(), ast, name, args) {
+                println!("script: {}: {}", name, err);
+            }
+        }
+    }
+}
+
+// Entities are passed to the script as integers.
+#[inline]
+fn to_entity(id: INT) -> Entity { Entity::from_bits(id as u64) }
+
+fn create_engine(shared: ScriptShared) -> Engine {
+    let mut engine = Engine::new();
+
+    let s = shared.clone();
+    engine.register_fn("balls", move || s.lock().unwrap().balls.clone());
+
+    let s = shared.clone();
+    engine.register_fn("spawn_ball", move |x: FLOAT, y: FLOAT, vx: FLOAT, vy: FLOAT, radius: FLOAT| {
+        s.lock().unwrap().commands.push(ScriptCommand::SpawnBall {
+            position: Vec2::new(x as f32, y as f32),
+            velocity: Vec2::new(vx as f32, vy as f32),
+            radius: radius as f32,
+        });
+    });
+
+    let s = shared.clone();
+    engine.register_fn("apply_force", move |id: INT, x: FLOAT, y: FLOAT| {
+        let force = Vec2::new(x as f32, y as f32);
+        s.lock().unwrap().commands.push(ScriptCommand::ApplyForce(to_entity(id), force));
+    });
+
+    let s = shared;
+    engine.register_fn("apply_impulse", move |id: INT, x: FLOAT, y: FLOAT| {
+        let impulse = Vec2::new(x as f32, y as f32);
+        s.lock().unwrap().commands.push(ScriptCommand::ApplyImpulse(to_entity(id), impulse));
+    });
+
+    engine
+}
+
+fn reload_script(mut script: ResMut<Script>, time: Res<Time>) {
+    if !script.reload_timer.tick(time.delta()).just_finished() && script.modified.is_some() {
+        return;
+    }
+
+    let modified = fs::metadata(&script.path).and_then(|m| m.modified()).ok();
+    if modified.is_none() || modified == script.modified {
+        return;
+    }
+
+    script.modified = modified;
+    match script.engine.compile_file(script.path.clone()) {
+        Ok(ast) => {
+            println!("script: loaded {}", script.path.display());
+            script.ast = Some(ast);
+        }
+        Err(err) => println!("script: unable to load {}: {}", script.path.display(), err),
+    }
+}
+
+fn run_tick_hook(
+    mut cmd: Commands,
+    script: Res<Script>,
+    palette: Res<Palette>,
+    edge: Res<EdgeCollider>,
+    mut rng: ResMut<SimRng>,
+    mut query: Query<(Entity, &mut Force, &mut Impulse), With<Ball>>,
+) {
+    if script.has_fn("on_tick") {
+        script.shared.lock().unwrap().balls = query.iter()
+            .map(|(entity, _, _)| Dynamic::from(entity.to_bits() as INT))
+            .collect();
+
+        script.call_fn("on_tick", (TIMESTEP as FLOAT, ));
+    }
+
+    apply_script_commands(&mut cmd, &script.shared, &palette, &edge, &mut rng, &mut query);
+}
+
+fn run_collision_hook(
+    mut cmd: Commands,
+    script: Res<Script>,
+    palette: Res<Palette>,
+    edge: Res<EdgeCollider>,
+    mut rng: ResMut<SimRng>,
+    mut collided: EventReader<BallCollided>,
+    mut query: Query<(Entity, &mut Force, &mut Impulse), With<Ball>>,
+) {
+    if !script.has_fn("on_collision") {
+        return;
+    }
+
+    for BallCollided(a, b) in collided.iter() {
+        script.call_fn("on_collision", (a.to_bits() as INT, b.to_bits() as INT));
+    }
+
+    apply_script_commands(&mut cmd, &script.shared, &palette, &edge, &mut rng, &mut query);
+}
+
+fn apply_script_commands(
+    cmd: &mut Commands,
+    shared: &ScriptShared,
+    palette: &Palette,
+    edge: &EdgeCollider,
+    rng: &mut StdRng,
+    query: &mut Query<(Entity, &mut Force, &mut Impulse), With<Ball>>,
+) {
+    for command in shared.lock().unwrap().commands.drain(..) {
+        match command {
+            ScriptCommand::SpawnBall { position, velocity, radius } => {
+                let position = match edge.check_spawn(position, velocity, radius) {
+                    Ok(position) => position,
+                    Err(err) => {
+                        println!("script: spawn_ball: {}", err);
+                        continue;
+                    }
+                };
+                BallBundle::builder(radius)
+                    .with_style(ball_style(palette.pick(rng)))
+                    .with_velocity(velocity)
+                    .with_position(position)
+                    .spawn(cmd);
+            }
+            ScriptCommand::ApplyForce(entity, force) => {
+                if let Ok((_, mut accumulated, _)) = query.get_mut(entity) {
+                    accumulated.0 += force;
+                }
+            }
+            ScriptCommand::ApplyImpulse(entity, impulse) => {
+                if let Ok((_, _, mut accumulated)) = query.get_mut(entity) {
+                    accumulated.0 += impulse;
+                }
+            }
+        }
+    }
+}
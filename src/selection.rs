@@ -0,0 +1,234 @@
+use std::collections::HashSet;
+
+use bevy::prelude::*;
+
+use crate::*;
+
+/// Selects all balls within a rectangle, which is drawn by dragging with
+/// `Action::SelectArea`, the left mouse button while holding shift by default.
+/// A click without dragging clears the selection. Selected balls can be deleted, frozen, recolored or
+/// pushed towards the cursor at once.
+pub struct SelectionPlugin {
+    impulse: f32,
+}
+
+impl SelectionPlugin {
+    pub fn with_impulse(impulse: f32) -> Self {
+        Self { impulse }
+    }
+}
+
+impl Default for SelectionPlugin {
+    fn default() -> Self { Self::with_impulse(400.) }
+}
+
+impl Plugin for SelectionPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Selection::with_impulse(self.impulse))
+            .bind_action(Action::DeleteSelection, [Binding::Key(KeyCode::Delete), Binding::Key(KeyCode::Back)])
+            .bind_action(Action::FreezeSelection, [Binding::Key(KeyCode::I)])
+            .bind_action(Action::RecolorSelection, [Binding::Key(KeyCode::C)])
+            .bind_action(Action::PushSelection, [Binding::Key(KeyCode::Space)])
+            .bind_action(Action::SelectArea, [Binding::ShiftMouse(MouseButton::Left)])
+            .add_system(select_balls)
+            .add_system(apply_batch_operations.after(select_balls))
+            .add_system(forget_despawned_balls)
+            .add_system(draw_selection);
+    }
+}
+
+pub struct Selection {
+    pub entities: HashSet<Entity>,
+    /// Rectangle which is being dragged, if any.
+    pub marquee: Option<Bounds>,
+    /// Change in speed of selected balls when they are pushed.
+    pub impulse: f32,
+}
+
+impl Selection {
+    pub fn with_impulse(impulse: f32) -> Self {
+        Self {
+            entities: HashSet::new(),
+            marquee: None,
+            impulse,
+        }
+    }
+
+    /// Replace the selection with the `balls` whose center is within `area`.
+    pub fn select(&mut self, area: Bounds, balls: impl IntoIterator<Item = (Entity, Vec2)>) {
+        self.entities.clear();
+        self.entities.extend(balls.into_iter()
+            .filter(|(_, position)| area.contains(*position))
+            .map(|(entity, _)| entity));
+    }
+}
+
+/// Excludes a ball from integration, it keeps its position until it is
+/// unfrozen, unless other balls push it away.
+#[derive(Component, Clone, Copy, Debug, Default, Reflect)]
+#[reflect(Component)]
+pub struct Frozen;
+
+// Rectangles smaller than this, in either direction, clear the selection.
+const MIN_MARQUEE_SIZE: f32 = 4.;
+
+fn select_balls(
+    mut selection: ResMut<Selection>,
+    mut drag_start: Local<Option<Vec2>>,
+    actions: Res<Input<Action>>,
+    tree: Res<BallTree>,
+    picker: Picker,
+    query: Query<(Entity, &Transform), With<Ball>>,
+) {
+    let cursor = match picker.cursor() {
+        Some(cursor) => cursor,
+        None => return,
+    };
+
+    if actions.just_pressed(Action::SelectArea) {
+        *drag_start = Some(cursor);
+    }
+    if let Some(start) = *drag_start {
+        selection.marquee = Some(Bounds::from_corners(start, cursor));
+    }
+    if !actions.just_released(Action::SelectArea) {
+        return;
+    }
+
+    let marquee = match (drag_start.take(), selection.marquee.take()) {
+        (Some(_), Some(marquee)) => marquee,
+        _ => return,
+    };
+    if marquee.width() < MIN_MARQUEE_SIZE || marquee.height() < MIN_MARQUEE_SIZE {
+        selection.entities.clear();
+        return;
+    }
+
+    // the tree might be empty or a tick old, so candidates are checked
+    // against their current position
+    let position = |entity: Entity| {
+        query.get(entity).ok().map(|(_, transform)| (entity, transform.translation.truncate()))
+    };
+    if tree.0.is_empty() {
+        selection.select(marquee, query.iter().filter_map(|(entity, _)| position(entity)));
+    } else {
+        let candidates = tree.0.query_area(marquee.expanded(*BALL_RADIUS.end()));
+        selection.select(marquee, candidates.into_iter().filter_map(|(_, entity)| position(entity)));
+    }
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn apply_batch_operations(
+    mut cmd: Commands,
+    mut selection: ResMut<Selection>,
+    mut pool: ResMut<BallPool>,
+    mut rng: ResMut<SimRng>,
+    mut edits: EventWriter<Edit>,
+    actions: Res<Input<Action>>,
+    palette: Res<Palette>,
+    picker: Picker,
+    mut query: Query<(&Ball, &Transform, &Velocity, &mut Impulse, &mut DrawMode, Option<&Frozen>)>,
+) {
+    if selection.entities.is_empty() {
+        return;
+    }
+
+    if actions.just_pressed(Action::DeleteSelection) {
+        let mut snapshots = Vec::with_capacity(selection.entities.len());
+        for entity in selection.entities.drain() {
+            if let Ok((ball, transform, velocity, _, draw_mode, frozen)) = query.get(entity) {
+                snapshots.push(BallSnapshot::new(entity, ball, transform, velocity, draw_mode, frozen.is_some()));
+                pool.release(&mut cmd, entity, ball);
+            }
+        }
+        edits.send(Edit::Despawned(snapshots));
+        return;
+    }
+
+    if actions.just_pressed(Action::FreezeSelection) {
+        // unfreeze when all selected balls are frozen already
+        let freeze = selection.entities.iter()
+            .any(|entity| matches!(query.get(*entity), Ok((.., None))));
+        for entity in selection.entities.iter() {
+            if freeze {
+                cmd.entity(*entity).insert(Frozen);
+            } else {
+                cmd.entity(*entity).remove::<Frozen>();
+            }
+        }
+        edits.send(Edit::Frozen(selection.entities.iter().copied().collect(), freeze));
+    }
+
+    if actions.just_pressed(Action::RecolorSelection) {
+        let color = palette.pick(&mut rng);
+        let mut previous = Vec::with_capacity(selection.entities.len());
+        for entity in selection.entities.iter() {
+            if let Ok((.., mut draw_mode, _)) = query.get_mut(*entity) {
+                previous.push((*entity, fill_color(&draw_mode)));
+                set_fill_color(&mut draw_mode, color);
+            }
+        }
+        edits.send(Edit::Recolored(previous));
+    }
+
+    if actions.just_pressed(Action::PushSelection) {
+        let cursor = match picker.cursor() {
+            Some(cursor) => cursor,
+            None => return,
+        };
+        let mut changes = Vec::with_capacity(selection.entities.len());
+        for entity in selection.entities.iter() {
+            if let Ok((ball, transform, _, mut impulse, ..)) = query.get_mut(*entity) {
+                let change = (cursor - transform.translation.truncate()).normalize_or_zero() * selection.impulse;
+                impulse.0 += change * ball.mass;
+                changes.push((*entity, change));
+            }
+        }
+        edits.send(Edit::Pushed(changes));
+    }
+}
+
+fn forget_despawned_balls(mut selection: ResMut<Selection>, mut despawned: EventReader<BallDespawned>) {
+    for BallDespawned(entity) in despawned.iter() {
+        selection.entities.remove(entity);
+    }
+}
+
+fn draw_selection(
+    selection: Res<Selection>,
+    mut debug_lines: ResMut<DebugLines>,
+    query: Query<(&Transform, &Ball)>,
+) {
+    if let Some(marquee) = selection.marquee {
+        marquee.debug_draw_lines(&mut debug_lines, Some(Color::WHITE));
+    }
+
+    for entity in selection.entities.iter() {
+        if let Ok((transform, ball)) = query.get(*entity) {
+            let size = ball.radius * 2. + 4.;
+            Bounds::new(transform.translation.truncate(), size, size)
+                .debug_draw_lines(&mut debug_lines, Some(Color::YELLOW));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn select_replaces_selection_with_balls_inside() {
+        let mut selection = Selection::with_impulse(1.);
+        selection.entities.insert(Entity::from_raw(9));
+
+        let balls = [
+            (Entity::from_raw(0), Vec2::new(5., 5.)),
+            (Entity::from_raw(1), Vec2::new(20., 5.)),
+            (Entity::from_raw(2), Vec2::new(-5., -5.)),
+        ];
+        selection.select(Bounds::from_corners(Vec2::new(-10., -10.), Vec2::new(10., 10.)), balls);
+
+        let expected: HashSet<Entity> = [Entity::from_raw(0), Entity::from_raw(2)].into_iter().collect();
+        assert_eq!(selection.entities, expected);
+    }
+}
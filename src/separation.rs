@@ -0,0 +1,88 @@
+use std::collections::HashMap;
+use std::mem;
+
+use crate::*;
+
+// Ticks a pair of balls has to stay in contact before it is considered
+// persistent, and only separated by the bias.
+const PERSISTENT_TICKS: u32 = 2;
+
+/// Soft separation of balls which stay in contact across ticks. New contacts
+/// are separated at once, so balls bounce as before, while persistent ones are
+/// moved apart by a fraction of their overlap each tick. This keeps crowded
+/// clusters from vibrating as balls are snapped back and forth.
+///
+/// Only used when collisions are not resolved in parallel islands.
+pub struct Separation {
+    bias: f32,
+    contacts: HashMap<[Entity; 2], u32>,
+    previous: HashMap<[Entity; 2], u32>,
+}
+
+impl Separation {
+    /// The `bias` is the fraction of the overlap of a persistent contact which
+    /// is resolved each tick, within `0..=1`.
+    pub fn with_bias(bias: f32) -> Self {
+        Self {
+            bias: bias.clamp(0., 1.),
+            contacts: HashMap::new(),
+            previous: HashMap::new(),
+        }
+    }
+
+    /// Forget the contacts which are older than the previous tick. Called at
+    /// the start of each tick.
+    #[inline]
+    pub fn begin_tick(&mut self) {
+        mem::swap(&mut self.contacts, &mut self.previous);
+        self.contacts.clear();
+    }
+
+    /// Record that `pair` is in contact during the current tick, and return
+    /// the fraction of its overlap to resolve.
+    pub fn contact(&mut self, pair: [Entity; 2]) -> f32 {
+        let ticks = match self.previous.get(&pair) {
+            Some(ticks) => ticks + 1,
+            None => 0,
+        };
+        self.contacts.insert(pair, ticks);
+
+        if ticks < PERSISTENT_TICKS { 1. } else { self.bias }
+    }
+}
+
+impl Default for Separation {
+    fn default() -> Self { Self::with_bias(0.2) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn persistent_contacts_are_separated_by_the_bias() {
+        let mut world = World::new();
+        let pair = [world.spawn().id(), world.spawn().id()];
+        let mut separation = Separation::with_bias(0.25);
+
+        let fractions: Vec<f32> = (0..4)
+            .map(|_| {
+                separation.begin_tick();
+                separation.contact(pair)
+            })
+            .collect();
+        assert_eq!(fractions, [1., 1., 0.25, 0.25]);
+
+        // A tick without contact resets the pair.
+        separation.begin_tick();
+        separation.begin_tick();
+        assert_eq!(separation.contact(pair), 1.);
+
+        let a = Transform::from_xyz(0., 0., 0.);
+        let b = Transform::from_xyz(6., 0., 0.);
+        let ball = Ball::new(5., MassModel::Constant(1.));
+        let [position_a, position_b] = separated_positions_by([(&a, &ball), (&b, &ball)], 0.5).unwrap();
+        assert_eq!(position_a, Vec2::new(-1., 0.));
+        assert_eq!(position_b, Vec2::new(7., 0.));
+    }
+}
@@ -0,0 +1,201 @@
+use bevy::diagnostic::Diagnostics;
+use bevy::prelude::*;
+
+use crate::*;
+
+/// Slows down time for balls inside a region, which is drawn by dragging
+/// with `Action::SlowMotionRegion`, the right mouse button by default. A click
+/// without dragging removes the region.
+pub struct SlowMotionPlugin {
+    time_scale: f32,
+}
+
+impl SlowMotionPlugin {
+    pub fn with_time_scale(time_scale: f32) -> Self {
+        Self { time_scale }
+    }
+}
+
+impl Default for SlowMotionPlugin {
+    fn default() -> Self { Self::with_time_scale(0.1) }
+}
+
+impl Plugin for SlowMotionPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(SlowMotion {
+            region: None,
+            time_scale: self.time_scale,
+            global_time_scale: 1.,
+        })
+            .bind_action(Action::SlowMotionRegion, [Binding::Mouse(MouseButton::Right)])
+            .add_system(select_slow_motion_region)
+            .add_system(draw_slow_motion_region);
+    }
+}
+
+pub struct SlowMotion {
+    pub region: Option<Bounds>,
+    /// Scale of time within the region, 1 is normal speed.
+    pub time_scale: f32,
+    /// Scale of time everywhere, on top of the scale of the region.
+    pub global_time_scale: f32,
+}
+
+impl Default for SlowMotion {
+    fn default() -> Self {
+        Self {
+            region: None,
+            time_scale: 1.,
+            global_time_scale: 1.,
+        }
+    }
+}
+
+impl SlowMotion {
+    /// Scale of time at `position`.
+    #[inline]
+    pub fn time_scale_at(&self, position: Vec2) -> f32 {
+        let time_scale = match self.region {
+            Some(region) if region.contains(position) => self.time_scale,
+            _ => 1.,
+        };
+        time_scale * self.global_time_scale
+    }
+}
+
+/// Slows down time for everything for a moment when the collisions per tick
+/// exceed a threshold, like on a big impact of a cluster of balls. Time then
+/// smoothly ramps back to normal speed. Requires the `COLLISIONS` diagnostic
+/// of the `PhysicsDiagnosticsPlugin`.
+pub struct AutoSlowMotionPlugin {
+    threshold: f32,
+}
+
+impl AutoSlowMotionPlugin {
+    pub fn with_threshold(threshold: f32) -> Self {
+        Self { threshold }
+    }
+}
+
+impl Default for AutoSlowMotionPlugin {
+    fn default() -> Self { Self::with_threshold(50.) }
+}
+
+impl Plugin for AutoSlowMotionPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(AutoSlowMotion::with_threshold(self.threshold))
+            .add_system(trigger_auto_slow_motion);
+    }
+}
+
+pub struct AutoSlowMotion {
+    /// Collisions per tick at which time is slowed down.
+    pub threshold: f32,
+    /// Scale of time right after a burst of collisions.
+    pub time_scale: f32,
+    /// Seconds time stays slowed down after the last burst.
+    pub hold: f32,
+    /// Seconds in which time ramps back to normal speed after the hold.
+    pub recovery: f32,
+    // seconds since the last burst, `None` before the first one
+    since_burst: Option<f32>,
+}
+
+impl AutoSlowMotion {
+    pub fn with_threshold(threshold: f32) -> Self {
+        Self { threshold, time_scale: 0.2, hold: 0.5, recovery: 1.5, since_burst: None }
+    }
+
+    /// Advance by `delta` seconds, in which there were `collisions` per tick,
+    /// and return the scale of time.
+    pub fn update(&mut self, collisions: f32, delta: f32) -> f32 {
+        if collisions >= self.threshold {
+            self.since_burst = Some(0.);
+        } else if let Some(since_burst) = self.since_burst.as_mut() {
+            *since_burst += delta;
+        }
+        self.current_time_scale()
+    }
+
+    /// Scale of time, which is eased back to 1 after the hold.
+    pub fn current_time_scale(&self) -> f32 {
+        let since_burst = match self.since_burst {
+            Some(since_burst) => since_burst,
+            None => return 1.,
+        };
+        let t = ((since_burst - self.hold) / self.recovery.max(f32::EPSILON)).clamp(0., 1.);
+        let eased = t * t * (3. - 2. * t);
+        self.time_scale + (1. - self.time_scale) * eased
+    }
+}
+
+// Regions smaller than this, in either direction, are discarded.
+const MIN_REGION_SIZE: f32 = 4.;
+
+fn select_slow_motion_region(
+    mut slow_motion: ResMut<SlowMotion>,
+    mut drag_start: Local<Option<Vec2>>,
+    mut previous: Local<Option<Bounds>>,
+    mut edits: EventWriter<Edit>,
+    actions: Res<Input<Action>>,
+    picker: Picker,
+) {
+    let cursor = match picker.cursor() {
+        Some(cursor) => cursor,
+        None => return,
+    };
+
+    if actions.just_pressed(Action::SlowMotionRegion) {
+        *drag_start = Some(cursor);
+        *previous = slow_motion.region;
+    }
+    if let Some(start) = *drag_start {
+        let region = Bounds::from_corners(start, cursor);
+        slow_motion.region = if region.width() < MIN_REGION_SIZE || region.height() < MIN_REGION_SIZE {
+            None
+        } else {
+            Some(region)
+        };
+    }
+    if actions.just_released(Action::SlowMotionRegion) && drag_start.take().is_some() && slow_motion.region != *previous {
+        edits.send(Edit::SlowMotionRegion(*previous));
+    }
+}
+
+fn trigger_auto_slow_motion(
+    mut auto: ResMut<AutoSlowMotion>,
+    mut slow_motion: ResMut<SlowMotion>,
+    diagnostics: Res<Diagnostics>,
+    time: Res<Time>,
+) {
+    let collisions = diagnostics.get(PhysicsDiagnosticsPlugin::COLLISIONS)
+        .and_then(|collisions| collisions.value())
+        .unwrap_or(0.);
+    slow_motion.global_time_scale = auto.update(collisions as f32, time.delta_seconds());
+}
+
+fn draw_slow_motion_region(slow_motion: Res<SlowMotion>, mut debug_lines: ResMut<DebugLines>) {
+    if let Some(region) = slow_motion.region {
+        region.debug_draw_lines(&mut debug_lines, Some(Color::CYAN));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bursts_slow_down_time_which_ramps_back() {
+        let mut auto = AutoSlowMotion { threshold: 10., time_scale: 0.2, hold: 1., recovery: 2., since_burst: None };
+        assert_eq!(auto.update(9., 0.5), 1.);
+        assert_eq!(auto.update(12., 0.5), 0.2);
+        assert_eq!(auto.update(0., 0.5), 0.2);
+
+        // halfway through the recovery
+        assert!((auto.update(0., 1.5) - 0.6).abs() < 1e-6);
+        assert_eq!(auto.update(0., 1.), 1.);
+
+        let slow_motion = SlowMotion { global_time_scale: 0.5, ..SlowMotion::default() };
+        assert_eq!(slow_motion.time_scale_at(Vec2::ZERO), 0.5);
+    }
+}
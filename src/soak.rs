@@ -0,0 +1,177 @@
+use std::fmt::{self, Formatter};
+use std::fs;
+use std::time::{Duration, Instant};
+
+use bevy::prelude::*;
+
+use crate::*;
+
+// Relative change of the total kinetic energy which is tolerated. Collisions
+// are elastic and walls don't absorb energy in a headless scene, so only
+// numerical errors change it.
+const ENERGY_TOLERANCE: f32 = 0.05;
+
+// Interval between progress reports of a soak test.
+const REPORT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Invariants of a headless scene which have to hold after every tick, next to
+/// the ones checked by `Scenario::check`.
+pub struct Invariants {
+    balls: usize,
+    energy: f32,
+}
+
+impl Invariants {
+    /// Record the state of the scene to compare later ticks with.
+    pub fn new(world: &World, entities: &[Entity]) -> Self {
+        Self {
+            balls: entities.len(),
+            energy: kinetic_energy(world, entities),
+        }
+    }
+
+    pub fn check(&self, world: &mut World, entities: &[Entity]) -> Result<(), SoakError> {
+        let balls = world.query_filtered::<(), With<Ball>>().iter(world).count();
+        if balls != self.balls {
+            return Err(SoakError::BallCount { expected: self.balls, actual: balls });
+        }
+        Scenario::check(world, entities)?;
+
+        let energy = kinetic_energy(world, entities);
+        if (energy - self.energy).abs() > self.energy * ENERGY_TOLERANCE {
+            return Err(SoakError::EnergyDrift { initial: self.energy, actual: energy });
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SoakError {
+    Scenario(ScenarioError),
+    BallCount { expected: usize, actual: usize },
+    EnergyDrift { initial: f32, actual: f32 },
+}
+
+impl From<ScenarioError> for SoakError {
+    fn from(err: ScenarioError) -> Self { SoakError::Scenario(err) }
+}
+
+impl fmt::Display for SoakError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        use SoakError::*;
+        match self {
+            Scenario(err) => err.fmt(f),
+            BallCount { expected, actual } => write!(f, "expected {} balls, found {}", expected, actual),
+            EnergyDrift { initial, actual } => write!(f, "kinetic energy drifted from {} to {}", initial, actual),
+        }
+    }
+}
+
+/// Total kinetic energy of the balls among `entities`.
+pub fn kinetic_energy(world: &World, entities: &[Entity]) -> f32 {
+    entities.iter()
+        .filter_map(|entity| Some((world.get::<Ball>(*entity)?, world.get::<Velocity>(*entity)?)))
+        .map(|(ball, velocity)| 0.5 * ball.mass * velocity.0.length_squared())
+        .sum()
+}
+
+/// Run the physics of the scene in `world` until `keep_going` returns false,
+/// checking the invariants after every tick. Returns the amount of ticks
+/// which were run, or the tick at which an invariant was first violated.
+pub fn soak_world(
+    world: &mut World,
+    entities: &[Entity],
+    mut keep_going: impl FnMut(usize, &mut World) -> bool,
+) -> Result<usize, (usize, SoakError)> {
+    let invariants = Invariants::new(world, entities);
+    let mut stage = physics_stage();
+    let mut tick = 0;
+    while keep_going(tick, world) {
+        stage.run(world);
+        tick += 1;
+        invariants.check(world, entities).map_err(|err| (tick, err))?;
+    }
+    Ok(tick)
+}
+
+/// Run the physics of a seeded scene as fast as possible for `duration`,
+/// checking the invariants after every tick. Exits with an error and writes
+/// the state of all balls to a file on the first violation.
+pub fn soak(duration: Duration, balls: usize, seed: u64, scenario: Scenario) {
+    let (mut world, entities) = scenario_world(scenario, seed, balls, Bounds::new(Vec2::ZERO, WIDTH, HEIGHT));
+    world.insert_resource(BROAD_PHASE);
+    world.insert_resource(TreeCapacity::new(QUADTREE_CAPACITY));
+
+    println!("soaking {} balls of scenario `{}` with seed {} for {:?}", balls, scenario.name(), seed, duration);
+    let start = Instant::now();
+    let mut last_report = start;
+    let result = soak_world(&mut world, &entities, |tick, world| {
+        let now = Instant::now();
+        if now - last_report >= REPORT_INTERVAL {
+            last_report = now;
+            println!("{:>8.0} s: {} ticks, checksum {:016x}", (now - start).as_secs_f64(), tick, state_checksum(world));
+        }
+        now - start < duration
+    });
+
+    match result {
+        Ok(ticks) => println!("ok, {} ticks without violations, checksum {:016x}", ticks, state_checksum(&mut world)),
+        Err((tick, err)) => {
+            println!("tick {}: {}, checksum {:016x}", tick, err, state_checksum(&mut world));
+            let path = format!("soak-{}-{}.txt", seed, tick);
+            match fs::write(&path, dump(&world, &entities)) {
+                Ok(()) => println!("state written to {}", path),
+                Err(err) => println!("unable to write {}: {}", path, err),
+            }
+            std::process::exit(1);
+        }
+    }
+}
+
+// Radius, mass, position and velocity of the balls, one ball per line.
+fn dump(world: &World, entities: &[Entity]) -> String {
+    let mut lines = vec!["# entity radius mass x y vx vy".to_string()];
+    for entity in entities {
+        if let (Some(ball), Some(transform), Some(velocity)) = (
+            world.get::<Ball>(*entity),
+            world.get::<Transform>(*entity),
+            world.get::<Velocity>(*entity),
+        ) {
+            lines.push(format!(
+                "{:?} {} {} {} {} {} {}",
+                entity, ball.radius, ball.mass,
+                transform.translation.x, transform.translation.y, velocity.0.x, velocity.0.y,
+            ));
+        }
+    }
+    lines.join("\n") + "\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn soak_stops_at_the_first_violation() {
+        let (mut world, entities) = scenario_world(Scenario::Uniform, 1666, 50, Bounds::new(Vec2::ZERO, 300., 200.));
+        assert_eq!(soak_world(&mut world, &entities, |tick, _| tick < 300), Ok(300));
+
+        // a ball which suddenly speeds up breaks the conservation of energy
+        let invariants = Invariants::new(&world, &entities);
+        assert_eq!(invariants.check(&mut world, &entities), Ok(()));
+        world.get_mut::<Velocity>(entities[0]).unwrap().0 *= 100.;
+        assert!(matches!(invariants.check(&mut world, &entities), Err(SoakError::EnergyDrift { .. })));
+
+        world.get_mut::<Velocity>(entities[0]).unwrap().0 = Vec2::new(f32::NAN, 0.);
+        assert_eq!(
+            invariants.check(&mut world, &entities),
+            Err(SoakError::Scenario(ScenarioError::NotFinite(entities[0]))),
+        );
+
+        world.despawn(entities[1]);
+        assert_eq!(
+            invariants.check(&mut world, &entities),
+            Err(SoakError::BallCount { expected: 50, actual: 49 }),
+        );
+    }
+}
@@ -0,0 +1,118 @@
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+
+use crate::*;
+
+/// Spatial queries on the balls, which use the quadtree of the last physics
+/// tick to find candidates. The tree might be a tick old, so candidates are
+/// checked against their current position; without a tree all balls are
+/// checked.
+#[derive(SystemParam)]
+pub struct Spatial<'w, 's> {
+    tree: Res<'w, BallTree>,
+    balls: Query<'w, 's, (Entity, &'static Transform, &'static Ball)>,
+}
+
+impl<'w, 's> Spatial<'w, 's> {
+    /// Distance between the surfaces of balls `a` and `b`, which is negative
+    /// when they overlap. Returns `None` when either entity is not a ball.
+    #[allow(dead_code)]
+    pub fn distance_between(&self, a: Entity, b: Entity) -> Option<f32> {
+        let (_, transform_a, ball_a) = self.balls.get(a).ok()?;
+        let (_, transform_b, ball_b) = self.balls.get(b).ok()?;
+        let distance = transform_a.translation.truncate().distance(transform_b.translation.truncate());
+        Some(distance - ball_a.radius - ball_b.radius)
+    }
+
+    /// Balls which overlap with the circle at `center` with `radius`, ordered
+    /// by entity.
+    #[allow(dead_code)]
+    pub fn entities_within(&self, center: Vec2, radius: f32) -> Vec<Entity> {
+        self.candidates(center, radius)
+            .into_iter()
+            .filter(|entity| self.surface_distance(*entity, center).map_or(false, |distance| distance <= radius))
+            .collect()
+    }
+
+    /// Ball with the surface closest to `point`. Of balls which contain the
+    /// point, the one it lies deepest within.
+    #[allow(dead_code)]
+    pub fn closest_to(&self, point: Vec2) -> Option<Entity> {
+        let closest = |candidates: Vec<Entity>| candidates.into_iter()
+            .filter_map(|entity| Some((entity, self.surface_distance(entity, point)?)))
+            .min_by(|(_, a), (_, b)| a.total_cmp(b));
+
+        // search circles of growing size, until one holds a ball which is
+        // within it; any ball closer than that is within it as well
+        if !self.tree.0.is_empty() {
+            let bounds = self.tree.0.bounds();
+            let reach = bounds.width().hypot(bounds.height()) + point.distance(bounds.center());
+            let mut radius = *BALL_RADIUS.end();
+            while radius < reach {
+                if let Some((entity, distance)) = closest(self.candidates(point, radius)) {
+                    if distance <= radius {
+                        return Some(entity);
+                    }
+                }
+                radius *= 2.;
+            }
+        }
+        closest(self.balls.iter().map(|(entity, ..)| entity).collect()).map(|(entity, _)| entity)
+    }
+
+    // Distance from `point` to the surface of ball `entity`, negative when
+    // the point is within the ball.
+    #[inline]
+    fn surface_distance(&self, entity: Entity, point: Vec2) -> Option<f32> {
+        let (_, transform, ball) = self.balls.get(entity).ok()?;
+        Some(transform.translation.truncate().distance(point) - ball.radius)
+    }
+
+    fn candidates(&self, center: Vec2, radius: f32) -> Vec<Entity> {
+        if self.tree.0.is_empty() {
+            return self.balls.iter().map(|(entity, ..)| entity).collect();
+        }
+        self.tree.0.query_circle(center, radius)
+            .into_iter()
+            .map(|(_, entity)| entity)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::SystemState;
+
+    use super::*;
+
+    #[test]
+    fn spatial_queries_find_balls_by_their_surface() {
+        let mut world = World::new();
+        let mut tree = QuadTree::new(Bounds::new(Vec2::ZERO, 400., 400.), Options::default());
+        let mut spawn = |center: Vec2, radius: f32| {
+            let entity = world.spawn()
+                .insert(Transform::from_translation(center.extend(0.)))
+                .insert(Ball::new(radius, MassModel::Constant(1.)))
+                .id();
+            tree.insert(Location::new(center, radius * 2., radius * 2.), entity).unwrap();
+            entity
+        };
+        let small = spawn(Vec2::new(0., 0.), 2.);
+        let large = spawn(Vec2::new(20., 0.), 10.);
+        let far = spawn(Vec2::new(-150., 150.), 5.);
+        world.insert_resource(BallTree(tree));
+
+        let mut state = SystemState::<Spatial>::new(&mut world);
+        let spatial = state.get_mut(&mut world);
+        assert_eq!(spatial.distance_between(small, large), Some(8.));
+        assert_eq!(spatial.distance_between(small, Entity::from_raw(99)), None);
+
+        assert_eq!(spatial.entities_within(Vec2::new(5., 0.), 1.), vec![]);
+        assert_eq!(spatial.entities_within(Vec2::new(5., 0.), 5.), vec![small, large]);
+
+        // the surface of the large ball is closer than the center of the
+        // small one
+        assert_eq!(spatial.closest_to(Vec2::new(7., 0.)), Some(large));
+        assert_eq!(spatial.closest_to(Vec2::new(-190., 190.)), Some(far));
+    }
+}
@@ -0,0 +1,152 @@
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::*;
+
+use crate::*;
+
+/// Draws balls as sprites of a texture atlas, like the faces of billiard balls
+/// or emoji, instead of as shapes. The atlas is a grid of equally sized
+/// sprites, each new ball is given the next sprite. Sprites are scaled to the
+/// radius of the ball and tinted with its fill color, so coloring balls keeps
+/// working. Balls which aren't circles keep their shape.
+pub struct BallSpritesPlugin {
+    path: &'static str,
+    tile_size: Vec2,
+    columns: usize,
+    rows: usize,
+}
+
+impl BallSpritesPlugin {
+    pub fn with_atlas(path: &'static str, tile_size: Vec2, columns: usize, rows: usize) -> Self {
+        Self { path, tile_size, columns, rows }
+    }
+}
+
+impl Plugin for BallSpritesPlugin {
+    fn build(&self, app: &mut App) {
+        let (path, tile_size, columns, rows) = (self.path, self.tile_size, self.columns, self.rows);
+        app.insert_resource(BallAtlas { atlas: Handle::default(), len: columns * rows, tinted: true })
+            .add_startup_system(move |
+                mut atlas: ResMut<BallAtlas>,
+                assets: Res<AssetServer>,
+                mut atlases: ResMut<Assets<TextureAtlas>>,
+            | {
+                let texture = assets.load(path);
+                atlas.atlas = atlases.add(TextureAtlas::from_grid(texture, tile_size, columns, rows));
+            })
+            .add_system(assign_ball_sprites)
+            .add_system_to_stage(CoreStage::PostUpdate, apply_ball_visuals)
+            .add_system_to_stage(CoreStage::PostUpdate, update_ball_sprites.after(apply_ball_visuals));
+    }
+}
+
+/// Texture atlas balls are drawn with when their `BallVisual` is a sprite.
+pub struct BallAtlas {
+    pub atlas: Handle<TextureAtlas>,
+    /// Amount of sprites in the atlas.
+    pub len: usize,
+    /// Tint sprites with the fill color of their ball, or else draw them as
+    /// they are.
+    pub tinted: bool,
+}
+
+// Circular balls which are spawned, or reused from the pool, with a shape.
+type NewBall = (Added<Ball>, Without<BoxBody>, Without<CapsuleBody>, Without<CompoundCollider>);
+
+type ChangedVisual = Or<(Changed<BallVisual>, Added<Ball>)>;
+
+type ChangedBall = Or<(Changed<DrawMode>, Changed<Ball>)>;
+
+fn assign_ball_sprites(
+    atlas: Res<BallAtlas>,
+    mut next: Local<usize>,
+    mut query: Query<&mut BallVisual, NewBall>,
+) {
+    for mut visual in query.iter_mut() {
+        if *visual == BallVisual::Shape && atlas.len > 0 {
+            *visual = BallVisual::Sprite(*next % atlas.len);
+            *next += 1;
+        }
+    }
+}
+
+// Add or remove the sprite of balls of which the visual changed. Pooled balls
+// which are reused get their shape back when their body is applied, so their
+// shape is emptied again.
+fn apply_ball_visuals(
+    mut cmd: Commands,
+    atlas: Res<BallAtlas>,
+    query: Query<(Entity, &BallVisual, &Ball, &DrawMode), ChangedVisual>,
+) {
+    for (entity, visual, ball, draw_mode) in query.iter() {
+        match *visual {
+            BallVisual::Shape => {
+                cmd.entity(entity).remove::<TextureAtlasSprite>();
+            }
+            BallVisual::Sprite(index) => {
+                cmd.entity(entity)
+                    .insert(ShapePath::new().build())
+                    .insert(atlas.atlas.clone())
+                    .insert(TextureAtlasSprite {
+                        index,
+                        color: sprite_color(&atlas, draw_mode),
+                        custom_size: Some(Vec2::splat(ball.radius * 2.)),
+                        ..default()
+                    });
+            }
+        }
+    }
+}
+
+#[inline]
+fn sprite_color(atlas: &BallAtlas, draw_mode: &DrawMode) -> Color {
+    if atlas.tinted { fill_color(draw_mode) } else { Color::WHITE }
+}
+
+// Keep sprites in the color and size of their ball.
+fn update_ball_sprites(
+    atlas: Res<BallAtlas>,
+    mut query: Query<(&Ball, &DrawMode, &mut TextureAtlasSprite), ChangedBall>,
+) {
+    for (ball, draw_mode, mut sprite) in query.iter_mut() {
+        sprite.color = sprite_color(&atlas, draw_mode);
+        sprite.custom_size = Some(Vec2::splat(ball.radius * 2.));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sprites_follow_the_visual_of_balls() {
+        let mut world = World::new();
+        world.insert_resource(BallAtlas { atlas: Handle::default(), len: 4, tinted: true });
+        let mut stage = SystemStage::single_threaded()
+            .with_system(assign_ball_sprites)
+            .with_system(apply_ball_visuals.after(assign_ball_sprites))
+            .with_system(update_ball_sprites.after(apply_ball_visuals));
+
+        let style = BallStyle::fill(Color::RED);
+        let ball = |x: f32| BallBundle::builder(5.).with_style(style).with_position(Vec2::new(x, 0.));
+        let a = world.spawn().insert_bundle(ball(0.).build()).id();
+        let b = world.spawn().insert_bundle(ball(20.).with_visual(BallVisual::Sprite(3)).build()).id();
+        let boxed = world.spawn().insert_bundle(ball(40.).build()).insert(BoxBody { half_extents: Vec2::ONE }).id();
+        stage.run(&mut world);
+        // the sprite is added once the commands of the first run are applied
+        stage.run(&mut world);
+
+        assert_eq!(world.get::<BallVisual>(a), Some(&BallVisual::Sprite(0)));
+        assert_eq!(world.get::<BallVisual>(b), Some(&BallVisual::Sprite(3)));
+        assert_eq!(world.get::<BallVisual>(boxed), Some(&BallVisual::Shape));
+        let sprite = world.get::<TextureAtlasSprite>(b).unwrap();
+        assert_eq!((sprite.index, sprite.color, sprite.custom_size), (3, Color::RED, Some(Vec2::splat(10.))));
+
+        world.get_mut::<Ball>(b).unwrap().radius = 8.;
+        stage.run(&mut world);
+        assert_eq!(world.get::<TextureAtlasSprite>(b).unwrap().custom_size, Some(Vec2::splat(16.)));
+
+        *world.get_mut::<BallVisual>(b).unwrap() = BallVisual::Shape;
+        stage.run(&mut world);
+        assert!(world.get::<TextureAtlasSprite>(b).is_none());
+    }
+}
@@ -0,0 +1,92 @@
+//! Transcendental functions of the physics, which are not guaranteed to give
+//! the same result on every platform. Adding, multiplying, dividing and taking
+//! the square root of floats is exactly rounded by IEEE 754, but trigonometric
+//! and logarithmic functions come from the math library of the platform and
+//! may differ in their last bits. With the `strict-math` feature the functions
+//! of this module are computed in software by `libm` instead.
+//!
+//! The feature only covers these functions. The rest of the physics, like the
+//! collision resolver, sticks to the exactly rounded operations, which is what
+//! lets a seed play out the same on other platforms. Physics code which needs
+//! another transcendental function has to add it here.
+
+use bevy::math::{Quat, Vec2};
+use rand::Rng;
+
+/// Sine and cosine of `x`.
+#[inline]
+pub fn sin_cos(x: f32) -> (f32, f32) {
+    #[cfg(feature = "strict-math")]
+    return (libm::sinf(x), libm::cosf(x));
+    #[cfg(not(feature = "strict-math"))]
+    return x.sin_cos();
+}
+
+/// Natural logarithm of `x`.
+#[inline]
+#[cfg_attr(not(feature = "strict-math"), allow(dead_code))]
+pub fn ln(x: f32) -> f32 {
+    #[cfg(feature = "strict-math")]
+    return libm::logf(x);
+    #[cfg(not(feature = "strict-math"))]
+    return x.ln();
+}
+
+/// Rotation by `angle` around the z axis, like `Quat::from_rotation_z`.
+#[inline]
+pub fn rotation_z(angle: f32) -> Quat {
+    let (sin, cos) = sin_cos(angle * 0.5);
+    Quat::from_xyzw(0., 0., sin, cos)
+}
+
+/// Pair of independent samples of the standard normal distribution, by the
+/// Box-Muller transform.
+#[cfg_attr(not(feature = "strict-math"), allow(dead_code))]
+pub fn gaussian(rng: &mut impl Rng) -> Vec2 {
+    // the open interval keeps the logarithm finite
+    let u: f32 = 1. - rng.gen::<f32>();
+    let v: f32 = rng.gen();
+    let radius = (-2. * ln(u)).sqrt();
+    let (sin, cos) = sin_cos(std::f32::consts::TAU * v);
+    Vec2::new(cos, sin) * radius
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+    #[cfg(feature = "strict-math")]
+    use crate::*;
+
+    #[test]
+    fn strict_math_matches_the_platform() {
+        for angle in [0., 0.3, -1.7, 3.1, 100.] {
+            let (sin, cos) = sin_cos(angle);
+            assert!((sin - angle.sin()).abs() < 1e-6 && (cos - angle.cos()).abs() < 1e-6, "{}", angle);
+            assert!(rotation_z(angle).abs_diff_eq(Quat::from_rotation_z(angle), 1e-6), "{}", angle);
+        }
+        assert!((ln(10.) - 10f32.ln()).abs() < 1e-6);
+
+        let mut rng = StdRng::seed_from_u64(1690);
+        let samples: Vec<Vec2> = (0..5000).map(|_| gaussian(&mut rng)).collect();
+        let mean = samples.iter().sum::<Vec2>() / samples.len() as f32;
+        let variance = samples.iter().fold(Vec2::ZERO, |sum, sample| sum + (*sample - mean) * (*sample - mean)) / samples.len() as f32;
+        assert!(mean.abs().max_element() < 0.05, "{}", mean);
+        assert!((variance - Vec2::ONE).abs().max_element() < 0.1, "{}", variance);
+    }
+
+    // the checksum was recorded on x86_64 Linux, other platforms have to
+    // arrive at the same state
+    #[cfg(feature = "strict-math")]
+    #[test]
+    fn seeds_play_out_the_same_on_every_platform() {
+        let (mut world, _) = headless_world(1690, 200, Bounds::new(Vec2::ZERO, 400., 300.));
+        let mut stage = physics_stage();
+        for _ in 0..120 {
+            stage.run(&mut world);
+        }
+        assert_eq!(state_checksum(&mut world), 0x9fbdc271aa339261);
+    }
+}
@@ -0,0 +1,171 @@
+use std::f32::consts::TAU;
+use std::ops::RangeInclusive;
+
+use bevy::prelude::*;
+use bevy::render::camera::Camera2d;
+use bevy_prototype_lyon::entity::Path;
+use bevy_prototype_lyon::prelude::*;
+
+use crate::*;
+
+/// Draws circular balls with fewer segments the smaller they are on screen,
+/// instead of tessellating every ball as finely as the largest one. Segments
+/// are rounded up to a power of two and the zoom level is only followed once
+/// it changed by `ZOOM_STEP`, so balls are re-tessellated only when zooming
+/// changes their detail materially.
+pub struct CircleDetailPlugin {
+    segments: RangeInclusive<usize>,
+}
+
+impl CircleDetailPlugin {
+    pub fn with_segments(segments: RangeInclusive<usize>) -> Self {
+        Self { segments }
+    }
+}
+
+impl Default for CircleDetailPlugin {
+    fn default() -> Self { Self::with_segments(8..=64) }
+}
+
+impl Plugin for CircleDetailPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(CircleDetail::with_segments(self.segments.clone()))
+            .add_system_to_stage(CoreStage::PostUpdate, follow_zoom)
+            .add_system_to_stage(CoreStage::PostUpdate, tessellate_circles.after(follow_zoom));
+    }
+}
+
+pub struct CircleDetail {
+    pub min_segments: usize,
+    pub max_segments: usize,
+    /// Length of the segments of a circle on screen, in pixels.
+    pub segment_length: f32,
+    /// Zoom level balls are tessellated for, in world units per pixel.
+    pub zoom: f32,
+}
+
+// Factor by which the zoom level has to change before balls are tessellated
+// for it.
+const ZOOM_STEP: f32 = 1.5;
+
+impl CircleDetail {
+    pub fn with_segments(segments: RangeInclusive<usize>) -> Self {
+        let min_segments = (*segments.start()).max(3);
+        Self {
+            min_segments,
+            max_segments: (*segments.end()).max(min_segments),
+            segment_length: 4.,
+            zoom: 1.,
+        }
+    }
+
+    /// Amount of segments of a circle with `radius` at the current zoom
+    /// level.
+    pub fn segments(&self, radius: f32) -> usize {
+        let circumference = TAU * radius / self.zoom.max(f32::EPSILON);
+        let segments = (circumference / self.segment_length).ceil() as usize;
+        segments.next_power_of_two().clamp(self.min_segments, self.max_segments)
+    }
+
+    /// Follow `zoom` when it differs by at least `ZOOM_STEP` from the zoom
+    /// level balls are tessellated for. Returns whether it did.
+    pub fn follow(&mut self, zoom: f32) -> bool {
+        let ratio = zoom / self.zoom;
+        if ratio < ZOOM_STEP && ratio > 1. / ZOOM_STEP {
+            return false;
+        }
+        self.zoom = zoom;
+        true
+    }
+}
+
+/// Radius and amount of segments a circular ball is drawn with.
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+pub struct CircleSegments {
+    pub radius: f32,
+    pub segments: usize,
+}
+
+/// Circle with `radius` as a polygon of `segments` segments.
+pub fn circle_path(radius: f32, segments: usize) -> Path {
+    ShapePath::build_as(&shapes::Polygon {
+        points: (0..segments)
+            .map(|i| i as f32 / segments as f32 * TAU)
+            .map(|angle| Vec2::new(angle.cos(), angle.sin()) * radius)
+            .collect(),
+        closed: true,
+    })
+}
+
+fn follow_zoom(mut detail: ResMut<CircleDetail>, cameras: Query<&Transform, With<Camera2d>>) {
+    if let Ok(camera) = cameras.get_single() {
+        detail.follow(camera.scale.x);
+    }
+}
+
+// Circular balls drawn as shapes.
+type Circles = (Without<BoxBody>, Without<CapsuleBody>, Without<CompoundCollider>);
+
+type CircleItem<'a> = (
+    Entity,
+    &'a Ball,
+    Option<&'a BallVisual>,
+    Option<&'a CircleSegments>,
+    ChangeTrackers<Ball>,
+    Option<ChangeTrackers<BallVisual>>,
+);
+
+// Replace the path of balls of which the amount of segments changed. Balls
+// which are spawned, reused from the pool or drawn as shapes again have the
+// path of their body, so they are always tessellated.
+fn tessellate_circles(
+    mut cmd: Commands,
+    detail: Res<CircleDetail>,
+    query: Query<CircleItem, Circles>,
+) {
+    for (entity, ball, visual, current, ball_tracker, visual_tracker) in query.iter() {
+        if visual.map_or(false, |visual| *visual != BallVisual::Shape) {
+            continue;
+        }
+        let wanted = CircleSegments { radius: ball.radius, segments: detail.segments(ball.radius) };
+        let reset = ball_tracker.is_added() || visual_tracker.map_or(false, |tracker| tracker.is_changed());
+        if !reset && current == Some(&wanted) {
+            continue;
+        }
+        cmd.entity(entity)
+            .insert(circle_path(wanted.radius, wanted.segments))
+            .insert(wanted);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn circles_get_fewer_segments_when_small_on_screen() {
+        let mut detail = CircleDetail::with_segments(8..=64);
+        assert_eq!(detail.segments(2.), 8);
+        assert_eq!(detail.segments(10.), 16);
+        assert_eq!(detail.segments(16.), 32);
+        assert_eq!(detail.segments(100.), 64);
+
+        // small changes in zoom are ignored
+        assert!(!detail.follow(1.2));
+        assert!(!detail.follow(0.8));
+        assert!(detail.follow(4.));
+        assert_eq!(detail.segments(16.), 8);
+
+        let mut world = World::new();
+        world.insert_resource(detail);
+        let mut stage = SystemStage::single_threaded().with_system(tessellate_circles);
+        let style = BallStyle::fill(Color::RED);
+        let ball = world.spawn().insert_bundle(BallBundle::builder(16.).with_style(style).build()).id();
+        stage.run(&mut world);
+        assert_eq!(world.get::<CircleSegments>(ball), Some(&CircleSegments { radius: 16., segments: 8 }));
+
+        world.resource_mut::<CircleDetail>().follow(0.5);
+        stage.run(&mut world);
+        assert_eq!(world.get::<CircleSegments>(ball), Some(&CircleSegments { radius: 16., segments: 64 }));
+    }
+}
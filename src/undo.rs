@@ -0,0 +1,274 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+
+use crate::*;
+
+/// Records interactive edits, which are undone with `Action::Undo` and redone
+/// with `Action::Redo`. Systems which edit the simulation send an `Edit`
+/// event describing what they changed.
+pub struct UndoPlugin {
+    capacity: usize,
+}
+
+impl UndoPlugin {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self { capacity }
+    }
+}
+
+impl Default for UndoPlugin {
+    fn default() -> Self { Self::with_capacity(100) }
+}
+
+impl Plugin for UndoPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<Edit>()
+            .insert_resource(UndoStack::with_capacity(self.capacity))
+            .bind_action(Action::Undo, [Binding::Ctrl(KeyCode::Z)])
+            .bind_action(Action::Redo, [Binding::Ctrl(KeyCode::Y)])
+            .add_system(record_edits)
+            .add_system(undo_redo.after(record_edits));
+    }
+}
+
+/// State of a ball, from which it can be spawned again.
+#[derive(Clone, Copy, Debug)]
+pub struct BallSnapshot {
+    pub entity: Entity,
+    pub radius: f32,
+    pub position: Vec2,
+    pub velocity: Vec2,
+    pub draw_mode: DrawMode,
+    pub frozen: bool,
+}
+
+impl BallSnapshot {
+    #[inline]
+    pub fn new(entity: Entity, ball: &Ball, transform: &Transform, velocity: &Velocity, draw_mode: &DrawMode, frozen: bool) -> Self {
+        Self {
+            entity,
+            radius: ball.radius,
+            position: transform.translation.truncate(),
+            velocity: velocity.0,
+            draw_mode: *draw_mode,
+            frozen,
+        }
+    }
+}
+
+/// An interactive change to the simulation, which can be reverted.
+#[derive(Clone, Debug)]
+pub enum Edit {
+    /// Balls were spawned.
+    Spawned(Vec<Entity>),
+    /// Balls were despawned, or returned to the pool.
+    Despawned(Vec<BallSnapshot>),
+    /// The velocity of balls was changed by the given amount.
+    Pushed(Vec<(Entity, Vec2)>),
+    /// Balls were given a new fill color, the value is their previous color.
+    Recolored(Vec<(Entity, Color)>),
+    /// Balls were frozen, or unfrozen when the value is `false`.
+    Frozen(Vec<Entity>, bool),
+    /// The slow motion region was changed, the value is the previous region.
+    SlowMotionRegion(Option<Bounds>),
+}
+
+impl Edit {
+    /// Replace all references to the `from` entity with `to`, for when a
+    /// despawned ball is spawned again as a new entity.
+    pub fn remap(&mut self, from: Entity, to: Entity) {
+        let remap = |entity: &mut Entity| if *entity == from { *entity = to };
+        match self {
+            Edit::Spawned(entities) | Edit::Frozen(entities, _) => entities.iter_mut().for_each(remap),
+            Edit::Despawned(snapshots) => snapshots.iter_mut().for_each(|snapshot| remap(&mut snapshot.entity)),
+            Edit::Pushed(changes) => changes.iter_mut().for_each(|(entity, _)| remap(entity)),
+            Edit::Recolored(colors) => colors.iter_mut().for_each(|(entity, _)| remap(entity)),
+            Edit::SlowMotionRegion(_) => {}
+        }
+    }
+}
+
+/// Edits which can be undone, and undone edits which can be redone.
+pub struct UndoStack {
+    /// Maximum amount of edits which can be undone, older edits are dropped.
+    pub capacity: usize,
+    undo: VecDeque<Edit>,
+    redo: Vec<Edit>,
+}
+
+impl UndoStack {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+        }
+    }
+
+    /// Record a new edit, which makes the undone edits impossible to redo.
+    pub fn push(&mut self, edit: Edit) {
+        self.redo.clear();
+        self.push_undo(edit);
+    }
+
+    #[inline]
+    fn push_undo(&mut self, edit: Edit) {
+        if self.undo.len() == self.capacity {
+            self.undo.pop_front();
+        }
+        self.undo.push_back(edit);
+    }
+
+    /// Replace all references to the `from` entity with `to` in all edits.
+    pub fn remap(&mut self, from: Entity, to: Entity) {
+        for edit in self.undo.iter_mut().chain(self.redo.iter_mut()) {
+            edit.remap(from, to);
+        }
+    }
+}
+
+fn record_edits(mut stack: ResMut<UndoStack>, mut edits: EventReader<Edit>) {
+    for edit in edits.iter() {
+        stack.push(edit.clone());
+    }
+}
+
+type EditQuery<'w, 's> = Query<'w, 's, (&'static Ball, &'static Transform, &'static mut Velocity, &'static mut DrawMode, Option<&'static Frozen>)>;
+
+fn undo_redo(
+    mut cmd: Commands,
+    mut stack: ResMut<UndoStack>,
+    mut pool: ResMut<BallPool>,
+    mut slow_motion: ResMut<SlowMotion>,
+    actions: Res<Input<Action>>,
+    mut query: EditQuery,
+) {
+    let stack = &mut *stack;
+    if actions.just_pressed(Action::Undo) {
+        if let Some(edit) = stack.undo.pop_back() {
+            let inverse = revert(edit, stack, &mut cmd, &mut pool, &mut slow_motion, &mut query);
+            stack.redo.push(inverse);
+        }
+    } else if actions.just_pressed(Action::Redo) {
+        if let Some(edit) = stack.redo.pop() {
+            let inverse = revert(edit, stack, &mut cmd, &mut pool, &mut slow_motion, &mut query);
+            stack.push_undo(inverse);
+        }
+    }
+}
+
+// Revert `edit`, and return the edit which reverts this again.
+fn revert(
+    edit: Edit,
+    stack: &mut UndoStack,
+    cmd: &mut Commands,
+    pool: &mut BallPool,
+    slow_motion: &mut SlowMotion,
+    query: &mut EditQuery,
+) -> Edit {
+    match edit {
+        Edit::Spawned(entities) => {
+            let mut snapshots = Vec::with_capacity(entities.len());
+            for entity in entities {
+                if let Ok((ball, transform, velocity, draw_mode, frozen)) = query.get(entity) {
+                    snapshots.push(BallSnapshot::new(entity, ball, transform, velocity, draw_mode, frozen.is_some()));
+                    pool.release(cmd, entity, ball);
+                }
+            }
+            Edit::Despawned(snapshots)
+        }
+        Edit::Despawned(snapshots) => {
+            let mut entities = Vec::with_capacity(snapshots.len());
+            for snapshot in snapshots {
+                let entity = spawn_snapshot(cmd, pool, &snapshot);
+                if entity != snapshot.entity {
+                    stack.remap(snapshot.entity, entity);
+                }
+                entities.push(entity);
+            }
+            Edit::Spawned(entities)
+        }
+        Edit::Pushed(changes) => {
+            for (entity, change) in changes.iter() {
+                if let Ok((_, _, mut velocity, ..)) = query.get_mut(*entity) {
+                    velocity.0 -= *change;
+                }
+            }
+            Edit::Pushed(changes.into_iter().map(|(entity, change)| (entity, -change)).collect())
+        }
+        Edit::Recolored(colors) => {
+            let mut previous = Vec::with_capacity(colors.len());
+            for (entity, color) in colors {
+                if let Ok((_, _, _, mut draw_mode, _)) = query.get_mut(entity) {
+                    previous.push((entity, fill_color(&draw_mode)));
+                    set_fill_color(&mut draw_mode, color);
+                }
+            }
+            Edit::Recolored(previous)
+        }
+        Edit::Frozen(entities, frozen) => {
+            for entity in entities.iter() {
+                if frozen {
+                    cmd.entity(*entity).remove::<Frozen>();
+                } else {
+                    cmd.entity(*entity).insert(Frozen);
+                }
+            }
+            Edit::Frozen(entities, !frozen)
+        }
+        Edit::SlowMotionRegion(region) => {
+            Edit::SlowMotionRegion(std::mem::replace(&mut slow_motion.region, region))
+        }
+    }
+}
+
+/// Spawn a ball from `snapshot`, reusing its entity when it is still pooled.
+/// Returns the entity of the ball.
+pub fn spawn_snapshot(cmd: &mut Commands, pool: &mut BallPool, snapshot: &BallSnapshot) -> Entity {
+    let entity = match pool.acquire_entity(cmd, snapshot.entity, MASS_MODEL, snapshot.velocity, snapshot.position) {
+        Some(entity) => {
+            cmd.entity(entity).insert(snapshot.draw_mode);
+            entity
+        }
+        None => {
+            BallBundle::builder(snapshot.radius)
+                .with_draw_mode(snapshot.draw_mode)
+                .with_velocity(snapshot.velocity)
+                .with_position(snapshot.position)
+                .spawn(cmd)
+                .id()
+        }
+    };
+    if snapshot.frozen {
+        cmd.entity(entity).insert(Frozen);
+    }
+    entity
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new_edits_clear_redo_and_remap_all_edits() {
+        let mut stack = UndoStack::with_capacity(2);
+        let (a, b) = (Entity::from_raw(0), Entity::from_raw(1));
+
+        stack.push(Edit::Spawned(vec![a]));
+        stack.push(Edit::Frozen(vec![a, b], true));
+        stack.push(Edit::Pushed(vec![(a, Vec2::X)]));
+        stack.redo.push(Edit::Recolored(vec![(a, Color::RED)]));
+
+        // the oldest edit is dropped once the stack is full
+        assert_eq!(stack.undo.len(), 2);
+        assert!(matches!(stack.undo[0], Edit::Frozen(..)));
+
+        stack.remap(a, Entity::from_raw(5));
+        assert!(matches!(&stack.undo[0], Edit::Frozen(entities, true) if entities == &[Entity::from_raw(5), b]));
+        assert!(matches!(&stack.redo[0], Edit::Recolored(colors) if colors[0].0 == Entity::from_raw(5)));
+
+        stack.push(Edit::SlowMotionRegion(None));
+        assert!(stack.redo.is_empty());
+    }
+}
@@ -0,0 +1,180 @@
+use bevy::prelude::*;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+
+use crate::*;
+
+/// Adds a wind which varies smoothly over the arena and over time, sampled
+/// from Perlin noise. The wind is drawn as arrows on a sparse grid.
+pub struct WindPlugin {
+    strength: f32,
+    seed: u64,
+}
+
+impl WindPlugin {
+    pub fn with_strength(strength: f32) -> Self {
+        Self { strength, seed: 0 }
+    }
+}
+
+impl Default for WindPlugin {
+    fn default() -> Self { Self::with_strength(150.) }
+}
+
+impl Plugin for WindPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Wind::new(self.strength, self.seed))
+            .add_system(draw_wind)
+            .add_system_to_stage(PhysicsStage, apply_wind.before(PhysicsSystem::Integrate));
+    }
+}
+
+/// Three dimensional Perlin noise, with a permutation of the lattice which is
+/// determined by a seed.
+pub struct Perlin {
+    perm: [u8; 512],
+}
+
+impl Perlin {
+    pub fn new(seed: u64) -> Self {
+        let mut values: Vec<u8> = (0..=255).collect();
+        values.shuffle(&mut StdRng::seed_from_u64(seed));
+
+        let mut perm = [0; 512];
+        for (i, value) in perm.iter_mut().enumerate() {
+            *value = values[i % 256];
+        }
+        Self { perm }
+    }
+
+    /// Noise at the given coordinates, roughly within -1..=1. The noise is
+    /// zero at integer coordinates.
+    pub fn sample(&self, x: f32, y: f32, z: f32) -> f32 {
+        let (xi, yi, zi) = (x.floor(), y.floor(), z.floor());
+        let (x, y, z) = (x - xi, y - yi, z - zi);
+        let (xi, yi, zi) = (xi as i32 as u8 as usize, yi as i32 as u8 as usize, zi as i32 as u8 as usize);
+        let (u, v, w) = (fade(x), fade(y), fade(z));
+
+        let p = &self.perm;
+        let a = p[xi] as usize + yi;
+        let aa = p[a] as usize + zi;
+        let ab = p[a + 1] as usize + zi;
+        let b = p[xi + 1] as usize + yi;
+        let ba = p[b] as usize + zi;
+        let bb = p[b + 1] as usize + zi;
+
+        lerp(w,
+            lerp(v,
+                lerp(u, grad(p[aa], x, y, z), grad(p[ba], x - 1., y, z)),
+                lerp(u, grad(p[ab], x, y - 1., z), grad(p[bb], x - 1., y - 1., z)),
+            ),
+            lerp(v,
+                lerp(u, grad(p[aa + 1], x, y, z - 1.), grad(p[ba + 1], x - 1., y, z - 1.)),
+                lerp(u, grad(p[ab + 1], x, y - 1., z - 1.), grad(p[bb + 1], x - 1., y - 1., z - 1.)),
+            ),
+        )
+    }
+}
+
+#[inline]
+fn fade(t: f32) -> f32 { t * t * t * (t * (t * 6. - 15.) + 10.) }
+
+#[inline]
+fn lerp(t: f32, a: f32, b: f32) -> f32 { a + t * (b - a) }
+
+// Dot product of the offset with one of twelve gradients, picked by `hash`.
+#[inline]
+fn grad(hash: u8, x: f32, y: f32, z: f32) -> f32 {
+    let h = hash & 15;
+    let u = if h < 8 { x } else { y };
+    let v = match h {
+        0..=3 => y,
+        12 | 14 => x,
+        _ => z,
+    };
+    (if h & 1 == 0 { u } else { -u }) + (if h & 2 == 0 { v } else { -v })
+}
+
+pub struct Wind {
+    /// Maximum acceleration of the wind.
+    pub strength: f32,
+    /// Distance over which the wind changes direction.
+    pub scale: f32,
+    /// Speed at which the wind changes over time.
+    pub rate: f32,
+    /// Simulated time, which the wind varies with.
+    pub elapsed: f32,
+    noise: Perlin,
+}
+
+impl Wind {
+    pub fn new(strength: f32, seed: u64) -> Self {
+        Self {
+            strength,
+            scale: 300.,
+            rate: 0.2,
+            elapsed: 0.,
+            noise: Perlin::new(seed),
+        }
+    }
+
+    /// Acceleration of the wind at `position`.
+    #[inline]
+    pub fn at(&self, position: Vec2) -> Vec2 {
+        let p = position / self.scale;
+        let t = self.elapsed * self.rate;
+        // the components are sampled far apart, so they vary independently
+        Vec2::new(
+            self.noise.sample(p.x, p.y, t),
+            self.noise.sample(p.x + 31.7, p.y + 47.3, t),
+        ) * self.strength
+    }
+}
+
+// Distance between the arrows which show the wind.
+const WIND_GRID_SPACING: f32 = 64.;
+
+fn apply_wind(mut wind: ResMut<Wind>, mut query: Query<(&Transform, &mut Force, &Ball)>) {
+    wind.elapsed += TIMESTEP;
+    for (transform, mut force, ball) in query.iter_mut() {
+        force.0 += wind.at(transform.translation.truncate()) * ball.mass;
+    }
+}
+
+fn draw_wind(mut debug_lines: ResMut<DebugLines>, wind: Res<Wind>, edge: Res<EdgeCollider>) {
+    let color = Color::rgba(0.6, 0.9, 0.6, 0.5);
+    let columns = (edge.bounds.width() / WIND_GRID_SPACING) as usize;
+    let rows = (edge.bounds.height() / WIND_GRID_SPACING) as usize;
+
+    for x in 0..columns {
+        for y in 0..rows {
+            let position = edge.bounds.min() + (Vec2::new(x as f32, y as f32) + 0.5) * WIND_GRID_SPACING;
+            // arrows are at most half the spacing long
+            let arrow = wind.at(position) / wind.strength.max(f32::EPSILON) * WIND_GRID_SPACING * 0.5;
+            let tip = position + arrow;
+            debug_lines.line_colored(position.extend(0.), tip.extend(0.), 0., color);
+            (tip - arrow * 0.2).debug_draw_lines(&mut debug_lines, Some(color));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn perlin_noise_is_smooth_and_bounded() {
+        let noise = Perlin::new(1648);
+        assert_eq!(noise.sample(3., 7., 2.), 0.);
+        assert_eq!(noise.sample(1.3, 2.7, 0.5), Perlin::new(1648).sample(1.3, 2.7, 0.5));
+
+        let mut previous = noise.sample(0., 0.5, 0.5);
+        for i in 1..1000 {
+            let value = noise.sample(i as f32 * 0.01, 0.5, 0.5);
+            assert!(value.abs() <= 1.1, "{}", value);
+            assert!((value - previous).abs() < 0.05, "{} -> {}", previous, value);
+            previous = value;
+        }
+    }
+}